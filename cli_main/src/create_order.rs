@@ -19,15 +19,26 @@ use byteorder::ByteOrder;
 use clap::ArgMatches;
 use rand::{RngCore, rngs::OsRng};
 use thiserror::Error;
-use crate::instruction::Order;
+use crate::instruction::{Order, PriceGuard, SpendLimit};
 
 use super::{parse_json, parse_keypair};
 
 
 pub const HELPER_PYTH_ID: &[u8] = &[70, 176, 37, 12, 106, 201, 74, 156, 64, 246, 254, 0, 195, 85, 90, 97, 88, 148, 195, 146, 24, 6, 246, 114, 86, 228, 185, 63, 193, 54, 105, 176];
 pub const PREDICATE_HEALTHFACTOR_ID: &[u8] = &[78, 11, 118, 213, 228, 92, 26, 55, 101, 204, 11, 75, 138, 91, 78, 249, 10, 197, 229, 133, 84, 247, 212, 213, 21, 232, 235, 119, 192, 110, 179, 177];
+pub const PREDICATE_RESERVE_RATE_ID: &[u8] = &[82, 62, 251, 106, 62, 255, 151, 128, 76, 203, 18, 129, 224, 124, 115, 168, 8, 119, 69, 69, 87, 230, 22, 84, 9, 4, 160, 146, 31, 1, 114, 230];
+pub const HELPER_CALL_AND_COMPARE_ID: &[u8] = &[25, 161, 251, 75, 116, 194, 255, 14, 99, 231, 37, 157, 45, 91, 198, 106, 243, 94, 105, 207, 150, 93, 131, 106, 38, 141, 97, 67, 129, 2, 67, 111];
+pub const HELPER_DUTCH_AUCTION_ID: &[u8] = &[51, 208, 206, 40, 35, 72, 186, 206, 33, 213, 203, 55, 111, 136, 209, 237, 144, 191, 65, 254, 37, 106, 219, 190, 215, 46, 99, 58, 28, 121, 26, 23];
+pub const HELPER_RANGE_ORDER_ID: &[u8] = &[172, 43, 39, 40, 212, 184, 2, 134, 253, 235, 172, 199, 48, 0, 246, 179, 88, 90, 0, 220, 253, 110, 233, 191, 117, 54, 198, 245, 39, 53, 195, 216];
+pub const HELPER_ORACLE_PEGGED_ID: &[u8] = &[147, 66, 98, 237, 18, 10, 111, 140, 81, 120, 227, 223, 143, 54, 49, 50, 178, 162, 122, 195, 102, 13, 23, 206, 94, 223, 168, 168, 18, 71, 167, 205];
+pub const PREDICATE_RESERVE_POSITION_ID: &[u8] = &[205, 13, 76, 250, 5, 232, 126, 17, 22, 20, 104, 118, 47, 12, 153, 96, 56, 100, 241, 190, 163, 132, 46, 209, 150, 108, 77, 244, 52, 112, 92, 238];
 
 pub const CALLBACK_SOLEND_LIQUIDATION_PROTECTION: &[u8] = &[32, 53, 9, 1, 169, 162, 247, 15, 108, 3, 155, 52, 149, 20, 2, 86, 154, 148, 207, 4, 134, 152, 207, 14, 16, 80, 168, 73, 173, 86, 193, 182];
+pub const CALLBACK_SOLEND_REPAY_AND_WITHDRAW: &[u8] = &[94, 206, 91, 8, 167, 42, 45, 118, 33, 172, 28, 249, 2, 162, 241, 3, 134, 195, 207, 80, 131, 95, 86, 70, 108, 45, 164, 65, 222, 223, 46, 154];
+pub const CALLBACK_SOLEND_DELEVERAGE: &[u8] = &[186, 145, 7, 159, 45, 36, 240, 10, 144, 137, 143, 216, 208, 184, 88, 64, 188, 85, 153, 255, 16, 3, 246, 149, 250, 228, 122, 8, 20, 194, 87, 33];
+pub const CALLBACK_SOLEND_LEVERAGE: &[u8] = &[203, 65, 63, 170, 37, 96, 177, 107, 175, 32, 120, 150, 38, 53, 206, 194, 168, 75, 36, 220, 156, 15, 82, 79, 184, 46, 110, 249, 36, 6, 43, 73];
+pub const CALLBACK_SOLEND_TOP_UP: &[u8] = &[236, 173, 235, 61, 150, 117, 141, 59, 143, 198, 13, 72, 103, 11, 20, 63, 12, 174, 83, 210, 0, 30, 204, 245, 80, 172, 7, 193, 94, 33, 149, 180];
+pub const CALLBACK_SOLEND_REFINANCE: &[u8] = &[90, 9, 4, 231, 190, 69, 74, 227, 86, 26, 198, 33, 230, 137, 122, 147, 39, 118, 36, 46, 185, 168, 67, 37, 91, 37, 160, 107, 9, 151, 161, 87];
 
 pub const SOLEND_ID: &[u8] = &[138, 193, 241, 114, 69, 245, 144, 57, 23, 131, 163, 184, 86, 117, 180, 107, 157, 175, 93, 163, 95, 242, 88, 210, 223, 21, 247, 109, 180, 231, 50, 89];
 
@@ -40,10 +51,18 @@ pub struct OrderBase {
     maker: String,
     making_amount: u64,
     taking_amount: u64,
+    get_maker_amount: Vec<u8>,
+    get_taker_amount: Vec<u8>,
     predicate: Vec<u8>,
+    post_condition: Vec<u8>,
     callback: Vec<u8>,
+    get_maker_amount_metas: Vec<CustomAccountMeta>,
+    get_taker_amount_metas: Vec<CustomAccountMeta>,
     predicate_metas: Vec<CustomAccountMeta>,
+    post_condition_metas: Vec<CustomAccountMeta>,
     callback_metas: Vec<CustomAccountMeta>,
+    price_guard: Option<CustomPriceGuard>,
+    spend_limits: Vec<CustomSpendLimit>,
     signature: Vec<u8>,
 }
 
@@ -54,6 +73,34 @@ pub struct CustomAccountMeta {
     is_writable: bool,
 }
 
+/// Optional oracle price-band guard, it's described in order base as
+/// ```
+/// "price_guard": {
+///     "pyth_price": "<pubkey>", // maker asset price quoted in taker asset
+///     "maker_decimals": <u8>,
+///     "taker_decimals": <u8>,
+///     "tolerance_bps": <i64>    // e.g. -200 allows fills at most 2% below oracle price
+/// }
+/// ```
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct CustomPriceGuard {
+    pyth_price: String,
+    maker_decimals: u8,
+    taker_decimals: u8,
+    tolerance_bps: i64,
+}
+
+/// Spend limits of callbacks, they are described in order base as
+/// ```
+/// "spend_limits": [{ "mint": "<pubkey>", "max_outflow": <u64> }]
+/// ```
+/// Taker asset is limited by taking amount if it isn't listed
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct CustomSpendLimit {
+    mint: String,
+    max_outflow: u64,
+}
+
 #[derive(Error, Debug)]
 pub enum CreateOrderError {
     #[error("Empty")]
@@ -73,34 +120,74 @@ pub fn create_order(arg_matches: &ArgMatches) {
     let making_amount = order_base["making_amount"].as_u64().unwrap();
     let taking_amount = order_base["taking_amount"].as_u64().unwrap();
 
-    let predicate = parse_predicate(&order_base);
-    let (predicate, predicate_metas) = match predicate {
-        Ok(predicate) => {
-            let mut predicate_metas = predicate.accounts.clone();
-            predicate_metas.insert(0, AccountMeta::new(predicate.program_id, false));
-        
-            let predicate_custom_metas = fill_custom_metas(predicate_metas);
-            let predicate = bincode::serialize(&predicate).unwrap();
-
-            (predicate, predicate_custom_metas)
-        },
-        Err(_) => (vec![], vec![])
+    let (
+        (get_maker_amount, get_maker_amount_metas),
+        (get_taker_amount, get_taker_amount_metas),
+    ) = match order_base["pricing"].as_str() {
+        Some("dutch") => (
+            parse_dutch_auction(&order_base, true),
+            parse_dutch_auction(&order_base, false),
+        ),
+        Some("range") => (
+            parse_range_order(&order_base, true),
+            parse_range_order(&order_base, false),
+        ),
+        Some("oracle") => (
+            parse_oracle_pegged(&order_base, true),
+            parse_oracle_pegged(&order_base, false),
+        ),
+        Some(_) => panic!("Unexpected pricing"),
+        None => (
+            parse_get_amount(&order_base["get_maker_amount"]),
+            parse_get_amount(&order_base["get_taker_amount"]),
+        ),
     };
 
-    let callback = parse_callback(&order_base);
-    let (callback, callback_metas) = match callback {
-        Ok(callback) => {
-            let mut callback_metas = callback.accounts.clone();
-            callback_metas.insert(0, AccountMeta::new(callback.program_id, false));
+    let price_guard: Option<CustomPriceGuard> =
+        serde_json::from_value(order_base["price_guard"].clone()).unwrap();
+
+    let (predicate, predicate_metas) = serialize_predicate(parse_predicate(&order_base));
+    // Post condition is described as an object with the same fields as predicate
+    let (post_condition, post_condition_metas) =
+        serialize_predicate(parse_predicate(&order_base["post_condition"]));
+
+    // Single callback is described in order base itself,
+    // several callbacks are described as array of objects with the same fields
+    let callbacks: Vec<Instruction> = match order_base["callback"].as_array() {
+        Some(callback_bases) => callback_bases
+            .iter()
+            .map(|callback_base| parse_callback(&order_base, callback_base).unwrap())
+            .collect(),
+        None => parse_callback(&order_base, &order_base).into_iter().collect(),
+    };
+    let (callback, callback_metas) = match callbacks.is_empty() {
+        false => {
+            let mut callback_metas = vec![];
+            for callback in callbacks.iter() {
+                callback_metas.push(AccountMeta::new(callback.program_id, false));
+                callback_metas.extend(callback.accounts.clone());
+            }
 
             let callback_custom_metas = fill_custom_metas(callback_metas);
-            let callback = bincode::serialize(&callback).unwrap();
+            let callback = bincode::serialize(&callbacks).unwrap();
 
             (callback, callback_custom_metas)
         },
-        Err(_) => (vec![], vec![])
+        true => (vec![], vec![])
     };
 
+    let mut spend_limits: Vec<CustomSpendLimit> = match order_base["spend_limits"].is_null() {
+        true => vec![],
+        false => serde_json::from_value(order_base["spend_limits"].clone()).unwrap(),
+    };
+    // Callback usually spends what the maker receives from the taker
+    if !callback.is_empty() && !spend_limits.iter().any(|spend_limit| spend_limit.mint == taker_asset) {
+        spend_limits.push(CustomSpendLimit {
+            mint: taker_asset.to_string(),
+            max_outflow: taking_amount,
+        });
+    }
+
     let mut order_base = OrderBase {
         salt: OsRng.next_u64(),
         maker_asset: maker_asset.to_string(),
@@ -108,10 +195,18 @@ pub fn create_order(arg_matches: &ArgMatches) {
         maker: maker.pubkey().to_string(),
         making_amount,
         taking_amount,
+        get_maker_amount,
+        get_taker_amount,
         predicate,
+        post_condition,
         callback,
+        get_maker_amount_metas,
+        get_taker_amount_metas,
         predicate_metas,
+        post_condition_metas,
         callback_metas,
+        price_guard,
+        spend_limits,
         signature: vec![],
     };
 
@@ -207,7 +302,124 @@ fn parse_predicate(order_base: &Value) -> Result<Instruction, Box<dyn Error>> {
                 },
                 _ => panic!("Unexpected lending protocol")
             }
-        }
+        },
+        "lending_reserve_position" => {
+            let lending_protocol = order_base["predicate_lending_protocol"].as_str().unwrap();
+
+            match lending_protocol {
+                "solend" => {
+                    instruction.program_id = Pubkey::new(PREDICATE_RESERVE_POSITION_ID);
+
+                    let predicate_obligation = order_base["predicate_obligation"].as_str().unwrap();
+                    let predicate_reserve = order_base["predicate_reserve"].as_str().unwrap();
+                    let predicate_position = order_base["predicate_position"].as_str().unwrap();
+                    let predicate_condition = order_base["predicate_condition"].as_str().unwrap();
+                    let amount = order_base["predicate_amount"].as_u64().unwrap();
+
+                    let obligation_account_id = Pubkey::from_str(predicate_obligation).unwrap();
+                    let reserve_id = Pubkey::from_str(predicate_reserve).unwrap();
+
+                    instruction.accounts = vec![
+                        AccountMeta::new_readonly(obligation_account_id, false),
+                    ];
+
+                    let mut data: [u8; 42] = [0; 42];
+                    data[0..32].copy_from_slice(reserve_id.as_ref());
+                    data[32] = match predicate_position {
+                        "deposit" => 0,
+                        "borrow" => 1,
+                        _ => panic!("Unexpected predicate_position")
+                    };
+                    byteorder::LE::write_u64(&mut data[33..41], amount);
+                    data[41] = match predicate_condition {
+                        "less" => 1,
+                        "more" => 0,
+                        _ => panic!("Unexpected predicate_condition")
+                    };
+
+                    instruction.data = data.to_vec();
+                },
+                _ => panic!("Unexpected lending protocol")
+            }
+        },
+        "lending_reserve_rate" => {
+            let lending_protocol = order_base["predicate_lending_protocol"].as_str().unwrap();
+
+            match lending_protocol {
+                "solend" => {
+                    instruction.program_id = Pubkey::new(PREDICATE_RESERVE_RATE_ID);
+
+                    let predicate_reserve = order_base["predicate_reserve"].as_str().unwrap();
+                    let predicate_rate = order_base["predicate_rate"].as_str().unwrap();
+                    let predicate_condition = order_base["predicate_condition"].as_str().unwrap();
+                    // Threshold is represented in basis points
+                    let threshold_bps = order_base["predicate_threshold_bps"].as_u64().unwrap();
+
+                    let reserve_id = Pubkey::from_str(predicate_reserve).unwrap();
+
+                    instruction.accounts = vec![
+                        AccountMeta::new_readonly(reserve_id, false),
+                    ];
+
+                    let mut data: [u8; 10] = [0; 10];
+                    data[0] = match predicate_rate {
+                        "utilization" => 0,
+                        "borrow_rate" => 1,
+                        _ => panic!("Unexpected predicate_rate")
+                    };
+                    byteorder::LE::write_u64(&mut data[1..9], threshold_bps);
+                    data[9] = match predicate_condition {
+                        "less" => 1,
+                        "more" => 0,
+                        _ => panic!("Unexpected predicate_condition")
+                    };
+
+                    instruction.data = data.to_vec();
+                },
+                _ => panic!("Unexpected lending protocol")
+            }
+        },
+        "call_and_compare" => {
+            instruction.program_id = Pubkey::new(HELPER_CALL_AND_COMPARE_ID);
+
+            let call_program = order_base["predicate_call_program"].as_str().unwrap();
+            let call_program = Pubkey::from_str(call_program).unwrap();
+
+            let call_data: Vec<u8> = order_base["predicate_call_data"]
+                .as_array()
+                .unwrap()
+                .iter()
+                .map(|value| value.as_u64().unwrap() as u8)
+                .collect();
+
+            let call_accounts = parse_account_metas(&order_base["predicate_call_accounts"]);
+
+            let call = Instruction {
+                program_id: call_program,
+                accounts: call_accounts.clone(),
+                data: call_data,
+            };
+
+            instruction.accounts = vec![
+                AccountMeta::new_readonly(call_program, false),
+            ];
+            instruction.accounts.extend(call_accounts);
+
+            let operator: u8 = match order_base["predicate_operator"].as_str().unwrap() {
+                "lt" => 0,
+                "gt" => 1,
+                "eq" => 2,
+                _ => panic!("Unexpected predicate_operator")
+            };
+            let value = order_base["predicate_value"].as_u64().unwrap();
+
+            let mut data: [u8; 17] = [0; 17];
+            data[0] = operator;
+            byteorder::LE::write_u128(&mut data[1..17], value as u128);
+
+            instruction.data = data.to_vec();
+            instruction.data.extend(bincode::serialize(&call).unwrap());
+        },
         _ => panic!("Unexpected predicate name")
     }
 
@@ -215,8 +427,198 @@ fn parse_predicate(order_base: &Value) -> Result<Instruction, Box<dyn Error>> {
 }
 
 
-fn parse_callback(order_base: &Value) -> Result<Instruction, Box<dyn Error>> {
-    let callback = order_base["callback"].as_str().ok_or(CreateOrderError::Empty)?;
+fn serialize_predicate(
+    predicate: Result<Instruction, Box<dyn Error>>,
+) -> (Vec<u8>, Vec<CustomAccountMeta>) {
+    match predicate {
+        Ok(predicate) => {
+            let mut predicate_metas = predicate.accounts.clone();
+            predicate_metas.insert(0, AccountMeta::new(predicate.program_id, false));
+        
+            let predicate_custom_metas = fill_custom_metas(predicate_metas);
+            let predicate = bincode::serialize(&predicate).unwrap();
+
+            (predicate, predicate_custom_metas)
+        },
+        Err(_) => (vec![], vec![])
+    }
+}
+
+/// Custom get amount instruction is described in order base as
+/// ```
+/// "get_maker_amount": {
+///     "program": "<pubkey>",
+///     "data": [<instruction data without swap amount>],
+///     "accounts": [{"pubkey": "<pubkey>", "is_signer": false, "is_writable": false}]
+/// }
+/// ```
+/// Empty array or missing field means the linear formula of the program.
+fn parse_get_amount(get_amount: &Value) -> (Vec<u8>, Vec<CustomAccountMeta>) {
+    let program = match get_amount["program"].as_str() {
+        Some(program) => Pubkey::from_str(program).unwrap(),
+        None => return (vec![], vec![]),
+    };
+
+    let data: Vec<u8> = get_amount["data"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|value| value.as_u64().unwrap() as u8)
+        .collect();
+
+    let instruction = Instruction {
+        program_id: program,
+        accounts: parse_account_metas(&get_amount["accounts"]),
+        data,
+    };
+
+    serialize_get_amount(&instruction)
+}
+
+/// Dutch auction is described in order base as
+/// ```
+/// "pricing": "dutch",
+/// "dutch_taking_amount_start": <u64>,
+/// "dutch_taking_amount_end": <u64>,
+/// "dutch_start_time": <unix timestamp>,
+/// "dutch_end_time": <unix timestamp>
+/// ```
+fn parse_dutch_auction(
+    order_base: &Value,
+    is_get_maker_amount: bool,
+) -> (Vec<u8>, Vec<CustomAccountMeta>) {
+    let making_amount = order_base["making_amount"].as_u64().unwrap();
+    let taking_amount_start = order_base["dutch_taking_amount_start"].as_u64().unwrap();
+    let taking_amount_end = order_base["dutch_taking_amount_end"].as_u64().unwrap();
+    let start_time = order_base["dutch_start_time"].as_i64().unwrap();
+    let end_time = order_base["dutch_end_time"].as_i64().unwrap();
+
+    let mut data: [u8; 41] = [0; 41];
+    data[0] = is_get_maker_amount as u8;
+    byteorder::LE::write_u64(&mut data[1..9], making_amount);
+    byteorder::LE::write_u64(&mut data[9..17], taking_amount_start);
+    byteorder::LE::write_u64(&mut data[17..25], taking_amount_end);
+    byteorder::LE::write_i64(&mut data[25..33], start_time);
+    byteorder::LE::write_i64(&mut data[33..41], end_time);
+
+    let instruction = Instruction {
+        program_id: Pubkey::new(HELPER_DUTCH_AUCTION_ID),
+        accounts: vec![
+            AccountMeta::new_readonly(Clock::id(), false),
+        ],
+        data: data.to_vec(),
+    };
+
+    serialize_get_amount(&instruction)
+}
+
+/// Range order is described in order base as
+/// ```
+/// "pricing": "range",
+/// "range_price_start": "<u128>", // taker amount per one maker amount scaled by 1e18
+/// "range_price_end": "<u128>"    // when nothing and whole making amount is filled
+/// ```
+fn parse_range_order(
+    order_base: &Value,
+    is_get_maker_amount: bool,
+) -> (Vec<u8>, Vec<CustomAccountMeta>) {
+    let making_amount = order_base["making_amount"].as_u64().unwrap();
+    let price_start = order_base["range_price_start"].as_str().unwrap();
+    let price_end = order_base["range_price_end"].as_str().unwrap();
+
+    let price_start = u128::from_str(price_start).unwrap();
+    let price_end = u128::from_str(price_end).unwrap();
+
+    let mut data: [u8; 41] = [0; 41];
+    data[0] = is_get_maker_amount as u8;
+    byteorder::LE::write_u64(&mut data[1..9], making_amount);
+    byteorder::LE::write_u128(&mut data[9..25], price_start);
+    byteorder::LE::write_u128(&mut data[25..41], price_end);
+
+    let instruction = Instruction {
+        program_id: Pubkey::new(HELPER_RANGE_ORDER_ID),
+        accounts: vec![],
+        data: data.to_vec(),
+    };
+
+    serialize_get_amount(&instruction)
+}
+
+/// Oracle pegged pricing is described in order base as
+/// ```
+/// "pricing": "oracle",
+/// "oracle_pyth_price": "<pubkey>",   // maker asset price quoted in taker asset
+/// "oracle_spread_bps": <i64>,        // e.g. -100 is oracle price minus 1%
+/// "oracle_maker_decimals": <u8>,
+/// "oracle_taker_decimals": <u8>,
+/// "oracle_min_price": "<u128>",      // optional, taker amount per one maker amount scaled by 1e18
+/// "oracle_max_price": "<u128>"       // optional
+/// ```
+fn parse_oracle_pegged(
+    order_base: &Value,
+    is_get_maker_amount: bool,
+) -> (Vec<u8>, Vec<CustomAccountMeta>) {
+    let pyth_price = order_base["oracle_pyth_price"].as_str().unwrap();
+    let spread_bps = order_base["oracle_spread_bps"].as_i64().unwrap();
+    let maker_decimals = order_base["oracle_maker_decimals"].as_u64().unwrap();
+    let taker_decimals = order_base["oracle_taker_decimals"].as_u64().unwrap();
+    let min_price = order_base["oracle_min_price"]
+        .as_str()
+        .map_or(0, |price| u128::from_str(price).unwrap());
+    let max_price = order_base["oracle_max_price"]
+        .as_str()
+        .map_or(0, |price| u128::from_str(price).unwrap());
+
+    let pyth_price_id = Pubkey::from_str(pyth_price).unwrap();
+
+    let mut data: [u8; 43] = [0; 43];
+    data[0] = is_get_maker_amount as u8;
+    byteorder::LE::write_i64(&mut data[1..9], spread_bps);
+    data[9] = maker_decimals as u8;
+    data[10] = taker_decimals as u8;
+    byteorder::LE::write_u128(&mut data[11..27], min_price);
+    byteorder::LE::write_u128(&mut data[27..43], max_price);
+
+    let instruction = Instruction {
+        program_id: Pubkey::new(HELPER_ORACLE_PEGGED_ID),
+        accounts: vec![
+            AccountMeta::new_readonly(pyth_price_id, false),
+        ],
+        data: data.to_vec(),
+    };
+
+    serialize_get_amount(&instruction)
+}
+
+fn serialize_get_amount(instruction: &Instruction) -> (Vec<u8>, Vec<CustomAccountMeta>) {
+    let mut metas = instruction.accounts.clone();
+    metas.insert(0, AccountMeta::new_readonly(instruction.program_id, false));
+
+    (bincode::serialize(instruction).unwrap(), fill_custom_metas(metas))
+}
+
+pub fn parse_account_metas(metas: &Value) -> Vec<AccountMeta> {
+    metas
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|value| {
+            let pubkey = Pubkey::from_str(value["pubkey"].as_str().unwrap()).unwrap();
+            let is_signer = value["is_signer"].as_bool().unwrap();
+
+            match value["is_writable"].as_bool().unwrap() {
+                true => AccountMeta::new(pubkey, is_signer),
+                false => AccountMeta::new_readonly(pubkey, is_signer),
+            }
+        })
+        .collect()
+}
+
+fn parse_callback(
+    order_base: &Value,
+    callback_base: &Value,
+) -> Result<Instruction, Box<dyn Error>> {
+    let callback = callback_base["callback"].as_str().ok_or(CreateOrderError::Empty)?;
 
     let mut instruction = Instruction {
         program_id: Pubkey::new_unique(),
@@ -228,7 +630,7 @@ fn parse_callback(order_base: &Value) -> Result<Instruction, Box<dyn Error>> {
 
     match callback {
         "liquidation_protection" => {
-            let callback_lending_protocol = order_base["callback_lending_protocol"].as_str().unwrap();
+            let callback_lending_protocol = callback_base["callback_lending_protocol"].as_str().unwrap();
 
             match callback_lending_protocol {
                 "solend" => {
@@ -269,12 +671,465 @@ fn parse_callback(order_base: &Value) -> Result<Instruction, Box<dyn Error>> {
                 _ => panic!("Unexpected lending protocol")
             }
         },
+        "repay_and_withdraw" => {
+            let callback_lending_protocol = callback_base["callback_lending_protocol"].as_str().unwrap();
+
+            match callback_lending_protocol {
+                "solend" => {
+                    instruction.program_id = Pubkey::new(CALLBACK_SOLEND_REPAY_AND_WITHDRAW);
+                    instruction.accounts = parse_solend_repay_and_withdraw_accounts(order_base, callback_base);
+
+                    // Target health mode: repay just enough to reach the ratio
+                    // instead of the amounts of the fill
+                    if let Some(target_health) = callback_base["callback_target_health"].as_str() {
+                        let mut data: [u8; 16] = [0; 16];
+                        byteorder::LE::write_u128(&mut data, u128::from_str(target_health).unwrap());
+
+                        instruction.data = data.to_vec();
+                    }
+                },
+                _ => panic!("Unexpected lending protocol")
+            }
+        },
+        "deleverage" => {
+            let callback_lending_protocol = callback_base["callback_lending_protocol"].as_str().unwrap();
+
+            match callback_lending_protocol {
+                "solend" => {
+                    instruction.program_id = Pubkey::new(CALLBACK_SOLEND_DELEVERAGE);
+                    instruction.accounts = parse_solend_deleverage_accounts(order_base, callback_base);
+
+                    let liquidity_amount = callback_base["callback_withdraw_amount"].as_u64().unwrap();
+                    let minimum_amount_out = callback_base["callback_minimum_amount_out"].as_u64().unwrap();
+
+                    let mut data: [u8; 16] = [0; 16];
+                    byteorder::LE::write_u64(&mut data[0..8], liquidity_amount);
+                    byteorder::LE::write_u64(&mut data[8..16], minimum_amount_out);
+
+                    instruction.data = data.to_vec();
+                },
+                _ => panic!("Unexpected lending protocol")
+            }
+        },
+        "leverage" => {
+            let callback_lending_protocol = callback_base["callback_lending_protocol"].as_str().unwrap();
+
+            match callback_lending_protocol {
+                "solend" => {
+                    instruction.program_id = Pubkey::new(CALLBACK_SOLEND_LEVERAGE);
+                    instruction.accounts = parse_solend_leverage_accounts(order_base, callback_base);
+
+                    let target_leverage = callback_base["callback_target_leverage"].as_str().unwrap();
+                    let min_health = callback_base["callback_min_health"].as_str().unwrap();
+                    let max_slippage_bps = callback_base["callback_max_slippage_bps"].as_u64().unwrap();
+                    let max_iterations = callback_base["callback_max_iterations"].as_u64().unwrap();
+
+                    let mut data: [u8; 41] = [0; 41];
+                    byteorder::LE::write_u128(&mut data[0..16], u128::from_str(target_leverage).unwrap());
+                    byteorder::LE::write_u128(&mut data[16..32], u128::from_str(min_health).unwrap());
+                    byteorder::LE::write_u64(&mut data[32..40], max_slippage_bps);
+                    data[40] = max_iterations as u8;
+
+                    instruction.data = data.to_vec();
+                },
+                _ => panic!("Unexpected lending protocol")
+            }
+        },
+        "top_up" => {
+            let callback_lending_protocol = callback_base["callback_lending_protocol"].as_str().unwrap();
+
+            match callback_lending_protocol {
+                "solend" => {
+                    instruction.program_id = Pubkey::new(CALLBACK_SOLEND_TOP_UP);
+                    instruction.accounts = parse_solend_top_up_accounts(order_base, callback_base);
+
+                    let liquidity_amount = callback_base["callback_top_up_amount"].as_u64().unwrap();
+                    let max_top_up_amount = callback_base["callback_max_top_up_amount"].as_u64().unwrap();
+
+                    let mut data: [u8; 16] = [0; 16];
+                    byteorder::LE::write_u64(&mut data[0..8], liquidity_amount);
+                    byteorder::LE::write_u64(&mut data[8..16], max_top_up_amount);
+
+                    instruction.data = data.to_vec();
+                },
+                _ => panic!("Unexpected lending protocol")
+            }
+        },
+        "refinance" => {
+            let callback_lending_protocol = callback_base["callback_lending_protocol"].as_str().unwrap();
+
+            match callback_lending_protocol {
+                "solend" => {
+                    instruction.program_id = Pubkey::new(CALLBACK_SOLEND_REFINANCE);
+                    instruction.accounts = parse_solend_refinance_accounts(order_base, callback_base);
+
+                    let borrow_amount = callback_base["callback_borrow_amount"].as_u64().unwrap();
+                    let withdraw_amount = match callback_base["callback_withdraw_amount"].as_str() {
+                        Some("all") => u64::MAX,
+                        _ => callback_base["callback_withdraw_amount"].as_u64().unwrap(),
+                    };
+                    let market_a_reserves = callback_base["callback_market_a"]["refresh_reserves"].as_array().unwrap().len();
+
+                    let mut data: [u8; 17] = [0; 17];
+                    byteorder::LE::write_u64(&mut data[0..8], borrow_amount);
+                    byteorder::LE::write_u64(&mut data[8..16], withdraw_amount);
+                    data[16] = market_a_reserves as u8;
+
+                    instruction.data = data.to_vec();
+                },
+                _ => panic!("Unexpected lending protocol")
+            }
+        },
         _ => panic!("Unexpected callback name")
     }
 
     Ok(instruction)
 }
 
+/// Accounts of the composite Solend callback are described in callback base as
+/// ```
+/// "callback_source_liquidity": "<pubkey>",         // maker token account of taker asset
+/// "callback_repay_reserve": "<pubkey>",
+/// "callback_repay_reserve_liquidity_supply": "<pubkey>",
+/// "callback_withdraw_reserve": "<pubkey>",
+/// "callback_withdraw_reserve_collateral_supply": "<pubkey>",
+/// "callback_withdraw_reserve_collateral_mint": "<pubkey>",
+/// "callback_withdraw_reserve_liquidity_supply": "<pubkey>",
+/// "callback_destination_collateral": "<pubkey>",
+/// "callback_destination_liquidity": "<pubkey>",    // maker token account of maker asset
+/// "callback_obligation": "<pubkey>",
+/// "callback_lending_market": "<pubkey>",
+/// "callback_lending_market_authority": "<pubkey>",
+/// "callback_solend_position": "<pubkey>",          // maker position of the obligation
+/// "callback_refresh_reserves": [
+///     { "reserve": "<pubkey>", "pyth_price": "<pubkey>", "switchboard_price": "<pubkey>" }
+/// ],                                               // deposits and then borrows of the obligation
+/// "callback_target_health": "<u128>",              // optional, unhealthy borrow value / borrowed value
+///                                                  // scaled by 1e18, e.g. "1500000000000000000"
+/// ```
+fn parse_solend_repay_and_withdraw_accounts(
+    order_base: &Value,
+    callback_base: &Value,
+) -> Vec<AccountMeta> {
+    let pubkey = |value: &Value| Pubkey::from_str(value.as_str().unwrap()).unwrap();
+
+    let delegate = pubkey(&order_base["delegate"]);
+
+    let mut accounts = vec![
+        AccountMeta::new_readonly(Pubkey::new(SOLEND_ID), false),
+        AccountMeta::new(pubkey(&callback_base["callback_source_liquidity"]), false),
+        AccountMeta::new(pubkey(&callback_base["callback_repay_reserve_liquidity_supply"]), false),
+        AccountMeta::new(pubkey(&callback_base["callback_repay_reserve"]), false),
+        AccountMeta::new(pubkey(&callback_base["callback_withdraw_reserve_collateral_supply"]), false),
+        AccountMeta::new(pubkey(&callback_base["callback_destination_collateral"]), false),
+        AccountMeta::new(pubkey(&callback_base["callback_withdraw_reserve"]), false),
+        AccountMeta::new(pubkey(&callback_base["callback_obligation"]), false),
+        AccountMeta::new_readonly(pubkey(&callback_base["callback_lending_market"]), false),
+        AccountMeta::new_readonly(pubkey(&callback_base["callback_lending_market_authority"]), false),
+        AccountMeta::new(pubkey(&callback_base["callback_destination_liquidity"]), false),
+        AccountMeta::new(pubkey(&callback_base["callback_withdraw_reserve_collateral_mint"]), false),
+        AccountMeta::new(pubkey(&callback_base["callback_withdraw_reserve_liquidity_supply"]), false),
+        AccountMeta::new_readonly(delegate, false),
+        AccountMeta::new_readonly(Clock::id(), false),
+        AccountMeta::new_readonly(spl_token::id(), false),
+        AccountMeta::new(pubkey(&callback_base["callback_solend_position"]), false),
+    ];
+
+    accounts.extend(parse_refresh_reserve_accounts(&callback_base["callback_refresh_reserves"]));
+
+    accounts
+}
+
+/// Accounts of the Solend deleverage callback are described in callback base as
+/// ```
+/// "callback_withdraw_reserve": "<pubkey>",
+/// "callback_withdraw_reserve_collateral_supply": "<pubkey>",
+/// "callback_withdraw_reserve_collateral_mint": "<pubkey>",
+/// "callback_withdraw_reserve_liquidity_supply": "<pubkey>",
+/// "callback_destination_collateral": "<pubkey>",
+/// "callback_withdraw_liquidity": "<pubkey>",       // maker token account of withdraw reserve liquidity
+/// "callback_swap_program": "<pubkey>",
+/// "callback_swap": "<pubkey>",
+/// "callback_swap_authority": "<pubkey>",
+/// "callback_swap_source": "<pubkey>",              // pool token account of withdraw reserve liquidity
+/// "callback_swap_destination": "<pubkey>",         // pool token account of repay reserve liquidity
+/// "callback_pool_mint": "<pubkey>",
+/// "callback_pool_fee": "<pubkey>",
+/// "callback_repay_liquidity": "<pubkey>",          // maker token account of repay reserve liquidity
+/// "callback_repay_reserve": "<pubkey>",
+/// "callback_repay_reserve_liquidity_supply": "<pubkey>",
+/// "callback_obligation": "<pubkey>",
+/// "callback_lending_market": "<pubkey>",
+/// "callback_lending_market_authority": "<pubkey>",
+/// "callback_solend_position": "<pubkey>",          // maker position of the obligation
+/// "callback_refresh_reserves": [ ... ],            // as in the repay and withdraw callback
+/// "callback_withdraw_amount": <u64>,
+/// "callback_minimum_amount_out": <u64>,
+/// ```
+fn parse_solend_deleverage_accounts(
+    order_base: &Value,
+    callback_base: &Value,
+) -> Vec<AccountMeta> {
+    let pubkey = |value: &Value| Pubkey::from_str(value.as_str().unwrap()).unwrap();
+
+    let delegate = pubkey(&order_base["delegate"]);
+
+    let mut accounts = vec![
+        AccountMeta::new_readonly(Pubkey::new(SOLEND_ID), false),
+        AccountMeta::new(pubkey(&callback_base["callback_withdraw_reserve_collateral_supply"]), false),
+        AccountMeta::new(pubkey(&callback_base["callback_destination_collateral"]), false),
+        AccountMeta::new(pubkey(&callback_base["callback_withdraw_reserve"]), false),
+        AccountMeta::new(pubkey(&callback_base["callback_obligation"]), false),
+        AccountMeta::new_readonly(pubkey(&callback_base["callback_lending_market"]), false),
+        AccountMeta::new_readonly(pubkey(&callback_base["callback_lending_market_authority"]), false),
+        AccountMeta::new(pubkey(&callback_base["callback_withdraw_liquidity"]), false),
+        AccountMeta::new(pubkey(&callback_base["callback_withdraw_reserve_collateral_mint"]), false),
+        AccountMeta::new(pubkey(&callback_base["callback_withdraw_reserve_liquidity_supply"]), false),
+        AccountMeta::new_readonly(pubkey(&callback_base["callback_swap_program"]), false),
+        AccountMeta::new_readonly(pubkey(&callback_base["callback_swap"]), false),
+        AccountMeta::new_readonly(pubkey(&callback_base["callback_swap_authority"]), false),
+        AccountMeta::new(pubkey(&callback_base["callback_swap_source"]), false),
+        AccountMeta::new(pubkey(&callback_base["callback_swap_destination"]), false),
+        AccountMeta::new(pubkey(&callback_base["callback_pool_mint"]), false),
+        AccountMeta::new(pubkey(&callback_base["callback_pool_fee"]), false),
+        AccountMeta::new(pubkey(&callback_base["callback_repay_liquidity"]), false),
+        AccountMeta::new(pubkey(&callback_base["callback_repay_reserve_liquidity_supply"]), false),
+        AccountMeta::new(pubkey(&callback_base["callback_repay_reserve"]), false),
+        AccountMeta::new_readonly(delegate, false),
+        AccountMeta::new_readonly(Clock::id(), false),
+        AccountMeta::new_readonly(spl_token::id(), false),
+        AccountMeta::new(pubkey(&callback_base["callback_solend_position"]), false),
+    ];
+
+    accounts.extend(parse_refresh_reserve_accounts(&callback_base["callback_refresh_reserves"]));
+
+    accounts
+}
+
+/// Accounts of the Solend leverage callback are described in callback base as
+/// ```
+/// "callback_obligation": "<pubkey>",
+/// "callback_lending_market": "<pubkey>",
+/// "callback_lending_market_authority": "<pubkey>",
+/// "callback_deposit_reserve": "<pubkey>",
+/// "callback_deposit_reserve_liquidity_supply": "<pubkey>",
+/// "callback_deposit_reserve_collateral_mint": "<pubkey>",
+/// "callback_deposit_reserve_collateral_supply": "<pubkey>",
+/// "callback_deposit_reserve_pyth_price": "<pubkey>",
+/// "callback_deposit_reserve_switchboard_price": "<pubkey>",
+/// "callback_destination_collateral": "<pubkey>",
+/// "callback_deposit_liquidity": "<pubkey>",        // maker token account of deposit reserve liquidity
+/// "callback_borrow_reserve": "<pubkey>",
+/// "callback_borrow_reserve_liquidity_supply": "<pubkey>",
+/// "callback_borrow_reserve_fee_receiver": "<pubkey>",
+/// "callback_borrow_liquidity": "<pubkey>",         // maker token account of borrow reserve liquidity
+/// "callback_swap_program": "<pubkey>",
+/// "callback_swap": "<pubkey>",
+/// "callback_swap_authority": "<pubkey>",
+/// "callback_swap_source": "<pubkey>",              // pool token account of borrow reserve liquidity
+/// "callback_swap_destination": "<pubkey>",         // pool token account of deposit reserve liquidity
+/// "callback_pool_mint": "<pubkey>",
+/// "callback_pool_fee": "<pubkey>",
+/// "callback_solend_position": "<pubkey>",          // maker position of the obligation
+/// "callback_refresh_reserves": [ ... ],            // as in the repay and withdraw callback
+/// "callback_target_leverage": "<u128>",            // scaled by 1e18, e.g. "2000000000000000000"
+/// "callback_min_health": "<u128>",                 // scaled by 1e18
+/// "callback_max_slippage_bps": <u64>,
+/// "callback_max_iterations": <u8>,
+/// ```
+fn parse_solend_leverage_accounts(
+    order_base: &Value,
+    callback_base: &Value,
+) -> Vec<AccountMeta> {
+    let pubkey = |value: &Value| Pubkey::from_str(value.as_str().unwrap()).unwrap();
+
+    let delegate = pubkey(&order_base["delegate"]);
+
+    let mut accounts = vec![
+        AccountMeta::new_readonly(Pubkey::new(SOLEND_ID), false),
+        AccountMeta::new(pubkey(&callback_base["callback_obligation"]), false),
+        AccountMeta::new_readonly(pubkey(&callback_base["callback_lending_market"]), false),
+        AccountMeta::new_readonly(pubkey(&callback_base["callback_lending_market_authority"]), false),
+        AccountMeta::new(pubkey(&callback_base["callback_deposit_reserve"]), false),
+        AccountMeta::new(pubkey(&callback_base["callback_deposit_reserve_liquidity_supply"]), false),
+        AccountMeta::new(pubkey(&callback_base["callback_deposit_reserve_collateral_mint"]), false),
+        AccountMeta::new(pubkey(&callback_base["callback_deposit_reserve_collateral_supply"]), false),
+        AccountMeta::new(pubkey(&callback_base["callback_destination_collateral"]), false),
+        AccountMeta::new_readonly(pubkey(&callback_base["callback_deposit_reserve_pyth_price"]), false),
+        AccountMeta::new_readonly(pubkey(&callback_base["callback_deposit_reserve_switchboard_price"]), false),
+        AccountMeta::new(pubkey(&callback_base["callback_deposit_liquidity"]), false),
+        AccountMeta::new(pubkey(&callback_base["callback_borrow_reserve"]), false),
+        AccountMeta::new(pubkey(&callback_base["callback_borrow_reserve_liquidity_supply"]), false),
+        AccountMeta::new(pubkey(&callback_base["callback_borrow_reserve_fee_receiver"]), false),
+        AccountMeta::new(pubkey(&callback_base["callback_borrow_liquidity"]), false),
+        AccountMeta::new_readonly(pubkey(&callback_base["callback_swap_program"]), false),
+        AccountMeta::new_readonly(pubkey(&callback_base["callback_swap"]), false),
+        AccountMeta::new_readonly(pubkey(&callback_base["callback_swap_authority"]), false),
+        AccountMeta::new(pubkey(&callback_base["callback_swap_source"]), false),
+        AccountMeta::new(pubkey(&callback_base["callback_swap_destination"]), false),
+        AccountMeta::new(pubkey(&callback_base["callback_pool_mint"]), false),
+        AccountMeta::new(pubkey(&callback_base["callback_pool_fee"]), false),
+        AccountMeta::new_readonly(delegate, false),
+        AccountMeta::new_readonly(Clock::id(), false),
+        AccountMeta::new_readonly(spl_token::id(), false),
+        AccountMeta::new(pubkey(&callback_base["callback_solend_position"]), false),
+    ];
+
+    accounts.extend(parse_refresh_reserve_accounts(&callback_base["callback_refresh_reserves"]));
+
+    accounts
+}
+
+/// Accounts of the Solend top-up callback are described in callback base as
+/// ```
+/// "callback_source_liquidity": "<pubkey>",         // maker token account of deposit reserve liquidity
+/// "callback_delegate_liquidity": "<pubkey>",       // delegate token account of deposit reserve liquidity
+/// "callback_destination_collateral": "<pubkey>",
+/// "callback_deposit_reserve": "<pubkey>",
+/// "callback_deposit_reserve_liquidity_supply": "<pubkey>",
+/// "callback_deposit_reserve_collateral_mint": "<pubkey>",
+/// "callback_deposit_reserve_collateral_supply": "<pubkey>",
+/// "callback_deposit_reserve_pyth_price": "<pubkey>",
+/// "callback_deposit_reserve_switchboard_price": "<pubkey>",
+/// "callback_obligation": "<pubkey>",
+/// "callback_lending_market": "<pubkey>",
+/// "callback_lending_market_authority": "<pubkey>",
+/// "callback_solend_position": "<pubkey>",          // maker position of the obligation
+/// "callback_top_up_amount": <u64>,                 // deposited liquidity per fill
+/// "callback_max_top_up_amount": <u64>,             // cap on liquidity deposited by the order
+/// ```
+fn parse_solend_top_up_accounts(
+    order_base: &Value,
+    callback_base: &Value,
+) -> Vec<AccountMeta> {
+    let pubkey = |value: &Value| Pubkey::from_str(value.as_str().unwrap()).unwrap();
+
+    let delegate = pubkey(&order_base["delegate"]);
+
+    vec![
+        AccountMeta::new_readonly(Pubkey::new(SOLEND_ID), false),
+        AccountMeta::new(pubkey(&callback_base["callback_source_liquidity"]), false),
+        AccountMeta::new(pubkey(&callback_base["callback_delegate_liquidity"]), false),
+        AccountMeta::new(pubkey(&callback_base["callback_destination_collateral"]), false),
+        AccountMeta::new(pubkey(&callback_base["callback_deposit_reserve"]), false),
+        AccountMeta::new(pubkey(&callback_base["callback_deposit_reserve_liquidity_supply"]), false),
+        AccountMeta::new(pubkey(&callback_base["callback_deposit_reserve_collateral_mint"]), false),
+        AccountMeta::new_readonly(pubkey(&callback_base["callback_lending_market"]), false),
+        AccountMeta::new_readonly(pubkey(&callback_base["callback_lending_market_authority"]), false),
+        AccountMeta::new(pubkey(&callback_base["callback_deposit_reserve_collateral_supply"]), false),
+        AccountMeta::new(pubkey(&callback_base["callback_obligation"]), false),
+        AccountMeta::new_readonly(pubkey(&callback_base["callback_deposit_reserve_pyth_price"]), false),
+        AccountMeta::new_readonly(pubkey(&callback_base["callback_deposit_reserve_switchboard_price"]), false),
+        AccountMeta::new_readonly(delegate, false),
+        AccountMeta::new_readonly(Clock::id(), false),
+        AccountMeta::new_readonly(spl_token::id(), false),
+        AccountMeta::new(pubkey(&callback_base["callback_solend_position"]), false),
+    ]
+}
+
+/// Accounts of the Solend refinance callback are described in callback base as
+/// ```
+/// "callback_market_a": {
+///     "program": "<pubkey>",                       // optional, Solend by default
+///     "obligation": "<pubkey>",
+///     "lending_market": "<pubkey>",
+///     "lending_market_authority": "<pubkey>",
+///     "solend_position": "<pubkey>",               // maker position of the obligation
+///     "refresh_reserves": [ ... ],                 // as `callback_refresh_reserves` of the repay and withdraw callback
+/// },
+/// "callback_market_b": { ... },                    // same fields for the market B
+/// "callback_repay_reserve": "<pubkey>",
+/// "callback_repay_reserve_liquidity_supply": "<pubkey>",
+/// "callback_withdraw_reserve": "<pubkey>",
+/// "callback_withdraw_reserve_collateral_supply": "<pubkey>",
+/// "callback_withdraw_reserve_collateral_mint": "<pubkey>",
+/// "callback_withdraw_reserve_liquidity_supply": "<pubkey>",
+/// "callback_withdraw_collateral": "<pubkey>",      // maker collateral token account of market A
+/// "callback_borrow_reserve": "<pubkey>",
+/// "callback_borrow_reserve_liquidity_supply": "<pubkey>",
+/// "callback_borrow_reserve_fee_receiver": "<pubkey>",
+/// "callback_deposit_reserve": "<pubkey>",
+/// "callback_deposit_reserve_liquidity_supply": "<pubkey>",
+/// "callback_deposit_reserve_collateral_mint": "<pubkey>",
+/// "callback_deposit_reserve_collateral_supply": "<pubkey>",
+/// "callback_deposit_reserve_pyth_price": "<pubkey>",
+/// "callback_deposit_reserve_switchboard_price": "<pubkey>",
+/// "callback_deposit_collateral": "<pubkey>",       // maker collateral token account of market B
+/// "callback_debt_liquidity": "<pubkey>",           // maker token account of borrowed and repaid liquidity
+/// "callback_collateral_liquidity": "<pubkey>",     // maker token account of withdrawn and deposited liquidity
+/// "callback_borrow_amount": <u64>,
+/// "callback_withdraw_amount": <u64> | "all",
+/// ```
+fn parse_solend_refinance_accounts(
+    order_base: &Value,
+    callback_base: &Value,
+) -> Vec<AccountMeta> {
+    let pubkey = |value: &Value| Pubkey::from_str(value.as_str().unwrap()).unwrap();
+    let program = |market: &Value| match market["program"].as_str() {
+        Some(program) => Pubkey::from_str(program).unwrap(),
+        None => Pubkey::new(SOLEND_ID),
+    };
+
+    let delegate = pubkey(&order_base["delegate"]);
+    let market_a = &callback_base["callback_market_a"];
+    let market_b = &callback_base["callback_market_b"];
+
+    let mut accounts = vec![
+        AccountMeta::new_readonly(program(market_a), false),
+        AccountMeta::new(pubkey(&market_a["obligation"]), false),
+        AccountMeta::new_readonly(pubkey(&market_a["lending_market"]), false),
+        AccountMeta::new_readonly(pubkey(&market_a["lending_market_authority"]), false),
+        AccountMeta::new(pubkey(&callback_base["callback_repay_reserve"]), false),
+        AccountMeta::new(pubkey(&callback_base["callback_repay_reserve_liquidity_supply"]), false),
+        AccountMeta::new(pubkey(&callback_base["callback_withdraw_reserve"]), false),
+        AccountMeta::new(pubkey(&callback_base["callback_withdraw_reserve_collateral_supply"]), false),
+        AccountMeta::new(pubkey(&callback_base["callback_withdraw_reserve_collateral_mint"]), false),
+        AccountMeta::new(pubkey(&callback_base["callback_withdraw_reserve_liquidity_supply"]), false),
+        AccountMeta::new(pubkey(&callback_base["callback_withdraw_collateral"]), false),
+        AccountMeta::new_readonly(program(market_b), false),
+        AccountMeta::new(pubkey(&market_b["obligation"]), false),
+        AccountMeta::new_readonly(pubkey(&market_b["lending_market"]), false),
+        AccountMeta::new_readonly(pubkey(&market_b["lending_market_authority"]), false),
+        AccountMeta::new(pubkey(&callback_base["callback_borrow_reserve"]), false),
+        AccountMeta::new(pubkey(&callback_base["callback_borrow_reserve_liquidity_supply"]), false),
+        AccountMeta::new(pubkey(&callback_base["callback_borrow_reserve_fee_receiver"]), false),
+        AccountMeta::new(pubkey(&callback_base["callback_deposit_reserve"]), false),
+        AccountMeta::new(pubkey(&callback_base["callback_deposit_reserve_liquidity_supply"]), false),
+        AccountMeta::new(pubkey(&callback_base["callback_deposit_reserve_collateral_mint"]), false),
+        AccountMeta::new(pubkey(&callback_base["callback_deposit_reserve_collateral_supply"]), false),
+        AccountMeta::new_readonly(pubkey(&callback_base["callback_deposit_reserve_pyth_price"]), false),
+        AccountMeta::new_readonly(pubkey(&callback_base["callback_deposit_reserve_switchboard_price"]), false),
+        AccountMeta::new(pubkey(&callback_base["callback_deposit_collateral"]), false),
+        AccountMeta::new(pubkey(&callback_base["callback_debt_liquidity"]), false),
+        AccountMeta::new(pubkey(&callback_base["callback_collateral_liquidity"]), false),
+        AccountMeta::new_readonly(delegate, false),
+        AccountMeta::new_readonly(Clock::id(), false),
+        AccountMeta::new_readonly(spl_token::id(), false),
+        AccountMeta::new(pubkey(&market_a["solend_position"]), false),
+        AccountMeta::new(pubkey(&market_b["solend_position"]), false),
+    ];
+
+    accounts.extend(parse_refresh_reserve_accounts(&market_a["refresh_reserves"]));
+    accounts.extend(parse_refresh_reserve_accounts(&market_b["refresh_reserves"]));
+
+    accounts
+}
+
+/// Reserve, pyth price and switchboard price of every refresh reserve item
+fn parse_refresh_reserve_accounts(refresh_reserves: &Value) -> Vec<AccountMeta> {
+    let pubkey = |value: &Value| Pubkey::from_str(value.as_str().unwrap()).unwrap();
+
+    let mut accounts = vec![];
+
+    for refresh_reserve in refresh_reserves.as_array().unwrap() {
+        accounts.push(AccountMeta::new(pubkey(&refresh_reserve["reserve"]), false));
+        accounts.push(AccountMeta::new_readonly(pubkey(&refresh_reserve["pyth_price"]), false));
+        accounts.push(AccountMeta::new_readonly(pubkey(&refresh_reserve["switchboard_price"]), false));
+    }
+
+    accounts
+}
+
 
 pub fn fill_custom_metas(
     metas: Vec<AccountMeta>,
@@ -306,8 +1161,24 @@ impl OrderBase {
             maker,
             making_amount: self.making_amount,
             taking_amount: self.taking_amount,
+            get_maker_amount: self.get_maker_amount.clone(),
+            get_taker_amount: self.get_taker_amount.clone(),
             predicate: self.predicate.clone(),
+            post_condition: self.post_condition.clone(),
             callback: self.callback.clone(),
+            price_guard: self.price_guard.as_ref().map(|price_guard| PriceGuard {
+                pyth_price: Pubkey::from_str(&price_guard.pyth_price).unwrap(),
+                maker_decimals: price_guard.maker_decimals,
+                taker_decimals: price_guard.taker_decimals,
+                tolerance_bps: price_guard.tolerance_bps,
+            }),
+            spend_limits: self.spend_limits
+                .iter()
+                .map(|spend_limit| SpendLimit {
+                    mint: Pubkey::from_str(&spend_limit.mint).unwrap(),
+                    max_outflow: spend_limit.max_outflow,
+                })
+                .collect(),
         }
     }
 }
\ No newline at end of file
@@ -19,8 +19,27 @@ pub struct Order {
     pub maker: Pubkey,
     pub making_amount: u64,
     pub taking_amount: u64,
+    pub get_maker_amount: Vec<u8>,
+    pub get_taker_amount: Vec<u8>,
     pub predicate: Vec<u8>,
+    pub post_condition: Vec<u8>,
     pub callback: Vec<u8>,
+    pub price_guard: Option<PriceGuard>,
+    pub spend_limits: Vec<SpendLimit>,
+}
+
+#[derive(BorshSchema, BorshSerialize, BorshDeserialize, Clone)]
+pub struct PriceGuard {
+    pub pyth_price: Pubkey,
+    pub maker_decimals: u8,
+    pub taker_decimals: u8,
+    pub tolerance_bps: i64,
+}
+
+#[derive(BorshSchema, BorshSerialize, BorshDeserialize, Clone)]
+pub struct SpendLimit {
+    pub mint: Pubkey,
+    pub max_outflow: u64,
 }
 
 #[derive(BorshSchema, BorshSerialize, BorshDeserialize)]
@@ -29,8 +48,23 @@ pub struct FillOrderArgs {
     pub order: Option<Order>,
     pub making_amount: u64,
     pub taking_amount: u64,
+    pub get_maker_amount_infos_num: u8,
+    pub get_taker_amount_infos_num: u8,
     pub predicate_infos_num: u8,
+    pub post_condition_infos_num: u8,
     pub callback_infos_num: u8,
+    /// Only upload the order onchain without filling it.
+    /// Used for orders that don't fit into one transaction
+    pub upload_only: bool,
+    /// Taker interaction. Bincode serialized `Instruction`, empty if none
+    pub interaction: Vec<u8>,
+    pub interaction_infos_num: u8,
+}
+
+#[derive(BorshSchema, BorshSerialize, BorshDeserialize)]
+pub struct CheckPredicateArgs {
+    pub order_hash: Option<[u8; 32]>,
+    pub predicate: Vec<u8>,
 }
 
 #[derive(BorshSchema, BorshSerialize, BorshDeserialize)]
@@ -80,17 +114,11 @@ pub enum SolarisAutoInstruction {
     ProxyDepositReserveLiquidityAndObligationCollateral {
         liquidity_amount: u64,
     },
-    /// 
+    ///
     /// 2
-    /// Init PDA delegate. Account which must be approved for transfer 
-    /// tokens from maker token-account.
-    /// 
-    /// Accounts expected:
-    /// 
-    /// 0. `[signer]` Payer
-    /// 1. `[writable]` PDA delegate. Seeds: ["solaris-automations", "delegate", bump]
-    /// 2. `[]` system-program
-    InitDelegate,
+    ProxyBorrowObligationLiquidity {
+        liquidity_amount: u64,
+    },
     ///
     /// 3
     ///
@@ -106,6 +134,69 @@ pub enum SolarisAutoInstruction {
     /// 7. `[]` System-program 
     /// 8. `[]` Spl-token
     InitSolendAccountsForDelegate,
+    /// 
+    /// 4
+    /// Init PDA delegate. Account which must be approved for transfer 
+    /// tokens from maker token-account.
+    /// 
+    /// Accounts expected:
+    /// 
+    /// 0. `[signer]` Payer
+    /// 1. `[writable]` PDA delegate. Seeds: ["solaris-automations", "delegate", bump]
+    /// 2. `[]` system-program
+    InitDelegate,
+    ///
+    /// 5
+    CheckPredicate(CheckPredicateArgs),
+    ///
+    /// 6
+    /// Create order buffer. Seeds: [prefix, order_buffer, authority]
+    ///
+    /// Accounts expected:
+    ///
+    /// 0. `[signer, writable]` Authority
+    /// 1. `[writable]` Order buffer
+    /// 2. `[]` system-program
+    InitOrderBuffer {
+        order_len: u32,
+    },
+    ///
+    /// 7
+    /// Write part of the serialized order into the order buffer
+    ///
+    /// Accounts expected:
+    ///
+    /// 0. `[signer]` Authority
+    /// 1. `[writable]` Order buffer
+    WriteOrderBuffer {
+        offset: u32,
+        bytes: Vec<u8>,
+    },
+    ///
+    /// 8
+    /// Verify maker signature, create onchain order and close the order buffer.
+    /// Ed25519 instruction must go right before this one.
+    ///
+    /// Accounts expected:
+    ///
+    /// 0. `[signer, writable]` Authority
+    /// 1. `[]` Sysvar instructions
+    /// 2. `[writable]` Order buffer
+    /// 3. `[writable]` Onchain order
+    /// 4. `[]` system-program
+    FinalizeOrder,
+    ///
+    /// 9
+    /// Repay the maker borrow in the delegate obligation, signed by the maker
+    SolendProxyRepayObligationLiquidity {
+        liquidity_amount: u64,
+    },
+    ///
+    /// 10
+    /// Withdraw the maker collateral from the delegate obligation, signed by the maker
+    SolendProxyWithdrawObligationCollateral {
+        collateral_amount: u64,
+    },
 }
 
 #[derive(Debug)]
@@ -121,19 +212,23 @@ pub fn fill_order(
     taker: &Pubkey,
     onchain_order: &Pubkey, 
     delegate: &Pubkey,
-    //get_maker_amount_accounts: &[Pubkey],
-    //get_taker_amount_accounts: &[Pubkey],
+    mut get_maker_amount_accounts: Vec<AccountMeta>,
+    mut get_taker_amount_accounts: Vec<AccountMeta>,
     mut predicate_accounts: Vec<AccountMeta>,
+    mut post_condition_accounts: Vec<AccountMeta>,
     mut callback_accounts: Vec<AccountMeta>,
+    mut interaction_accounts: Vec<AccountMeta>,
     taker_ta_taker_asset_account: &Pubkey,
     maker_ta_taker_asset_account: &Pubkey,
     taker_ta_maker_asset_account: &Pubkey,
     maker_ta_maker_asset_account: &Pubkey,
+    price_guard_oracle: Option<&Pubkey>,
 
     order: Option<Order>,
     making_amount: u64,
     taking_amount: u64,
     threshold_amount: u64,
+    interaction: Vec<u8>,
 
     order_stage: OrderStage,
 ) -> Instruction {
@@ -141,10 +236,14 @@ pub fn fill_order(
         order,
         making_amount,
         taking_amount,
-        //get_maker_amount_infos_num: get_maker_amount_accounts.len() as u8,
-        //get_taker_amount_infos_num: get_taker_amount_accounts.len() as u8,
+        get_maker_amount_infos_num: get_maker_amount_accounts.len() as u8,
+        get_taker_amount_infos_num: get_taker_amount_accounts.len() as u8,
         predicate_infos_num: predicate_accounts.len() as u8,
+        post_condition_infos_num: post_condition_accounts.len() as u8,
         callback_infos_num: callback_accounts.len() as u8,   
+        upload_only: matches!(order_stage, OrderStage::Create),
+        interaction,
+        interaction_infos_num: interaction_accounts.len() as u8,
     };
 
     let data = SolarisAutoInstruction::FillOrder(fill_order_args)
@@ -157,27 +256,18 @@ pub fn fill_order(
         AccountMeta::new(*onchain_order, false),
         AccountMeta::new_readonly(system_program::ID, false),
     ];
-/* 
-    if !get_maker_amount_accounts.is_empty(){
-        accounts.push(AccountMeta::new_readonly(get_maker_amount_accounts[0], false));
-        get_maker_amount_accounts[1..].iter()
-            .for_each(|id| accounts.push(AccountMeta::new(*id, false)));
-    }
-
-    if !get_taker_amount_accounts.is_empty() {
-        accounts.push(AccountMeta::new_readonly(get_taker_amount_accounts[0], false));
-        get_taker_amount_accounts[1..].iter()
-            .for_each(|id| accounts.push(AccountMeta::new(*id, false)));
-    }
-*/
 
     match order_stage {
         OrderStage::Create => {
 
         },
         OrderStage::Filled => {
+            accounts.append(&mut get_maker_amount_accounts);
+            accounts.append(&mut get_taker_amount_accounts);
             accounts.append(&mut predicate_accounts);
+            accounts.append(&mut post_condition_accounts);
             accounts.append(&mut callback_accounts);
+            accounts.append(&mut interaction_accounts);
 
             accounts.push(AccountMeta::new(*taker_ta_taker_asset_account, false));
             accounts.push(AccountMeta::new(*maker_ta_taker_asset_account, false));
@@ -185,6 +275,10 @@ pub fn fill_order(
             accounts.push(AccountMeta::new(*taker_ta_maker_asset_account, false));
             accounts.push(AccountMeta::new(*delegate, false));
             accounts.push(AccountMeta::new_readonly(spl_token::ID, false));
+
+            if let Some(price_guard_oracle) = price_guard_oracle {
+                accounts.push(AccountMeta::new_readonly(*price_guard_oracle, false));
+            }
         }, 
         OrderStage::Closed => {
 
@@ -212,6 +306,74 @@ pub fn init_delegate(
         AccountMeta::new_readonly(system_program::id(), false),
     ];
 
+    Instruction{
+        program_id: *program_id,
+        accounts,
+        data,
+    }
+}
+
+pub fn init_order_buffer(
+    program_id: &Pubkey,
+    authority: &Pubkey,
+    order_buffer: &Pubkey,
+    order_len: u32,
+) -> Instruction {
+    let data = SolarisAutoInstruction::InitOrderBuffer { order_len }
+        .try_to_vec().unwrap();
+
+    let accounts = vec![
+        AccountMeta::new(*authority, true),
+        AccountMeta::new(*order_buffer, false),
+        AccountMeta::new_readonly(system_program::id(), false),
+    ];
+
+    Instruction{
+        program_id: *program_id,
+        accounts,
+        data,
+    }
+}
+
+pub fn write_order_buffer(
+    program_id: &Pubkey,
+    authority: &Pubkey,
+    order_buffer: &Pubkey,
+    offset: u32,
+    bytes: Vec<u8>,
+) -> Instruction {
+    let data = SolarisAutoInstruction::WriteOrderBuffer { offset, bytes }
+        .try_to_vec().unwrap();
+
+    let accounts = vec![
+        AccountMeta::new_readonly(*authority, true),
+        AccountMeta::new(*order_buffer, false),
+    ];
+
+    Instruction{
+        program_id: *program_id,
+        accounts,
+        data,
+    }
+}
+
+pub fn finalize_order(
+    program_id: &Pubkey,
+    authority: &Pubkey,
+    order_buffer: &Pubkey,
+    onchain_order: &Pubkey,
+) -> Instruction {
+    let data = SolarisAutoInstruction::FinalizeOrder
+        .try_to_vec().unwrap();
+
+    let accounts = vec![
+        AccountMeta::new(*authority, true),
+        AccountMeta::new_readonly(sysvar::instructions::id(), false),
+        AccountMeta::new(*order_buffer, false),
+        AccountMeta::new(*onchain_order, false),
+        AccountMeta::new_readonly(system_program::id(), false),
+    ];
+
     Instruction{
         program_id: *program_id,
         accounts,
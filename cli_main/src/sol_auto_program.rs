@@ -11,18 +11,26 @@ use solana_sdk::{
     pubkey::Pubkey, 
     transaction::Transaction,
     keccak,
-    signature::Signer,
+    packet::PACKET_DATA_SIZE,
+    signature::{Keypair, Signer},
 };
 use borsh::BorshSerialize;
 use serde_json::Value;
 
-use crate::instruction::*;
+use crate::{
+    instruction::*,
+    create_order::parse_account_metas,
+};
 use super::{parse_json, parse_keypair};
 
 pub const PREFIX: &str = "solaris-automations";
 pub const ONCHAIN_ORDER: &str = "onchain_order";
 pub const COLLATERAL_TA: &str = "collateral_ta_v2";
 pub const DELEGATE: &str = "delegate";
+pub const ORDER_BUFFER: &str = "order_buffer";
+
+/// Order bytes per `WriteOrderBuffer` transaction
+pub const ORDER_BUFFER_CHUNK_SIZE: usize = 900;
 
 pub fn send_fill_order(
     client: RpcClient,
@@ -52,8 +60,15 @@ pub fn send_fill_order(
 
     let taking_amount = order_value["taking_amount"].as_u64().unwrap();
     let order = parse_order(&order_value);
-    let (predicate_metas, callback_metas) = parse_metas(&order_value);
+    let (
+        get_maker_amount_metas,
+        get_taker_amount_metas,
+        predicate_metas,
+        post_condition_metas,
+        callback_metas,
+    ) = parse_metas(&order_value);
     let sign = parse_signature_instr(&order_value);
+    let (interaction, interaction_metas) = parse_interaction(&settings["interaction"]);
 
     println!("Maker is {:?}", order.maker.to_string());
     println!("Taker is {:?}", payer_keypair.pubkey().to_string());
@@ -62,56 +77,165 @@ pub fn send_fill_order(
 
     let onchain_order_id = get_pda_onchain_order(&program_id, order_hash.as_ref());
 
-    let mut instructions = vec![];
-
-    let (order_arg, order_stage) = match order.callback.is_empty() {
-        true => {
-            instructions.push(sign);
-            (Some(order.clone()), OrderStage::Filled)
-        },
-        false => {
-            match client.get_account(&onchain_order_id) {
-                Ok(_) => (None, OrderStage::Filled),
-                Err(_) => {
-                    instructions.push(sign);
-                    (Some(order.clone()), OrderStage::Create)
-                },
-            }
-        },
-    };
-
     println!("order.taking_amount is {}", order.taking_amount);
 
-    instructions.push(
+    let build_fill_order = |order_arg: Option<Order>, order_stage: OrderStage| {
         fill_order(
             &program_id,
             &order.maker,
             &payer_keypair.pubkey(),
             &onchain_order_id,
             &delegate_id,
-            predicate_metas, 
-            callback_metas, 
+            get_maker_amount_metas.clone(),
+            get_taker_amount_metas.clone(),
+            predicate_metas.clone(), 
+            post_condition_metas.clone(),
+            callback_metas.clone(), 
+            interaction_metas.clone(),
             &taker_ta_taker_asset,
             &maker_ta_taker_asset,
             &taker_ta_maker_asset,
             &maker_ta_maker_asset,
+            order.price_guard.as_ref().map(|price_guard| &price_guard.pyth_price),
 
             order_arg,
             0,
             taking_amount,
             0,
+            interaction.clone(),
 
             order_stage,
-        ),
-    );
+        )
+    };
+
+    // Order is already onchain so only fill it
+    if client.get_account(&onchain_order_id).is_ok() {
+        return send_instructions(
+            &client,
+            &[build_fill_order(None, OrderStage::Filled)],
+            &payer_keypair,
+        )
+    }
+
+    let fill_instructions = [
+        sign.clone(),
+        build_fill_order(Some(order.clone()), OrderStage::Filled),
+    ];
+
+    if is_fit_into_transaction(&fill_instructions, &payer_keypair.pubkey())? {
+        println!("Order is filled with one transaction");
+
+        return send_instructions(&client, &fill_instructions, &payer_keypair)
+    }
+
+    let upload_instructions = [
+        sign.clone(),
+        build_fill_order(Some(order.clone()), OrderStage::Create),
+    ];
+
+    match is_fit_into_transaction(&upload_instructions, &payer_keypair.pubkey())? {
+        true => {
+            println!("Order doesn't fit into one transaction, upload it first");
+
+            send_instructions(&client, &upload_instructions, &payer_keypair)?;
+        },
+        false => {
+            println!("Order doesn't fit into one transaction, upload it with order buffer");
+
+            upload_order_buffer(
+                &client,
+                &program_id,
+                &payer_keypair,
+                &order,
+                sign,
+                &onchain_order_id,
+            )?;
+        },
+    }
+
+    send_instructions(
+        &client,
+        &[build_fill_order(None, OrderStage::Filled)],
+        &payer_keypair,
+    )
+}
+
+/// Streams serialized order into the order buffer by chunks
+/// and finalizes it into the onchain order
+pub fn upload_order_buffer(
+    client: &RpcClient,
+    program_id: &Pubkey,
+    payer_keypair: &Keypair,
+    order: &Order,
+    sign: Instruction,
+    onchain_order_id: &Pubkey,
+) -> Result<(), Box<dyn Error>> {
+    let order_buffer_id = get_pda_order_buffer(program_id, &payer_keypair.pubkey());
+    let order_bytes = order.try_to_vec()?;
+
+    send_instructions(
+        client,
+        &[init_order_buffer(
+            program_id,
+            &payer_keypair.pubkey(),
+            &order_buffer_id,
+            order_bytes.len() as u32,
+        )],
+        payer_keypair,
+    )?;
+
+    for (index, chunk) in order_bytes.chunks(ORDER_BUFFER_CHUNK_SIZE).enumerate() {
+        println!("Write order buffer chunk {}", index);
+
+        send_instructions(
+            client,
+            &[write_order_buffer(
+                program_id,
+                &payer_keypair.pubkey(),
+                &order_buffer_id,
+                (index * ORDER_BUFFER_CHUNK_SIZE) as u32,
+                chunk.to_vec(),
+            )],
+            payer_keypair,
+        )?;
+    }
+
+    send_instructions(
+        client,
+        &[
+            sign,
+            finalize_order(
+                program_id,
+                &payer_keypair.pubkey(),
+                &order_buffer_id,
+                onchain_order_id,
+            ),
+        ],
+        payer_keypair,
+    )
+}
+
+pub fn is_fit_into_transaction(
+    instructions: &[Instruction],
+    payer: &Pubkey,
+) -> Result<bool, Box<dyn Error>> {
+    let transaction = Transaction::new_with_payer(instructions, Some(payer));
 
+    Ok(bincode::serialize(&transaction)?.len() <= PACKET_DATA_SIZE)
+}
+
+pub fn send_instructions(
+    client: &RpcClient,
+    instructions: &[Instruction],
+    payer_keypair: &Keypair,
+) -> Result<(), Box<dyn Error>> {
     let mut transaction = Transaction::new_with_payer(
-        &instructions,
+        instructions,
         Some(&payer_keypair.pubkey()),
     );
 
     let blockhash = client.get_recent_blockhash()?.0;
-    transaction.try_sign(&[&payer_keypair], blockhash)?;
+    transaction.try_sign(&[payer_keypair], blockhash)?;
 
     client.send_and_confirm_transaction_with_spinner(&transaction)?;
 
@@ -149,6 +273,15 @@ pub fn get_pda_delegate_id(program_id: &Pubkey) -> Pubkey {
     delegate
 }
 
+pub fn get_pda_order_buffer(program_id: &Pubkey, authority: &Pubkey) -> Pubkey {
+    let (order_buffer, _) = Pubkey::find_program_address(
+        &[PREFIX.as_bytes(), ORDER_BUFFER.as_bytes(), authority.as_ref()],
+        program_id,
+    );
+
+    order_buffer
+}
+
 pub fn get_pda_onchain_order(program_id: &Pubkey, order_hash: &[u8]) -> Pubkey {
     let (onchain_order, _) = Pubkey::find_program_address(
         &[PREFIX.as_bytes(), ONCHAIN_ORDER.as_bytes(), order_hash],
@@ -167,15 +300,17 @@ pub fn parse_order(
     let maker = order["maker"].as_str().unwrap();
     let making_amount = order["making_amount"].as_u64().unwrap();
     let taking_amount = order["taking_amount"].as_u64().unwrap();
-    //let get_maker_amount = order["get_maker_amount"].as_array().unwrap();
-    //let get_taker_amount = order["get_taker_amount"].as_array().unwrap();
+    let get_maker_amount = order["get_maker_amount"].as_array().unwrap();
+    let get_taker_amount = order["get_taker_amount"].as_array().unwrap();
     let predicate = order["predicate"].as_array().unwrap();
+    let post_condition = order["post_condition"].as_array().unwrap();
     let callback = order["callback"].as_array().unwrap();
+    let price_guard = order["price_guard"].as_object();
+    let spend_limits = order["spend_limits"].as_array();
 
     let maker_asset = Pubkey::from_str(maker_asset).unwrap();
     let taker_asset = Pubkey::from_str(taker_asset).unwrap();
     let maker = Pubkey::from_str(maker).unwrap();
-    /* 
     let get_maker_amount: Vec<u8> = get_maker_amount
         .iter()
         .map(|value| value.as_u64().unwrap() as u8)
@@ -184,15 +319,33 @@ pub fn parse_order(
         .iter()
         .map(|value| value.as_u64().unwrap() as u8)
         .collect();
-    */
     let predicate: Vec<u8> = predicate
         .iter()
         .map(|value| value.as_u64().unwrap() as u8)
         .collect();
+    let post_condition: Vec<u8> = post_condition
+        .iter()
+        .map(|value| value.as_u64().unwrap() as u8)
+        .collect();
     let callback: Vec<u8> = callback
         .iter()
         .map(|value| value.as_u64().unwrap() as u8)
         .collect();
+    let price_guard = price_guard.map(|price_guard| PriceGuard {
+        pyth_price: Pubkey::from_str(price_guard["pyth_price"].as_str().unwrap()).unwrap(),
+        maker_decimals: price_guard["maker_decimals"].as_u64().unwrap() as u8,
+        taker_decimals: price_guard["taker_decimals"].as_u64().unwrap() as u8,
+        tolerance_bps: price_guard["tolerance_bps"].as_i64().unwrap(),
+    });
+    let spend_limits: Vec<SpendLimit> = spend_limits
+        .map_or(vec![], |spend_limits| spend_limits
+            .iter()
+            .map(|spend_limit| SpendLimit {
+                mint: Pubkey::from_str(spend_limit["mint"].as_str().unwrap()).unwrap(),
+                max_outflow: spend_limit["max_outflow"].as_u64().unwrap(),
+            })
+            .collect()
+        );
 
     Order {
         salt,
@@ -201,15 +354,23 @@ pub fn parse_order(
         maker,
         making_amount,
         taking_amount,
+        get_maker_amount,
+        get_taker_amount,
         predicate,
+        post_condition,
         callback,
+        price_guard,
+        spend_limits,
     }
 }
 
 pub fn parse_metas(
     order: &Value,
-) -> (Vec<AccountMeta>, Vec<AccountMeta>) {
+) -> (Vec<AccountMeta>, Vec<AccountMeta>, Vec<AccountMeta>, Vec<AccountMeta>, Vec<AccountMeta>) {
+    let get_maker_amount_metas = order["get_maker_amount_metas"].as_array().unwrap();
+    let get_taker_amount_metas = order["get_taker_amount_metas"].as_array().unwrap();
     let predicate_metas = order["predicate_metas"].as_array().unwrap();
+    let post_condition_metas = order["post_condition_metas"].as_array().unwrap();
     let callback_metas = order["callback_metas"].as_array().unwrap();
 
     let parse_account_meta = |value: &Value| {
@@ -225,17 +386,67 @@ pub fn parse_metas(
         }
     };
 
+    let get_maker_amount_metas: Vec<AccountMeta> = get_maker_amount_metas
+        .iter()
+        .map(parse_account_meta)
+        .collect();
+
+    let get_taker_amount_metas: Vec<AccountMeta> = get_taker_amount_metas
+        .iter()
+        .map(parse_account_meta)
+        .collect();
+
     let predicate_metas: Vec<AccountMeta> = predicate_metas
         .iter()
         .map(parse_account_meta)
         .collect();
 
+    let post_condition_metas: Vec<AccountMeta> = post_condition_metas
+        .iter()
+        .map(parse_account_meta)
+        .collect();
+
     let callback_metas: Vec<AccountMeta> = callback_metas
         .iter()
         .map(parse_account_meta)
         .collect();
 
-    (predicate_metas, callback_metas)
+    (
+        get_maker_amount_metas,
+        get_taker_amount_metas,
+        predicate_metas,
+        post_condition_metas,
+        callback_metas,
+    )
+}
+
+/// Optional taker interaction from settings in format
+/// `{ "program": <pubkey>, "data": [<u8>], "accounts": [<account meta>] }`
+pub fn parse_interaction(
+    interaction: &Value,
+) -> (Vec<u8>, Vec<AccountMeta>) {
+    let program = match interaction["program"].as_str() {
+        Some(program) => Pubkey::from_str(program).unwrap(),
+        None => return (vec![], vec![]),
+    };
+
+    let data: Vec<u8> = interaction["data"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|value| value.as_u64().unwrap() as u8)
+        .collect();
+
+    let instruction = Instruction {
+        program_id: program,
+        accounts: parse_account_metas(&interaction["accounts"]),
+        data,
+    };
+
+    let mut metas = instruction.accounts.clone();
+    metas.insert(0, AccountMeta::new_readonly(program, false));
+
+    (bincode::serialize(&instruction).unwrap(), metas)
 }
 
 pub fn parse_signature_instr(
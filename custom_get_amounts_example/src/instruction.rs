@@ -6,13 +6,19 @@ use borsh::{
 
 #[derive(BorshSchema, BorshSerialize, BorshDeserialize)]
 pub enum CustomGetAmountsInstruction {
-    /// Custom get [taker/maker] amount instructions 
+    /// Custom get [taker/maker] amount instructions. 
+    /// Order stores only the instruction tag, swap amount
+    /// is appended by Solaris automations program at fill.
     ///
     /// Accounts expected:
     /// 
-    GetMakerAmount,
+    GetMakerAmount {
+        swap_taker_amount: u64,
+    },
     ///
     /// Accounts expected:
     /// 
-    GetTakerAmount,
+    GetTakerAmount {
+        swap_maker_amount: u64,
+    },
 }
@@ -23,28 +23,34 @@ impl Processor {
         let instruction = CustomGetAmountsInstruction::try_from_slice(instruction_data)?;
 
         match instruction {
-            CustomGetAmountsInstruction::GetMakerAmount
-            => {
+            CustomGetAmountsInstruction::GetMakerAmount {
+                swap_taker_amount,
+            } => {
                 Self::process_get_maker_amount(
                     program_id,
                     accounts,
+                    swap_taker_amount,
                 )
             },
-            CustomGetAmountsInstruction::GetTakerAmount 
-            => {
+            CustomGetAmountsInstruction::GetTakerAmount {
+                swap_maker_amount,
+            } => {
                 Self::process_get_taker_amount(
                     program_id,
                     accounts,
+                    swap_maker_amount,
                 )
             }
         }
     }
 
+    // Example with the fixed price: 1 maker token for 2 taker tokens
     pub fn process_get_maker_amount(
         program_id: &Pubkey,
         accounts: &[AccountInfo],
+        swap_taker_amount: u64,
     ) -> ProgramResult {
-        let maker_amount: u64 = 1_000_000_000;
+        let maker_amount: u64 = swap_taker_amount / 2;
 
         set_return_data(&maker_amount.to_le_bytes());
 
@@ -54,7 +60,14 @@ impl Processor {
     pub fn process_get_taker_amount(
         program_id: &Pubkey,
         accounts: &[AccountInfo],
+        swap_maker_amount: u64,
     ) -> ProgramResult {
+        let taker_amount = swap_maker_amount
+            .checked_mul(2)
+            .ok_or(ProgramError::InvalidArgument)?;
+
+        set_return_data(&taker_amount.to_le_bytes());
+
         Ok(())
     }
 }
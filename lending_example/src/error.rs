@@ -0,0 +1,76 @@
+use thiserror::Error;
+use num_derive::FromPrimitive;
+use solana_program::{
+    program_error::{
+        PrintProgramError,
+        ProgramError
+    },
+    decode_error::DecodeError,
+    msg,
+};
+
+#[derive(Error, Debug, Copy, Clone, FromPrimitive)]
+pub enum LendingError {
+    #[error("Failed to unpack instruction data")]
+    InstructionUnpackError,
+
+    #[error("Input account must not be this one")]
+    InvalidAccountInput,
+
+    #[error("Invalid program address generated from lending market")]
+    InvalidMarketAuthority,
+
+    #[error("Input obligation owner is not the expected owner or didn't sign")]
+    InvalidObligationOwner,
+
+    #[error("Input oracle is not the reserve oracle or has no price")]
+    InvalidOracleConfig,
+
+    #[error("Input amount is invalid")]
+    InvalidAmount,
+
+    #[error("Reserve state needs to be refreshed")]
+    ReserveStale,
+
+    #[error("Obligation state needs to be refreshed")]
+    ObligationStale,
+
+    #[error("Obligation has no collateral in the reserve")]
+    ObligationCollateralEmpty,
+
+    #[error("Obligation has no liquidity borrowed from the reserve")]
+    ObligationLiquidityEmpty,
+
+    #[error("Obligation reserve limit exceeded")]
+    ObligationReserveLimit,
+
+    #[error("Not enough liquidity available in the reserve")]
+    InsufficientLiquidity,
+
+    #[error("Borrow exceeds the allowed borrow value of the obligation")]
+    BorrowTooLarge,
+
+    #[error("Withdraw leaves the obligation below its borrows")]
+    WithdrawTooLarge,
+
+    #[error("Math operation overflow")]
+    MathOverflow,
+}
+
+impl PrintProgramError for LendingError {
+    fn print<E>(&self) {
+        msg!(&self.to_string());
+    }
+}
+
+impl<T> DecodeError<T> for LendingError {
+    fn type_of() -> &'static str {
+        "Lending Error"
+    }
+}
+
+impl From<LendingError> for ProgramError {
+    fn from(e: LendingError) -> Self {
+        ProgramError::Custom(e as u32)
+    }
+}
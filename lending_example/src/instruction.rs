@@ -0,0 +1,146 @@
+use std::convert::TryInto;
+use solana_program::program_error::ProgramError;
+
+use crate::error::LendingError;
+
+/// Solend instructions used by the callbacks. Tags, accounts and data are
+/// the same as in the Solend token-lending program.
+pub enum LendingInstruction {
+    /// 3
+    /// Sets the reserve liquidity price from the oracle.
+    ///
+    /// Accounts expected:
+    ///
+    /// 0. `[writable]` Reserve account
+    /// 1. `[]` Pyth price account: price of one whole liquidity token
+    ///     in the quote currency as `u64` at offset 0
+    /// 2. `[]` Switchboard price account, ignored
+    /// 3. `[]` Clock sysvar
+    RefreshReserve,
+    /// 7
+    /// Updates market values of the obligation from the refreshed reserves.
+    ///
+    /// Accounts expected:
+    ///
+    /// 0. `[writable]` Obligation account
+    /// 1. `[]` Clock sysvar
+    /// 2.. `[]` Reserve accounts refreshed in the current slot:
+    ///     deposit reserves and then borrow reserves in order of the obligation
+    RefreshObligation,
+    /// 10
+    /// Borrows liquidity against the obligation collateral, no borrow fee is charged.
+    ///
+    /// Accounts expected:
+    ///
+    /// 0. `[writable]` Reserve liquidity supply
+    /// 1. `[writable]` Destination liquidity token account
+    /// 2. `[writable]` Borrow reserve account, refreshed
+    /// 3. `[writable]` Borrow reserve fee receiver, ignored
+    /// 4. `[writable]` Obligation account, refreshed
+    /// 5. `[]` Lending market account
+    /// 6. `[]` Lending market authority derived from `[lending market]`
+    /// 7. `[signer]` Obligation owner
+    /// 8. `[]` Clock sysvar
+    /// 9. `[]` Token program id
+    BorrowObligationLiquidity {
+        liquidity_amount: u64,
+    },
+    /// 11
+    /// Repays borrowed liquidity, at most the borrowed amount.
+    ///
+    /// Accounts expected:
+    ///
+    /// 0. `[writable]` Source liquidity token account
+    /// 1. `[writable]` Reserve liquidity supply
+    /// 2. `[writable]` Repay reserve account, refreshed
+    /// 3. `[writable]` Obligation account
+    /// 4. `[]` Lending market account
+    /// 5. `[signer]` User transfer authority
+    /// 6. `[]` Clock sysvar
+    /// 7. `[]` Token program id
+    RepayObligationLiquidity {
+        liquidity_amount: u64,
+    },
+    /// 14
+    /// Refreshes the reserve, deposits liquidity for collateral and
+    /// deposits the collateral into the obligation.
+    ///
+    /// Accounts expected:
+    ///
+    /// 0. `[writable]` Source liquidity token account
+    /// 1. `[writable]` User collateral token account
+    /// 2. `[writable]` Reserve account
+    /// 3. `[writable]` Reserve liquidity supply
+    /// 4. `[writable]` Reserve collateral mint
+    /// 5. `[]` Lending market account
+    /// 6. `[]` Lending market authority derived from `[lending market]`
+    /// 7. `[writable]` Reserve collateral supply
+    /// 8. `[writable]` Obligation account
+    /// 9. `[signer]` Obligation owner
+    /// 10 `[]` Pyth price account
+    /// 11 `[]` Switchboard price account, ignored
+    /// 12 `[signer]` User transfer authority
+    /// 13 `[]` Clock sysvar
+    /// 14 `[]` Token program id
+    DepositReserveLiquidityAndObligationCollateral {
+        liquidity_amount: u64,
+    },
+    /// 15
+    /// Withdraws collateral from the obligation and redeems it for liquidity.
+    /// `u64::MAX` withdraws as much as the borrows allow.
+    ///
+    /// Accounts expected:
+    ///
+    /// 0. `[writable]` Reserve collateral supply
+    /// 1. `[writable]` User collateral token account
+    /// 2. `[writable]` Withdraw reserve account, refreshed
+    /// 3. `[writable]` Obligation account, refreshed
+    /// 4. `[]` Lending market account
+    /// 5. `[]` Lending market authority derived from `[lending market]`
+    /// 6. `[writable]` Destination liquidity token account
+    /// 7. `[writable]` Reserve collateral mint
+    /// 8. `[writable]` Reserve liquidity supply
+    /// 9. `[signer]` Obligation owner
+    /// 10 `[signer]` User transfer authority
+    /// 11 `[]` Clock sysvar
+    /// 12 `[]` Token program id
+    WithdrawObligationCollateralAndRedeemReserveCollateral {
+        collateral_amount: u64,
+    },
+}
+
+impl LendingInstruction {
+    pub fn unpack(input: &[u8]) -> Result<Self, ProgramError> {
+        let (tag, rest) = input
+            .split_first()
+            .ok_or(LendingError::InstructionUnpackError)?;
+
+        Ok(match tag {
+            3 => Self::RefreshReserve,
+            7 => Self::RefreshObligation,
+            10 => Self::BorrowObligationLiquidity {
+                liquidity_amount: Self::unpack_u64(rest)?,
+            },
+            11 => Self::RepayObligationLiquidity {
+                liquidity_amount: Self::unpack_u64(rest)?,
+            },
+            14 => Self::DepositReserveLiquidityAndObligationCollateral {
+                liquidity_amount: Self::unpack_u64(rest)?,
+            },
+            15 => Self::WithdrawObligationCollateralAndRedeemReserveCollateral {
+                collateral_amount: Self::unpack_u64(rest)?,
+            },
+            _ => return Err(LendingError::InstructionUnpackError.into()),
+        })
+    }
+
+    fn unpack_u64(input: &[u8]) -> Result<u64, ProgramError> {
+        let amount = input
+            .get(..8)
+            .and_then(|slice| slice.try_into().ok())
+            .map(u64::from_le_bytes)
+            .ok_or(LendingError::InstructionUnpackError)?;
+
+        Ok(amount)
+    }
+}
@@ -0,0 +1,732 @@
+use std::convert::TryInto;
+use solana_program::{
+    account_info::{next_account_info, AccountInfo},
+    clock::Clock,
+    entrypoint::ProgramResult,
+    msg,
+    program::{invoke, invoke_signed},
+    program_error::ProgramError,
+    program_pack::Pack,
+    pubkey::Pubkey,
+    sysvar::Sysvar,
+};
+
+use crate::{
+    error::LendingError,
+    instruction::LendingInstruction,
+    state::{
+        get_lending_market_authority,
+        LastUpdate,
+        Obligation,
+        ObligationCollateral,
+        ObligationLiquidity,
+        Reserve,
+        WAD,
+    },
+};
+
+pub struct Processor;
+impl Processor {
+    pub fn process(
+        program_id: &Pubkey,
+        accounts: &[AccountInfo],
+        instruction_data: &[u8],
+    ) -> ProgramResult {
+        let instruction = LendingInstruction::unpack(instruction_data)?;
+
+        match instruction {
+            LendingInstruction::RefreshReserve => {
+                msg!("Instruction: RefreshReserve");
+                Self::process_refresh_reserve(program_id, accounts)
+            },
+            LendingInstruction::RefreshObligation => {
+                msg!("Instruction: RefreshObligation");
+                Self::process_refresh_obligation(program_id, accounts)
+            },
+            LendingInstruction::BorrowObligationLiquidity {
+                liquidity_amount,
+            } => {
+                msg!("Instruction: BorrowObligationLiquidity");
+                Self::process_borrow_obligation_liquidity(program_id, accounts, liquidity_amount)
+            },
+            LendingInstruction::RepayObligationLiquidity {
+                liquidity_amount,
+            } => {
+                msg!("Instruction: RepayObligationLiquidity");
+                Self::process_repay_obligation_liquidity(program_id, accounts, liquidity_amount)
+            },
+            LendingInstruction::DepositReserveLiquidityAndObligationCollateral {
+                liquidity_amount,
+            } => {
+                msg!("Instruction: DepositReserveLiquidityAndObligationCollateral");
+                Self::process_deposit_reserve_liquidity_and_obligation_collateral(program_id, accounts, liquidity_amount)
+            },
+            LendingInstruction::WithdrawObligationCollateralAndRedeemReserveCollateral {
+                collateral_amount,
+            } => {
+                msg!("Instruction: WithdrawObligationCollateralAndRedeemReserveCollateral");
+                Self::process_withdraw_obligation_collateral_and_redeem_reserve_collateral(program_id, accounts, collateral_amount)
+            },
+        }
+    }
+
+    pub fn process_refresh_reserve(
+        program_id: &Pubkey,
+        accounts: &[AccountInfo],
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+
+        let reserve_info = next_account_info(account_info_iter)?;
+        let pyth_price_info = next_account_info(account_info_iter)?;
+        let _switchboard_price_info = next_account_info(account_info_iter)?;
+        let clock = Clock::from_account_info(next_account_info(account_info_iter)?)?;
+
+        let mut reserve = Self::load_reserve(program_id, reserve_info)?;
+
+        Self::refresh_reserve(&mut reserve, pyth_price_info, &clock)?;
+
+        Reserve::pack(reserve, &mut reserve_info.data.borrow_mut())
+    }
+
+    pub fn process_refresh_obligation(
+        program_id: &Pubkey,
+        accounts: &[AccountInfo],
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+
+        let obligation_info = next_account_info(account_info_iter)?;
+        let clock = Clock::from_account_info(next_account_info(account_info_iter)?)?;
+
+        let mut obligation = Self::load_obligation(program_id, obligation_info)?;
+
+        let mut deposited_value: u128 = 0;
+        let mut allowed_borrow_value: u128 = 0;
+        let mut unhealthy_borrow_value: u128 = 0;
+        let mut borrowed_value: u128 = 0;
+
+        for collateral in obligation.deposits.iter_mut() {
+            let reserve = Self::load_fresh_reserve(program_id, account_info_iter, &collateral.deposit_reserve, &clock)?;
+
+            let liquidity_amount = reserve
+                .collateral_to_liquidity(collateral.deposited_amount)
+                .ok_or(LendingError::MathOverflow)?;
+
+            collateral.market_value = reserve
+                .market_value((liquidity_amount as u128).checked_mul(WAD).ok_or(LendingError::MathOverflow)?)
+                .ok_or(LendingError::MathOverflow)?;
+
+            deposited_value = deposited_value
+                .checked_add(collateral.market_value)
+                .ok_or(LendingError::MathOverflow)?;
+            allowed_borrow_value = allowed_borrow_value
+                .checked_add(collateral.market_value * reserve.loan_to_value_ratio as u128 / 100)
+                .ok_or(LendingError::MathOverflow)?;
+            unhealthy_borrow_value = unhealthy_borrow_value
+                .checked_add(collateral.market_value * reserve.liquidation_threshold as u128 / 100)
+                .ok_or(LendingError::MathOverflow)?;
+        }
+
+        for liquidity in obligation.borrows.iter_mut() {
+            let reserve = Self::load_fresh_reserve(program_id, account_info_iter, &liquidity.borrow_reserve, &clock)?;
+
+            liquidity.market_value = reserve
+                .market_value(liquidity.borrowed_amount_wads)
+                .ok_or(LendingError::MathOverflow)?;
+
+            borrowed_value = borrowed_value
+                .checked_add(liquidity.market_value)
+                .ok_or(LendingError::MathOverflow)?;
+        }
+
+        if account_info_iter.next().is_some() {
+            return Err(LendingError::InvalidAccountInput.into())
+        }
+
+        obligation.deposited_value = deposited_value;
+        obligation.borrowed_value = borrowed_value;
+        obligation.allowed_borrow_value = allowed_borrow_value;
+        obligation.unhealthy_borrow_value = unhealthy_borrow_value;
+        obligation.last_update = LastUpdate::new(clock.slot);
+
+        Obligation::pack(obligation, &mut obligation_info.data.borrow_mut())
+    }
+
+    pub fn process_borrow_obligation_liquidity(
+        program_id: &Pubkey,
+        accounts: &[AccountInfo],
+        liquidity_amount: u64,
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+
+        let source_liquidity_info = next_account_info(account_info_iter)?;
+        let destination_liquidity_info = next_account_info(account_info_iter)?;
+        let borrow_reserve_info = next_account_info(account_info_iter)?;
+        let _fee_receiver_info = next_account_info(account_info_iter)?;
+        let obligation_info = next_account_info(account_info_iter)?;
+        let lending_market_info = next_account_info(account_info_iter)?;
+        let lending_market_authority_info = next_account_info(account_info_iter)?;
+        let obligation_owner_info = next_account_info(account_info_iter)?;
+        let clock = Clock::from_account_info(next_account_info(account_info_iter)?)?;
+        let token_program_info = next_account_info(account_info_iter)?;
+
+        if liquidity_amount == 0 {
+            return Err(LendingError::InvalidAmount.into())
+        }
+
+        let mut reserve = Self::load_reserve(program_id, borrow_reserve_info)?;
+        let mut obligation = Self::load_obligation(program_id, obligation_info)?;
+
+        Self::assert_obligation_owner(&obligation, obligation_owner_info)?;
+        let authority_bump = Self::assert_market(program_id, &reserve, &obligation, lending_market_info, lending_market_authority_info)?;
+
+        if *source_liquidity_info.key != reserve.liquidity_supply {
+            return Err(LendingError::InvalidAccountInput.into())
+        }
+
+        if !reserve.last_update.is_fresh(clock.slot) {
+            return Err(LendingError::ReserveStale.into())
+        }
+
+        if !obligation.last_update.is_fresh(clock.slot) {
+            return Err(LendingError::ObligationStale.into())
+        }
+
+        if liquidity_amount > reserve.liquidity_available_amount {
+            return Err(LendingError::InsufficientLiquidity.into())
+        }
+
+        let borrow_amount_wads = (liquidity_amount as u128)
+            .checked_mul(WAD)
+            .ok_or(LendingError::MathOverflow)?;
+        let borrow_value = reserve
+            .market_value(borrow_amount_wads)
+            .ok_or(LendingError::MathOverflow)?;
+
+        if obligation.borrowed_value.checked_add(borrow_value).ok_or(LendingError::MathOverflow)? > obligation.allowed_borrow_value {
+            return Err(LendingError::BorrowTooLarge.into())
+        }
+
+        reserve.liquidity_available_amount -= liquidity_amount;
+        reserve.liquidity_borrowed_amount_wads = reserve.liquidity_borrowed_amount_wads
+            .checked_add(borrow_amount_wads)
+            .ok_or(LendingError::MathOverflow)?;
+        reserve.last_update.mark_stale();
+
+        match obligation.find_liquidity_index(borrow_reserve_info.key) {
+            Some(index) => {
+                let liquidity = &mut obligation.borrows[index];
+
+                liquidity.borrowed_amount_wads = liquidity.borrowed_amount_wads
+                    .checked_add(borrow_amount_wads)
+                    .ok_or(LendingError::MathOverflow)?;
+            },
+            None => {
+                if obligation.is_full() {
+                    return Err(LendingError::ObligationReserveLimit.into())
+                }
+
+                obligation.borrows.push(ObligationLiquidity {
+                    borrow_reserve: *borrow_reserve_info.key,
+                    cumulative_borrow_rate_wads: reserve.liquidity_cumulative_borrow_rate_wads,
+                    borrowed_amount_wads: borrow_amount_wads,
+                    market_value: 0,
+                });
+            },
+        }
+        obligation.last_update.mark_stale();
+
+        Reserve::pack(reserve, &mut borrow_reserve_info.data.borrow_mut())?;
+        Obligation::pack(obligation, &mut obligation_info.data.borrow_mut())?;
+
+        invoke_signed(
+            &spl_token::instruction::transfer(
+                token_program_info.key,
+                source_liquidity_info.key,
+                destination_liquidity_info.key,
+                lending_market_authority_info.key,
+                &[],
+                liquidity_amount,
+            )?,
+            &[
+                source_liquidity_info.clone(),
+                destination_liquidity_info.clone(),
+                lending_market_authority_info.clone(),
+                token_program_info.clone(),
+            ],
+            &[&[lending_market_info.key.as_ref(), &[authority_bump]]],
+        )
+    }
+
+    pub fn process_repay_obligation_liquidity(
+        program_id: &Pubkey,
+        accounts: &[AccountInfo],
+        liquidity_amount: u64,
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+
+        let source_liquidity_info = next_account_info(account_info_iter)?;
+        let destination_liquidity_info = next_account_info(account_info_iter)?;
+        let repay_reserve_info = next_account_info(account_info_iter)?;
+        let obligation_info = next_account_info(account_info_iter)?;
+        let lending_market_info = next_account_info(account_info_iter)?;
+        let user_transfer_authority_info = next_account_info(account_info_iter)?;
+        let clock = Clock::from_account_info(next_account_info(account_info_iter)?)?;
+        let token_program_info = next_account_info(account_info_iter)?;
+
+        let mut reserve = Self::load_reserve(program_id, repay_reserve_info)?;
+        let mut obligation = Self::load_obligation(program_id, obligation_info)?;
+
+        if reserve.lending_market != *lending_market_info.key
+            || obligation.lending_market != *lending_market_info.key
+            || *destination_liquidity_info.key != reserve.liquidity_supply {
+            return Err(LendingError::InvalidAccountInput.into())
+        }
+
+        if !reserve.last_update.is_fresh(clock.slot) {
+            return Err(LendingError::ReserveStale.into())
+        }
+
+        let index = obligation
+            .find_liquidity_index(repay_reserve_info.key)
+            .ok_or(LendingError::ObligationLiquidityEmpty)?;
+        let liquidity = &mut obligation.borrows[index];
+
+        let repay_amount = liquidity_amount.min(liquidity.borrowed_amount());
+
+        if repay_amount == 0 {
+            return Err(LendingError::InvalidAmount.into())
+        }
+
+        let settle_amount_wads = (repay_amount as u128 * WAD).min(liquidity.borrowed_amount_wads);
+
+        liquidity.borrowed_amount_wads -= settle_amount_wads;
+
+        if liquidity.borrowed_amount_wads == 0 {
+            obligation.borrows.remove(index);
+        }
+        obligation.last_update.mark_stale();
+
+        reserve.liquidity_available_amount = reserve.liquidity_available_amount
+            .checked_add(repay_amount)
+            .ok_or(LendingError::MathOverflow)?;
+        reserve.liquidity_borrowed_amount_wads = reserve.liquidity_borrowed_amount_wads
+            .saturating_sub(settle_amount_wads);
+        reserve.last_update.mark_stale();
+
+        Reserve::pack(reserve, &mut repay_reserve_info.data.borrow_mut())?;
+        Obligation::pack(obligation, &mut obligation_info.data.borrow_mut())?;
+
+        invoke(
+            &spl_token::instruction::transfer(
+                token_program_info.key,
+                source_liquidity_info.key,
+                destination_liquidity_info.key,
+                user_transfer_authority_info.key,
+                &[],
+                repay_amount,
+            )?,
+            &[
+                source_liquidity_info.clone(),
+                destination_liquidity_info.clone(),
+                user_transfer_authority_info.clone(),
+                token_program_info.clone(),
+            ],
+        )
+    }
+
+    pub fn process_deposit_reserve_liquidity_and_obligation_collateral(
+        program_id: &Pubkey,
+        accounts: &[AccountInfo],
+        liquidity_amount: u64,
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+
+        let source_liquidity_info = next_account_info(account_info_iter)?;
+        let user_collateral_info = next_account_info(account_info_iter)?;
+        let reserve_info = next_account_info(account_info_iter)?;
+        let reserve_liquidity_supply_info = next_account_info(account_info_iter)?;
+        let reserve_collateral_mint_info = next_account_info(account_info_iter)?;
+        let lending_market_info = next_account_info(account_info_iter)?;
+        let lending_market_authority_info = next_account_info(account_info_iter)?;
+        let reserve_collateral_supply_info = next_account_info(account_info_iter)?;
+        let obligation_info = next_account_info(account_info_iter)?;
+        let obligation_owner_info = next_account_info(account_info_iter)?;
+        let pyth_price_info = next_account_info(account_info_iter)?;
+        let _switchboard_price_info = next_account_info(account_info_iter)?;
+        let user_transfer_authority_info = next_account_info(account_info_iter)?;
+        let clock = Clock::from_account_info(next_account_info(account_info_iter)?)?;
+        let token_program_info = next_account_info(account_info_iter)?;
+
+        if liquidity_amount == 0 {
+            return Err(LendingError::InvalidAmount.into())
+        }
+
+        let mut reserve = Self::load_reserve(program_id, reserve_info)?;
+        let mut obligation = Self::load_obligation(program_id, obligation_info)?;
+
+        Self::assert_obligation_owner(&obligation, obligation_owner_info)?;
+        let authority_bump = Self::assert_market(program_id, &reserve, &obligation, lending_market_info, lending_market_authority_info)?;
+
+        if *reserve_liquidity_supply_info.key != reserve.liquidity_supply
+            || *reserve_collateral_mint_info.key != reserve.collateral_mint
+            || *reserve_collateral_supply_info.key != reserve.collateral_supply {
+            return Err(LendingError::InvalidAccountInput.into())
+        }
+
+        Self::refresh_reserve(&mut reserve, pyth_price_info, &clock)?;
+
+        let collateral_amount = reserve
+            .liquidity_to_collateral(liquidity_amount)
+            .ok_or(LendingError::MathOverflow)?;
+
+        if collateral_amount == 0 {
+            return Err(LendingError::InvalidAmount.into())
+        }
+
+        reserve.liquidity_available_amount = reserve.liquidity_available_amount
+            .checked_add(liquidity_amount)
+            .ok_or(LendingError::MathOverflow)?;
+        reserve.collateral_mint_total_supply = reserve.collateral_mint_total_supply
+            .checked_add(collateral_amount)
+            .ok_or(LendingError::MathOverflow)?;
+        reserve.last_update.mark_stale();
+
+        match obligation.find_collateral_index(reserve_info.key) {
+            Some(index) => {
+                let collateral = &mut obligation.deposits[index];
+
+                collateral.deposited_amount = collateral.deposited_amount
+                    .checked_add(collateral_amount)
+                    .ok_or(LendingError::MathOverflow)?;
+            },
+            None => {
+                if obligation.is_full() {
+                    return Err(LendingError::ObligationReserveLimit.into())
+                }
+
+                obligation.deposits.push(ObligationCollateral {
+                    deposit_reserve: *reserve_info.key,
+                    deposited_amount: collateral_amount,
+                    market_value: 0,
+                });
+            },
+        }
+        obligation.last_update.mark_stale();
+
+        Reserve::pack(reserve, &mut reserve_info.data.borrow_mut())?;
+        Obligation::pack(obligation, &mut obligation_info.data.borrow_mut())?;
+
+        invoke(
+            &spl_token::instruction::transfer(
+                token_program_info.key,
+                source_liquidity_info.key,
+                reserve_liquidity_supply_info.key,
+                user_transfer_authority_info.key,
+                &[],
+                liquidity_amount,
+            )?,
+            &[
+                source_liquidity_info.clone(),
+                reserve_liquidity_supply_info.clone(),
+                user_transfer_authority_info.clone(),
+                token_program_info.clone(),
+            ],
+        )?;
+
+        invoke_signed(
+            &spl_token::instruction::mint_to(
+                token_program_info.key,
+                reserve_collateral_mint_info.key,
+                user_collateral_info.key,
+                lending_market_authority_info.key,
+                &[],
+                collateral_amount,
+            )?,
+            &[
+                reserve_collateral_mint_info.clone(),
+                user_collateral_info.clone(),
+                lending_market_authority_info.clone(),
+                token_program_info.clone(),
+            ],
+            &[&[lending_market_info.key.as_ref(), &[authority_bump]]],
+        )?;
+
+        invoke(
+            &spl_token::instruction::transfer(
+                token_program_info.key,
+                user_collateral_info.key,
+                reserve_collateral_supply_info.key,
+                user_transfer_authority_info.key,
+                &[],
+                collateral_amount,
+            )?,
+            &[
+                user_collateral_info.clone(),
+                reserve_collateral_supply_info.clone(),
+                user_transfer_authority_info.clone(),
+                token_program_info.clone(),
+            ],
+        )
+    }
+
+    pub fn process_withdraw_obligation_collateral_and_redeem_reserve_collateral(
+        program_id: &Pubkey,
+        accounts: &[AccountInfo],
+        collateral_amount: u64,
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+
+        let reserve_collateral_supply_info = next_account_info(account_info_iter)?;
+        let user_collateral_info = next_account_info(account_info_iter)?;
+        let reserve_info = next_account_info(account_info_iter)?;
+        let obligation_info = next_account_info(account_info_iter)?;
+        let lending_market_info = next_account_info(account_info_iter)?;
+        let lending_market_authority_info = next_account_info(account_info_iter)?;
+        let destination_liquidity_info = next_account_info(account_info_iter)?;
+        let reserve_collateral_mint_info = next_account_info(account_info_iter)?;
+        let reserve_liquidity_supply_info = next_account_info(account_info_iter)?;
+        let obligation_owner_info = next_account_info(account_info_iter)?;
+        let user_transfer_authority_info = next_account_info(account_info_iter)?;
+        let clock = Clock::from_account_info(next_account_info(account_info_iter)?)?;
+        let token_program_info = next_account_info(account_info_iter)?;
+
+        let mut reserve = Self::load_reserve(program_id, reserve_info)?;
+        let mut obligation = Self::load_obligation(program_id, obligation_info)?;
+
+        Self::assert_obligation_owner(&obligation, obligation_owner_info)?;
+        let authority_bump = Self::assert_market(program_id, &reserve, &obligation, lending_market_info, lending_market_authority_info)?;
+
+        if *reserve_liquidity_supply_info.key != reserve.liquidity_supply
+            || *reserve_collateral_mint_info.key != reserve.collateral_mint
+            || *reserve_collateral_supply_info.key != reserve.collateral_supply {
+            return Err(LendingError::InvalidAccountInput.into())
+        }
+
+        if !reserve.last_update.is_fresh(clock.slot) {
+            return Err(LendingError::ReserveStale.into())
+        }
+
+        if !obligation.last_update.is_fresh(clock.slot) {
+            return Err(LendingError::ObligationStale.into())
+        }
+
+        let index = obligation
+            .find_collateral_index(reserve_info.key)
+            .ok_or(LendingError::ObligationCollateralEmpty)?;
+
+        let withdraw_amount = Self::get_withdraw_amount(&obligation, &reserve, index, collateral_amount)?;
+
+        if withdraw_amount == 0 {
+            return Err(LendingError::InvalidAmount.into())
+        }
+
+        let liquidity_amount = reserve
+            .collateral_to_liquidity(withdraw_amount)
+            .ok_or(LendingError::MathOverflow)?;
+
+        let collateral = &mut obligation.deposits[index];
+
+        collateral.deposited_amount -= withdraw_amount;
+
+        if collateral.deposited_amount == 0 {
+            obligation.deposits.remove(index);
+        }
+        obligation.last_update.mark_stale();
+
+        reserve.liquidity_available_amount = reserve.liquidity_available_amount
+            .checked_sub(liquidity_amount)
+            .ok_or(LendingError::InsufficientLiquidity)?;
+        reserve.collateral_mint_total_supply -= withdraw_amount;
+        reserve.last_update.mark_stale();
+
+        Reserve::pack(reserve, &mut reserve_info.data.borrow_mut())?;
+        Obligation::pack(obligation, &mut obligation_info.data.borrow_mut())?;
+
+        invoke_signed(
+            &spl_token::instruction::transfer(
+                token_program_info.key,
+                reserve_collateral_supply_info.key,
+                user_collateral_info.key,
+                lending_market_authority_info.key,
+                &[],
+                withdraw_amount,
+            )?,
+            &[
+                reserve_collateral_supply_info.clone(),
+                user_collateral_info.clone(),
+                lending_market_authority_info.clone(),
+                token_program_info.clone(),
+            ],
+            &[&[lending_market_info.key.as_ref(), &[authority_bump]]],
+        )?;
+
+        invoke(
+            &spl_token::instruction::burn(
+                token_program_info.key,
+                user_collateral_info.key,
+                reserve_collateral_mint_info.key,
+                user_transfer_authority_info.key,
+                &[],
+                withdraw_amount,
+            )?,
+            &[
+                user_collateral_info.clone(),
+                reserve_collateral_mint_info.clone(),
+                user_transfer_authority_info.clone(),
+                token_program_info.clone(),
+            ],
+        )?;
+
+        invoke_signed(
+            &spl_token::instruction::transfer(
+                token_program_info.key,
+                reserve_liquidity_supply_info.key,
+                destination_liquidity_info.key,
+                lending_market_authority_info.key,
+                &[],
+                liquidity_amount,
+            )?,
+            &[
+                reserve_liquidity_supply_info.clone(),
+                destination_liquidity_info.clone(),
+                lending_market_authority_info.clone(),
+                token_program_info.clone(),
+            ],
+            &[&[lending_market_info.key.as_ref(), &[authority_bump]]],
+        )
+    }
+
+    /// Collateral that can be withdrawn keeping the allowed borrow value
+    /// above the borrowed value. `u64::MAX` is the maximum
+    fn get_withdraw_amount(
+        obligation: &Obligation,
+        reserve: &Reserve,
+        index: usize,
+        collateral_amount: u64,
+    ) -> Result<u64, ProgramError> {
+        let collateral = &obligation.deposits[index];
+
+        if obligation.borrows.is_empty() || reserve.loan_to_value_ratio == 0 || collateral.market_value == 0 {
+            return match collateral_amount {
+                u64::MAX => Ok(collateral.deposited_amount),
+                _ if collateral_amount > collateral.deposited_amount => Err(LendingError::WithdrawTooLarge.into()),
+                _ => Ok(collateral_amount),
+            }
+        }
+
+        let max_withdraw_value = obligation.allowed_borrow_value
+            .checked_sub(obligation.borrowed_value)
+            .ok_or(LendingError::WithdrawTooLarge)?
+            .checked_mul(100)
+            .ok_or(LendingError::MathOverflow)?
+            / reserve.loan_to_value_ratio as u128;
+
+        let max_withdraw_amount: u64 = max_withdraw_value
+            .checked_mul(collateral.deposited_amount as u128)
+            .ok_or(LendingError::MathOverflow)?
+            .checked_div(collateral.market_value)
+            .ok_or(LendingError::MathOverflow)?
+            .min(collateral.deposited_amount as u128)
+            .try_into()
+            .map_err(|_| LendingError::MathOverflow)?;
+
+        match collateral_amount {
+            u64::MAX => Ok(max_withdraw_amount),
+            _ if collateral_amount > max_withdraw_amount => Err(LendingError::WithdrawTooLarge.into()),
+            _ => Ok(collateral_amount),
+        }
+    }
+
+    /// Sets the liquidity price from the example oracle: price of one
+    /// whole token in the quote currency as `u64` at offset 0
+    fn refresh_reserve(
+        reserve: &mut Reserve,
+        pyth_price_info: &AccountInfo,
+        clock: &Clock,
+    ) -> ProgramResult {
+        if *pyth_price_info.key != reserve.liquidity_pyth_oracle {
+            return Err(LendingError::InvalidOracleConfig.into())
+        }
+
+        let price = pyth_price_info.data
+            .borrow()
+            .get(..8)
+            .and_then(|slice| slice.try_into().ok())
+            .map(u64::from_le_bytes)
+            .ok_or(LendingError::InvalidOracleConfig)?;
+
+        reserve.liquidity_market_price = (price as u128)
+            .checked_mul(WAD)
+            .ok_or(LendingError::MathOverflow)?;
+        reserve.last_update = LastUpdate::new(clock.slot);
+
+        Ok(())
+    }
+
+    fn load_reserve(program_id: &Pubkey, reserve_info: &AccountInfo) -> Result<Reserve, ProgramError> {
+        if reserve_info.owner != program_id {
+            return Err(ProgramError::IncorrectProgramId)
+        }
+
+        Reserve::unpack(&reserve_info.data.borrow())
+    }
+
+    fn load_fresh_reserve<'a, 'b: 'a, I: Iterator<Item = &'a AccountInfo<'b>>>(
+        program_id: &Pubkey,
+        account_info_iter: &mut I,
+        reserve_id: &Pubkey,
+        clock: &Clock,
+    ) -> Result<Reserve, ProgramError> {
+        let reserve_info = next_account_info(account_info_iter)?;
+
+        if reserve_info.key != reserve_id {
+            return Err(LendingError::InvalidAccountInput.into())
+        }
+
+        let reserve = Self::load_reserve(program_id, reserve_info)?;
+
+        if !reserve.last_update.is_fresh(clock.slot) {
+            return Err(LendingError::ReserveStale.into())
+        }
+
+        Ok(reserve)
+    }
+
+    fn load_obligation(program_id: &Pubkey, obligation_info: &AccountInfo) -> Result<Obligation, ProgramError> {
+        if obligation_info.owner != program_id {
+            return Err(ProgramError::IncorrectProgramId)
+        }
+
+        Obligation::unpack(&obligation_info.data.borrow())
+    }
+
+    fn assert_obligation_owner(obligation: &Obligation, obligation_owner_info: &AccountInfo) -> ProgramResult {
+        if obligation.owner != *obligation_owner_info.key || !obligation_owner_info.is_signer {
+            return Err(LendingError::InvalidObligationOwner.into())
+        }
+
+        Ok(())
+    }
+
+    /// Reserve and obligation of the lending market, returns the authority bump seed
+    fn assert_market(
+        program_id: &Pubkey,
+        reserve: &Reserve,
+        obligation: &Obligation,
+        lending_market_info: &AccountInfo,
+        lending_market_authority_info: &AccountInfo,
+    ) -> Result<u8, ProgramError> {
+        if reserve.lending_market != *lending_market_info.key
+            || obligation.lending_market != *lending_market_info.key {
+            return Err(LendingError::InvalidAccountInput.into())
+        }
+
+        let (authority, bump_seed) = get_lending_market_authority(program_id, lending_market_info.key);
+
+        if authority != *lending_market_authority_info.key {
+            return Err(LendingError::InvalidMarketAuthority.into())
+        }
+
+        Ok(bump_seed)
+    }
+}
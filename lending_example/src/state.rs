@@ -0,0 +1,578 @@
+use arrayref::{array_mut_ref, array_ref, array_refs, mut_array_refs};
+use solana_program::{
+    program_error::ProgramError,
+    program_pack::{IsInitialized, Pack, Sealed},
+    pubkey::Pubkey,
+};
+
+/// Identity of the WAD scaled values, precise to 18 digits
+pub const WAD: u128 = 1_000_000_000_000_000_000;
+
+pub const RESERVE_LEN: usize = 619; // 1 + 8 + 1 + 32 + 32 + 1 + 32 + 32 + 32 + 8 + 16 + 16 + 16 + 32 + 8 + 32 + 1 + 1 + 1 + 1 + 1 + 1 + 1 + 8 + 8 + 1 + 8 + 8 + 32 + 248
+pub const OBLIGATION_LEN: usize = 1300; // 1 + 8 + 1 + 32 + 32 + 16 + 16 + 16 + 16 + 64 + 1 + 1 + (88 * 1) + (112 * 9)
+pub const MAX_OBLIGATION_RESERVES: usize = 10;
+
+const OBLIGATION_COLLATERAL_LEN: usize = 88; // 32 + 8 + 16 + 32
+const OBLIGATION_LIQUIDITY_LEN: usize = 112; // 32 + 16 + 16 + 16 + 32
+
+/// Lending market authority derived from `[lending market]`. Owns reserve
+/// supplies and mints the reserve collateral
+pub fn get_lending_market_authority(program_id: &Pubkey, lending_market: &Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[lending_market.as_ref()], program_id)
+}
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct LastUpdate {
+    pub slot: u64,
+    pub stale: bool,
+}
+
+impl LastUpdate {
+    pub fn new(slot: u64) -> Self {
+        Self { slot, stale: false }
+    }
+
+    pub fn mark_stale(&mut self) {
+        self.stale = true;
+    }
+
+    /// Refreshed in the current slot and not changed after
+    pub fn is_fresh(&self, slot: u64) -> bool {
+        !self.stale && self.slot == slot
+    }
+}
+
+/// Reserve with the Solend layout. Interest doesn't accrue, so the
+/// cumulative borrow rate stays as initialized. Config fields that
+/// aren't used are packed as zeros
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Reserve {
+    pub version: u8,
+    pub last_update: LastUpdate,
+    pub lending_market: Pubkey,
+    pub liquidity_mint: Pubkey,
+    pub liquidity_mint_decimals: u8,
+    pub liquidity_supply: Pubkey,
+    pub liquidity_pyth_oracle: Pubkey,
+    pub liquidity_switchboard_oracle: Pubkey,
+    pub liquidity_available_amount: u64,
+    pub liquidity_borrowed_amount_wads: u128,
+    pub liquidity_cumulative_borrow_rate_wads: u128,
+    /// Price of one whole liquidity token in the quote currency, scaled by WAD
+    pub liquidity_market_price: u128,
+    pub collateral_mint: Pubkey,
+    pub collateral_mint_total_supply: u64,
+    pub collateral_supply: Pubkey,
+    /// As a percentage
+    pub loan_to_value_ratio: u8,
+    /// As a percentage
+    pub liquidation_threshold: u8,
+    pub fee_receiver: Pubkey,
+}
+
+impl Reserve {
+    /// Available and borrowed liquidity, scaled by WAD
+    pub fn total_liquidity_wads(&self) -> Option<u128> {
+        (self.liquidity_available_amount as u128)
+            .checked_mul(WAD)?
+            .checked_add(self.liquidity_borrowed_amount_wads)
+    }
+
+    /// Collateral minted for `liquidity_amount`. Empty reserve has 1:1 exchange rate
+    pub fn liquidity_to_collateral(&self, liquidity_amount: u64) -> Option<u64> {
+        let total_liquidity_wads = self.total_liquidity_wads()?;
+
+        if self.collateral_mint_total_supply == 0 || total_liquidity_wads == 0 {
+            return Some(liquidity_amount)
+        }
+
+        let collateral_amount = (liquidity_amount as u128)
+            .checked_mul(WAD)?
+            .checked_mul(self.collateral_mint_total_supply as u128)?
+            / total_liquidity_wads;
+
+        Some(collateral_amount as u64)
+    }
+
+    /// Liquidity redeemed for `collateral_amount`, rounded down
+    pub fn collateral_to_liquidity(&self, collateral_amount: u64) -> Option<u64> {
+        if self.collateral_mint_total_supply == 0 {
+            return Some(collateral_amount)
+        }
+
+        let liquidity_amount = (collateral_amount as u128)
+            .checked_mul(self.total_liquidity_wads()?)?
+            / (self.collateral_mint_total_supply as u128)
+            / WAD;
+
+        Some(liquidity_amount as u64)
+    }
+
+    /// Market value of the WAD scaled liquidity amount, scaled by WAD
+    pub fn market_value(&self, liquidity_amount_wads: u128) -> Option<u128> {
+        liquidity_amount_wads
+            .checked_mul(self.liquidity_market_price / WAD)?
+            .checked_div(10u128.checked_pow(self.liquidity_mint_decimals as u32)?)
+    }
+}
+
+impl Sealed for Reserve {}
+
+impl IsInitialized for Reserve {
+    fn is_initialized(&self) -> bool {
+        self.version != 0
+    }
+}
+
+impl Pack for Reserve {
+    const LEN: usize = RESERVE_LEN;
+
+    fn pack_into_slice(&self, dst: &mut [u8]) {
+        let output = array_mut_ref![dst, 0, RESERVE_LEN];
+        let (
+            version,
+            last_update_slot,
+            last_update_stale,
+            lending_market,
+            liquidity_mint,
+            liquidity_mint_decimals,
+            liquidity_supply,
+            liquidity_pyth_oracle,
+            liquidity_switchboard_oracle,
+            liquidity_available_amount,
+            liquidity_borrowed_amount_wads,
+            liquidity_cumulative_borrow_rate_wads,
+            liquidity_market_price,
+            collateral_mint,
+            collateral_mint_total_supply,
+            collateral_supply,
+            config_rates,
+            fee_receiver,
+            padding,
+        ) = mut_array_refs![
+            output,
+            1,
+            8,
+            1,
+            32,
+            32,
+            1,
+            32,
+            32,
+            32,
+            8,
+            16,
+            16,
+            16,
+            32,
+            8,
+            32,
+            7 + 8 + 8 + 1 + 8 + 8,
+            32,
+            248
+        ];
+
+        version[0] = self.version;
+        *last_update_slot = self.last_update.slot.to_le_bytes();
+        last_update_stale[0] = self.last_update.stale as u8;
+        lending_market.copy_from_slice(self.lending_market.as_ref());
+        liquidity_mint.copy_from_slice(self.liquidity_mint.as_ref());
+        liquidity_mint_decimals[0] = self.liquidity_mint_decimals;
+        liquidity_supply.copy_from_slice(self.liquidity_supply.as_ref());
+        liquidity_pyth_oracle.copy_from_slice(self.liquidity_pyth_oracle.as_ref());
+        liquidity_switchboard_oracle.copy_from_slice(self.liquidity_switchboard_oracle.as_ref());
+        *liquidity_available_amount = self.liquidity_available_amount.to_le_bytes();
+        *liquidity_borrowed_amount_wads = self.liquidity_borrowed_amount_wads.to_le_bytes();
+        *liquidity_cumulative_borrow_rate_wads = self.liquidity_cumulative_borrow_rate_wads.to_le_bytes();
+        *liquidity_market_price = self.liquidity_market_price.to_le_bytes();
+        collateral_mint.copy_from_slice(self.collateral_mint.as_ref());
+        *collateral_mint_total_supply = self.collateral_mint_total_supply.to_le_bytes();
+        collateral_supply.copy_from_slice(self.collateral_supply.as_ref());
+
+        // optimal utilization rate, loan to value ratio, liquidation bonus, liquidation threshold, ...
+        *config_rates = [0; 40];
+        config_rates[1] = self.loan_to_value_ratio;
+        config_rates[3] = self.liquidation_threshold;
+
+        fee_receiver.copy_from_slice(self.fee_receiver.as_ref());
+        *padding = [0; 248];
+    }
+
+    fn unpack_from_slice(src: &[u8]) -> Result<Self, ProgramError> {
+        let input = array_ref![src, 0, RESERVE_LEN];
+        let (
+            version,
+            last_update_slot,
+            last_update_stale,
+            lending_market,
+            liquidity_mint,
+            liquidity_mint_decimals,
+            liquidity_supply,
+            liquidity_pyth_oracle,
+            liquidity_switchboard_oracle,
+            liquidity_available_amount,
+            liquidity_borrowed_amount_wads,
+            liquidity_cumulative_borrow_rate_wads,
+            liquidity_market_price,
+            collateral_mint,
+            collateral_mint_total_supply,
+            collateral_supply,
+            config_rates,
+            fee_receiver,
+            _padding,
+        ) = array_refs![
+            input,
+            1,
+            8,
+            1,
+            32,
+            32,
+            1,
+            32,
+            32,
+            32,
+            8,
+            16,
+            16,
+            16,
+            32,
+            8,
+            32,
+            7 + 8 + 8 + 1 + 8 + 8,
+            32,
+            248
+        ];
+
+        Ok(Reserve {
+            version: version[0],
+            last_update: LastUpdate {
+                slot: u64::from_le_bytes(*last_update_slot),
+                stale: last_update_stale[0] != 0,
+            },
+            lending_market: Pubkey::new_from_array(*lending_market),
+            liquidity_mint: Pubkey::new_from_array(*liquidity_mint),
+            liquidity_mint_decimals: liquidity_mint_decimals[0],
+            liquidity_supply: Pubkey::new_from_array(*liquidity_supply),
+            liquidity_pyth_oracle: Pubkey::new_from_array(*liquidity_pyth_oracle),
+            liquidity_switchboard_oracle: Pubkey::new_from_array(*liquidity_switchboard_oracle),
+            liquidity_available_amount: u64::from_le_bytes(*liquidity_available_amount),
+            liquidity_borrowed_amount_wads: u128::from_le_bytes(*liquidity_borrowed_amount_wads),
+            liquidity_cumulative_borrow_rate_wads: u128::from_le_bytes(*liquidity_cumulative_borrow_rate_wads),
+            liquidity_market_price: u128::from_le_bytes(*liquidity_market_price),
+            collateral_mint: Pubkey::new_from_array(*collateral_mint),
+            collateral_mint_total_supply: u64::from_le_bytes(*collateral_mint_total_supply),
+            collateral_supply: Pubkey::new_from_array(*collateral_supply),
+            loan_to_value_ratio: config_rates[1],
+            liquidation_threshold: config_rates[3],
+            fee_receiver: Pubkey::new_from_array(*fee_receiver),
+        })
+    }
+}
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct ObligationCollateral {
+    pub deposit_reserve: Pubkey,
+    pub deposited_amount: u64,
+    pub market_value: u128,
+}
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct ObligationLiquidity {
+    pub borrow_reserve: Pubkey,
+    pub cumulative_borrow_rate_wads: u128,
+    pub borrowed_amount_wads: u128,
+    pub market_value: u128,
+}
+
+impl ObligationLiquidity {
+    /// Borrowed amount rounded up
+    pub fn borrowed_amount(&self) -> u64 {
+        ((self.borrowed_amount_wads + WAD - 1) / WAD) as u64
+    }
+}
+
+/// Obligation with the Solend layout. Values are scaled by WAD
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Obligation {
+    pub version: u8,
+    pub last_update: LastUpdate,
+    pub lending_market: Pubkey,
+    pub owner: Pubkey,
+    pub deposits: Vec<ObligationCollateral>,
+    pub borrows: Vec<ObligationLiquidity>,
+    pub deposited_value: u128,
+    pub borrowed_value: u128,
+    pub allowed_borrow_value: u128,
+    pub unhealthy_borrow_value: u128,
+}
+
+impl Obligation {
+    pub fn find_collateral_index(&self, reserve: &Pubkey) -> Option<usize> {
+        self.deposits.iter().position(|collateral| collateral.deposit_reserve == *reserve)
+    }
+
+    pub fn find_liquidity_index(&self, reserve: &Pubkey) -> Option<usize> {
+        self.borrows.iter().position(|liquidity| liquidity.borrow_reserve == *reserve)
+    }
+
+    pub fn is_full(&self) -> bool {
+        self.deposits.len() + self.borrows.len() >= MAX_OBLIGATION_RESERVES
+    }
+}
+
+impl Sealed for Obligation {}
+
+impl IsInitialized for Obligation {
+    fn is_initialized(&self) -> bool {
+        self.version != 0
+    }
+}
+
+impl Pack for Obligation {
+    const LEN: usize = OBLIGATION_LEN;
+
+    fn pack_into_slice(&self, dst: &mut [u8]) {
+        let output = array_mut_ref![dst, 0, OBLIGATION_LEN];
+        let (
+            version,
+            last_update_slot,
+            last_update_stale,
+            lending_market,
+            owner,
+            deposited_value,
+            borrowed_value,
+            allowed_borrow_value,
+            unhealthy_borrow_value,
+            padding,
+            deposits_len,
+            borrows_len,
+            data_flat,
+        ) = mut_array_refs![
+            output,
+            1,
+            8,
+            1,
+            32,
+            32,
+            16,
+            16,
+            16,
+            16,
+            64,
+            1,
+            1,
+            OBLIGATION_COLLATERAL_LEN + (OBLIGATION_LIQUIDITY_LEN * (MAX_OBLIGATION_RESERVES - 1))
+        ];
+
+        version[0] = self.version;
+        *last_update_slot = self.last_update.slot.to_le_bytes();
+        last_update_stale[0] = self.last_update.stale as u8;
+        lending_market.copy_from_slice(self.lending_market.as_ref());
+        owner.copy_from_slice(self.owner.as_ref());
+        *deposited_value = self.deposited_value.to_le_bytes();
+        *borrowed_value = self.borrowed_value.to_le_bytes();
+        *allowed_borrow_value = self.allowed_borrow_value.to_le_bytes();
+        *unhealthy_borrow_value = self.unhealthy_borrow_value.to_le_bytes();
+        *padding = [0; 64];
+        deposits_len[0] = self.deposits.len() as u8;
+        borrows_len[0] = self.borrows.len() as u8;
+
+        let mut offset = 0;
+
+        for collateral in self.deposits.iter() {
+            let collateral_flat = array_mut_ref![data_flat, offset, OBLIGATION_COLLATERAL_LEN];
+            let (deposit_reserve, deposited_amount, market_value, padding) =
+                mut_array_refs![collateral_flat, 32, 8, 16, 32];
+
+            deposit_reserve.copy_from_slice(collateral.deposit_reserve.as_ref());
+            *deposited_amount = collateral.deposited_amount.to_le_bytes();
+            *market_value = collateral.market_value.to_le_bytes();
+            *padding = [0; 32];
+
+            offset += OBLIGATION_COLLATERAL_LEN;
+        }
+
+        for liquidity in self.borrows.iter() {
+            let liquidity_flat = array_mut_ref![data_flat, offset, OBLIGATION_LIQUIDITY_LEN];
+            let (borrow_reserve, cumulative_borrow_rate_wads, borrowed_amount_wads, market_value, padding) =
+                mut_array_refs![liquidity_flat, 32, 16, 16, 16, 32];
+
+            borrow_reserve.copy_from_slice(liquidity.borrow_reserve.as_ref());
+            *cumulative_borrow_rate_wads = liquidity.cumulative_borrow_rate_wads.to_le_bytes();
+            *borrowed_amount_wads = liquidity.borrowed_amount_wads.to_le_bytes();
+            *market_value = liquidity.market_value.to_le_bytes();
+            *padding = [0; 32];
+
+            offset += OBLIGATION_LIQUIDITY_LEN;
+        }
+
+        for byte in data_flat[offset..].iter_mut() {
+            *byte = 0;
+        }
+    }
+
+    fn unpack_from_slice(src: &[u8]) -> Result<Self, ProgramError> {
+        let input = array_ref![src, 0, OBLIGATION_LEN];
+        let (
+            version,
+            last_update_slot,
+            last_update_stale,
+            lending_market,
+            owner,
+            deposited_value,
+            borrowed_value,
+            allowed_borrow_value,
+            unhealthy_borrow_value,
+            _padding,
+            deposits_len,
+            borrows_len,
+            data_flat,
+        ) = array_refs![
+            input,
+            1,
+            8,
+            1,
+            32,
+            32,
+            16,
+            16,
+            16,
+            16,
+            64,
+            1,
+            1,
+            OBLIGATION_COLLATERAL_LEN + (OBLIGATION_LIQUIDITY_LEN * (MAX_OBLIGATION_RESERVES - 1))
+        ];
+
+        let deposits_len = deposits_len[0] as usize;
+        let borrows_len = borrows_len[0] as usize;
+
+        if deposits_len + borrows_len > MAX_OBLIGATION_RESERVES {
+            return Err(ProgramError::InvalidAccountData)
+        }
+
+        let mut offset = 0;
+
+        let mut deposits = Vec::with_capacity(deposits_len);
+        for _ in 0..deposits_len {
+            let collateral_flat = array_ref![data_flat, offset, OBLIGATION_COLLATERAL_LEN];
+            let (deposit_reserve, deposited_amount, market_value, _padding) =
+                array_refs![collateral_flat, 32, 8, 16, 32];
+
+            deposits.push(ObligationCollateral {
+                deposit_reserve: Pubkey::new_from_array(*deposit_reserve),
+                deposited_amount: u64::from_le_bytes(*deposited_amount),
+                market_value: u128::from_le_bytes(*market_value),
+            });
+
+            offset += OBLIGATION_COLLATERAL_LEN;
+        }
+
+        let mut borrows = Vec::with_capacity(borrows_len);
+        for _ in 0..borrows_len {
+            let liquidity_flat = array_ref![data_flat, offset, OBLIGATION_LIQUIDITY_LEN];
+            let (borrow_reserve, cumulative_borrow_rate_wads, borrowed_amount_wads, market_value, _padding) =
+                array_refs![liquidity_flat, 32, 16, 16, 16, 32];
+
+            borrows.push(ObligationLiquidity {
+                borrow_reserve: Pubkey::new_from_array(*borrow_reserve),
+                cumulative_borrow_rate_wads: u128::from_le_bytes(*cumulative_borrow_rate_wads),
+                borrowed_amount_wads: u128::from_le_bytes(*borrowed_amount_wads),
+                market_value: u128::from_le_bytes(*market_value),
+            });
+
+            offset += OBLIGATION_LIQUIDITY_LEN;
+        }
+
+        Ok(Obligation {
+            version: version[0],
+            last_update: LastUpdate {
+                slot: u64::from_le_bytes(*last_update_slot),
+                stale: last_update_stale[0] != 0,
+            },
+            lending_market: Pubkey::new_from_array(*lending_market),
+            owner: Pubkey::new_from_array(*owner),
+            deposits,
+            borrows,
+            deposited_value: u128::from_le_bytes(*deposited_value),
+            borrowed_value: u128::from_le_bytes(*borrowed_value),
+            allowed_borrow_value: u128::from_le_bytes(*allowed_borrow_value),
+            unhealthy_borrow_value: u128::from_le_bytes(*unhealthy_borrow_value),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn exchange_rate_and_market_value() {
+        let mut reserve = Reserve {
+            version: 1,
+            liquidity_mint_decimals: 6,
+            liquidity_available_amount: 1_000,
+            liquidity_market_price: 20 * WAD,
+            ..Reserve::default()
+        };
+
+        // Empty reserve exchanges 1:1
+        assert_eq!(reserve.liquidity_to_collateral(100), Some(100));
+        assert_eq!(reserve.collateral_to_liquidity(100), Some(100));
+
+        // 1_000 + 1_000 liquidity for 1_000 collateral
+        reserve.liquidity_borrowed_amount_wads = 1_000 * WAD;
+        reserve.collateral_mint_total_supply = 1_000;
+        assert_eq!(reserve.liquidity_to_collateral(100), Some(50));
+        assert_eq!(reserve.collateral_to_liquidity(50), Some(100));
+        assert_eq!(reserve.collateral_to_liquidity(1), Some(2));
+
+        // 2 whole tokens at 20
+        assert_eq!(reserve.market_value(2_000_000 * WAD), Some(40 * WAD));
+    }
+
+    #[test]
+    fn pack_solend_layout() {
+        let reserve = Reserve {
+            version: 1,
+            last_update: LastUpdate::new(7),
+            lending_market: Pubkey::new_unique(),
+            liquidity_mint: Pubkey::new_unique(),
+            liquidity_mint_decimals: 6,
+            liquidity_available_amount: 1_000,
+            liquidity_borrowed_amount_wads: 500 * WAD,
+            liquidity_cumulative_borrow_rate_wads: WAD,
+            liquidity_market_price: 20 * WAD,
+            loan_to_value_ratio: 75,
+            liquidation_threshold: 80,
+            ..Reserve::default()
+        };
+
+        let mut data = vec![0; Reserve::LEN];
+        Reserve::pack(reserve.clone(), &mut data).unwrap();
+        assert_eq!(Reserve::unpack(&data), Ok(reserve));
+
+        let obligation = Obligation {
+            version: 1,
+            owner: Pubkey::new_unique(),
+            deposits: vec![ObligationCollateral {
+                deposit_reserve: Pubkey::new_unique(),
+                deposited_amount: 1_000,
+                market_value: 20_000 * WAD,
+            }],
+            borrows: vec![ObligationLiquidity {
+                borrow_reserve: Pubkey::new_unique(),
+                cumulative_borrow_rate_wads: WAD,
+                borrowed_amount_wads: 500 * WAD + 1,
+                market_value: 500 * WAD,
+            }],
+            borrowed_value: 500 * WAD,
+            ..Obligation::default()
+        };
+
+        let mut data = vec![0; Obligation::LEN];
+        Obligation::pack(obligation.clone(), &mut data).unwrap();
+        assert_eq!(Obligation::unpack(&data), Ok(obligation.clone()));
+        assert_eq!(obligation.borrows[0].borrowed_amount(), 501);
+    }
+}
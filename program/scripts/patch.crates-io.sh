@@ -11,8 +11,11 @@ if [[ -z $solana_dir ]]; then
   exit 1
 fi
 
+# Tests of the program depend on the example programs, so they are patched too
 workspace_crates=(
   "$here"/../Cargo.toml
+  "$here"/../../swap_example/Cargo.toml
+  "$here"/../../lending_example/Cargo.toml
 )
 
 if [[ ! -r "$solana_dir"/scripts/read-cargo-variable.sh ]]; then
@@ -26,7 +26,7 @@ if ! git diff --quiet && [[ -z $DIRTY_OK ]]; then
 fi
 
 declare tomls=()
-while IFS='' read -r line; do tomls+=("$line"); done < <(find . -name Cargo.toml)
+while IFS='' read -r line; do tomls+=("$line"); done < <(find . ../swap_example ../lending_example -name Cargo.toml)
 
 crates=(
   solana-clap-utils
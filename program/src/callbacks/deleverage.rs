@@ -0,0 +1,248 @@
+use solana_program::{
+    account_info::{AccountInfo, next_account_info},
+    instruction::Instruction,
+    entrypoint::ProgramResult,
+    program_error::ProgramError,
+    program::invoke_signed,
+    msg,
+};
+
+use borsh::BorshSerialize;
+use byteorder::ByteOrder;
+
+use crate::{
+    id,
+    error::SolarisAutoError,
+    helpers::{
+        lending_healthfactor::Obligation,
+        lending_reserve::Reserve,
+        lending_position::{
+            assert_delegate_obligation,
+            load_solend_position,
+            get_refresh_reserves,
+            accrue_position_interest,
+            get_position_borrowed_amount,
+            repay_position_liquidity,
+            withdraw_position_collateral,
+            check_position_health,
+        },
+    },
+    utils::{
+        get_seeds_delegate,
+        get_token_account_amount,
+        solend_repay_obligation_liquidity,
+        solend_withdraw_obligation_collateral_and_redeem_reserve_collateral,
+        token_swap,
+    },
+};
+
+use super::{
+    liquidation_protection::refresh_obligation,
+    process_callback::CallbackContext,
+};
+
+// Pubkey is "DZH6coY27MPxg9HC8KTfDrTyhxFfFQhTeLZ6ozXS16St"
+pub const CALLBACK_SOLEND_DELEVERAGE: &[u8] = &[186, 145, 7, 159, 45, 36, 240, 10, 144, 137, 143, 216, 208, 184, 88, 64, 188, 85, 153, 255, 16, 3, 246, 149, 250, 228, 122, 8, 20, 194, 87, 33];
+
+/// Deleverage without the taker asset. Withdraws and redeems `liquidity_amount`
+/// of collateral, swaps everything redeemed through a constant product pool
+/// with the SPL token-swap interface and repays debt with everything received.
+/// Delegate is the obligation owner and the transfer authority of both maker
+/// token accounts, so they stay with the maker between the steps.
+/// Withdrawal is debited from the maker position and repay is capped by
+/// the maker borrow, the position must stay healthy.
+///
+/// Accounts required:
+///
+/// 0. `[]` Callback program id: DZH6coY27MPxg9HC8KTfDrTyhxFfFQhTeLZ6ozXS16St
+/// 1. `[]` Solend program
+/// 2. `[writable]` Withdraw reserve collateral supply SPL Token account.
+/// 3. `[writable]` Destination collateral token account.
+/// 4. `[writable]` Withdraw reserve account.
+/// 5. `[writable]` Obligation account.
+/// 6. `[]` Lending market account.
+/// 7. `[]` Derived lending market authority.
+/// 8. `[writable]` Maker token account of the withdraw reserve liquidity.
+/// 9. `[writable]` Withdraw reserve collateral SPL Token mint.
+/// 10 `[writable]` Withdraw reserve liquidity supply SPL Token account.
+/// 11 `[]` Swap program
+/// 12 `[]` Swap account
+/// 13 `[]` Swap authority
+/// 14 `[writable]` Swap token account of the withdraw reserve liquidity.
+/// 15 `[writable]` Swap token account of the repay reserve liquidity.
+/// 16 `[writable]` Pool token mint.
+/// 17 `[writable]` Pool fee account.
+/// 18 `[writable]` Maker token account of the repay reserve liquidity.
+/// 19 `[writable]` Repay reserve liquidity supply SPL Token account.
+/// 20 `[writable]` Repay reserve account.
+/// 21 `[]` Delegate. Obligation owner and transfer authority.
+/// 22 `[]` Clock sysvar.
+/// 23 `[]` Token program id.
+/// 24 `[writable]` Maker Solend position of the obligation.
+/// 25.. For every reserve of the obligation, deposits and then borrows:
+///     `[writable]` Reserve account, `[]` Pyth price, `[]` Switchboard price
+///
+/// Instruction data format is
+/// ```
+/// pub struct SolendDeleverage {
+///     liquidity_amount: u64,   // collateral liquidity to withdraw
+///     minimum_amount_out: u64, // swap slippage protection
+/// }
+/// ```
+pub fn process_callback_solend_deleverage(
+    instr: &Instruction,
+    accounts: &[AccountInfo],
+    context: &CallbackContext,
+) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+
+    let _callback_program_info = next_account_info(account_info_iter)?;
+    let solend_program_info = next_account_info(account_info_iter)?;
+    let reserve_collateral_info = next_account_info(account_info_iter)?;
+    let user_collateral_info = next_account_info(account_info_iter)?;
+    let withdraw_reserve_info = next_account_info(account_info_iter)?;
+    let obligation_info = next_account_info(account_info_iter)?;
+    let lending_market_info = next_account_info(account_info_iter)?;
+    let lending_market_authority_info = next_account_info(account_info_iter)?;
+    let user_withdraw_liquidity_info = next_account_info(account_info_iter)?;
+    let reserve_collateral_mint_info = next_account_info(account_info_iter)?;
+    let reserve_liquidity_supply_info = next_account_info(account_info_iter)?;
+    let swap_program_info = next_account_info(account_info_iter)?;
+    let swap_info = next_account_info(account_info_iter)?;
+    let swap_authority_info = next_account_info(account_info_iter)?;
+    let swap_source_info = next_account_info(account_info_iter)?;
+    let swap_destination_info = next_account_info(account_info_iter)?;
+    let pool_mint_info = next_account_info(account_info_iter)?;
+    let pool_fee_info = next_account_info(account_info_iter)?;
+    let user_repay_liquidity_info = next_account_info(account_info_iter)?;
+    let repay_reserve_liquidity_supply_info = next_account_info(account_info_iter)?;
+    let repay_reserve_info = next_account_info(account_info_iter)?;
+    let delegate_info = next_account_info(account_info_iter)?;
+    let _clock_info = next_account_info(account_info_iter)?;
+    let _token_program_info = next_account_info(account_info_iter)?;
+    let solend_position_info = next_account_info(account_info_iter)?;
+
+    let refresh_reserve_infos: Vec<&AccountInfo> = account_info_iter.collect();
+
+    if refresh_reserve_infos.is_empty() || refresh_reserve_infos.len() % 3 != 0 {
+        return Err(ProgramError::NotEnoughAccountKeys)
+    }
+
+    if instr.data.len() < 16 {
+        return Err(SolarisAutoError::InvalidCallbackInst.into())
+    }
+
+    let liquidity_amount = byteorder::LE::read_u64(&instr.data[0..8]);
+    let minimum_amount_out = byteorder::LE::read_u64(&instr.data[8..16]);
+
+    assert_delegate_obligation(solend_program_info, obligation_info)?;
+
+    let mut solend_position = load_solend_position(
+        &id(),
+        solend_position_info,
+        &context.maker,
+        obligation_info.key,
+    )?;
+
+    refresh_obligation(solend_program_info, obligation_info, &refresh_reserve_infos, accounts)?;
+
+    let obligation = Obligation::unpack(&obligation_info.data.borrow())?;
+    accrue_position_interest(&mut solend_position, &obligation)?;
+
+    let withdraw_reserve = Reserve::unpack(&withdraw_reserve_info.data.borrow())?;
+    let collateral_amount = withdraw_reserve.collateral_to_redeem(liquidity_amount)?;
+
+    withdraw_position_collateral(&mut solend_position, withdraw_reserve_info.key, collateral_amount)?;
+
+    let withdraw_liquidity_before = get_token_account_amount(user_withdraw_liquidity_info)?;
+
+    invoke_signed(
+        &solend_withdraw_obligation_collateral_and_redeem_reserve_collateral(
+            solend_program_info.key,
+            reserve_collateral_info.key,
+            user_collateral_info.key,
+            withdraw_reserve_info.key,
+            obligation_info.key,
+            lending_market_info.key,
+            lending_market_authority_info.key,
+            user_withdraw_liquidity_info.key,
+            reserve_collateral_mint_info.key,
+            reserve_liquidity_supply_info.key,
+            delegate_info.key,
+            delegate_info.key,
+            collateral_amount,
+        ),
+        accounts,
+        &[&get_seeds_delegate()],
+    )?;
+
+    let amount_in = get_token_account_amount(user_withdraw_liquidity_info)?
+        .checked_sub(withdraw_liquidity_before)
+        .ok_or(ProgramError::from(SolarisAutoError::MathOverflow))?;
+
+    msg!("withdrawn liquidity is {}", amount_in);
+
+    let repay_liquidity_before = get_token_account_amount(user_repay_liquidity_info)?;
+
+    invoke_signed(
+        &token_swap(
+            swap_program_info.key,
+            swap_info.key,
+            swap_authority_info.key,
+            delegate_info.key,
+            user_withdraw_liquidity_info.key,
+            swap_source_info.key,
+            swap_destination_info.key,
+            user_repay_liquidity_info.key,
+            pool_mint_info.key,
+            pool_fee_info.key,
+            amount_in,
+            minimum_amount_out,
+        ),
+        accounts,
+        &[&get_seeds_delegate()],
+    )?;
+
+    let swapped_amount = get_token_account_amount(user_repay_liquidity_info)?
+        .checked_sub(repay_liquidity_before)
+        .ok_or(ProgramError::from(SolarisAutoError::MathOverflow))?;
+
+    let repay_amount = swapped_amount.min(
+        get_position_borrowed_amount(&solend_position, repay_reserve_info.key)?
+    );
+
+    msg!("repay amount is {}", repay_amount);
+
+    // Withdraw marks the reserve and the obligation stale
+    refresh_obligation(solend_program_info, obligation_info, &refresh_reserve_infos, accounts)?;
+
+    let obligation = Obligation::unpack(&obligation_info.data.borrow())?;
+    let repay_liquidity_before = get_token_account_amount(user_repay_liquidity_info)?;
+
+    invoke_signed(
+        &solend_repay_obligation_liquidity(
+            solend_program_info.key,
+            user_repay_liquidity_info.key,
+            repay_reserve_liquidity_supply_info.key,
+            repay_reserve_info.key,
+            obligation_info.key,
+            lending_market_info.key,
+            delegate_info.key,
+            repay_amount,
+        ),
+        accounts,
+        &[&get_seeds_delegate()],
+    )?;
+
+    let repaid_amount = repay_liquidity_before
+        .checked_sub(get_token_account_amount(user_repay_liquidity_info)?)
+        .ok_or(ProgramError::from(SolarisAutoError::MathOverflow))?;
+
+    repay_position_liquidity(&mut solend_position, repay_reserve_info.key, repaid_amount)?;
+
+    check_position_health(&solend_position, &obligation, &get_refresh_reserves(&refresh_reserve_infos)?)?;
+
+    solend_position.serialize(&mut *solend_position_info.data.borrow_mut())?;
+
+    Ok(())
+}
@@ -0,0 +1,33 @@
+use solana_program::{
+    account_info::AccountInfo,
+    instruction::Instruction,
+    program::invoke,
+    entrypoint::ProgramResult,
+    program_error::ProgramError,
+};
+
+use crate::{
+    id as program_id,
+    error::SolarisAutoError,
+    utils::assert_instruction_accounts,
+};
+
+/// Runs taker interaction between the transfers of the fill. `instr` is bincode
+/// serialized `Instruction` supplied by the taker and not signed by the maker,
+/// so it's invoked without delegate seeds and can't call this program.
+/// Supplied accounts are the interaction program and its accounts.
+pub fn process_interaction(
+    instr: &[u8],
+    accounts: &[AccountInfo],
+) -> ProgramResult {
+    let interaction: Instruction = bincode::deserialize(instr)
+        .or(Err(ProgramError::from(SolarisAutoError::InvalidInteractionInst)))?;
+
+    if interaction.program_id == program_id() {
+        return Err(SolarisAutoError::InvalidInteractionInst.into())
+    }
+
+    assert_instruction_accounts(&interaction, accounts)?;
+
+    invoke(&interaction, accounts)
+}
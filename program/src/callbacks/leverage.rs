@@ -0,0 +1,546 @@
+use solana_program::{
+    account_info::{AccountInfo, next_account_info},
+    instruction::Instruction,
+    entrypoint::ProgramResult,
+    program_error::ProgramError,
+    program::invoke_signed,
+    pubkey::Pubkey,
+    msg,
+};
+
+use borsh::BorshSerialize;
+use byteorder::ByteOrder;
+
+use crate::{
+    id,
+    error::SolarisAutoError,
+    helpers::{
+        lending_healthfactor::{Obligation, WAD},
+        lending_reserve::{Reserve, BPS},
+        lending_position::{
+            assert_delegate_obligation,
+            load_solend_position,
+            get_refresh_reserves,
+            accrue_position_interest,
+            borrow_position_liquidity,
+            deposit_position_collateral,
+            get_position_obligation,
+            check_position_health,
+        },
+        range_order::U256,
+    },
+    utils::{
+        get_seeds_delegate,
+        get_token_account_amount,
+        solend_borrow_obligation_liquidity,
+        solend_deposit_reserve_liquidity_and_obligation_collateral,
+        token_swap,
+    },
+};
+
+use super::{
+    liquidation_protection::{refresh_obligation, to_u256},
+    process_callback::CallbackContext,
+};
+
+// Pubkey is "EgRUCSUSuYpPjmqhNZhU1SMJoDJRA6ttwRTa96psDf2Q"
+pub const CALLBACK_SOLEND_LEVERAGE: &[u8] = &[203, 65, 63, 170, 37, 96, 177, 107, 175, 32, 120, 150, 38, 53, 206, 194, 168, 75, 36, 220, 156, 15, 82, 79, 184, 46, 110, 249, 36, 6, 43, 73];
+
+/// Leverage loop. Every iteration refreshes the obligation, borrows
+/// as much as allowed by `target_leverage`, `min_health_ratio` and
+/// the Solend borrow limit, swaps the borrowed liquidity into the deposit
+/// reserve liquidity and deposits it. Stops after `max_iterations` or
+/// when nothing is left to borrow. Fails if the final health is below
+/// `min_health_ratio`. Used with a predicate, e.g. on the oracle price.
+/// Delegate is the obligation owner and the transfer authority.
+/// Targets and health are of the maker share of the obligation, see
+/// `get_position_obligation`. Borrows and deposits are recorded in the maker position.
+///
+/// Accounts required:
+///
+/// 0. `[]` Callback program id: EgRUCSUSuYpPjmqhNZhU1SMJoDJRA6ttwRTa96psDf2Q
+/// 1. `[]` Solend program
+/// 2. `[writable]` Obligation account.
+/// 3. `[]` Lending market account.
+/// 4. `[]` Derived lending market authority.
+/// 5. `[writable]` Deposit reserve account.
+/// 6. `[writable]` Deposit reserve liquidity supply SPL Token account.
+/// 7. `[writable]` Deposit reserve collateral SPL Token mint.
+/// 8. `[writable]` Deposit reserve collateral supply SPL Token account.
+/// 9. `[writable]` Destination collateral token account.
+/// 10 `[]` Deposit reserve Pyth price.
+/// 11 `[]` Deposit reserve Switchboard price.
+/// 12 `[writable]` Maker token account of the deposit reserve liquidity.
+/// 13 `[writable]` Borrow reserve account.
+/// 14 `[writable]` Borrow reserve liquidity supply SPL Token account.
+/// 15 `[writable]` Borrow reserve liquidity fee receiver.
+/// 16 `[writable]` Maker token account of the borrow reserve liquidity.
+/// 17 `[]` Swap program
+/// 18 `[]` Swap account
+/// 19 `[]` Swap authority
+/// 20 `[writable]` Swap token account of the borrow reserve liquidity.
+/// 21 `[writable]` Swap token account of the deposit reserve liquidity.
+/// 22 `[writable]` Pool token mint.
+/// 23 `[writable]` Pool fee account.
+/// 24 `[]` Delegate. Obligation owner and transfer authority.
+/// 25 `[]` Clock sysvar.
+/// 26 `[]` Token program id.
+/// 27 `[writable]` Maker Solend position of the obligation.
+/// 28.. For every reserve of the obligation, deposits and then borrows:
+///     `[writable]` Reserve account, `[]` Pyth price, `[]` Switchboard price
+///
+/// Instruction data format is
+/// ```
+/// pub struct SolendLeverage {
+///     target_leverage: u128,  // deposited value / (deposited value - borrowed value), scaled by WAD
+///     min_health_ratio: u128, // unhealthy borrow value / borrowed value, scaled by WAD
+///     max_slippage_bps: u64,  // swap output below the reserve prices
+///     max_iterations: u8,
+/// }
+/// ```
+pub fn process_callback_solend_leverage(
+    instr: &Instruction,
+    accounts: &[AccountInfo],
+    context: &CallbackContext,
+) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+
+    let _callback_program_info = next_account_info(account_info_iter)?;
+    let solend_program_info = next_account_info(account_info_iter)?;
+    let obligation_info = next_account_info(account_info_iter)?;
+    let lending_market_info = next_account_info(account_info_iter)?;
+    let lending_market_authority_info = next_account_info(account_info_iter)?;
+    let deposit_reserve_info = next_account_info(account_info_iter)?;
+    let deposit_reserve_liquidity_supply_info = next_account_info(account_info_iter)?;
+    let deposit_reserve_collateral_mint_info = next_account_info(account_info_iter)?;
+    let deposit_reserve_collateral_supply_info = next_account_info(account_info_iter)?;
+    let user_collateral_info = next_account_info(account_info_iter)?;
+    let deposit_reserve_pyth_info = next_account_info(account_info_iter)?;
+    let deposit_reserve_switchboard_info = next_account_info(account_info_iter)?;
+    let user_deposit_liquidity_info = next_account_info(account_info_iter)?;
+    let borrow_reserve_info = next_account_info(account_info_iter)?;
+    let borrow_reserve_liquidity_supply_info = next_account_info(account_info_iter)?;
+    let borrow_reserve_fee_receiver_info = next_account_info(account_info_iter)?;
+    let user_borrow_liquidity_info = next_account_info(account_info_iter)?;
+    let swap_program_info = next_account_info(account_info_iter)?;
+    let swap_info = next_account_info(account_info_iter)?;
+    let swap_authority_info = next_account_info(account_info_iter)?;
+    let swap_source_info = next_account_info(account_info_iter)?;
+    let swap_destination_info = next_account_info(account_info_iter)?;
+    let pool_mint_info = next_account_info(account_info_iter)?;
+    let pool_fee_info = next_account_info(account_info_iter)?;
+    let delegate_info = next_account_info(account_info_iter)?;
+    let _clock_info = next_account_info(account_info_iter)?;
+    let _token_program_info = next_account_info(account_info_iter)?;
+    let solend_position_info = next_account_info(account_info_iter)?;
+
+    let refresh_reserve_infos: Vec<&AccountInfo> = account_info_iter.collect();
+
+    if refresh_reserve_infos.is_empty() || refresh_reserve_infos.len() % 3 != 0 {
+        return Err(ProgramError::NotEnoughAccountKeys)
+    }
+
+    if instr.data.len() < 41 {
+        return Err(SolarisAutoError::InvalidCallbackInst.into())
+    }
+
+    let target = LeverageTarget {
+        target_leverage: byteorder::LE::read_u128(&instr.data[0..16]),
+        min_health_ratio: byteorder::LE::read_u128(&instr.data[16..32]),
+        max_slippage_bps: byteorder::LE::read_u64(&instr.data[32..40]),
+    };
+    let max_iterations = instr.data[40];
+
+    if target.max_slippage_bps >= BPS {
+        return Err(SolarisAutoError::InvalidCallbackInst.into())
+    }
+
+    assert_delegate_obligation(solend_program_info, obligation_info)?;
+
+    let mut solend_position = load_solend_position(
+        &id(),
+        solend_position_info,
+        &context.maker,
+        obligation_info.key,
+    )?;
+
+    // Loan to value ratios and liquidation thresholds only
+    let reserves = get_refresh_reserves(&refresh_reserve_infos)?;
+
+    for iteration in 0..max_iterations {
+        refresh_obligation(solend_program_info, obligation_info, &refresh_reserve_infos, accounts)?;
+
+        let obligation = Obligation::unpack(&obligation_info.data.borrow())?;
+        let deposit_reserve = Reserve::unpack(&deposit_reserve_info.data.borrow())?;
+        let borrow_reserve = Reserve::unpack(&borrow_reserve_info.data.borrow())?;
+
+        accrue_position_interest(&mut solend_position, &obligation)?;
+        let position_obligation = get_position_obligation(&solend_position, &obligation, &reserves)?;
+
+        let borrow_value = target.get_borrow_value(&position_obligation, &deposit_reserve)?;
+        let borrow_amount = value_to_amount(borrow_value, &borrow_reserve)?;
+
+        msg!("iteration {}: borrow_amount is {}", iteration, borrow_amount);
+
+        if borrow_amount == 0 {
+            break
+        }
+
+        let borrow_liquidity_before = get_token_account_amount(user_borrow_liquidity_info)?;
+        let borrowed_amount_wads_before = obligation
+            .find_liquidity(borrow_reserve_info.key)
+            .map(|liquidity| to_u256(&liquidity.borrowed_amount_wads))
+            .unwrap_or_default();
+
+        invoke_signed(
+            &solend_borrow_obligation_liquidity(
+                solend_program_info.key,
+                borrow_reserve_liquidity_supply_info.key,
+                user_borrow_liquidity_info.key,
+                borrow_reserve_info.key,
+                borrow_reserve_fee_receiver_info.key,
+                obligation_info.key,
+                lending_market_info.key,
+                lending_market_authority_info.key,
+                delegate_info.key,
+                borrow_amount,
+            ),
+            accounts,
+            &[&get_seeds_delegate()],
+        )?;
+
+        // Borrowed amount includes the borrow fee
+        let obligation = Obligation::unpack(&obligation_info.data.borrow())?;
+        let liquidity = obligation
+            .find_liquidity(borrow_reserve_info.key)
+            .ok_or(ProgramError::from(SolarisAutoError::InvalidObligationData))?;
+
+        borrow_position_liquidity(
+            &mut solend_position,
+            borrow_reserve_info.key,
+            to_u256(&liquidity.borrowed_amount_wads)
+                .checked_sub(borrowed_amount_wads_before)
+                .ok_or(ProgramError::from(SolarisAutoError::MathOverflow))?,
+            to_u256(&liquidity.cumulative_borrow_rate_wads),
+        )?;
+
+        // Borrow fee is taken from the borrowed amount
+        let amount_in = get_token_account_amount(user_borrow_liquidity_info)?
+            .checked_sub(borrow_liquidity_before)
+            .ok_or(ProgramError::from(SolarisAutoError::MathOverflow))?;
+
+        let minimum_amount_out = get_minimum_amount_out(
+            amount_in,
+            &borrow_reserve,
+            &deposit_reserve,
+            target.max_slippage_bps,
+        )?;
+
+        let deposit_liquidity_before = get_token_account_amount(user_deposit_liquidity_info)?;
+
+        invoke_signed(
+            &token_swap(
+                swap_program_info.key,
+                swap_info.key,
+                swap_authority_info.key,
+                delegate_info.key,
+                user_borrow_liquidity_info.key,
+                swap_source_info.key,
+                swap_destination_info.key,
+                user_deposit_liquidity_info.key,
+                pool_mint_info.key,
+                pool_fee_info.key,
+                amount_in,
+                minimum_amount_out,
+            ),
+            accounts,
+            &[&get_seeds_delegate()],
+        )?;
+
+        let deposit_amount = get_token_account_amount(user_deposit_liquidity_info)?
+            .checked_sub(deposit_liquidity_before)
+            .ok_or(ProgramError::from(SolarisAutoError::MathOverflow))?;
+
+        msg!("iteration {}: deposit_amount is {}", iteration, deposit_amount);
+
+        let deposited_amount_before = get_deposited_amount(obligation_info, deposit_reserve_info.key)?;
+
+        invoke_signed(
+            &solend_deposit_reserve_liquidity_and_obligation_collateral(
+                solend_program_info.key,
+                user_deposit_liquidity_info.key,
+                user_collateral_info.key,
+                deposit_reserve_info.key,
+                deposit_reserve_liquidity_supply_info.key,
+                deposit_reserve_collateral_mint_info.key,
+                lending_market_info.key,
+                lending_market_authority_info.key,
+                deposit_reserve_collateral_supply_info.key,
+                obligation_info.key,
+                delegate_info.key,
+                deposit_reserve_pyth_info.key,
+                deposit_reserve_switchboard_info.key,
+                delegate_info.key,
+                deposit_amount,
+            ),
+            accounts,
+            &[&get_seeds_delegate()],
+        )?;
+
+        let collateral_amount = get_deposited_amount(obligation_info, deposit_reserve_info.key)?
+            .checked_sub(deposited_amount_before)
+            .ok_or(ProgramError::from(SolarisAutoError::MathOverflow))?;
+
+        deposit_position_collateral(&mut solend_position, deposit_reserve_info.key, collateral_amount)?;
+    }
+
+    refresh_obligation(solend_program_info, obligation_info, &refresh_reserve_infos, accounts)?;
+
+    let obligation = Obligation::unpack(&obligation_info.data.borrow())?;
+    let position_obligation = get_position_obligation(&solend_position, &obligation, &reserves)?;
+
+    if !target.is_healthy(&position_obligation)? {
+        return Err(SolarisAutoError::HealthBelowMinimum.into())
+    }
+
+    check_position_health(&solend_position, &obligation, &reserves)?;
+
+    solend_position.serialize(&mut *solend_position_info.data.borrow_mut())?;
+
+    Ok(())
+}
+
+fn get_deposited_amount(
+    obligation_info: &AccountInfo,
+    reserve_id: &Pubkey,
+) -> Result<u64, ProgramError> {
+    let obligation = Obligation::unpack(&obligation_info.data.borrow())?;
+
+    Ok(obligation.find_collateral(reserve_id).map_or(0, |collateral| collateral.deposited_amount))
+}
+
+pub struct LeverageTarget {
+    /// Deposited value / (deposited value - borrowed value), scaled by WAD
+    pub target_leverage: u128,
+    /// Unhealthy borrow value / borrowed value, scaled by WAD
+    pub min_health_ratio: u128,
+    /// Deposited value is expected to be at least this much below the borrowed value
+    pub max_slippage_bps: u64,
+}
+
+impl LeverageTarget {
+    /// Value to borrow in the next iteration, scaled by WAD like obligation values.
+    /// Minimum of three limits, where X is the borrow value and k is
+    /// the deposited share of it after slippage:
+    /// target leverage (D + k * X) / (D + k * X - B - X) <= target_leverage,
+    /// health (U + k * X * t) / (B + X) >= min_health_ratio, t is the deposit
+    /// reserve liquidation threshold, and Solend allowed borrow value.
+    pub fn get_borrow_value(
+        &self,
+        obligation: &Obligation,
+        deposit_reserve: &Reserve,
+    ) -> Result<U256, ProgramError> {
+        let overflow = || ProgramError::from(SolarisAutoError::MathOverflow);
+        let wad = U256::from(WAD);
+        let bps = U256::from(BPS);
+        let percent = U256::from(100);
+
+        let deposited_value = to_u256(&obligation.deposited_value);
+        let borrowed_value = to_u256(&obligation.borrowed_value);
+        let unhealthy_borrow_value = to_u256(&obligation.unhealthy_borrow_value);
+        let allowed_borrow_value = to_u256(&obligation.allowed_borrow_value);
+
+        if deposited_value <= borrowed_value {
+            return Err(SolarisAutoError::HealthBelowMinimum.into())
+        }
+
+        let target_leverage = U256::from(self.target_leverage);
+        let min_health_ratio = U256::from(self.min_health_ratio);
+        let deposited_bps = U256::from(BPS.saturating_sub(self.max_slippage_bps));
+
+        // X = (L * (D - B) - D) / (k + L * (1 - k))
+        let target_value = target_leverage
+            .checked_mul(deposited_value - borrowed_value)
+            .ok_or_else(overflow)?;
+        let leverage_limit = match target_value > deposited_value * wad {
+            true => (target_value - deposited_value * wad)
+                .checked_mul(bps)
+                .and_then(|value| {
+                    value.checked_div(
+                        deposited_bps * wad + target_leverage.checked_mul(bps - deposited_bps)?
+                    )
+                })
+                .ok_or_else(overflow)?,
+            false => U256::zero(),
+        };
+
+        // X = (U - H * B) / (H - k * t), no limit if every iteration raises health
+        let health_gain = deposited_bps * U256::from(deposit_reserve.liquidation_threshold) * wad;
+        let health_cost = min_health_ratio.checked_mul(bps * percent).ok_or_else(overflow)?;
+        let min_unhealthy_value = min_health_ratio.checked_mul(borrowed_value).ok_or_else(overflow)?;
+        let health_limit = match (health_cost > health_gain, unhealthy_borrow_value * wad > min_unhealthy_value) {
+            (false, _) => U256::MAX,
+            (true, false) => U256::zero(),
+            (true, true) => (unhealthy_borrow_value * wad - min_unhealthy_value)
+                .checked_mul(bps * percent)
+                .ok_or_else(overflow)?
+                / (health_cost - health_gain),
+        };
+
+        let borrow_limit = allowed_borrow_value.saturating_sub(borrowed_value);
+
+        Ok(leverage_limit.min(health_limit).min(borrow_limit))
+    }
+
+    pub fn is_healthy(&self, obligation: &Obligation) -> Result<bool, ProgramError> {
+        let min_unhealthy_value = U256::from(self.min_health_ratio)
+            .checked_mul(to_u256(&obligation.borrowed_value))
+            .ok_or(ProgramError::from(SolarisAutoError::MathOverflow))?;
+
+        Ok(to_u256(&obligation.unhealthy_borrow_value) * U256::from(WAD) >= min_unhealthy_value)
+    }
+}
+
+/// Reserve liquidity amount of `value` scaled by WAD. Rounded down
+pub fn value_to_amount(value: U256, reserve: &Reserve) -> Result<u64, ProgramError> {
+    let price = to_u256(&reserve.liquidity_market_price);
+
+    let amount = U256::from(10)
+        .checked_pow(U256::from(reserve.liquidity_mint_decimals))
+        .and_then(|decimals| value.checked_mul(decimals))
+        .and_then(|value| value.checked_div(price))
+        .ok_or(ProgramError::from(SolarisAutoError::MathOverflow))?;
+
+    Ok(amount.min(U256::from(u64::MAX)).as_u64())
+}
+
+/// Swap output by the reserve prices minus `max_slippage_bps`. Rounded down
+pub fn get_minimum_amount_out(
+    amount_in: u64,
+    from_reserve: &Reserve,
+    to_reserve: &Reserve,
+    max_slippage_bps: u64,
+) -> Result<u64, ProgramError> {
+    let pow10 = |decimals: u8| U256::from(10).checked_pow(U256::from(decimals));
+
+    let amount_out = pow10(to_reserve.liquidity_mint_decimals)
+        .and_then(|decimals| {
+            U256::from(amount_in)
+                .checked_mul(to_u256(&from_reserve.liquidity_market_price))?
+                .checked_mul(decimals)?
+                .checked_mul(U256::from(BPS.saturating_sub(max_slippage_bps)))
+        })
+        .and_then(|value| {
+            let denominator = to_u256(&to_reserve.liquidity_market_price)
+                .checked_mul(pow10(from_reserve.liquidity_mint_decimals)?)?
+                .checked_mul(U256::from(BPS))?;
+
+            value.checked_div(denominator)
+        })
+        .ok_or(ProgramError::from(SolarisAutoError::MathOverflow))?;
+
+    if amount_out > U256::from(u64::MAX) {
+        return Err(SolarisAutoError::MathOverflow.into())
+    }
+
+    Ok(amount_out.as_u64())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::helpers::lending_healthfactor::Decimal;
+
+    fn reserve(decimals: u8, price: u64, liquidation_threshold: u8) -> Reserve {
+        Reserve {
+            lending_market: Pubkey::new_unique(),
+            liquidity_mint: Pubkey::new_unique(),
+            liquidity_mint_decimals: decimals,
+            liquidity_supply: Pubkey::new_unique(),
+            liquidity_pyth_oracle: Pubkey::new_unique(),
+            liquidity_switchboard_oracle: Pubkey::new_unique(),
+            liquidity_available_amount: 0,
+            liquidity_borrowed_amount_wads: Decimal::default(),
+            liquidity_market_price: Decimal::from(price),
+            collateral_mint: Pubkey::new_unique(),
+            collateral_mint_total_supply: 0,
+            collateral_supply: Pubkey::new_unique(),
+            optimal_utilization_rate: 80,
+            loan_to_value_ratio: 75,
+            liquidation_bonus: 5,
+            liquidation_threshold,
+            min_borrow_rate: 0,
+            optimal_borrow_rate: 8,
+            max_borrow_rate: 50,
+        }
+    }
+
+    fn obligation(deposited: u64, borrowed: u64, allowed: u64, unhealthy: u64) -> Obligation {
+        Obligation {
+            lending_market: Pubkey::new_unique(),
+            owner: Pubkey::new_unique(),
+            deposits: vec![],
+            borrows: vec![],
+            deposited_value: Decimal::from(deposited),
+            borrowed_value: Decimal::from(borrowed),
+            allowed_borrow_value: Decimal::from(allowed),
+            unhealthy_borrow_value: Decimal::from(unhealthy),
+        }
+    }
+
+    #[test]
+    fn leverage_borrow_value() {
+        let sol_reserve = reserve(9, 100, 80);
+        let usdc_reserve = reserve(6, 1, 90);
+        let wad = U256::from(WAD);
+
+        let mut target = LeverageTarget {
+            target_leverage: 2 * WAD as u128,
+            min_health_ratio: 1_500_000_000_000_000_000,
+            max_slippage_bps: 0,
+        };
+
+        // 10 SOL deposited, limited by the Solend borrow limit
+        assert_eq!(target.get_borrow_value(&obligation(1_000, 0, 750, 800), &sol_reserve), Ok(wad * 750));
+
+        // Limited by the target leverage: (1_750 + X) / (1_750 + X - 750 - X) = 2
+        assert_eq!(target.get_borrow_value(&obligation(1_750, 750, 1_312, 1_400), &sol_reserve), Ok(wad * 250));
+
+        // Limited by the health: (800 + 0.8 * X) / (500 + X) = 1.5
+        target.target_leverage = 5 * WAD as u128;
+        assert_eq!(
+            value_to_amount(target.get_borrow_value(&obligation(1_000, 500, 750, 800), &sol_reserve).unwrap(), &usdc_reserve),
+            Ok(71_428_571),
+        );
+
+        // Slippage lowers the deposited value: (2_000 - 1_750) / (0.99 + 2 * 0.01)
+        target.target_leverage = 2 * WAD as u128;
+        target.max_slippage_bps = 100;
+        assert_eq!(
+            value_to_amount(target.get_borrow_value(&obligation(1_750, 750, 1_312, 1_400), &sol_reserve).unwrap(), &usdc_reserve),
+            Ok(247_524_752),
+        );
+
+        // Nothing to borrow
+        target.min_health_ratio = 2 * WAD as u128;
+        assert_eq!(target.get_borrow_value(&obligation(1_000, 500, 750, 800), &sol_reserve), Ok(U256::zero()));
+        assert_eq!(target.is_healthy(&obligation(1_000, 500, 750, 800)), Ok(false));
+        assert_eq!(target.is_healthy(&obligation(1_000, 400, 750, 800)), Ok(true));
+
+        assert_eq!(
+            target.get_borrow_value(&obligation(500, 500, 750, 800), &sol_reserve),
+            Err(ProgramError::from(SolarisAutoError::HealthBelowMinimum)),
+        );
+    }
+
+    #[test]
+    fn minimum_amount_out() {
+        let sol_reserve = reserve(9, 100, 80);
+        let usdc_reserve = reserve(6, 1, 90);
+
+        // 100 USDC for 1 SOL minus 1%
+        assert_eq!(get_minimum_amount_out(100_000_000, &usdc_reserve, &sol_reserve, 100), Ok(990_000_000));
+        assert_eq!(get_minimum_amount_out(990_000_000, &sol_reserve, &usdc_reserve, 0), Ok(99_000_000));
+        assert_eq!(get_minimum_amount_out(1, &usdc_reserve, &sol_reserve, 0), Ok(10));
+    }
+}
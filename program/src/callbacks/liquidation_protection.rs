@@ -2,19 +2,50 @@ use solana_program::{
     account_info::{AccountInfo, next_account_info},
     instruction::{Instruction, AccountMeta},
     entrypoint::ProgramResult,
+    program_error::ProgramError,
     pubkey::Pubkey,
     program::{invoke, invoke_signed},
+    msg,
 };
 
+use borsh::BorshSerialize;
 use byteorder::ByteOrder;
 
 use crate::{
+    id,
     error::SolarisAutoError,
-    utils::get_seeds_delegate,
+    helpers::{
+        lending_healthfactor::{Obligation, Decimal, WAD},
+        lending_reserve::Reserve,
+        lending_position::{
+            assert_delegate_obligation,
+            load_solend_position,
+            get_refresh_reserves,
+            accrue_position_interest,
+            get_position_borrowed_amount,
+            get_position_obligation,
+            repay_position_liquidity,
+            withdraw_position_collateral,
+            check_position_health,
+        },
+        range_order::U256,
+    },
+    utils::{
+        get_seeds_delegate,
+        get_token_account_amount,
+        solend_refresh_reserve,
+        solend_refresh_obligation,
+        solend_repay_obligation_liquidity,
+        solend_withdraw_obligation_collateral_and_redeem_reserve_collateral,
+    },
 };
 
+use super::process_callback::CallbackContext;
+
 //Pubkey is "3Aix9sK31V2sSLwhbnBcYH6FtN79oTXDLN6vskjPsTbj"
 pub const CALLBACK_SOLEND_LIQUIDATION_PROTECTION: &[u8] = &[32, 53, 9, 1, 169, 162, 247, 15, 108, 3, 155, 52, 149, 20, 2, 86, 154, 148, 207, 4, 134, 152, 207, 14, 16, 80, 168, 73, 173, 86, 193, 182];
+// Pubkey is "7P5pt5cTxtdNd4dGCqUfkwcx8Uv8uJiwRw8pkbErxoQd"
+pub const CALLBACK_SOLEND_REPAY_AND_WITHDRAW: &[u8] = &[94, 206, 91, 8, 167, 42, 45, 118, 33, 172, 28, 249, 2, 162, 241, 3, 134, 195, 207, 80, 131, 95, 86, 70, 108, 45, 164, 65, 222, 223, 46, 154];
 
 ///  Repay borrowed liquidity to a reserve. Requires a refreshed obligation and reserve.
 ///
@@ -195,4 +226,536 @@ pub fn process_callback_withdraw(
     )?;
 
     Ok(())
+}
+
+/// Liquidation protection in one callback. Refreshes every reserve of the obligation,
+/// repays debt with the taking amount of the fill, refreshes them again and
+/// withdraws and redeems collateral to pay making amount to the taker.
+/// Collateral amount is computed by the exchange rate of the refreshed reserve.
+/// Delegate is the obligation owner and the transfer authority.
+/// Repay is capped by the maker borrow and withdrawal is debited from
+/// the maker position, which must stay healthy.
+///
+/// In the target health mode fill amounts don't come from the taker:
+/// the taking amount is the minimum repay that brings the maker position
+/// to `target_ratio` after the matching withdrawal, see `quote_solend_repay_and_withdraw`.
+/// Nonzero taking amount of the taker is the maximum, fill fails if the quote exceeds it.
+///
+/// Accounts required:
+///
+/// 0. `[]` Callback program id: 7P5pt5cTxtdNd4dGCqUfkwcx8Uv8uJiwRw8pkbErxoQd
+/// 1. `[]` Solend program
+/// 2. `[writable]` Source liquidity token account. Maker token account
+///                   that receives taker asset, delegate can transfer from it.
+/// 3. `[writable]` Repay reserve liquidity supply SPL Token account.
+/// 4. `[writable]` Repay reserve account.
+/// 5. `[writable]` Withdraw reserve collateral supply SPL Token account.
+/// 6. `[writable]` Destination collateral token account.
+/// 7. `[writable]` Withdraw reserve account.
+/// 8. `[writable]` Obligation account.
+/// 9. `[]` Lending market account.
+/// 10 `[]` Derived lending market authority.
+/// 11 `[writable]` Destination liquidity token account. Maker token account
+///                   of maker asset.
+/// 12 `[writable]` Withdraw reserve collateral SPL Token mint.
+/// 13 `[writable]` Withdraw reserve liquidity supply SPL Token account.
+/// 14 `[]` Delegate. Obligation owner and transfer authority.
+/// 15 `[]` Clock sysvar.
+/// 16 `[]` Token program id.
+/// 17 `[writable]` Maker Solend position of the obligation.
+/// 18.. For every reserve of the obligation, deposits and then borrows:
+///     `[writable]` Reserve account, `[]` Pyth price, `[]` Switchboard price
+///
+/// Instruction data is empty or has the target health mode format
+/// ```
+/// pub struct SolendRepayAndWithdrawTargetHealth {
+///     target_ratio: u128, // unhealthy borrow value / borrowed value, scaled by WAD
+/// }
+/// ```
+pub fn process_callback_solend_repay_and_withdraw(
+    _instr: &Instruction,
+    accounts: &[AccountInfo],
+    context: &CallbackContext,
+) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+
+    let _callback_program_info = next_account_info(account_info_iter)?;
+    let solend_program_info = next_account_info(account_info_iter)?;
+    let source_liquidity_info = next_account_info(account_info_iter)?;
+    let repay_reserve_liquidity_supply_info = next_account_info(account_info_iter)?;
+    let repay_reserve_info = next_account_info(account_info_iter)?;
+    let reserve_collateral_info = next_account_info(account_info_iter)?;
+    let user_collateral_info = next_account_info(account_info_iter)?;
+    let withdraw_reserve_info = next_account_info(account_info_iter)?;
+    let obligation_info = next_account_info(account_info_iter)?;
+    let lending_market_info = next_account_info(account_info_iter)?;
+    let lending_market_authority_info = next_account_info(account_info_iter)?;
+    let user_liquidity_info = next_account_info(account_info_iter)?;
+    let reserve_collateral_mint_info = next_account_info(account_info_iter)?;
+    let reserve_liquidity_supply_info = next_account_info(account_info_iter)?;
+    let delegate_info = next_account_info(account_info_iter)?;
+    let _clock_info = next_account_info(account_info_iter)?;
+    let _token_program_info = next_account_info(account_info_iter)?;
+    let solend_position_info = next_account_info(account_info_iter)?;
+
+    let refresh_reserve_infos: Vec<&AccountInfo> = account_info_iter.collect();
+
+    if refresh_reserve_infos.is_empty() || refresh_reserve_infos.len() % 3 != 0 {
+        return Err(ProgramError::NotEnoughAccountKeys)
+    }
+
+    assert_delegate_obligation(solend_program_info, obligation_info)?;
+
+    let mut solend_position = load_solend_position(
+        &id(),
+        solend_position_info,
+        &context.maker,
+        obligation_info.key,
+    )?;
+
+    refresh_obligation(solend_program_info, obligation_info, &refresh_reserve_infos, accounts)?;
+
+    let obligation = Obligation::unpack(&obligation_info.data.borrow())?;
+    accrue_position_interest(&mut solend_position, &obligation)?;
+
+    let repay_amount = context.taking_amount.min(
+        get_position_borrowed_amount(&solend_position, repay_reserve_info.key)?
+    );
+
+    msg!("repay_amount is {}", repay_amount);
+
+    let source_liquidity_before = get_token_account_amount(source_liquidity_info)?;
+
+    invoke_signed(
+        &solend_repay_obligation_liquidity(
+            solend_program_info.key,
+            source_liquidity_info.key,
+            repay_reserve_liquidity_supply_info.key,
+            repay_reserve_info.key,
+            obligation_info.key,
+            lending_market_info.key,
+            delegate_info.key,
+            repay_amount,
+        ),
+        accounts,
+        &[&get_seeds_delegate()],
+    )?;
+
+    let repaid_amount = source_liquidity_before
+        .checked_sub(get_token_account_amount(source_liquidity_info)?)
+        .ok_or(ProgramError::from(SolarisAutoError::MathOverflow))?;
+
+    repay_position_liquidity(&mut solend_position, repay_reserve_info.key, repaid_amount)?;
+
+    // Repay marks the reserve and the obligation stale
+    refresh_obligation(solend_program_info, obligation_info, &refresh_reserve_infos, accounts)?;
+
+    let withdraw_reserve = Reserve::unpack(&withdraw_reserve_info.data.borrow())?;
+    let collateral_amount = withdraw_reserve.collateral_to_redeem(context.making_amount)?;
+
+    msg!("collateral_amount is {}", collateral_amount);
+
+    withdraw_position_collateral(&mut solend_position, withdraw_reserve_info.key, collateral_amount)?;
+
+    let obligation = Obligation::unpack(&obligation_info.data.borrow())?;
+    check_position_health(&solend_position, &obligation, &get_refresh_reserves(&refresh_reserve_infos)?)?;
+
+    solend_position.serialize(&mut *solend_position_info.data.borrow_mut())?;
+
+    invoke_signed(
+        &solend_withdraw_obligation_collateral_and_redeem_reserve_collateral(
+            solend_program_info.key,
+            reserve_collateral_info.key,
+            user_collateral_info.key,
+            withdraw_reserve_info.key,
+            obligation_info.key,
+            lending_market_info.key,
+            lending_market_authority_info.key,
+            user_liquidity_info.key,
+            reserve_collateral_mint_info.key,
+            reserve_liquidity_supply_info.key,
+            delegate_info.key,
+            delegate_info.key,
+            collateral_amount,
+        ),
+        accounts,
+        &[&get_seeds_delegate()],
+    )
+}
+
+/// Refreshes reserves given as [reserve, pyth price, switchboard price] triples
+/// and then the obligation
+pub fn refresh_obligation(
+    solend_program_info: &AccountInfo,
+    obligation_info: &AccountInfo,
+    refresh_reserve_infos: &[&AccountInfo],
+    accounts: &[AccountInfo],
+) -> ProgramResult {
+    let mut reserve_ids = vec![];
+
+    for reserve_infos in refresh_reserve_infos.chunks(3) {
+        invoke(
+            &solend_refresh_reserve(
+                solend_program_info.key,
+                reserve_infos[0].key,
+                reserve_infos[1].key,
+                reserve_infos[2].key,
+            ),
+            accounts,
+        )?;
+
+        reserve_ids.push(*reserve_infos[0].key);
+    }
+
+    invoke(
+        &solend_refresh_obligation(solend_program_info.key, obligation_info.key, &reserve_ids),
+        accounts,
+    )
+}
+
+/// Quotes the taking amount of the fill in the target health mode of
+/// `process_callback_solend_repay_and_withdraw`, `None` if the mode is off.
+/// Refreshes the obligation first. Health is of the `maker` share of the
+/// obligation, see `get_position_obligation`, and the repay is capped by
+/// the maker borrow. Withdrawal is estimated by the signed order ratio
+/// `order_making_amount / order_taking_amount`.
+pub fn quote_solend_repay_and_withdraw(
+    instr: &Instruction,
+    accounts: &[AccountInfo],
+    maker: &Pubkey,
+    order_making_amount: u64,
+    order_taking_amount: u64,
+) -> Result<Option<u64>, ProgramError> {
+    if instr.data.is_empty() {
+        return Ok(None)
+    }
+
+    if instr.data.len() < 16 || accounts.len() < 18 {
+        return Err(SolarisAutoError::InvalidCallbackInst.into())
+    }
+
+    let target_ratio = byteorder::LE::read_u128(&instr.data[0..16]);
+
+    let solend_program_info = &accounts[1];
+    let repay_reserve_info = &accounts[4];
+    let withdraw_reserve_info = &accounts[7];
+    let obligation_info = &accounts[8];
+    let solend_position_info = &accounts[17];
+
+    let refresh_reserve_infos: Vec<&AccountInfo> = accounts[18..].iter().collect();
+
+    if refresh_reserve_infos.is_empty() || refresh_reserve_infos.len() % 3 != 0 {
+        return Err(ProgramError::NotEnoughAccountKeys)
+    }
+
+    assert_delegate_obligation(solend_program_info, obligation_info)?;
+
+    let mut solend_position = load_solend_position(&id(), solend_position_info, maker, obligation_info.key)?;
+
+    refresh_obligation(solend_program_info, obligation_info, &refresh_reserve_infos, accounts)?;
+
+    let obligation = Obligation::unpack(&obligation_info.data.borrow())?;
+    let repay_reserve = Reserve::unpack(&repay_reserve_info.data.borrow())?;
+    let withdraw_reserve = Reserve::unpack(&withdraw_reserve_info.data.borrow())?;
+
+    accrue_position_interest(&mut solend_position, &obligation)?;
+    let position_obligation = get_position_obligation(
+        &solend_position,
+        &obligation,
+        &get_refresh_reserves(&refresh_reserve_infos)?,
+    )?;
+
+    let repay_amount = get_target_health_repay_amount(
+        &TargetHealth {
+            obligation: &position_obligation,
+            repay_reserve_id: repay_reserve_info.key,
+            repay_reserve: &repay_reserve,
+            withdraw_reserve: &withdraw_reserve,
+            target_ratio,
+        },
+        order_making_amount,
+        order_taking_amount,
+    )?.min(get_position_borrowed_amount(&solend_position, repay_reserve_info.key)?);
+
+    msg!("target health repay_amount is {}", repay_amount);
+
+    Ok(Some(repay_amount))
+}
+
+pub struct TargetHealth<'a> {
+    /// Maker share of the obligation
+    pub obligation: &'a Obligation,
+    pub repay_reserve_id: &'a Pubkey,
+    pub repay_reserve: &'a Reserve,
+    pub withdraw_reserve: &'a Reserve,
+    /// Unhealthy borrow value / borrowed value, scaled by WAD
+    pub target_ratio: u128,
+}
+
+/// Minimum repay amount R of the repay reserve liquidity such that
+/// (U - M * p_w * t_w) / (B - R * p_r) >= target_ratio, where U is
+/// the unhealthy borrow value, B is the borrowed value, p are prices per
+/// token amount, t_w is the withdraw reserve liquidation threshold and
+/// M = R * making_amount / taking_amount is the withdrawal paid to the taker.
+/// Rounded up and capped by the obligation debt in the repay reserve.
+pub fn get_target_health_repay_amount(
+    target_health: &TargetHealth,
+    making_amount: u64,
+    taking_amount: u64,
+) -> Result<u64, ProgramError> {
+    let TargetHealth { obligation, repay_reserve, withdraw_reserve, target_ratio, .. } = target_health;
+
+    if taking_amount == 0 || *target_ratio == 0 {
+        return Err(SolarisAutoError::InvalidCallbackInst.into())
+    }
+
+    let borrowed_amount_wads = obligation
+        .find_liquidity(target_health.repay_reserve_id)
+        .map(|liquidity| liquidity.borrowed_amount_wads)
+        .ok_or(ProgramError::from(SolarisAutoError::TargetHealthUnreachable))?;
+
+    let overflow = || ProgramError::from(SolarisAutoError::MathOverflow);
+    let wad = U256::from(WAD);
+    let target_ratio = U256::from(*target_ratio);
+    let pow10 = |decimals: u8| U256::from(10).checked_pow(U256::from(decimals)).ok_or_else(overflow);
+    let repay_decimals = pow10(repay_reserve.liquidity_mint_decimals)?;
+    let withdraw_decimals = pow10(withdraw_reserve.liquidity_mint_decimals)?;
+
+    // target_ratio * B - U, scaled by WAD^2
+    let target_borrowed_value = target_ratio
+        .checked_mul(to_u256(&obligation.borrowed_value))
+        .ok_or_else(overflow)?;
+    let unhealthy_borrow_value = to_u256(&obligation.unhealthy_borrow_value) * wad;
+
+    if target_borrowed_value <= unhealthy_borrow_value {
+        return Err(SolarisAutoError::TargetHealthReached.into())
+    }
+
+    // Withdrawal per repay amount scaled by WAD, rounded up
+    // in favour of health
+    let (ratio, remainder) = (U256::from(making_amount) * wad).div_mod(U256::from(taking_amount));
+    let ratio = match remainder.is_zero() {
+        true => ratio,
+        false => ratio + 1,
+    };
+
+    // Health gain of a repay unit: target_ratio * p_r - ratio * p_w * t_w,
+    // multiplied by 10^(d_r + d_w) * 100
+    let repay_gain = target_ratio
+        .checked_mul(to_u256(&repay_reserve.liquidity_market_price))
+        .and_then(|value| value.checked_mul(withdraw_decimals))
+        .and_then(|value| value.checked_mul(U256::from(100)))
+        .ok_or_else(overflow)?;
+    let withdraw_loss = ratio
+        .checked_mul(U256::from(withdraw_reserve.liquidation_threshold))
+        .and_then(|value| value.checked_mul(to_u256(&withdraw_reserve.liquidity_market_price)))
+        .and_then(|value| value.checked_mul(repay_decimals))
+        .ok_or_else(overflow)?;
+
+    if repay_gain <= withdraw_loss {
+        return Err(SolarisAutoError::TargetHealthUnreachable.into())
+    }
+
+    let (repay_amount, remainder) = (target_borrowed_value - unhealthy_borrow_value)
+        .checked_mul(repay_decimals)
+        .and_then(|value| value.checked_mul(withdraw_decimals))
+        .and_then(|value| value.checked_mul(U256::from(100)))
+        .ok_or_else(overflow)?
+        .div_mod(repay_gain - withdraw_loss);
+    let repay_amount = match remainder.is_zero() {
+        true => repay_amount,
+        false => repay_amount + 1,
+    };
+
+    let (debt, remainder) = to_u256(&borrowed_amount_wads).div_mod(wad);
+    let debt = match remainder.is_zero() {
+        true => debt,
+        false => debt + 1,
+    };
+
+    let repay_amount = repay_amount.min(debt);
+
+    if repay_amount > U256::from(u64::MAX) {
+        return Err(SolarisAutoError::MathOverflow.into())
+    }
+
+    Ok(repay_amount.as_u64())
+}
+
+pub fn to_u256(value: &Decimal) -> U256 {
+    let words = (value.0).0;
+
+    U256([words[0], words[1], words[2], 0])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        helpers::lending_healthfactor::{ObligationCollateral, ObligationLiquidity},
+        state::{SolendPosition, PositionCollateral, PositionLiquidity},
+    };
+
+    fn reserve(decimals: u8, price: u64, liquidation_threshold: u8) -> Reserve {
+        Reserve {
+            lending_market: Pubkey::new_unique(),
+            liquidity_mint: Pubkey::new_unique(),
+            liquidity_mint_decimals: decimals,
+            liquidity_supply: Pubkey::new_unique(),
+            liquidity_pyth_oracle: Pubkey::new_unique(),
+            liquidity_switchboard_oracle: Pubkey::new_unique(),
+            liquidity_available_amount: 0,
+            liquidity_borrowed_amount_wads: Decimal::default(),
+            liquidity_market_price: Decimal::from(price),
+            collateral_mint: Pubkey::new_unique(),
+            collateral_mint_total_supply: 0,
+            collateral_supply: Pubkey::new_unique(),
+            optimal_utilization_rate: 80,
+            loan_to_value_ratio: 75,
+            liquidation_bonus: 5,
+            liquidation_threshold,
+            min_borrow_rate: 0,
+            optimal_borrow_rate: 8,
+            max_borrow_rate: 50,
+        }
+    }
+
+    #[test]
+    fn target_health_repay_amount() {
+        let usdc_reserve_id = Pubkey::new_unique();
+        let usdc_reserve = reserve(6, 1, 90);
+        let sol_reserve = reserve(9, 100, 80);
+
+        // 10 SOL deposited, 700 USDC borrowed, health is 800 / 700
+        let obligation = Obligation {
+            lending_market: Pubkey::new_unique(),
+            owner: Pubkey::new_unique(),
+            deposits: vec![],
+            borrows: vec![ObligationLiquidity {
+                borrow_reserve: usdc_reserve_id,
+                cumulative_borrow_rate_wads: Decimal::from(1),
+                borrowed_amount_wads: Decimal::from(700_000_000),
+                market_value: Decimal::from(700),
+            }],
+            deposited_value: Decimal::from(1_000),
+            borrowed_value: Decimal::from(700),
+            allowed_borrow_value: Decimal::from(750),
+            unhealthy_borrow_value: Decimal::from(800),
+        };
+
+        let target_health = |target_ratio: u128| TargetHealth {
+            obligation: &obligation,
+            repay_reserve_id: &usdc_reserve_id,
+            repay_reserve: &usdc_reserve,
+            withdraw_reserve: &sol_reserve,
+            target_ratio,
+        };
+
+        // 1 SOL per 100 USDC: (800 - 0.8 * R) / (700 - R) >= 1.5, R = 357.142857..
+        let ratio_150 = 1_500_000_000_000_000_000;
+        assert_eq!(
+            get_target_health_repay_amount(&target_health(ratio_150), 1_000_000_000, 100_000_000),
+            Ok(357_142_858),
+        );
+
+        // Repay without withdrawal: (800 / 1.5) >= 700 - R
+        assert_eq!(
+            get_target_health_repay_amount(&target_health(ratio_150), 0, 1),
+            Ok(166_666_667),
+        );
+
+        // Capped by the debt in the repay reserve, the rest is borrowed elsewhere
+        let mut partial_obligation = obligation.clone();
+        partial_obligation.borrows[0].borrowed_amount_wads = Decimal::from(300_000_000);
+        assert_eq!(
+            get_target_health_repay_amount(
+                &TargetHealth { obligation: &partial_obligation, ..target_health(ratio_150) },
+                1_000_000_000,
+                100_000_000,
+            ),
+            Ok(300_000_000),
+        );
+
+        assert_eq!(
+            get_target_health_repay_amount(&target_health(WAD as u128), 1_000_000_000, 100_000_000),
+            Err(ProgramError::from(SolarisAutoError::TargetHealthReached)),
+        );
+
+        // Withdrawal lowers health more than repay raises it
+        assert_eq!(
+            get_target_health_repay_amount(&target_health(ratio_150), 2_000_000_000, 100_000_000),
+            Err(ProgramError::from(SolarisAutoError::TargetHealthUnreachable)),
+        );
+    }
+
+    #[test]
+    fn target_health_repay_amount_of_position() {
+        let usdc_reserve_id = Pubkey::new_unique();
+        let usdc_reserve = reserve(6, 1, 90);
+        let sol_reserve_id = Pubkey::new_unique();
+        let sol_reserve = reserve(9, 100, 80);
+
+        // Two makers with 10 SOL each borrowed 700 and 300 USDC, health is 1_600 / 1_000
+        let obligation = Obligation {
+            lending_market: Pubkey::new_unique(),
+            owner: Pubkey::new_unique(),
+            deposits: vec![ObligationCollateral {
+                deposit_reserve: sol_reserve_id,
+                deposited_amount: 20_000_000_000,
+                market_value: Decimal::from(2_000),
+            }],
+            borrows: vec![ObligationLiquidity {
+                borrow_reserve: usdc_reserve_id,
+                cumulative_borrow_rate_wads: Decimal::from(1),
+                borrowed_amount_wads: Decimal::from(1_000_000_000),
+                market_value: Decimal::from(1_000),
+            }],
+            deposited_value: Decimal::from(2_000),
+            borrowed_value: Decimal::from(1_000),
+            allowed_borrow_value: Decimal::from(1_500),
+            unhealthy_borrow_value: Decimal::from(1_600),
+        };
+
+        let position = |borrowed_amount: u128| SolendPosition {
+            deposits: vec![PositionCollateral {
+                deposit_reserve: sol_reserve_id,
+                deposited_amount: 10_000_000_000,
+            }],
+            borrows: vec![PositionLiquidity {
+                borrow_reserve: usdc_reserve_id,
+                borrowed_amount_wads: borrowed_amount * WAD as u128,
+                cumulative_borrow_rate_wads: WAD as u128,
+            }],
+            ..SolendPosition::new(Pubkey::new_unique(), Pubkey::new_unique())
+        };
+        let reserves = vec![(sol_reserve_id, sol_reserve.clone())];
+
+        let target_health = |obligation| TargetHealth {
+            obligation,
+            repay_reserve_id: &usdc_reserve_id,
+            repay_reserve: &usdc_reserve,
+            withdraw_reserve: &sol_reserve,
+            target_ratio: 1_500_000_000_000_000_000,
+        };
+
+        // Whole obligation is above the target
+        assert_eq!(
+            get_target_health_repay_amount(&target_health(&obligation), 1_000_000_000, 100_000_000),
+            Err(ProgramError::from(SolarisAutoError::TargetHealthReached)),
+        );
+
+        // But the first maker share is 800 / 700, same as in `target_health_repay_amount`
+        let first_obligation = get_position_obligation(&position(700_000_000), &obligation, &reserves).unwrap();
+        assert_eq!(
+            get_target_health_repay_amount(&target_health(&first_obligation), 1_000_000_000, 100_000_000),
+            Ok(357_142_858),
+        );
+
+        // And the second is 800 / 300
+        let second_obligation = get_position_obligation(&position(300_000_000), &obligation, &reserves).unwrap();
+        assert_eq!(
+            get_target_health_repay_amount(&target_health(&second_obligation), 1_000_000_000, 100_000_000),
+            Err(ProgramError::from(SolarisAutoError::TargetHealthReached)),
+        );
+    }
 }
\ No newline at end of file
@@ -1,2 +1,8 @@
 pub mod process_callback;
-pub mod liquidation_protection;
\ No newline at end of file
+pub mod liquidation_protection;
+pub mod interaction;
+pub mod spend_limits;
+pub mod deleverage;
+pub mod leverage;
+pub mod top_up;
+pub mod refinance;
\ No newline at end of file
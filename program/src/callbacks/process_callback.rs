@@ -1,29 +1,241 @@
+use std::cell::Cell;
 use solana_program::{
     account_info::AccountInfo,
     instruction::Instruction,
     program::invoke,
     entrypoint::ProgramResult,
     program_error::{ProgramError, PrintProgramError},
+    pubkey::Pubkey,
+    msg,
 };
 
 use crate::{
     error::SolarisAutoError,
+    utils::assert_instruction_accounts,
 };
 
-use super::liquidation_protection;
+use super::{liquidation_protection, deleverage, leverage, top_up, refinance};
 
+/// Amounts of the current fill. Built-in callbacks use them
+/// instead of the amounts hard-coded in the signed instruction data.
+/// Callbacks on the delegate obligation act on the position of `maker`
+#[derive(Clone, Debug, PartialEq)]
+pub struct CallbackContext {
+    pub maker: Pubkey,
+    pub making_amount: u64,
+    pub taking_amount: u64,
+    /// Order state updated by the top-up callback
+    pub topped_up_amount: Cell<u64>,
+}
+
+/// Runs callbacks of the order one by one. `instr` is bincode serialized
+/// `Vec<Instruction>`. Every callback consumes `1 + accounts.len()` infos:
+/// callback program and accounts that required by callback instruction.
+/// Supplied accounts must match the signed callback instruction.
+/// Fails as a whole if any callback fails.
 pub fn process_callback(
     instr: &[u8],
     accounts: &[AccountInfo],
+    context: &CallbackContext,
 ) -> ProgramResult {
-    bincode::deserialize::<Instruction>(instr)
-        .or(Err(ProgramError::from(SolarisAutoError::InvalidCallbackInst)))
-        .and_then(|callback| {
-            match callback.program_id.as_ref() {
-                liquidation_protection::CALLBACK_SOLEND_LIQUIDATION_PROTECTION => {
-                    liquidation_protection::process_callback_solend_repay_obligation_liquidity(&callback, accounts)
-                },
-                _ => invoke(&callback, accounts)
+    _process_callback(instr, accounts, |callback, infos| invoke_callback(callback, infos, context))
+}
+
+/// Taking amount quoted by built-in callbacks before the fill, e.g.
+/// the repay amount of Solend repay and withdraw in the target health mode.
+/// `None` if no callback quotes. Only one callback of the order can quote.
+pub fn quote_callback(
+    instr: &[u8],
+    accounts: &[AccountInfo],
+    maker: &Pubkey,
+    order_making_amount: u64,
+    order_taking_amount: u64,
+) -> Result<Option<u64>, ProgramError> {
+    let quoted_amount = Cell::new(None);
+
+    _process_callback(instr, accounts, |callback, infos| {
+        let amount = match callback.program_id.as_ref() {
+            liquidation_protection::CALLBACK_SOLEND_REPAY_AND_WITHDRAW => {
+                liquidation_protection::quote_solend_repay_and_withdraw(
+                    callback,
+                    infos,
+                    maker,
+                    order_making_amount,
+                    order_taking_amount,
+                )?
+            },
+            _ => None,
+        };
+
+        if amount.is_some() {
+            if quoted_amount.get().is_some() {
+                return Err(SolarisAutoError::InvalidCallbackInst.into())
             }
-        })
-}
\ No newline at end of file
+
+            quoted_amount.set(amount);
+        }
+
+        Ok(())
+    })?;
+
+    Ok(quoted_amount.get())
+}
+
+fn _process_callback<F>(
+    instr: &[u8],
+    accounts: &[AccountInfo],
+    invoke_callback: F,
+) -> ProgramResult
+    where F: Fn(&Instruction, &[AccountInfo]) -> ProgramResult {
+    let callbacks: Vec<Instruction> = bincode::deserialize(instr)
+        .or(Err(ProgramError::from(SolarisAutoError::InvalidCallbackInst)))?;
+
+    let mut offset = 0;
+    for (i, callback) in callbacks.iter().enumerate() {
+        let infos_num = 1 + callback.accounts.len();
+
+        let callback_infos = accounts
+            .get(offset..offset + infos_num)
+            .ok_or(ProgramError::NotEnoughAccountKeys)?;
+
+        assert_instruction_accounts(callback, callback_infos)?;
+
+        if let Err(error) = invoke_callback(callback, callback_infos) {
+            msg!("Callback {} failed", i);
+            error.print::<SolarisAutoError>();
+
+            return Err(error)
+        }
+
+        offset += infos_num;
+    }
+
+    Ok(())
+}
+
+fn invoke_callback(
+    callback: &Instruction,
+    accounts: &[AccountInfo],
+    context: &CallbackContext,
+) -> ProgramResult {
+    match callback.program_id.as_ref() {
+        liquidation_protection::CALLBACK_SOLEND_LIQUIDATION_PROTECTION => {
+            liquidation_protection::process_callback_solend_repay_obligation_liquidity(callback, accounts)
+        },
+        liquidation_protection::CALLBACK_SOLEND_REPAY_AND_WITHDRAW => {
+            liquidation_protection::process_callback_solend_repay_and_withdraw(callback, accounts, context)
+        },
+        deleverage::CALLBACK_SOLEND_DELEVERAGE => {
+            deleverage::process_callback_solend_deleverage(callback, accounts, context)
+        },
+        leverage::CALLBACK_SOLEND_LEVERAGE => {
+            leverage::process_callback_solend_leverage(callback, accounts, context)
+        },
+        top_up::CALLBACK_SOLEND_TOP_UP => {
+            top_up::process_callback_solend_top_up(callback, accounts, context)
+        },
+        refinance::CALLBACK_SOLEND_REFINANCE => {
+            refinance::process_callback_solend_refinance(callback, accounts, context)
+        }
+        _ => invoke(callback, accounts)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cell::RefCell;
+    use solana_sdk::{
+        pubkey::Pubkey,
+        account_info::AccountInfo,
+        instruction::{Instruction, AccountMeta},
+    };
+
+    #[test]
+    fn process_callbacks_sequentially() {
+        let keys: Vec<Pubkey> = (0..5).map(|_| Pubkey::new_unique()).collect();
+        let owner = Pubkey::new_unique();
+        let mut lamports: Vec<u64> = vec![0; 5];
+        let mut data = [[0u8; 0]; 5];
+
+        let infos: Vec<AccountInfo> = keys.iter()
+            .zip(lamports.iter_mut())
+            .zip(data.iter_mut())
+            .map(|((key, lamports), data)| {
+                AccountInfo::new(key, false, true, lamports, data, &owner, false, 0)
+            })
+            .collect();
+
+        let callbacks = bincode::serialize(&vec![
+            Instruction {
+                program_id: keys[0],
+                accounts: vec![AccountMeta::new(keys[1], false)],
+                data: vec![],
+            },
+            Instruction {
+                program_id: keys[2],
+                accounts: vec![
+                    AccountMeta::new(keys[3], false),
+                    AccountMeta::new(keys[4], false),
+                ],
+                data: vec![],
+            },
+        ]).unwrap();
+
+        let invoked = RefCell::new(vec![]);
+        let invoke_plug = |instr: &Instruction, infos: &[AccountInfo]| {
+            invoked.borrow_mut().push((instr.program_id, infos.iter().map(|info| *info.key).collect::<Vec<_>>()));
+            Ok(())
+        };
+
+        assert_eq!(_process_callback(&callbacks, &infos, invoke_plug), Ok(()));
+        assert_eq!(
+            invoked.into_inner(),
+            vec![
+                (keys[0], vec![keys[0], keys[1]]),
+                (keys[2], vec![keys[2], keys[3], keys[4]]),
+            ],
+        );
+
+        let invoke_plug_err = |instr: &Instruction, _infos: &[AccountInfo]| {
+            match instr.program_id == keys[2] {
+                true => Err(ProgramError::Custom(0)),
+                false => Ok(()),
+            }
+        };
+
+        assert_eq!(
+            _process_callback(&callbacks, &infos, invoke_plug_err),
+            Err(ProgramError::Custom(0)),
+        );
+        assert_eq!(
+            _process_callback(&callbacks, &infos[..4], |_, _| Ok(())),
+            Err(ProgramError::NotEnoughAccountKeys),
+        );
+
+        let mut substituted_infos = infos.clone();
+        substituted_infos.swap(3, 4);
+        assert_eq!(
+            _process_callback(&callbacks, &substituted_infos, |_, _| Ok(())),
+            Err(ProgramError::from(SolarisAutoError::InstructionAccountsMismatch)),
+        );
+
+        // Writable account satisfies readonly meta, but not the other way around
+        let readonly_callbacks = bincode::serialize(&vec![
+            Instruction {
+                program_id: keys[0],
+                accounts: vec![AccountMeta::new_readonly(keys[1], false)],
+                data: vec![],
+            },
+        ]).unwrap();
+
+        assert_eq!(_process_callback(&readonly_callbacks, &infos[..2], |_, _| Ok(())), Ok(()));
+
+        let mut readonly_infos = infos.clone();
+        readonly_infos[1].is_writable = false;
+        assert_eq!(
+            _process_callback(&callbacks, &readonly_infos, |_, _| Ok(())),
+            Err(ProgramError::from(SolarisAutoError::InstructionAccountsMismatch)),
+        );
+    }
+}
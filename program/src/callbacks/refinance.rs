@@ -0,0 +1,459 @@
+use solana_program::{
+    account_info::{AccountInfo, next_account_info},
+    instruction::Instruction,
+    entrypoint::ProgramResult,
+    program_error::ProgramError,
+    program::invoke_signed,
+    pubkey::Pubkey,
+    msg,
+};
+
+use borsh::BorshSerialize;
+use byteorder::ByteOrder;
+
+use crate::{
+    id,
+    error::SolarisAutoError,
+    state::SolendPosition,
+    helpers::{
+        lending_healthfactor::Obligation,
+        lending_reserve::Reserve,
+        lending_position::{
+            assert_delegate_obligation,
+            load_solend_position,
+            get_refresh_reserves,
+            accrue_position_interest,
+            borrow_position_liquidity,
+            get_position_borrowed_amount,
+            repay_position_liquidity,
+            deposit_position_collateral,
+            withdraw_position_collateral,
+            check_position_health,
+        },
+    },
+    utils::{
+        get_seeds_delegate,
+        get_token_account_amount,
+        solend_borrow_obligation_liquidity,
+        solend_deposit_reserve_liquidity_and_obligation_collateral,
+        solend_repay_obligation_liquidity,
+        solend_withdraw_obligation_collateral_and_redeem_reserve_collateral,
+    },
+};
+
+use super::{
+    liquidation_protection::{refresh_obligation, to_u256},
+    process_callback::CallbackContext,
+};
+
+// Pubkey is "74Tfpq5zF529Pw3TfJaUPvrh2qpeAEWbi8ThnbysSjVG"
+pub const CALLBACK_SOLEND_REFINANCE: &[u8] = &[90, 9, 4, 231, 190, 69, 74, 227, 86, 26, 198, 33, 230, 137, 122, 147, 39, 118, 36, 46, 185, 168, 67, 37, 91, 37, 160, 107, 9, 151, 161, 87];
+
+/// Moves a position from lending market A to lending market B, e.g. when
+/// signed with the lending reserve rate predicate on the A borrow reserve.
+/// Borrows `borrow_amount` on B, repays A debt with everything received,
+/// withdraws and redeems `withdraw_amount` of collateral liquidity from A
+/// and deposits everything redeemed into B. The B obligation must already
+/// have collateral for the borrow. Markets can belong to different
+/// deployments of the SPL token-lending program. Delegate is the owner of
+/// both obligations and the transfer authority of both maker token accounts.
+/// Maker positions of both obligations are updated: repay is capped by
+/// the maker borrow on A, withdrawal by the maker collateral on A, and
+/// both positions must stay healthy.
+///
+/// Accounts required:
+///
+/// 0. `[]` Callback program id: 74Tfpq5zF529Pw3TfJaUPvrh2qpeAEWbi8ThnbysSjVG
+/// 1. `[]` Lending program of market A
+/// 2. `[writable]` Obligation account of market A.
+/// 3. `[]` Lending market A account.
+/// 4. `[]` Derived lending market A authority.
+/// 5. `[writable]` Repay reserve account.
+/// 6. `[writable]` Repay reserve liquidity supply SPL Token account.
+/// 7. `[writable]` Withdraw reserve account.
+/// 8. `[writable]` Withdraw reserve collateral supply SPL Token account.
+/// 9. `[writable]` Withdraw reserve collateral SPL Token mint.
+/// 10 `[writable]` Withdraw reserve liquidity supply SPL Token account.
+/// 11 `[writable]` Destination collateral token account of market A.
+/// 12 `[]` Lending program of market B
+/// 13 `[writable]` Obligation account of market B.
+/// 14 `[]` Lending market B account.
+/// 15 `[]` Derived lending market B authority.
+/// 16 `[writable]` Borrow reserve account.
+/// 17 `[writable]` Borrow reserve liquidity supply SPL Token account.
+/// 18 `[writable]` Borrow reserve liquidity fee receiver.
+/// 19 `[writable]` Deposit reserve account.
+/// 20 `[writable]` Deposit reserve liquidity supply SPL Token account.
+/// 21 `[writable]` Deposit reserve collateral SPL Token mint.
+/// 22 `[writable]` Deposit reserve collateral supply SPL Token account.
+/// 23 `[]` Deposit reserve Pyth price.
+/// 24 `[]` Deposit reserve Switchboard price.
+/// 25 `[writable]` Destination collateral token account of market B.
+/// 26 `[writable]` Maker token account of the debt liquidity.
+/// 27 `[writable]` Maker token account of the collateral liquidity.
+/// 28 `[]` Delegate. Obligation owner and transfer authority.
+/// 29 `[]` Clock sysvar.
+/// 30 `[]` Token program id.
+/// 31 `[writable]` Maker Solend position of the obligation A.
+/// 32 `[writable]` Maker Solend position of the obligation B.
+/// 33.. For every reserve of the obligation A and then of the obligation B
+///     after the refinance, deposits and then borrows:
+///     `[writable]` Reserve account, `[]` Pyth price, `[]` Switchboard price
+///
+/// Instruction data format is
+/// ```
+/// pub struct SolendRefinance {
+///     borrow_amount: u64,     // borrowed on market B
+///     withdraw_amount: u64,   // collateral liquidity withdrawn from market A,
+///                             // u64::MAX for all collateral of the maker position
+///     market_a_reserves: u8,  // number of refresh reserves of the obligation A
+/// }
+/// ```
+pub fn process_callback_solend_refinance(
+    instr: &Instruction,
+    accounts: &[AccountInfo],
+    context: &CallbackContext,
+) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+
+    let _callback_program_info = next_account_info(account_info_iter)?;
+    let program_a_info = next_account_info(account_info_iter)?;
+    let obligation_a_info = next_account_info(account_info_iter)?;
+    let lending_market_a_info = next_account_info(account_info_iter)?;
+    let lending_market_a_authority_info = next_account_info(account_info_iter)?;
+    let repay_reserve_info = next_account_info(account_info_iter)?;
+    let repay_reserve_liquidity_supply_info = next_account_info(account_info_iter)?;
+    let withdraw_reserve_info = next_account_info(account_info_iter)?;
+    let withdraw_reserve_collateral_supply_info = next_account_info(account_info_iter)?;
+    let withdraw_reserve_collateral_mint_info = next_account_info(account_info_iter)?;
+    let withdraw_reserve_liquidity_supply_info = next_account_info(account_info_iter)?;
+    let user_collateral_a_info = next_account_info(account_info_iter)?;
+    let program_b_info = next_account_info(account_info_iter)?;
+    let obligation_b_info = next_account_info(account_info_iter)?;
+    let lending_market_b_info = next_account_info(account_info_iter)?;
+    let lending_market_b_authority_info = next_account_info(account_info_iter)?;
+    let borrow_reserve_info = next_account_info(account_info_iter)?;
+    let borrow_reserve_liquidity_supply_info = next_account_info(account_info_iter)?;
+    let borrow_reserve_fee_receiver_info = next_account_info(account_info_iter)?;
+    let deposit_reserve_info = next_account_info(account_info_iter)?;
+    let deposit_reserve_liquidity_supply_info = next_account_info(account_info_iter)?;
+    let deposit_reserve_collateral_mint_info = next_account_info(account_info_iter)?;
+    let deposit_reserve_collateral_supply_info = next_account_info(account_info_iter)?;
+    let deposit_reserve_pyth_info = next_account_info(account_info_iter)?;
+    let deposit_reserve_switchboard_info = next_account_info(account_info_iter)?;
+    let user_collateral_b_info = next_account_info(account_info_iter)?;
+    let user_debt_liquidity_info = next_account_info(account_info_iter)?;
+    let user_collateral_liquidity_info = next_account_info(account_info_iter)?;
+    let delegate_info = next_account_info(account_info_iter)?;
+    let _clock_info = next_account_info(account_info_iter)?;
+    let _token_program_info = next_account_info(account_info_iter)?;
+    let solend_position_a_info = next_account_info(account_info_iter)?;
+    let solend_position_b_info = next_account_info(account_info_iter)?;
+
+    let refresh_reserve_infos: Vec<&AccountInfo> = account_info_iter.collect();
+
+    if instr.data.len() < 17 {
+        return Err(SolarisAutoError::InvalidCallbackInst.into())
+    }
+
+    let borrow_amount = byteorder::LE::read_u64(&instr.data[0..8]);
+    let withdraw_amount = byteorder::LE::read_u64(&instr.data[8..16]);
+    let market_a_reserves = instr.data[16] as usize * 3;
+
+    if market_a_reserves == 0
+        || refresh_reserve_infos.len() <= market_a_reserves
+        || refresh_reserve_infos.len() % 3 != 0 {
+        return Err(ProgramError::NotEnoughAccountKeys)
+    }
+
+    let (refresh_reserve_a_infos, refresh_reserve_b_infos) = refresh_reserve_infos.split_at(market_a_reserves);
+
+    assert_delegate_obligation(program_a_info, obligation_a_info)?;
+    assert_delegate_obligation(program_b_info, obligation_b_info)?;
+
+    let mut solend_position_a = load_solend_position(
+        &id(),
+        solend_position_a_info,
+        &context.maker,
+        obligation_a_info.key,
+    )?;
+    let mut solend_position_b = load_solend_position(
+        &id(),
+        solend_position_b_info,
+        &context.maker,
+        obligation_b_info.key,
+    )?;
+
+    // Borrow on B. New reserves of the obligation B are refreshed after the deposit
+
+    let obligation_b = Obligation::unpack(&obligation_b_info.data.borrow())?;
+    let refresh_reserve_b_infos_before = get_obligation_refresh_infos(&obligation_b, refresh_reserve_b_infos);
+
+    refresh_obligation(program_b_info, obligation_b_info, &refresh_reserve_b_infos_before, accounts)?;
+
+    let obligation_b = Obligation::unpack(&obligation_b_info.data.borrow())?;
+    accrue_position_interest(&mut solend_position_b, &obligation_b)?;
+
+    let borrowed_amount_wads_before = obligation_b
+        .find_liquidity(borrow_reserve_info.key)
+        .map(|liquidity| to_u256(&liquidity.borrowed_amount_wads))
+        .unwrap_or_default();
+
+    let debt_liquidity_before = get_token_account_amount(user_debt_liquidity_info)?;
+
+    invoke_signed(
+        &solend_borrow_obligation_liquidity(
+            program_b_info.key,
+            borrow_reserve_liquidity_supply_info.key,
+            user_debt_liquidity_info.key,
+            borrow_reserve_info.key,
+            borrow_reserve_fee_receiver_info.key,
+            obligation_b_info.key,
+            lending_market_b_info.key,
+            lending_market_b_authority_info.key,
+            delegate_info.key,
+            borrow_amount,
+        ),
+        accounts,
+        &[&get_seeds_delegate()],
+    )?;
+
+    // Borrowed amount includes the borrow fee
+    let obligation_b = Obligation::unpack(&obligation_b_info.data.borrow())?;
+    let liquidity = obligation_b
+        .find_liquidity(borrow_reserve_info.key)
+        .ok_or(ProgramError::from(SolarisAutoError::InvalidObligationData))?;
+
+    borrow_position_liquidity(
+        &mut solend_position_b,
+        borrow_reserve_info.key,
+        to_u256(&liquidity.borrowed_amount_wads)
+            .checked_sub(borrowed_amount_wads_before)
+            .ok_or(ProgramError::from(SolarisAutoError::MathOverflow))?,
+        to_u256(&liquidity.cumulative_borrow_rate_wads),
+    )?;
+
+    let borrowed_amount = get_token_account_amount(user_debt_liquidity_info)?
+        .checked_sub(debt_liquidity_before)
+        .ok_or(ProgramError::from(SolarisAutoError::MathOverflow))?;
+
+    // Repay on A, at most the maker borrow
+
+    refresh_obligation(program_a_info, obligation_a_info, refresh_reserve_a_infos, accounts)?;
+
+    let obligation_a = Obligation::unpack(&obligation_a_info.data.borrow())?;
+    accrue_position_interest(&mut solend_position_a, &obligation_a)?;
+
+    let repay_amount = borrowed_amount.min(
+        get_position_borrowed_amount(&solend_position_a, repay_reserve_info.key)?
+    );
+
+    msg!("repay amount is {}", repay_amount);
+
+    let debt_liquidity_before = get_token_account_amount(user_debt_liquidity_info)?;
+
+    invoke_signed(
+        &solend_repay_obligation_liquidity(
+            program_a_info.key,
+            user_debt_liquidity_info.key,
+            repay_reserve_liquidity_supply_info.key,
+            repay_reserve_info.key,
+            obligation_a_info.key,
+            lending_market_a_info.key,
+            delegate_info.key,
+            repay_amount,
+        ),
+        accounts,
+        &[&get_seeds_delegate()],
+    )?;
+
+    let repaid_amount = debt_liquidity_before
+        .checked_sub(get_token_account_amount(user_debt_liquidity_info)?)
+        .ok_or(ProgramError::from(SolarisAutoError::MathOverflow))?;
+
+    repay_position_liquidity(&mut solend_position_a, repay_reserve_info.key, repaid_amount)?;
+
+    // Withdraw from A. Full repay removes the borrow from the obligation,
+    // so only its remaining reserves are refreshed
+
+    let obligation_a = Obligation::unpack(&obligation_a_info.data.borrow())?;
+    let refresh_reserve_a_infos = get_obligation_refresh_infos(&obligation_a, refresh_reserve_a_infos);
+
+    refresh_obligation(program_a_info, obligation_a_info, &refresh_reserve_a_infos, accounts)?;
+
+    let withdraw_reserve = Reserve::unpack(&withdraw_reserve_info.data.borrow())?;
+    let collateral_amount = get_withdraw_collateral_amount(
+        &solend_position_a,
+        withdraw_reserve_info.key,
+        &withdraw_reserve,
+        withdraw_amount,
+    )?;
+
+    msg!("collateral_amount is {}", collateral_amount);
+
+    withdraw_position_collateral(&mut solend_position_a, withdraw_reserve_info.key, collateral_amount)?;
+
+    let obligation_a = Obligation::unpack(&obligation_a_info.data.borrow())?;
+    check_position_health(&solend_position_a, &obligation_a, &get_refresh_reserves(&refresh_reserve_a_infos)?)?;
+
+    let collateral_liquidity_before = get_token_account_amount(user_collateral_liquidity_info)?;
+
+    invoke_signed(
+        &solend_withdraw_obligation_collateral_and_redeem_reserve_collateral(
+            program_a_info.key,
+            withdraw_reserve_collateral_supply_info.key,
+            user_collateral_a_info.key,
+            withdraw_reserve_info.key,
+            obligation_a_info.key,
+            lending_market_a_info.key,
+            lending_market_a_authority_info.key,
+            user_collateral_liquidity_info.key,
+            withdraw_reserve_collateral_mint_info.key,
+            withdraw_reserve_liquidity_supply_info.key,
+            delegate_info.key,
+            delegate_info.key,
+            collateral_amount,
+        ),
+        accounts,
+        &[&get_seeds_delegate()],
+    )?;
+
+    let deposit_amount = get_token_account_amount(user_collateral_liquidity_info)?
+        .checked_sub(collateral_liquidity_before)
+        .ok_or(ProgramError::from(SolarisAutoError::MathOverflow))?;
+
+    msg!("deposit amount is {}", deposit_amount);
+
+    // Deposit into B, the deposit reserve is refreshed by the lending program
+
+    let deposited_amount_before = get_deposited_amount(obligation_b_info, deposit_reserve_info.key)?;
+
+    invoke_signed(
+        &solend_deposit_reserve_liquidity_and_obligation_collateral(
+            program_b_info.key,
+            user_collateral_liquidity_info.key,
+            user_collateral_b_info.key,
+            deposit_reserve_info.key,
+            deposit_reserve_liquidity_supply_info.key,
+            deposit_reserve_collateral_mint_info.key,
+            lending_market_b_info.key,
+            lending_market_b_authority_info.key,
+            deposit_reserve_collateral_supply_info.key,
+            obligation_b_info.key,
+            delegate_info.key,
+            deposit_reserve_pyth_info.key,
+            deposit_reserve_switchboard_info.key,
+            delegate_info.key,
+            deposit_amount,
+        ),
+        accounts,
+        &[&get_seeds_delegate()],
+    )?;
+
+    let collateral_amount = get_deposited_amount(obligation_b_info, deposit_reserve_info.key)?
+        .checked_sub(deposited_amount_before)
+        .ok_or(ProgramError::from(SolarisAutoError::MathOverflow))?;
+
+    deposit_position_collateral(&mut solend_position_b, deposit_reserve_info.key, collateral_amount)?;
+
+    // Borrow and deposit mark the obligation B stale
+
+    let obligation_b = Obligation::unpack(&obligation_b_info.data.borrow())?;
+    let refresh_reserve_b_infos = get_obligation_refresh_infos(&obligation_b, refresh_reserve_b_infos);
+
+    refresh_obligation(program_b_info, obligation_b_info, &refresh_reserve_b_infos, accounts)?;
+
+    let obligation_b = Obligation::unpack(&obligation_b_info.data.borrow())?;
+    check_position_health(&solend_position_b, &obligation_b, &get_refresh_reserves(&refresh_reserve_b_infos)?)?;
+
+    solend_position_a.serialize(&mut *solend_position_a_info.data.borrow_mut())?;
+    solend_position_b.serialize(&mut *solend_position_b_info.data.borrow_mut())?;
+
+    Ok(())
+}
+
+/// Collateral to withdraw for `liquidity_amount`, `u64::MAX` withdraws
+/// all collateral of the maker position in the reserve
+pub fn get_withdraw_collateral_amount(
+    position: &SolendPosition,
+    reserve_id: &Pubkey,
+    reserve: &Reserve,
+    liquidity_amount: u64,
+) -> Result<u64, ProgramError> {
+    if liquidity_amount == u64::MAX {
+        return position.deposits
+            .iter()
+            .find(|collateral| collateral.deposit_reserve == *reserve_id)
+            .map(|collateral| collateral.deposited_amount)
+            .ok_or(ProgramError::from(SolarisAutoError::PositionNotEntitled))
+    }
+
+    reserve.collateral_to_redeem(liquidity_amount)
+}
+
+fn get_deposited_amount(
+    obligation_info: &AccountInfo,
+    reserve_id: &Pubkey,
+) -> Result<u64, ProgramError> {
+    let obligation = Obligation::unpack(&obligation_info.data.borrow())?;
+
+    Ok(obligation.find_collateral(reserve_id).map_or(0, |collateral| collateral.deposited_amount))
+}
+
+/// Keeps refresh triples of reserves that are still in the obligation
+fn get_obligation_refresh_infos<'a, 'b>(
+    obligation: &Obligation,
+    refresh_reserve_infos: &[&'a AccountInfo<'b>],
+) -> Vec<&'a AccountInfo<'b>> {
+    refresh_reserve_infos
+        .chunks(3)
+        .filter(|reserve_infos| is_obligation_reserve(obligation, reserve_infos[0].key))
+        .flatten()
+        .copied()
+        .collect()
+}
+
+fn is_obligation_reserve(obligation: &Obligation, reserve_id: &Pubkey) -> bool {
+    obligation.deposits.iter().any(|collateral| collateral.deposit_reserve == *reserve_id)
+        || obligation.borrows.iter().any(|liquidity| liquidity.borrow_reserve == *reserve_id)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::helpers::lending_healthfactor::{Decimal, ObligationCollateral, ObligationLiquidity};
+
+    #[test]
+    fn obligation_reserve() {
+        let deposit_reserve = Pubkey::new_unique();
+        let borrow_reserve = Pubkey::new_unique();
+
+        let mut obligation = Obligation {
+            lending_market: Pubkey::new_unique(),
+            owner: Pubkey::new_unique(),
+            deposits: vec![ObligationCollateral {
+                deposit_reserve,
+                deposited_amount: 1_000,
+                market_value: Decimal::default(),
+            }],
+            borrows: vec![ObligationLiquidity {
+                borrow_reserve,
+                cumulative_borrow_rate_wads: Decimal::from(1),
+                borrowed_amount_wads: Decimal::from(500),
+                market_value: Decimal::default(),
+            }],
+            deposited_value: Decimal::default(),
+            borrowed_value: Decimal::default(),
+            allowed_borrow_value: Decimal::default(),
+            unhealthy_borrow_value: Decimal::default(),
+        };
+
+        assert!(is_obligation_reserve(&obligation, &deposit_reserve));
+        assert!(is_obligation_reserve(&obligation, &borrow_reserve));
+        assert!(!is_obligation_reserve(&obligation, &Pubkey::new_unique()));
+
+        // Full repay removes the borrow
+        obligation.borrows.clear();
+        assert!(!is_obligation_reserve(&obligation, &borrow_reserve));
+    }
+}
@@ -0,0 +1,208 @@
+use solana_program::{
+    account_info::AccountInfo,
+    entrypoint::ProgramResult,
+    program_option::COption,
+    program_pack::Pack,
+    pubkey::Pubkey,
+    msg,
+};
+use spl_token::state::Account as TokenAccount;
+
+use crate::{
+    error::SolarisAutoError,
+    state::SpendLimit,
+};
+
+pub struct TokenBalance {
+    pub token_account: Pubkey,
+    pub mint: Pubkey,
+    pub amount: u64,
+    /// Owned by the order maker
+    pub is_maker: bool,
+}
+
+/// Balances of callback token accounts the delegate can move:
+/// owned by the maker or the delegate, or approved to the delegate.
+/// Taken before callbacks to check spend limits after them.
+pub fn get_callback_balances(
+    maker: &Pubkey,
+    delegate: &Pubkey,
+    accounts: &[AccountInfo],
+) -> Vec<TokenBalance> {
+    let mut balances: Vec<TokenBalance> = vec![];
+
+    for info in accounts.iter() {
+        if *info.owner != spl_token::id()
+            || balances.iter().any(|balance| balance.token_account == *info.key) {
+            continue
+        }
+
+        if let Ok(token_account) = TokenAccount::unpack(&info.data.borrow()) {
+            let is_maker = token_account.owner == *maker;
+
+            if is_maker
+                || token_account.owner == *delegate
+                || token_account.delegate == COption::Some(*delegate) {
+                balances.push(TokenBalance {
+                    token_account: *info.key,
+                    mint: token_account.mint,
+                    amount: token_account.amount,
+                    is_maker,
+                });
+            }
+        }
+    }
+
+    balances
+}
+
+/// Checks net outflow per mint from the maker token accounts
+/// snapshotted in `balances_before`. Other snapshotted accounts
+/// can't lose tokens at all. Closed accounts are counted as empty.
+pub fn check_spend_limits(
+    spend_limits: &[SpendLimit],
+    balances_before: &[TokenBalance],
+    accounts: &[AccountInfo],
+) -> ProgramResult {
+    let balances_after: Vec<u64> = balances_before
+        .iter()
+        .map(|balance| {
+            accounts
+                .iter()
+                .find(|info| *info.key == balance.token_account)
+                .and_then(|info| TokenAccount::unpack(&info.data.borrow()).ok())
+                .map_or(0, |token_account| token_account.amount)
+        })
+        .collect();
+
+    check_foreign_outflows(balances_before, &balances_after)?;
+    check_outflows(spend_limits, &get_outflows(balances_before, &balances_after))
+}
+
+/// Fails if any account not owned by the maker lost tokens
+pub fn check_foreign_outflows(
+    balances_before: &[TokenBalance],
+    balances_after: &[u64],
+) -> ProgramResult {
+    for (before, after) in balances_before.iter().zip(balances_after.iter()) {
+        if !before.is_maker && *after < before.amount {
+            msg!("Callback spent {} from token account {}", before.amount - after, before.token_account);
+
+            return Err(SolarisAutoError::ForeignAccountSpent.into())
+        }
+    }
+
+    Ok(())
+}
+
+/// Net outflow per mint from the maker accounts, negative for inflow
+pub fn get_outflows(
+    balances_before: &[TokenBalance],
+    balances_after: &[u64],
+) -> Vec<(Pubkey, i128)> {
+    let mut outflows: Vec<(Pubkey, i128)> = vec![];
+
+    for (before, after) in balances_before.iter().zip(balances_after.iter()) {
+        if !before.is_maker {
+            continue
+        }
+
+        let outflow = before.amount as i128 - *after as i128;
+
+        match outflows.iter_mut().find(|(mint, _)| *mint == before.mint) {
+            Some((_, total)) => *total += outflow,
+            None => outflows.push((before.mint, outflow)),
+        }
+    }
+
+    outflows
+}
+
+pub fn check_outflows(
+    spend_limits: &[SpendLimit],
+    outflows: &[(Pubkey, i128)],
+) -> ProgramResult {
+    for (mint, outflow) in outflows.iter() {
+        let max_outflow = spend_limits
+            .iter()
+            .find(|spend_limit| spend_limit.mint == *mint)
+            .map_or(0, |spend_limit| spend_limit.max_outflow);
+
+        if *outflow > max_outflow as i128 {
+            msg!("Outflow of mint {} is {}, limit is {}", mint, outflow, max_outflow);
+
+            return Err(SolarisAutoError::SpendLimitExceeded.into())
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use solana_program::program_error::ProgramError;
+
+    #[test]
+    fn outflows_are_netted_per_mint() {
+        let usdc = Pubkey::new_unique();
+        let sol = Pubkey::new_unique();
+
+        let balance = |mint: Pubkey, amount: u64| TokenBalance {
+            token_account: Pubkey::new_unique(),
+            mint,
+            amount,
+            is_maker: true,
+        };
+
+        let balances_before = vec![
+            balance(usdc, 100),
+            balance(usdc, 50),
+            balance(sol, 10),
+        ];
+
+        // 30 USDC moved between maker accounts, 80 USDC spent, 5 SOL received
+        let outflows = get_outflows(&balances_before, &[40, 30, 15]);
+        assert_eq!(outflows, vec![(usdc, 80), (sol, -5)]);
+
+        let spend_limits = vec![SpendLimit { mint: usdc, max_outflow: 80 }];
+        assert_eq!(check_outflows(&spend_limits, &outflows), Ok(()));
+
+        let spend_limits = vec![SpendLimit { mint: usdc, max_outflow: 79 }];
+        assert_eq!(
+            check_outflows(&spend_limits, &outflows),
+            Err(ProgramError::from(SolarisAutoError::SpendLimitExceeded)),
+        );
+
+        // Mint without limit can't be spent
+        assert_eq!(
+            check_outflows(&[], &get_outflows(&balances_before, &[100, 50, 9])),
+            Err(ProgramError::from(SolarisAutoError::SpendLimitExceeded)),
+        );
+    }
+
+    #[test]
+    fn foreign_accounts_cant_be_spent() {
+        let usdc = Pubkey::new_unique();
+
+        let balance = |amount: u64, is_maker: bool| TokenBalance {
+            token_account: Pubkey::new_unique(),
+            mint: usdc,
+            amount,
+            is_maker,
+        };
+
+        // Maker account and an account of another maker approved to the delegate
+        let balances_before = vec![balance(100, true), balance(50, false)];
+
+        // Foreign outflow isn't netted with the maker inflow and doesn't count in limits
+        assert_eq!(
+            check_foreign_outflows(&balances_before, &[150, 0]),
+            Err(ProgramError::from(SolarisAutoError::ForeignAccountSpent)),
+        );
+        assert_eq!(get_outflows(&balances_before, &[150, 0]), vec![(usdc, -50)]);
+
+        assert_eq!(check_foreign_outflows(&balances_before, &[60, 60]), Ok(()));
+        assert_eq!(get_outflows(&balances_before, &[60, 60]), vec![(usdc, 40)]);
+    }
+}
@@ -0,0 +1,200 @@
+use solana_program::{
+    account_info::{AccountInfo, next_account_info},
+    instruction::Instruction,
+    entrypoint::ProgramResult,
+    program_error::ProgramError,
+    program::invoke_signed,
+    msg,
+};
+
+use borsh::BorshSerialize;
+use byteorder::ByteOrder;
+
+use crate::{
+    id,
+    error::SolarisAutoError,
+    helpers::{
+        lending_healthfactor::Obligation,
+        lending_position::{
+            assert_delegate_obligation,
+            load_solend_position,
+            deposit_position_collateral,
+        },
+    },
+    utils::{
+        get_seeds_delegate,
+        solend_deposit_reserve_liquidity_and_obligation_collateral,
+    },
+};
+
+use super::process_callback::CallbackContext;
+
+// Pubkey is "Gvu2pkYLCdbua1jDbUit5LbXD6o1kY5t6MsWnWCjo1hu"
+pub const CALLBACK_SOLEND_TOP_UP: &[u8] = &[236, 173, 235, 61, 150, 117, 141, 59, 143, 198, 13, 72, 103, 11, 20, 63, 12, 174, 83, 210, 0, 30, 204, 245, 80, 172, 7, 193, 94, 33, 149, 180];
+
+/// Tops up obligation collateral from the maker wallet, usually signed
+/// with the healthfactor predicate. Transfers liquidity from the maker
+/// token account to the delegate and deposits it like
+/// `SolendProxyDepositReserveLiquidityAndObligationCollateral`.
+/// Every fill deposits `liquidity_amount`, but the order never deposits
+/// more than `max_top_up_amount` over all fills, counted in the order state.
+/// Deposited collateral is recorded in the maker position.
+///
+/// Accounts required:
+///
+/// 0. `[]` Callback program id: Gvu2pkYLCdbua1jDbUit5LbXD6o1kY5t6MsWnWCjo1hu
+/// 1. `[]` Solend program
+/// 2. `[writable]` Maker token account of the reserve liquidity.
+///                   Delegate can transfer from it.
+/// 3. `[writable]` Source liquidity token account of the delegate.
+/// 4. `[writable]` Destination collateral token account.
+/// 5. `[writable]` Reserve account.
+/// 6. `[writable]` Reserve liquidity supply SPL Token account.
+/// 7. `[writable]` Reserve collateral SPL Token mint.
+/// 8. `[]` Lending market account.
+/// 9. `[]` Derived lending market authority.
+/// 10 `[writable]` Destination deposit reserve collateral supply SPL Token account.
+/// 11 `[writable]` Obligation account.
+/// 12 `[]` Pyth price oracle account.
+/// 13 `[]` Switchboard price feed oracle account.
+/// 14 `[]` Delegate. Obligation owner and transfer authority.
+/// 15 `[]` Clock sysvar.
+/// 16 `[]` Token program id.
+/// 17 `[writable]` Maker Solend position of the obligation.
+///
+/// Instruction data format is
+/// ```
+/// pub struct SolendTopUp {
+///     liquidity_amount: u64,  // deposited per fill
+///     max_top_up_amount: u64, // deposited by the order over all fills
+/// }
+/// ```
+pub fn process_callback_solend_top_up(
+    instr: &Instruction,
+    accounts: &[AccountInfo],
+    context: &CallbackContext,
+) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+
+    let _callback_program_info = next_account_info(account_info_iter)?;
+    let solend_program_info = next_account_info(account_info_iter)?;
+    let maker_liquidity_info = next_account_info(account_info_iter)?;
+    let source_liquidity_info = next_account_info(account_info_iter)?;
+    let destination_collateral_info = next_account_info(account_info_iter)?;
+    let reserve_info = next_account_info(account_info_iter)?;
+    let reserve_liquidity_supply_info = next_account_info(account_info_iter)?;
+    let reserve_collateral_mint_info = next_account_info(account_info_iter)?;
+    let lending_market_info = next_account_info(account_info_iter)?;
+    let lending_market_authority_info = next_account_info(account_info_iter)?;
+    let reserve_collateral_supply_info = next_account_info(account_info_iter)?;
+    let obligation_info = next_account_info(account_info_iter)?;
+    let pyth_price_info = next_account_info(account_info_iter)?;
+    let switchboard_price_info = next_account_info(account_info_iter)?;
+    let delegate_info = next_account_info(account_info_iter)?;
+    let _clock_info = next_account_info(account_info_iter)?;
+    let token_program_info = next_account_info(account_info_iter)?;
+    let solend_position_info = next_account_info(account_info_iter)?;
+
+    if instr.data.len() < 16 {
+        return Err(SolarisAutoError::InvalidCallbackInst.into())
+    }
+
+    let liquidity_amount = byteorder::LE::read_u64(&instr.data[0..8]);
+    let max_top_up_amount = byteorder::LE::read_u64(&instr.data[8..16]);
+
+    assert_delegate_obligation(solend_program_info, obligation_info)?;
+
+    let mut solend_position = load_solend_position(
+        &id(),
+        solend_position_info,
+        &context.maker,
+        obligation_info.key,
+    )?;
+
+    let topped_up_amount = context.topped_up_amount.get();
+    let top_up_amount = get_top_up_amount(topped_up_amount, liquidity_amount, max_top_up_amount);
+
+    msg!("top_up_amount is {}", top_up_amount);
+
+    if top_up_amount == 0 {
+        return Err(SolarisAutoError::TopUpCapReached.into())
+    }
+
+    context.topped_up_amount.set(topped_up_amount + top_up_amount);
+
+    let obligation = Obligation::unpack(&obligation_info.data.borrow())?;
+
+    invoke_signed(
+        &spl_token::instruction::transfer(
+            token_program_info.key,
+            maker_liquidity_info.key,
+            source_liquidity_info.key,
+            delegate_info.key,
+            &[delegate_info.key],
+            top_up_amount,
+        )?,
+        accounts,
+        &[&get_seeds_delegate()],
+    )?;
+
+    let deposited_amount_before = obligation
+        .find_collateral(reserve_info.key)
+        .map_or(0, |collateral| collateral.deposited_amount);
+
+    invoke_signed(
+        &solend_deposit_reserve_liquidity_and_obligation_collateral(
+            solend_program_info.key,
+            source_liquidity_info.key,
+            destination_collateral_info.key,
+            reserve_info.key,
+            reserve_liquidity_supply_info.key,
+            reserve_collateral_mint_info.key,
+            lending_market_info.key,
+            lending_market_authority_info.key,
+            reserve_collateral_supply_info.key,
+            obligation_info.key,
+            delegate_info.key,
+            pyth_price_info.key,
+            switchboard_price_info.key,
+            delegate_info.key,
+            top_up_amount,
+        ),
+        accounts,
+        &[&get_seeds_delegate()],
+    )?;
+
+    let collateral_amount = Obligation::unpack(&obligation_info.data.borrow())?
+        .find_collateral(reserve_info.key)
+        .map_or(0, |collateral| collateral.deposited_amount)
+        .checked_sub(deposited_amount_before)
+        .ok_or(ProgramError::from(SolarisAutoError::MathOverflow))?;
+
+    deposit_position_collateral(&mut solend_position, reserve_info.key, collateral_amount)?;
+
+    solend_position.serialize(&mut *solend_position_info.data.borrow_mut())?;
+
+    Ok(())
+}
+
+/// `liquidity_amount` capped by the room left under `max_top_up_amount`
+pub fn get_top_up_amount(
+    topped_up_amount: u64,
+    liquidity_amount: u64,
+    max_top_up_amount: u64,
+) -> u64 {
+    liquidity_amount.min(max_top_up_amount.saturating_sub(topped_up_amount))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn top_up_amount() {
+        assert_eq!(get_top_up_amount(0, 100, 1_000), 100);
+        assert_eq!(get_top_up_amount(900, 50, 1_000), 50);
+        assert_eq!(get_top_up_amount(900, 150, 1_000), 100);
+        assert_eq!(get_top_up_amount(1_000, 150, 1_000), 0);
+        assert_eq!(get_top_up_amount(1_100, 150, 1_000), 0);
+    }
+}
\ No newline at end of file
@@ -62,6 +62,100 @@ pub enum SolarisAutoError {
 
     #[error("Math overflow")]
     MathOverflow,
+
+    #[error("Cannot unpack obligation account")]
+    InvalidObligationData,
+    #[error("Lending reserve position predicate failed")]
+    LendingReservePositionFailed,
+    #[error("Cannot unpack reserve account")]
+    InvalidReserveData,
+    #[error("Lending reserve rate predicate failed")]
+    LendingReserveRateFailed,
+    #[error("Obligation is owned by unsupported lending program")]
+    UnsupportedLendingProgram,
+
+    #[error("Return data must be u64 or u128")]
+    InvalidReturnData,
+    #[error("Return data must be set by invoked program")]
+    InvalidReturnDataProgramId,
+    #[error("Invalid compare operator")]
+    InvalidCompareOperator,
+    #[error("Call and compare predicate failed")]
+    CallAndCompareFailed,
+
+    #[error("Onchain order address doesn't match order hash")]
+    InvalidOnchainOrder,
+
+    #[error("Cannot deserialize get amount instruction")]
+    InvalidGetAmountInst,
+
+    #[error("Making amount exceeds remaining maker amount")]
+    NotEnoughRemainingMakerAmount,
+
+    #[error("Oracle price is not available")]
+    OraclePriceUnavailable,
+
+    #[error("Price guard oracle doesn't match order")]
+    InvalidPriceGuardOracle,
+    #[error("Fill price deviates from oracle price more than allowed")]
+    PriceGuardFailed,
+
+    #[error("Supplied accounts don't match signed instruction")]
+    InstructionAccountsMismatch,
+
+    #[error("Order buffer is invalid or belongs to another authority")]
+    InvalidOrderBuffer,
+
+    #[error("Write exceeds order buffer length")]
+    OrderBufferOverflow,
+
+    #[error("Cannot deserialize interaction instruction or it calls this program")]
+    InvalidInteractionInst,
+
+    #[error("Interaction can't be used with order callback")]
+    InteractionWithCallback,
+
+    #[error("Maker received less than taking amount")]
+    NotEnoughTakerAmountReceived,
+
+    #[error("Callback spent more than order spend limit")]
+    SpendLimitExceeded,
+
+    #[error("Obligation is already at the target health")]
+    TargetHealthReached,
+
+    #[error("Target health can't be reached by repay and withdraw")]
+    TargetHealthUnreachable,
+
+    #[error("Obligation health is below the signed minimum")]
+    HealthBelowMinimum,
+
+    #[error("Order already deposited the signed top-up cap")]
+    TopUpCapReached,
+
+    #[error("Invalid Solend position account")]
+    InvalidSolendPosition,
+
+    #[error("Amount exceeds the maker position")]
+    PositionNotEntitled,
+
+    #[error("Maker position borrows exceed its allowed borrow value")]
+    PositionUnhealthy,
+
+    #[error("Quoted taking amount exceeds the taker maximum")]
+    QuotedTakingAmountTooHigh,
+
+    #[error("Callback spent from a token account of another owner")]
+    ForeignAccountSpent,
+
+    #[error("Maker account doesn't match order maker")]
+    InvalidMaker,
+
+    #[error("Obligation isn't owned by the delegate")]
+    InvalidObligationOwner,
+
+    #[error("Token account isn't owned by the maker")]
+    InvalidMakerTokenAccount,
 }
 
 impl PrintProgramError for SolarisAutoError {
@@ -0,0 +1,124 @@
+use solana_program::{
+    account_info::AccountInfo,
+    instruction::Instruction,
+    entrypoint::ProgramResult,
+    program::{invoke, get_return_data},
+    program_error::ProgramError,
+    msg,
+};
+use byteorder::ByteOrder;
+
+use crate::error::SolarisAutoError;
+
+// Pubkey is "2j4T6ALBkUnMPjprxNET2f5rfyVCoW1x5g9Gfzoa62GE"
+pub const HELPER_CALL_AND_COMPARE_ID: &[u8] = &[25, 161, 251, 75, 116, 194, 255, 14, 99, 231, 37, 157, 45, 91, 198, 106, 243, 94, 105, 207, 150, 93, 131, 106, 38, 141, 97, 67, 129, 2, 67, 111];
+
+pub const OPERATOR_LT: u8 = 0;
+pub const OPERATOR_GT: u8 = 1;
+pub const OPERATOR_EQ: u8 = 2;
+
+/// Predicate that invokes an arbitrary program and compares the value it sets
+/// with `set_return_data` against the constant. Return data must be u64 or u128
+/// in little endian and must be set by the invoked program.
+///
+/// Accounts required:
+///
+/// 0. `[]` Helper call and compare program id: 2j4T6ALBkUnMPjprxNET2f5rfyVCoW1x5g9Gfzoa62GE
+/// 1. `[]` Invoked program
+/// 2.. Accounts that required by the invoked instruction
+///
+/// Instruction data format is
+/// ```
+/// pub struct HelperCallAndCompare {
+///     operator: u8,    // 0 - returned value < value,
+///                      // 1 - returned value > value,
+///                      // 2 - returned value == value
+///     value: u128,
+///     instruction: Vec<u8>, // bincode serialized `Instruction`, till the end of data
+/// }
+/// ```
+pub fn process_call_and_compare(
+    instr: &Instruction,
+    accounts: &[AccountInfo],
+) -> ProgramResult {
+    if instr.data.len() < 17 {
+        return Err(SolarisAutoError::InvalidPredicateInst.into())
+    }
+
+    let operator = instr.data[0];
+    let value = byteorder::LE::read_u128(&instr.data[1..17]);
+
+    let call = bincode::deserialize::<Instruction>(&instr.data[17..])
+        .or(Err(ProgramError::from(SolarisAutoError::InvalidPredicateInst)))?;
+
+    invoke(&call, accounts)?;
+
+    let returned_value = get_return_data()
+        .ok_or(ProgramError::from(SolarisAutoError::InvalidReturnData))
+        .and_then(|(program_id, data)| {
+            if program_id != call.program_id {
+                return Err(SolarisAutoError::InvalidReturnDataProgramId.into())
+            }
+
+            unpack_returned_value(&data)
+        })?;
+
+    msg!("returned value is {}", returned_value);
+    msg!("compared value is {}", value);
+
+    if compare(returned_value, operator, value)? {
+        Ok(())
+    } else {
+        Err(SolarisAutoError::CallAndCompareFailed.into())
+    }
+}
+
+pub fn unpack_returned_value(data: &[u8]) -> Result<u128, ProgramError> {
+    match data.len() {
+        8 => Ok(byteorder::LE::read_u64(data) as u128),
+        16 => Ok(byteorder::LE::read_u128(data)),
+        _ => Err(SolarisAutoError::InvalidReturnData.into())
+    }
+}
+
+pub fn compare(
+    returned_value: u128,
+    operator: u8,
+    value: u128,
+) -> Result<bool, ProgramError> {
+    match operator {
+        OPERATOR_LT => Ok(returned_value < value),
+        OPERATOR_GT => Ok(returned_value > value),
+        OPERATOR_EQ => Ok(returned_value == value),
+        _ => Err(SolarisAutoError::InvalidCompareOperator.into())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn compare_operators() {
+        assert_eq!(compare(1, OPERATOR_LT, 2), Ok(true));
+        assert_eq!(compare(2, OPERATOR_LT, 2), Ok(false));
+        assert_eq!(compare(3, OPERATOR_GT, 2), Ok(true));
+        assert_eq!(compare(2, OPERATOR_GT, 2), Ok(false));
+        assert_eq!(compare(2, OPERATOR_EQ, 2), Ok(true));
+        assert_eq!(compare(1, OPERATOR_EQ, 2), Ok(false));
+        assert_eq!(
+            compare(1, 3, 2),
+            Err(ProgramError::from(SolarisAutoError::InvalidCompareOperator)),
+        );
+    }
+
+    #[test]
+    fn returned_value() {
+        assert_eq!(unpack_returned_value(&42u64.to_le_bytes()), Ok(42));
+        assert_eq!(unpack_returned_value(&(u64::MAX as u128 + 1).to_le_bytes()), Ok(u64::MAX as u128 + 1));
+        assert_eq!(
+            unpack_returned_value(&[0; 4]),
+            Err(ProgramError::from(SolarisAutoError::InvalidReturnData)),
+        );
+    }
+}
@@ -0,0 +1,148 @@
+use solana_program::{
+    account_info::{AccountInfo, next_account_info},
+    instruction::Instruction,
+    program_error::ProgramError,
+    sysvar::{Sysvar, clock::Clock},
+    msg,
+};
+use std::convert::TryFrom;
+use byteorder::ByteOrder;
+
+use crate::error::SolarisAutoError;
+
+use super::get_amounts::{get_maker_amount, get_taker_amount};
+
+// Pubkey is "4VGTjyhnBRNABMK1F7xwgiHCeqY6rN7XHcSvDNjugo8r"
+pub const HELPER_DUTCH_AUCTION_ID: &[u8] = &[51, 208, 206, 40, 35, 72, 186, 206, 33, 213, 203, 55, 111, 136, 209, 237, 144, 191, 65, 254, 37, 106, 219, 190, 215, 46, 99, 58, 28, 121, 26, 23];
+
+/// Built-in get [maker/taker] amount with dutch auction pricing.
+/// Order taking amount moves linearly from `taking_amount_start` at `start_time`
+/// to `taking_amount_end` at `end_time` and stays constant outside of the window.
+/// The current ratio `making_amount / taking_amount` is applied like
+/// in the linear helper, rounded in favour of the maker.
+///
+/// Accounts required:
+///
+/// 0. `[]` Helper dutch auction program id: 4VGTjyhnBRNABMK1F7xwgiHCeqY6rN7XHcSvDNjugo8r
+/// 1. `[]` Clock sysvar
+///
+/// Instruction data format is
+/// ```
+/// pub struct HelperDutchAuction {
+///     get_maker_amount: bool,
+///     making_amount: u64,
+///     taking_amount_start: u64,
+///     taking_amount_end: u64,
+///     start_time: i64, // unix timestamp
+///     end_time: i64,   // unix timestamp
+///     swap_amount: u64, // swap_taker_amount if `get_maker_amount` == _true_
+///                       // swap_maker_amount if `get_maker_amount` == _false_
+///                       // appended by `invoke_get_amount`
+/// }
+/// ```
+pub fn process_dutch_auction(
+    instr: &Instruction,
+    accounts: &[AccountInfo],
+) -> Result<u64, ProgramError> {
+    let account_info_iter = &mut accounts.iter();
+
+    let _program_info = next_account_info(account_info_iter)?;
+    let clock_info = next_account_info(account_info_iter)?;
+
+    let clock = Clock::from_account_info(clock_info)?;
+
+    if instr.data.len() < 49 {
+        return Err(SolarisAutoError::InvalidGetAmountInst.into())
+    }
+
+    let is_get_maker_amount = instr.data[0] != 0;
+    let making_amount = byteorder::LE::read_u64(&instr.data[1..9]);
+    let taking_amount_start = byteorder::LE::read_u64(&instr.data[9..17]);
+    let taking_amount_end = byteorder::LE::read_u64(&instr.data[17..25]);
+    let start_time = byteorder::LE::read_i64(&instr.data[25..33]);
+    let end_time = byteorder::LE::read_i64(&instr.data[33..41]);
+    let swap_amount = byteorder::LE::read_u64(&instr.data[41..49]);
+
+    let taking_amount = get_auction_taking_amount(
+        taking_amount_start,
+        taking_amount_end,
+        start_time,
+        end_time,
+        clock.unix_timestamp,
+    )?;
+
+    msg!("auction taking_amount is {}", taking_amount);
+
+    match is_get_maker_amount {
+        true => get_maker_amount(making_amount, taking_amount, swap_amount),
+        false => get_taker_amount(making_amount, taking_amount, swap_amount),
+    }
+}
+
+/// Order taking amount at `now`. Interpolated value is rounded up,
+/// so the maker never receives less than the curve allows.
+pub fn get_auction_taking_amount(
+    taking_amount_start: u64,
+    taking_amount_end: u64,
+    start_time: i64,
+    end_time: i64,
+    now: i64,
+) -> Result<u64, ProgramError> {
+    if end_time <= start_time {
+        return Err(SolarisAutoError::InvalidGetAmountInst.into())
+    }
+
+    if now <= start_time {
+        return Ok(taking_amount_start)
+    }
+
+    if now >= end_time {
+        return Ok(taking_amount_end)
+    }
+
+    let elapsed = (now as i128 - start_time as i128) as u128;
+    let duration = (end_time as i128 - start_time as i128) as u128;
+
+    let taking_amount = match taking_amount_start > taking_amount_end {
+        true => {
+            let delta = (taking_amount_start - taking_amount_end) as u128 * elapsed / duration;
+
+            taking_amount_start as u128 - delta
+        },
+        false => {
+            let delta = (taking_amount_end - taking_amount_start) as u128 * elapsed;
+
+            taking_amount_start as u128 + (delta + duration - 1) / duration
+        },
+    };
+
+    u64::try_from(taking_amount)
+        .or(Err(ProgramError::from(SolarisAutoError::MathOverflow)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn auction_taking_amount() {
+        // Decreasing price for the taker
+        assert_eq!(get_auction_taking_amount(1_000, 500, 100, 200, 0), Ok(1_000));
+        assert_eq!(get_auction_taking_amount(1_000, 500, 100, 200, 150), Ok(750));
+        assert_eq!(get_auction_taking_amount(1_000, 500, 100, 200, 300), Ok(500));
+        assert_eq!(get_auction_taking_amount(10, 0, 0, 3, 1), Ok(7));
+
+        // Increasing price for the taker
+        assert_eq!(get_auction_taking_amount(500, 1_000, 100, 200, 150), Ok(750));
+        assert_eq!(get_auction_taking_amount(0, 10, 0, 3, 1), Ok(4));
+
+        assert_eq!(
+            get_auction_taking_amount(u64::MAX, 0, i64::MIN, i64::MAX, 0),
+            Ok(u64::MAX / 2),
+        );
+        assert_eq!(
+            get_auction_taking_amount(1_000, 500, 200, 200, 200),
+            Err(ProgramError::from(SolarisAutoError::InvalidGetAmountInst)),
+        );
+    }
+}
@@ -1,61 +1,256 @@
 use solana_program::{
     account_info::AccountInfo,
     instruction::Instruction,
+    program::{invoke, get_return_data},
+    program_error::ProgramError,
     pubkey::Pubkey,
 };
+use std::convert::TryFrom;
 use byteorder::ByteOrder;
+
+use crate::{
+    error::SolarisAutoError,
+    utils::assert_instruction_accounts,
+};
+
+use super::call_and_compare::unpack_returned_value;
+use super::dutch_auction;
+use super::range_order;
+use super::oracle_pegged;
+
 //Pubkey is "Go5vxb6EqNzoupdUuyaXkUX2SYr4eQmoVfa2sFm5PX6Z"
 pub const HELPER_GET_AMOUNTS_ID: &[u8] = &[234, 173, 152, 136, 255, 42, 231, 127, 101, 7, 52, 33, 126, 91, 30, 78, 154, 206, 135, 149, 215, 253, 222, 19, 175, 11, 224, 12, 185, 149, 14, 42];
 
-/// TODO: 
-/// 
+/// Built-in linear get [maker/taker] amount. It is used when order
+/// doesn't provide custom instruction, but can be set explicitly as well.
+/// Amounts are rounded in favour of the maker, see `get_maker_amount`
+/// and `get_taker_amount`.
+///
 /// Accounts required:
-/// 
-/// 
-/// 
-/// Instruction data format is 
+///
+/// 0. `[]` Helper get amounts program id: Go5vxb6EqNzoupdUuyaXkUX2SYr4eQmoVfa2sFm5PX6Z
+///
+/// Instruction data format is
 /// ```
 /// pub struct HelperGetAmount {
-///     get_maker_amount: bool, 
+///     get_maker_amount: bool,
 ///     order_maker_amount: u64,
 ///     order_taker_amount: u64,
 ///     swap_amount: u64, // swap_taker_amount if `get_maker_amount` == _true_
 ///                       // swap_maker_amount if `get_maker_amount` == _false_
+///                       // appended by `invoke_get_amount`
 /// }
 /// ```
 pub fn process_get_amounts(
     instr: &Instruction,
     _accounts: &[AccountInfo],
-) -> u64 {
-    let get_maker_amount = instr.data[0] != 0;
-    let order_maker_amount = byteorder::LE::read_u64(&instr.data[1..9]); 
-    let order_taker_amount = byteorder::LE::read_u64(&instr.data[10..19]); 
-    let swap_amount = byteorder::LE::read_u64(&instr.data[19..28]); 
-
-    match get_maker_amount {
-        true => {
-            swap_amount * order_maker_amount / order_taker_amount
+) -> Result<u64, ProgramError> {
+    if instr.data.len() < 25 {
+        return Err(SolarisAutoError::InvalidGetAmountInst.into())
+    }
+
+    let is_get_maker_amount = instr.data[0] != 0;
+    let order_maker_amount = byteorder::LE::read_u64(&instr.data[1..9]);
+    let order_taker_amount = byteorder::LE::read_u64(&instr.data[9..17]);
+    let swap_amount = byteorder::LE::read_u64(&instr.data[17..25]);
+
+    match is_get_maker_amount {
+        true => get_maker_amount(order_maker_amount, order_taker_amount, swap_amount),
+        false => get_taker_amount(order_maker_amount, order_taker_amount, swap_amount),
+    }
+}
+
+/// Invokes custom get [maker/taker] amount instruction from order.
+/// Swap amount is appended to instruction data as u64 in little endian:
+/// swap taker amount for get_maker_amount, swap maker amount for get_taker_amount.
+/// Invoked program must set the resulting amount with `set_return_data`.
+/// Built-in helpers are processed in place without CPI, range order
+/// helper also gets remaining maker amount of the order.
+pub fn invoke_get_amount(
+    encoded_instr: &[u8],
+    swap_amount: u64,
+    remaining_maker_amount: u64,
+    accounts: &[AccountInfo],
+) -> Result<u64, ProgramError> {
+    let mut instr = bincode::deserialize::<Instruction>(encoded_instr)
+        .or(Err(ProgramError::from(SolarisAutoError::InvalidGetAmountInst)))?;
+
+    assert_instruction_accounts(&instr, accounts)?;
+
+    instr.data.extend_from_slice(&swap_amount.to_le_bytes());
+
+    match instr.program_id.as_ref() {
+        HELPER_GET_AMOUNTS_ID => {
+            return process_get_amounts(&instr, accounts)
         },
-        false => {
-            swap_amount * order_maker_amount / order_taker_amount
-        }
+        dutch_auction::HELPER_DUTCH_AUCTION_ID => {
+            return dutch_auction::process_dutch_auction(&instr, accounts)
+        },
+        range_order::HELPER_RANGE_ORDER_ID => {
+            return range_order::process_range_order(&instr, accounts, remaining_maker_amount)
+        },
+        oracle_pegged::HELPER_ORACLE_PEGGED_ID => {
+            return oracle_pegged::process_oracle_pegged(&instr, accounts)
+        },
+        _ => invoke(&instr, accounts)?
     }
+
+    let amount = get_return_data()
+        .ok_or(ProgramError::from(SolarisAutoError::InvalidReturnData))
+        .and_then(|(program_id, data)| {
+            if program_id != instr.program_id {
+                return Err(SolarisAutoError::InvalidReturnDataProgramId.into())
+            }
+
+            unpack_returned_value(&data)
+        })?;
+
+    u64::try_from(amount)
+        .or(Err(ProgramError::from(SolarisAutoError::MathOverflow)))
 }
 
-// This is a CRUTCH
+/// Maker amount for the given taker amount by the signed ratio:
+/// `swap_taker_amount * order_maker_amount / order_taker_amount`.
+/// Rounded down, so the taker never receives more than the ratio allows.
 pub fn get_maker_amount(
     order_maker_amount: u64,
     order_taker_amount: u64,
     swap_taker_amount: u64,
-) -> u64 {
-    swap_taker_amount * order_maker_amount / order_taker_amount
+) -> Result<u64, ProgramError> {
+    let amount = (swap_taker_amount as u128)
+        .checked_mul(order_maker_amount as u128)
+        .and_then(|amount| amount.checked_div(order_taker_amount as u128))
+        .ok_or(ProgramError::from(SolarisAutoError::MathOverflow))?;
+
+    u64::try_from(amount)
+        .or(Err(ProgramError::from(SolarisAutoError::MathOverflow)))
 }
 
-// This is also a CRUTCH
+/// Taker amount for the given maker amount by the signed ratio:
+/// `swap_maker_amount * order_taker_amount / order_maker_amount`.
+/// Rounded up, so the taker never pays less than the ratio allows.
 pub fn get_taker_amount(
     order_maker_amount: u64,
     order_taker_amount: u64,
     swap_maker_amount: u64,
-) -> u64 {
-    swap_maker_amount * order_maker_amount / order_taker_amount
+) -> Result<u64, ProgramError> {
+    let amount = (swap_maker_amount as u128)
+        .checked_mul(order_taker_amount as u128)
+        .and_then(|amount| ceil_div(amount, order_maker_amount as u128))
+        .ok_or(ProgramError::from(SolarisAutoError::MathOverflow))?;
+
+    u64::try_from(amount)
+        .or(Err(ProgramError::from(SolarisAutoError::MathOverflow)))
+}
+
+fn ceil_div(numerator: u128, denominator: u128) -> Option<u128> {
+    let quotient = numerator.checked_div(denominator)?;
+
+    match numerator % denominator {
+        0 => Some(quotient),
+        _ => quotient.checked_add(1),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::helpers::test_utils::Lcg;
+
+    #[test]
+    fn linear_amounts() {
+        assert_eq!(get_maker_amount(100, 200, 50), Ok(25));
+        assert_eq!(get_taker_amount(100, 200, 25), Ok(50));
+
+        // Rounding in favour of the maker
+        assert_eq!(get_maker_amount(100, 300, 1), Ok(0));
+        assert_eq!(get_maker_amount(100, 300, 5), Ok(1));
+        assert_eq!(get_taker_amount(300, 100, 1), Ok(1));
+        assert_eq!(get_taker_amount(300, 100, 4), Ok(2));
+
+        // u128 intermediates
+        assert_eq!(get_maker_amount(u64::MAX, u64::MAX, u64::MAX), Ok(u64::MAX));
+        assert_eq!(get_taker_amount(u64::MAX, u64::MAX, u64::MAX), Ok(u64::MAX));
+    }
+
+    #[test]
+    fn linear_amounts_overflow() {
+        assert_eq!(
+            get_maker_amount(u64::MAX, 1, 2),
+            Err(ProgramError::from(SolarisAutoError::MathOverflow)),
+        );
+        assert_eq!(
+            get_taker_amount(1, u64::MAX, 2),
+            Err(ProgramError::from(SolarisAutoError::MathOverflow)),
+        );
+        assert_eq!(
+            get_maker_amount(1, 0, 1),
+            Err(ProgramError::from(SolarisAutoError::MathOverflow)),
+        );
+        assert_eq!(
+            get_taker_amount(0, 1, 1),
+            Err(ProgramError::from(SolarisAutoError::MathOverflow)),
+        );
+    }
+
+    #[test]
+    fn partial_fills_never_beat_signed_ratio() {
+        let mut rng = Lcg(42);
+
+        for _ in 0..1_000 {
+            let order_maker_amount = rng.next(1_000_000_000);
+            let order_taker_amount = rng.next(1_000_000_000);
+
+            // Taker fills by taker amount
+            let mut total_making = 0u128;
+            let mut total_taking = 0u128;
+            for _ in 0..20 {
+                let taking = rng.next(order_taker_amount);
+                let making = get_maker_amount(order_maker_amount, order_taker_amount, taking).unwrap();
+
+                total_making += making as u128;
+                total_taking += taking as u128;
+
+                assert!(total_making * order_taker_amount as u128 <= total_taking * order_maker_amount as u128);
+            }
+
+            // Maker fills by maker amount
+            let mut total_making = 0u128;
+            let mut total_taking = 0u128;
+            for _ in 0..20 {
+                let making = rng.next(order_maker_amount);
+                let taking = get_taker_amount(order_maker_amount, order_taker_amount, making).unwrap();
+
+                total_making += making as u128;
+                total_taking += taking as u128;
+
+                assert!(total_making * order_taker_amount as u128 <= total_taking * order_maker_amount as u128);
+            }
+        }
+    }
+
+    #[test]
+    fn builtin_get_amounts() {
+        let mut data = vec![1];
+        data.extend_from_slice(&100u64.to_le_bytes());
+        data.extend_from_slice(&300u64.to_le_bytes());
+        data.extend_from_slice(&5u64.to_le_bytes());
+
+        let mut instr = Instruction {
+            program_id: Pubkey::new(HELPER_GET_AMOUNTS_ID),
+            accounts: vec![],
+            data,
+        };
+
+        assert_eq!(process_get_amounts(&instr, &[]), Ok(1));
+
+        instr.data[0] = 0;
+        assert_eq!(process_get_amounts(&instr, &[]), Ok(15));
+
+        instr.data.truncate(24);
+        assert_eq!(
+            process_get_amounts(&instr, &[]),
+            Err(ProgramError::from(SolarisAutoError::InvalidGetAmountInst)),
+        );
+    }
 }
@@ -0,0 +1,208 @@
+use solana_program::{
+    account_info::AccountInfo,
+    pubkey::Pubkey,
+    program_error::ProgramError,
+};
+use arrayref::{array_refs, array_ref};
+
+use crate::error::SolarisAutoError;
+
+use super::lending_healthfactor::{
+    Obligation,
+    ObligationCollateral,
+    ObligationLiquidity,
+    unpack_decimal,
+};
+
+// Pubkey is "So1endDq2YkqhipRh3WViPa8hdiSpxWy6z3Z6tMCpAo"
+pub const SOLEND_ID: &[u8] = &[6, 155, 139, 152, 90, 171, 83, 42, 69, 9, 13, 232, 85, 127, 205, 220, 190, 108, 183, 239, 199, 58, 10, 101, 176, 111, 146, 3, 93, 183, 62, 236];
+// Pubkey is "ALend7Ketfx5bxh6ghsCDXAoDrhvEmsXT3cynB6aPLgx"
+pub const SOLEND_DEVNET_ID: &[u8] = &[138, 193, 241, 114, 69, 245, 144, 57, 23, 131, 163, 184, 86, 117, 180, 107, 157, 175, 93, 163, 95, 242, 88, 210, 223, 21, 247, 109, 180, 231, 50, 89];
+// Pubkey is "LendZqTs7gn5CTSJU1jWKhKuVpjJGom45nnwPb2AMTi"
+pub const SPL_TOKEN_LENDING_ID: &[u8] = &[5, 8, 194, 206, 177, 181, 208, 92, 135, 73, 128, 172, 82, 207, 101, 151, 64, 231, 233, 185, 53, 106, 175, 42, 3, 98, 103, 50, 99, 82, 108, 21];
+// Pubkey is "Port7uDYB3wk6GJAw4KT1WpTeMtSu9bTcChBHkX2LfR"
+pub const PORT_FINANCE_ID: &[u8] = &[5, 215, 195, 57, 144, 122, 80, 228, 67, 218, 144, 223, 161, 64, 158, 247, 235, 202, 59, 12, 166, 149, 163, 31, 29, 219, 60, 92, 38, 94, 213, 72];
+
+const SPL_OBLIGATION_COLLATERAL_LEN: usize = 56; // 32 + 8 + 16
+const SPL_OBLIGATION_LIQUIDITY_LEN: usize = 80; // 32 + 16 + 16 + 16
+const SPL_MAX_OBLIGATION_RESERVES: usize = 10;
+const SPL_OBLIGATION_LEN: usize = 916; // 1 + 8 + 1 + 32 + 32 + 16 + 16 + 16 + 16 + 1 + 1 + (56 * 1) + (80 * 9)
+
+/// Adapter for lending protocols that share the token-lending obligation model.
+/// It unpacks protocol specific obligation layout into normalized position:
+/// deposited value, borrowed value, liquidation threshold (unhealthy borrow value)
+/// and per-reserve balances.
+pub trait LendingAdapter {
+    fn unpack_obligation(&self, src: &[u8]) -> Result<Obligation, ProgramError>;
+}
+
+/// Solend. Obligation has 64 bytes of padding and every reserve entry
+/// is padded with 32 bytes.
+pub struct SolendAdapter;
+
+impl LendingAdapter for SolendAdapter {
+    fn unpack_obligation(&self, src: &[u8]) -> Result<Obligation, ProgramError> {
+        Obligation::unpack(src)
+    }
+}
+
+/// SPL token-lending and forks that keep its obligation layout, e.g. Port Finance
+pub struct SplTokenLendingAdapter;
+
+impl LendingAdapter for SplTokenLendingAdapter {
+    fn unpack_obligation(&self, src: &[u8]) -> Result<Obligation, ProgramError> {
+        if src.len() < SPL_OBLIGATION_LEN {
+            return Err(SolarisAutoError::InvalidObligationData.into())
+        }
+
+        let input = array_ref![src, 0, SPL_OBLIGATION_LEN];
+
+        let (
+            _version,
+            _last_update_slot,
+            _last_update_stale,
+            lending_market,
+            owner,
+            deposited_value,
+            borrowed_value,
+            allowed_borrow_value,
+            unhealthy_borrow_value,
+            deposits_len,
+            borrows_len,
+            data_flat,
+        ) = array_refs![
+            input,
+            1,
+            8,
+            1,
+            32,
+            32,
+            16,
+            16,
+            16,
+            16,
+            1,
+            1,
+            SPL_OBLIGATION_COLLATERAL_LEN + (SPL_OBLIGATION_LIQUIDITY_LEN * (SPL_MAX_OBLIGATION_RESERVES - 1))
+        ];
+
+        let deposits_len = deposits_len[0] as usize;
+        let borrows_len = borrows_len[0] as usize;
+
+        if deposits_len + borrows_len > SPL_MAX_OBLIGATION_RESERVES {
+            return Err(SolarisAutoError::InvalidObligationData.into())
+        }
+
+        let mut offset = 0;
+
+        let mut deposits = Vec::with_capacity(deposits_len);
+        for _ in 0..deposits_len {
+            let collateral = array_ref![data_flat, offset, SPL_OBLIGATION_COLLATERAL_LEN];
+            let (deposit_reserve, deposited_amount, market_value) =
+                array_refs![collateral, 32, 8, 16];
+
+            deposits.push(ObligationCollateral {
+                deposit_reserve: Pubkey::new_from_array(*deposit_reserve),
+                deposited_amount: u64::from_le_bytes(*deposited_amount),
+                market_value: unpack_decimal(market_value),
+            });
+
+            offset += SPL_OBLIGATION_COLLATERAL_LEN;
+        }
+
+        let mut borrows = Vec::with_capacity(borrows_len);
+        for _ in 0..borrows_len {
+            let liquidity = array_ref![data_flat, offset, SPL_OBLIGATION_LIQUIDITY_LEN];
+            let (borrow_reserve, cumulative_borrow_rate_wads, borrowed_amount_wads, market_value) =
+                array_refs![liquidity, 32, 16, 16, 16];
+
+            borrows.push(ObligationLiquidity {
+                borrow_reserve: Pubkey::new_from_array(*borrow_reserve),
+                cumulative_borrow_rate_wads: unpack_decimal(cumulative_borrow_rate_wads),
+                borrowed_amount_wads: unpack_decimal(borrowed_amount_wads),
+                market_value: unpack_decimal(market_value),
+            });
+
+            offset += SPL_OBLIGATION_LIQUIDITY_LEN;
+        }
+
+        Ok(Obligation {
+            lending_market: Pubkey::new_from_array(*lending_market),
+            owner: Pubkey::new_from_array(*owner),
+            deposits,
+            borrows,
+            deposited_value: unpack_decimal(deposited_value),
+            borrowed_value: unpack_decimal(borrowed_value),
+            allowed_borrow_value: unpack_decimal(allowed_borrow_value),
+            unhealthy_borrow_value: unpack_decimal(unhealthy_borrow_value),
+        })
+    }
+}
+
+/// Returns adapter for the lending program that owns obligation
+pub fn get_lending_adapter(
+    lending_program_id: &Pubkey,
+) -> Result<Box<dyn LendingAdapter>, ProgramError> {
+    match lending_program_id.as_ref() {
+        SOLEND_ID | SOLEND_DEVNET_ID => Ok(Box::new(SolendAdapter)),
+        SPL_TOKEN_LENDING_ID | PORT_FINANCE_ID => Ok(Box::new(SplTokenLendingAdapter)),
+        _ => Err(SolarisAutoError::UnsupportedLendingProgram.into())
+    }
+}
+
+/// Unpacks obligation with adapter selected by the owner program of account
+pub fn load_obligation(
+    obligation_info: &AccountInfo,
+) -> Result<Obligation, ProgramError> {
+    get_lending_adapter(obligation_info.owner)?
+        .unpack_obligation(&obligation_info.data.borrow())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::helpers::lending_healthfactor::{Decimal, WAD};
+
+    #[test]
+    fn unpack_spl_obligation() {
+        let usdc_reserve = Pubkey::new_unique();
+        let sol_reserve = Pubkey::new_unique();
+
+        let mut data = vec![0; SPL_OBLIGATION_LEN];
+
+        let mut offset = 1 + 8 + 1 + 32 + 32 + 16;
+        data[offset..offset + 16].copy_from_slice(&(80 * WAD as u128).to_le_bytes());
+        offset += 16 + 16 + 16;
+        data[offset] = 1;
+        data[offset + 1] = 1;
+        offset += 2;
+
+        data[offset..offset + 32].copy_from_slice(sol_reserve.as_ref());
+        data[offset + 32..offset + 40].copy_from_slice(&1_000u64.to_le_bytes());
+        offset += SPL_OBLIGATION_COLLATERAL_LEN;
+
+        data[offset..offset + 32].copy_from_slice(usdc_reserve.as_ref());
+        data[offset + 48..offset + 64].copy_from_slice(&(80 * WAD as u128).to_le_bytes());
+
+        let obligation = get_lending_adapter(&Pubkey::new(PORT_FINANCE_ID))
+            .unwrap()
+            .unpack_obligation(&data)
+            .unwrap();
+
+        assert_eq!(obligation.borrowed_value, Decimal::from(80));
+        assert_eq!(obligation.find_collateral(&sol_reserve).unwrap().deposited_amount, 1_000);
+        assert_eq!(
+            obligation.find_liquidity(&usdc_reserve).unwrap().borrowed_amount_wads,
+            Decimal::from(80),
+        );
+    }
+
+    #[test]
+    fn unsupported_lending_program() {
+        assert!(get_lending_adapter(&Pubkey::new(SOLEND_DEVNET_ID)).is_ok());
+        assert_eq!(
+            get_lending_adapter(&Pubkey::new_unique()).err(),
+            Some(ProgramError::from(SolarisAutoError::UnsupportedLendingProgram)),
+        );
+    }
+}
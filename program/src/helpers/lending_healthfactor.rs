@@ -4,26 +4,36 @@ use solana_program::{
     msg,
     entrypoint::ProgramResult,
     instruction::Instruction,
-
+    program_error::ProgramError,
 };
 use arrayref::{array_refs, array_ref};
+use byteorder::ByteOrder;
 use uint::construct_uint;
 
 use crate::error::SolarisAutoError;
 
+use super::lending_adapter::load_obligation;
+
 // Pubkey is "FHdz7Ws3ettxHn8mJwD6PXLm7fMKZ91tMdicJoCR6fuk"
 pub const PREDICATE_HEALTHFACTOR_ID: &[u8] = &[78, 11, 118, 213, 228, 92, 26, 55, 101, 204, 11, 75, 138, 91, 78, 249, 10, 197, 229, 133, 84, 247, 212, 213, 21, 232, 235, 119, 192, 110, 179, 177];
+// Pubkey is "EoSLnvn5qo8168qfF8DSe6ecArbCtvxRzVoFndeDC52D"
+pub const PREDICATE_RESERVE_POSITION_ID: &[u8] = &[205, 13, 76, 250, 5, 232, 126, 17, 22, 20, 104, 118, 47, 12, 153, 96, 56, 100, 241, 190, 163, 132, 46, 209, 150, 108, 77, 244, 52, 112, 92, 238];
 
 // U192 with 192 bits consisting of 3x64-bit words
 construct_uint! {
     pub struct U192(3);
 }
 
+/// Scale of precision
+pub const SCALE: usize = 18;
+/// Identity
+pub const WAD: u64 = 1_000_000_000_000_000_000;
+
 /// Large decimal values, precise to 18 digits
 #[derive(Clone, Copy, Debug, Default, PartialEq, PartialOrd, Eq, Ord)]
 pub struct Decimal(pub U192);
 
-fn unpack_decimal(src: &[u8; 16]) -> Decimal {
+pub fn unpack_decimal(src: &[u8; 16]) -> Decimal {
    Decimal::from_scaled_val(u128::from_le_bytes(*src))
 }
 
@@ -34,17 +44,171 @@ impl Decimal {
    }
 }
 
+impl From<u64> for Decimal {
+   fn from(val: u64) -> Self {
+      Self(U192::from(val) * U192::from(WAD))
+   }
+}
+
 const OBLIGATION_COLLATERAL_LEN: usize = 88; // 32 + 8 + 16 + 32
 const OBLIGATION_LIQUIDITY_LEN: usize = 112; // 32 + 16 + 16 + 16 + 32
 const MAX_OBLIGATION_RESERVES: usize = 10;
 const OBLIGATION_LEN: usize = 1300; // 1 + 8 + 1 + 32 + 32 + 16 + 16 + 16 + 16 + 64 + 1 + 1 + (88 * 1) + (112 * 9)
 
+/// Obligation collateral state
+#[derive(Clone, Debug, PartialEq)]
+pub struct ObligationCollateral {
+    /// Reserve collateral is deposited to
+    pub deposit_reserve: Pubkey,
+    /// Amount of collateral deposited
+    pub deposited_amount: u64,
+    /// Collateral market value in quote currency
+    pub market_value: Decimal,
+}
+
+/// Obligation liquidity state
+#[derive(Clone, Debug, PartialEq)]
+pub struct ObligationLiquidity {
+    /// Reserve liquidity is borrowed from
+    pub borrow_reserve: Pubkey,
+    /// Borrow rate used for calculating interest
+    pub cumulative_borrow_rate_wads: Decimal,
+    /// Amount of liquidity borrowed plus interest
+    pub borrowed_amount_wads: Decimal,
+    /// Liquidity market value in quote currency
+    pub market_value: Decimal,
+}
+
+/// Normalized obligation of token-lending protocols. Only fields that are
+/// used by predicates are unpacked
+#[derive(Clone, Debug, PartialEq)]
+pub struct Obligation {
+    /// Lending market address
+    pub lending_market: Pubkey,
+    /// Owner authority which can borrow liquidity
+    pub owner: Pubkey,
+    /// Deposited collateral for the obligation, unique by deposit reserve address
+    pub deposits: Vec<ObligationCollateral>,
+    /// Borrowed liquidity for the obligation, unique by borrow reserve address
+    pub borrows: Vec<ObligationLiquidity>,
+    /// Market value of deposits
+    pub deposited_value: Decimal,
+    /// Market value of borrows
+    pub borrowed_value: Decimal,
+    /// The maximum borrow value at the weighted average loan to value ratio
+    pub allowed_borrow_value: Decimal,
+    /// The dangerous borrow value at the weighted average liquidation threshold
+    pub unhealthy_borrow_value: Decimal,
+}
+
+impl Obligation {
+    /// Unpacks Solend obligation. Use `lending_adapter::load_obligation`
+    /// for accounts of any supported lending program
+    pub fn unpack(src: &[u8]) -> Result<Obligation, ProgramError> {
+        if src.len() < OBLIGATION_LEN {
+            return Err(SolarisAutoError::InvalidObligationData.into())
+        }
+
+        let input = array_ref![src, 0, OBLIGATION_LEN];
+
+        let (
+            _version,
+            _last_update_slot,
+            _last_update_stale,
+            lending_market,
+            owner,
+            deposited_value,
+            borrowed_value,
+            allowed_borrow_value,
+            unhealthy_borrow_value,
+            _padding,
+            deposits_len,
+            borrows_len,
+            data_flat,
+        ) = array_refs![
+            input,
+            1,
+            8,
+            1,
+            32,
+            32,
+            16,
+            16,
+            16,
+            16,
+            64,
+            1,
+            1,
+            OBLIGATION_COLLATERAL_LEN + (OBLIGATION_LIQUIDITY_LEN * (MAX_OBLIGATION_RESERVES - 1))
+        ];
+
+        let deposits_len = deposits_len[0] as usize;
+        let borrows_len = borrows_len[0] as usize;
+
+        if deposits_len + borrows_len > MAX_OBLIGATION_RESERVES {
+            return Err(SolarisAutoError::InvalidObligationData.into())
+        }
+
+        let mut offset = 0;
+
+        let mut deposits = Vec::with_capacity(deposits_len);
+        for _ in 0..deposits_len {
+            let collateral = array_ref![data_flat, offset, OBLIGATION_COLLATERAL_LEN];
+            let (deposit_reserve, deposited_amount, market_value, _padding) =
+                array_refs![collateral, 32, 8, 16, 32];
+
+            deposits.push(ObligationCollateral {
+                deposit_reserve: Pubkey::new_from_array(*deposit_reserve),
+                deposited_amount: u64::from_le_bytes(*deposited_amount),
+                market_value: unpack_decimal(market_value),
+            });
+
+            offset += OBLIGATION_COLLATERAL_LEN;
+        }
+
+        let mut borrows = Vec::with_capacity(borrows_len);
+        for _ in 0..borrows_len {
+            let liquidity = array_ref![data_flat, offset, OBLIGATION_LIQUIDITY_LEN];
+            let (borrow_reserve, cumulative_borrow_rate_wads, borrowed_amount_wads, market_value, _padding) =
+                array_refs![liquidity, 32, 16, 16, 16, 32];
+
+            borrows.push(ObligationLiquidity {
+                borrow_reserve: Pubkey::new_from_array(*borrow_reserve),
+                cumulative_borrow_rate_wads: unpack_decimal(cumulative_borrow_rate_wads),
+                borrowed_amount_wads: unpack_decimal(borrowed_amount_wads),
+                market_value: unpack_decimal(market_value),
+            });
+
+            offset += OBLIGATION_LIQUIDITY_LEN;
+        }
+
+        Ok(Obligation {
+            lending_market: Pubkey::new_from_array(*lending_market),
+            owner: Pubkey::new_from_array(*owner),
+            deposits,
+            borrows,
+            deposited_value: unpack_decimal(deposited_value),
+            borrowed_value: unpack_decimal(borrowed_value),
+            allowed_borrow_value: unpack_decimal(allowed_borrow_value),
+            unhealthy_borrow_value: unpack_decimal(unhealthy_borrow_value),
+        })
+    }
+
+    pub fn find_collateral(&self, reserve: &Pubkey) -> Option<&ObligationCollateral> {
+        self.deposits.iter().find(|collateral| collateral.deposit_reserve == *reserve)
+    }
+
+    pub fn find_liquidity(&self, reserve: &Pubkey) -> Option<&ObligationLiquidity> {
+        self.borrows.iter().find(|liquidity| liquidity.borrow_reserve == *reserve)
+    }
+}
+
 /// Predicate that return Ok(()) if borrowed_value >= required_borrowed_value
 /// 
 /// Accounts required:
 /// 
 /// 0. `[]` Predicate healthfactor program id: 5kwKgdtbBN4HtGHtTuhDr37vJWAxTfx8QkxFGWwFqeoq
-/// 1. `[]` Obligation account: https://pyth.network/developers/accounts/?cluster=devnet#
+/// 1. `[]` Obligation account. Owned by any program supported by `lending_adapter`
 /// 
 /// Instruction data format is 
 /// ```
@@ -58,46 +222,18 @@ pub fn process_healthfactor(
 ) -> ProgramResult {
     let account_info_iter = &mut accounts.iter();
 
-    let program_info = next_account_info(account_info_iter)?;
+    let _program_info = next_account_info(account_info_iter)?;
     let obligation_info = next_account_info(account_info_iter)?;
-    
-    let obligation = &mut *obligation_info.data.borrow_mut();
-    let input = array_ref![obligation, 0, OBLIGATION_LEN];
-
-    let (
-        _version,
-        _last_update_slot,
-        _last_update_stale,
-        _lending_market,
-        _owner,
-        _deposited_value,
-        borrowed_value,
-        allowed_borrow_value,
-        unhealthy_borrow_value,
-        _padding,
-        _deposits_len,
-        _borrows_len,
-        _data_flat,
-    ) = array_refs![
-        &input,
-        1,
-        8,
-        1,
-        32,
-        32,
-        16,
-        16,
-        16,
-        16,
-        64,
-        1,
-        1,
-        OBLIGATION_COLLATERAL_LEN + (OBLIGATION_LIQUIDITY_LEN * (MAX_OBLIGATION_RESERVES - 1))
-    ];
+
+    let obligation = load_obligation(obligation_info)?;
+
+    if instr.data.len() < 16 {
+        return Err(SolarisAutoError::InvalidPredicateInst.into())
+    }
 
     let required_borrowed_value = array_ref![instr.data, 0, 16];
     let required_borrowed_value = unpack_decimal(required_borrowed_value);
-    let borrowed_value = unpack_decimal(&borrowed_value);
+    let borrowed_value = obligation.borrowed_value;
 
     msg!("borrowed value is {:?}", borrowed_value);
     msg!("required value is {:?}", required_borrowed_value);
@@ -107,4 +243,146 @@ pub fn process_healthfactor(
     } else {
         Err(SolarisAutoError::LendingHealthfactorFailed.into())
     }
-}
\ No newline at end of file
+}
+
+/// Predicate that return Ok(()) if deposited collateral or borrowed liquidity
+/// of the specific reserve in obligation is [more/less] than required amount.
+/// If the reserve isn't used by obligation its amount is 0.
+///
+/// Accounts required:
+///
+/// 0. `[]` Predicate reserve position program id: EoSLnvn5qo8168qfF8DSe6ecArbCtvxRzVoFndeDC52D
+/// 1. `[]` Obligation account. Owned by any program supported by `lending_adapter`
+///
+/// Instruction data format is
+/// ```
+/// pub struct LendingReservePosition {
+///     reserve: Pubkey,
+///     is_borrow: bool,  // _false_ - deposited collateral amount,
+///                       // _true_ - borrowed liquidity amount
+///     amount: u64,
+///     less_than_amount: bool,
+/// }
+/// ```
+pub fn process_reserve_position(
+    instr: &Instruction,
+    accounts: &[AccountInfo],
+) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+
+    let _program_info = next_account_info(account_info_iter)?;
+    let obligation_info = next_account_info(account_info_iter)?;
+
+    let obligation = load_obligation(obligation_info)?;
+
+    if instr.data.len() < 42 {
+        return Err(SolarisAutoError::InvalidPredicateInst.into())
+    }
+
+    let reserve = Pubkey::new_from_array(*array_ref![instr.data, 0, 32]);
+    let is_borrow = instr.data[32] != 0;
+    let amount = byteorder::LE::read_u64(&instr.data[33..41]);
+    let less_than_amount = instr.data[41] != 0;
+
+    // Collateral is compared in collateral tokens,
+    // liquidity is compared in liquidity tokens scaled by WAD
+    let (actual_amount, required_amount) = match is_borrow {
+        true => {
+            let borrowed_amount = obligation.find_liquidity(&reserve)
+                .map(|liquidity| liquidity.borrowed_amount_wads)
+                .unwrap_or_default();
+
+            (borrowed_amount, Decimal::from(amount))
+        },
+        false => {
+            let deposited_amount = obligation.find_collateral(&reserve)
+                .map(|collateral| collateral.deposited_amount)
+                .unwrap_or(0);
+
+            (Decimal::from(deposited_amount), Decimal::from(amount))
+        }
+    };
+
+    msg!("actual amount of reserve {} is {:?}", reserve, actual_amount);
+    msg!("required amount is {:?}", required_amount);
+
+    let is_passed = match less_than_amount {
+        true => actual_amount < required_amount,
+        false => actual_amount >= required_amount,
+    };
+
+    if is_passed {
+        Ok(())
+    } else {
+        Err(SolarisAutoError::LendingReservePositionFailed.into())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn pack_obligation(
+        deposits: &[(Pubkey, u64)],
+        borrows: &[(Pubkey, u128)],
+    ) -> Vec<u8> {
+        let mut data = vec![0; OBLIGATION_LEN];
+
+        let mut offset = 1 + 8 + 1 + 32 + 32 + 16 + 16 + 16 + 16 + 64;
+        data[offset] = deposits.len() as u8;
+        data[offset + 1] = borrows.len() as u8;
+        offset += 2;
+
+        for (reserve, amount) in deposits {
+            data[offset..offset + 32].copy_from_slice(reserve.as_ref());
+            data[offset + 32..offset + 40].copy_from_slice(&amount.to_le_bytes());
+            offset += OBLIGATION_COLLATERAL_LEN;
+        }
+
+        for (reserve, amount_wads) in borrows {
+            data[offset..offset + 32].copy_from_slice(reserve.as_ref());
+            data[offset + 48..offset + 64].copy_from_slice(&amount_wads.to_le_bytes());
+            offset += OBLIGATION_LIQUIDITY_LEN;
+        }
+
+        data
+    }
+
+    #[test]
+    fn unpack_obligation() {
+        let sol_reserve = Pubkey::new_unique();
+        let usdc_reserve = Pubkey::new_unique();
+        let eth_reserve = Pubkey::new_unique();
+
+        let data = pack_obligation(
+            &[(sol_reserve, 1_000)],
+            &[(usdc_reserve, 80 * WAD as u128), (eth_reserve, WAD as u128 / 2)],
+        );
+
+        let obligation = Obligation::unpack(&data).unwrap();
+
+        assert_eq!(obligation.deposits.len(), 1);
+        assert_eq!(obligation.borrows.len(), 2);
+        assert_eq!(obligation.find_collateral(&sol_reserve).unwrap().deposited_amount, 1_000);
+        assert_eq!(
+            obligation.find_liquidity(&usdc_reserve).unwrap().borrowed_amount_wads,
+            Decimal::from(80),
+        );
+        assert_eq!(
+            obligation.find_liquidity(&eth_reserve).unwrap().borrowed_amount_wads,
+            Decimal::from_scaled_val(WAD as u128 / 2),
+        );
+        assert!(obligation.find_collateral(&usdc_reserve).is_none());
+    }
+
+    #[test]
+    fn unpack_obligation_invalid_reserves_len() {
+        let mut data = pack_obligation(&[], &[]);
+        data[1 + 8 + 1 + 32 + 32 + 16 + 16 + 16 + 16 + 64] = MAX_OBLIGATION_RESERVES as u8 + 1;
+
+        assert_eq!(
+            Obligation::unpack(&data),
+            Err(ProgramError::from(SolarisAutoError::InvalidObligationData)),
+        );
+    }
+}
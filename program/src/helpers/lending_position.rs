@@ -0,0 +1,551 @@
+use solana_program::{
+    account_info::AccountInfo,
+    entrypoint::ProgramResult,
+    program_error::ProgramError,
+    pubkey::Pubkey,
+};
+
+use crate::{
+    error::SolarisAutoError,
+    state::{
+        SolendPosition,
+        PositionCollateral,
+        PositionLiquidity,
+        MAX_POSITION_RESERVES,
+    },
+    callbacks::liquidation_protection::to_u256,
+    utils::{assert_owned_by, get_delegate_id},
+};
+
+use super::{
+    lending_healthfactor::{
+        Obligation,
+        ObligationCollateral,
+        ObligationLiquidity,
+        Decimal,
+        U192,
+        WAD,
+    },
+    lending_adapter::{SOLEND_ID, SOLEND_DEVNET_ID},
+    lending_reserve::Reserve,
+    range_order::U256,
+};
+
+fn overflow() -> ProgramError {
+    SolarisAutoError::MathOverflow.into()
+}
+
+fn to_decimal(value: U256) -> Result<Decimal, ProgramError> {
+    if value.0[3] != 0 {
+        return Err(overflow())
+    }
+
+    Ok(Decimal(U192([value.0[0], value.0[1], value.0[2]])))
+}
+
+fn to_u128(value: U256) -> Result<u128, ProgramError> {
+    if value > U256::from(u128::MAX) {
+        return Err(overflow())
+    }
+
+    Ok(value.as_u128())
+}
+
+/// The delegate signs instructions of the lending program, so only known
+/// Solend deployments get its signature
+pub fn assert_solend_program(solend_program_info: &AccountInfo) -> ProgramResult {
+    match solend_program_info.key.as_ref() {
+        SOLEND_ID | SOLEND_DEVNET_ID => Ok(()),
+        _ => Err(SolarisAutoError::UnsupportedLendingProgram.into()),
+    }
+}
+
+/// Obligation of the delegate in a known Solend deployment
+pub fn assert_delegate_obligation(
+    solend_program_info: &AccountInfo,
+    obligation_info: &AccountInfo,
+) -> ProgramResult {
+    assert_solend_program(solend_program_info)?;
+
+    assert_owned_by(obligation_info, solend_program_info.key)?;
+
+    let obligation = Obligation::unpack(&obligation_info.data.borrow())?;
+
+    if obligation.owner != get_delegate_id()? {
+        return Err(SolarisAutoError::InvalidObligationOwner.into())
+    }
+
+    Ok(())
+}
+
+/// Position of `maker` in the delegate obligation `obligation_id`
+pub fn load_solend_position(
+    program_id: &Pubkey,
+    solend_position_info: &AccountInfo,
+    maker: &Pubkey,
+    obligation_id: &Pubkey,
+) -> Result<SolendPosition, ProgramError> {
+    assert_owned_by(solend_position_info, program_id)?;
+
+    let solend_position = SolendPosition::from_account_info(solend_position_info)?;
+
+    if solend_position.maker != *maker || solend_position.obligation != *obligation_id {
+        return Err(SolarisAutoError::InvalidSolendPosition.into())
+    }
+
+    Ok(solend_position)
+}
+
+/// Reserves of [reserve, pyth price, switchboard price] refresh triples
+pub fn get_refresh_reserves(
+    refresh_reserve_infos: &[&AccountInfo],
+) -> Result<Vec<(Pubkey, Reserve)>, ProgramError> {
+    let mut reserves = vec![];
+
+    for reserve_infos in refresh_reserve_infos.chunks(3) {
+        reserves.push((*reserve_infos[0].key, Reserve::unpack(&reserve_infos[0].data.borrow())?));
+    }
+
+    Ok(reserves)
+}
+
+fn check_position_reserves(position: &SolendPosition) -> ProgramResult {
+    if position.deposits.len() + position.borrows.len() > MAX_POSITION_RESERVES {
+        return Err(SolarisAutoError::InvalidSolendPosition.into())
+    }
+
+    Ok(())
+}
+
+pub fn deposit_position_collateral(
+    position: &mut SolendPosition,
+    deposit_reserve: &Pubkey,
+    collateral_amount: u64,
+) -> ProgramResult {
+    match position.deposits.iter_mut().find(|collateral| collateral.deposit_reserve == *deposit_reserve) {
+        Some(collateral) => {
+            collateral.deposited_amount = collateral.deposited_amount
+                .checked_add(collateral_amount)
+                .ok_or_else(overflow)?;
+        },
+        None => position.deposits.push(PositionCollateral {
+            deposit_reserve: *deposit_reserve,
+            deposited_amount: collateral_amount,
+        }),
+    }
+
+    check_position_reserves(position)
+}
+
+/// Fails if the maker deposited less than `collateral_amount`
+pub fn withdraw_position_collateral(
+    position: &mut SolendPosition,
+    deposit_reserve: &Pubkey,
+    collateral_amount: u64,
+) -> ProgramResult {
+    let index = position.deposits
+        .iter()
+        .position(|collateral| collateral.deposit_reserve == *deposit_reserve)
+        .ok_or(ProgramError::from(SolarisAutoError::PositionNotEntitled))?;
+
+    let deposited_amount = position.deposits[index].deposited_amount
+        .checked_sub(collateral_amount)
+        .ok_or(ProgramError::from(SolarisAutoError::PositionNotEntitled))?;
+
+    match deposited_amount {
+        0 => { position.deposits.remove(index); },
+        _ => position.deposits[index].deposited_amount = deposited_amount,
+    }
+
+    Ok(())
+}
+
+/// Compounds maker borrows by the cumulative borrow rates of the refreshed obligation
+pub fn accrue_position_interest(
+    position: &mut SolendPosition,
+    obligation: &Obligation,
+) -> ProgramResult {
+    for liquidity in position.borrows.iter_mut() {
+        let obligation_liquidity = match obligation.find_liquidity(&liquidity.borrow_reserve) {
+            Some(obligation_liquidity) => obligation_liquidity,
+            None => continue,
+        };
+
+        let cumulative_borrow_rate_wads = to_u256(&obligation_liquidity.cumulative_borrow_rate_wads);
+
+        if liquidity.cumulative_borrow_rate_wads == 0 {
+            return Err(SolarisAutoError::InvalidSolendPosition.into())
+        }
+
+        let borrowed_amount_wads = U256::from(liquidity.borrowed_amount_wads)
+            .checked_mul(cumulative_borrow_rate_wads)
+            .ok_or_else(overflow)?
+            / U256::from(liquidity.cumulative_borrow_rate_wads);
+
+        liquidity.borrowed_amount_wads = to_u128(borrowed_amount_wads)?;
+        liquidity.cumulative_borrow_rate_wads = to_u128(cumulative_borrow_rate_wads)?;
+    }
+
+    Ok(())
+}
+
+/// Interest must be accrued first
+pub fn borrow_position_liquidity(
+    position: &mut SolendPosition,
+    borrow_reserve: &Pubkey,
+    borrowed_amount_wads: U256,
+    cumulative_borrow_rate_wads: U256,
+) -> ProgramResult {
+    let borrowed_amount_wads = to_u128(borrowed_amount_wads)?;
+    let cumulative_borrow_rate_wads = to_u128(cumulative_borrow_rate_wads)?;
+
+    match position.borrows.iter_mut().find(|liquidity| liquidity.borrow_reserve == *borrow_reserve) {
+        Some(liquidity) => {
+            liquidity.borrowed_amount_wads = liquidity.borrowed_amount_wads
+                .checked_add(borrowed_amount_wads)
+                .ok_or_else(overflow)?;
+            liquidity.cumulative_borrow_rate_wads = cumulative_borrow_rate_wads;
+        },
+        None => position.borrows.push(PositionLiquidity {
+            borrow_reserve: *borrow_reserve,
+            borrowed_amount_wads,
+            cumulative_borrow_rate_wads,
+        }),
+    }
+
+    check_position_reserves(position)
+}
+
+/// Maker borrow rounded up, interest must be accrued first
+pub fn get_position_borrowed_amount(
+    position: &SolendPosition,
+    borrow_reserve: &Pubkey,
+) -> Result<u64, ProgramError> {
+    let liquidity = position.borrows
+        .iter()
+        .find(|liquidity| liquidity.borrow_reserve == *borrow_reserve)
+        .ok_or(ProgramError::from(SolarisAutoError::PositionNotEntitled))?;
+
+    let (amount, remainder) = U256::from(liquidity.borrowed_amount_wads).div_mod(U256::from(WAD));
+    let amount = match remainder.is_zero() {
+        true => amount,
+        false => amount + 1,
+    };
+
+    if amount > U256::from(u64::MAX) {
+        return Err(overflow())
+    }
+
+    Ok(amount.as_u64())
+}
+
+/// Repaid amount above the maker borrow closes it
+pub fn repay_position_liquidity(
+    position: &mut SolendPosition,
+    borrow_reserve: &Pubkey,
+    repay_amount: u64,
+) -> ProgramResult {
+    let index = position.borrows
+        .iter()
+        .position(|liquidity| liquidity.borrow_reserve == *borrow_reserve)
+        .ok_or(ProgramError::from(SolarisAutoError::PositionNotEntitled))?;
+
+    let repay_amount_wads = (repay_amount as u128)
+        .checked_mul(WAD as u128)
+        .ok_or_else(overflow)?;
+
+    match position.borrows[index].borrowed_amount_wads.checked_sub(repay_amount_wads) {
+        Some(borrowed_amount_wads) if borrowed_amount_wads > 0 => {
+            position.borrows[index].borrowed_amount_wads = borrowed_amount_wads;
+        },
+        _ => { position.borrows.remove(index); },
+    }
+
+    Ok(())
+}
+
+/// Maker share of the refreshed obligation: deposits and borrows of the position
+/// capped by the obligation, with obligation market values pro rata. Values are
+/// summed like the lending program does, by loan to value ratios and liquidation
+/// thresholds of the deposit reserves from `reserves`
+pub fn get_position_obligation(
+    position: &SolendPosition,
+    obligation: &Obligation,
+    reserves: &[(Pubkey, Reserve)],
+) -> Result<Obligation, ProgramError> {
+    let mut position_obligation = Obligation {
+        lending_market: obligation.lending_market,
+        owner: obligation.owner,
+        deposits: vec![],
+        borrows: vec![],
+        deposited_value: Decimal::default(),
+        borrowed_value: Decimal::default(),
+        allowed_borrow_value: Decimal::default(),
+        unhealthy_borrow_value: Decimal::default(),
+    };
+
+    let mut deposited_value = U256::zero();
+    let mut allowed_borrow_value = U256::zero();
+    let mut unhealthy_borrow_value = U256::zero();
+    let mut borrowed_value = U256::zero();
+
+    for collateral in position.deposits.iter() {
+        let obligation_collateral = match obligation.find_collateral(&collateral.deposit_reserve) {
+            Some(obligation_collateral) if obligation_collateral.deposited_amount > 0 => obligation_collateral,
+            _ => continue,
+        };
+
+        let reserve = reserves
+            .iter()
+            .find(|(reserve_id, _)| *reserve_id == collateral.deposit_reserve)
+            .map(|(_, reserve)| reserve)
+            .ok_or(ProgramError::NotEnoughAccountKeys)?;
+
+        let deposited_amount = collateral.deposited_amount.min(obligation_collateral.deposited_amount);
+
+        let market_value = to_u256(&obligation_collateral.market_value)
+            .checked_mul(U256::from(deposited_amount))
+            .ok_or_else(overflow)?
+            / U256::from(obligation_collateral.deposited_amount);
+
+        deposited_value = deposited_value.checked_add(market_value).ok_or_else(overflow)?;
+        allowed_borrow_value = allowed_borrow_value
+            .checked_add(market_value * U256::from(reserve.loan_to_value_ratio) / U256::from(100))
+            .ok_or_else(overflow)?;
+        unhealthy_borrow_value = unhealthy_borrow_value
+            .checked_add(market_value * U256::from(reserve.liquidation_threshold) / U256::from(100))
+            .ok_or_else(overflow)?;
+
+        position_obligation.deposits.push(ObligationCollateral {
+            deposit_reserve: collateral.deposit_reserve,
+            deposited_amount,
+            market_value: to_decimal(market_value)?,
+        });
+    }
+
+    for liquidity in position.borrows.iter() {
+        let obligation_liquidity = match obligation.find_liquidity(&liquidity.borrow_reserve) {
+            Some(obligation_liquidity) if !obligation_liquidity.borrowed_amount_wads.0.is_zero() => obligation_liquidity,
+            _ => continue,
+        };
+
+        let obligation_borrowed_amount_wads = to_u256(&obligation_liquidity.borrowed_amount_wads);
+        let borrowed_amount_wads = U256::from(liquidity.borrowed_amount_wads).min(obligation_borrowed_amount_wads);
+
+        let market_value = to_u256(&obligation_liquidity.market_value)
+            .checked_mul(borrowed_amount_wads)
+            .ok_or_else(overflow)?
+            / obligation_borrowed_amount_wads;
+
+        borrowed_value = borrowed_value.checked_add(market_value).ok_or_else(overflow)?;
+
+        position_obligation.borrows.push(ObligationLiquidity {
+            borrow_reserve: liquidity.borrow_reserve,
+            cumulative_borrow_rate_wads: obligation_liquidity.cumulative_borrow_rate_wads,
+            borrowed_amount_wads: to_decimal(borrowed_amount_wads)?,
+            market_value: to_decimal(market_value)?,
+        });
+    }
+
+    position_obligation.deposited_value = to_decimal(deposited_value)?;
+    position_obligation.borrowed_value = to_decimal(borrowed_value)?;
+    position_obligation.allowed_borrow_value = to_decimal(allowed_borrow_value)?;
+    position_obligation.unhealthy_borrow_value = to_decimal(unhealthy_borrow_value)?;
+
+    Ok(position_obligation)
+}
+
+/// Maker share of the refreshed obligation must stay within its loan to value:
+/// the value of maker borrows can't exceed the allowed borrow value of maker deposits.
+/// See `get_position_obligation`
+pub fn check_position_health(
+    position: &SolendPosition,
+    obligation: &Obligation,
+    reserves: &[(Pubkey, Reserve)],
+) -> ProgramResult {
+    let position_obligation = get_position_obligation(position, obligation, reserves)?;
+
+    if position_obligation.borrowed_value > position_obligation.allowed_borrow_value {
+        return Err(SolarisAutoError::PositionUnhealthy.into())
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn reserve(loan_to_value_ratio: u8) -> Reserve {
+        Reserve {
+            lending_market: Pubkey::new_unique(),
+            liquidity_mint: Pubkey::new_unique(),
+            liquidity_mint_decimals: 6,
+            liquidity_supply: Pubkey::new_unique(),
+            liquidity_pyth_oracle: Pubkey::new_unique(),
+            liquidity_switchboard_oracle: Pubkey::new_unique(),
+            liquidity_available_amount: 1_000,
+            liquidity_borrowed_amount_wads: Decimal::from(0),
+            liquidity_market_price: Decimal::from(1),
+            collateral_mint: Pubkey::new_unique(),
+            collateral_mint_total_supply: 1_000,
+            collateral_supply: Pubkey::new_unique(),
+            optimal_utilization_rate: 80,
+            loan_to_value_ratio,
+            liquidation_bonus: 5,
+            liquidation_threshold: 80,
+            min_borrow_rate: 0,
+            optimal_borrow_rate: 8,
+            max_borrow_rate: 50,
+        }
+    }
+
+    #[test]
+    fn position_collateral() {
+        let deposit_reserve = Pubkey::new_unique();
+        let mut position = SolendPosition::new(Pubkey::new_unique(), Pubkey::new_unique());
+
+        deposit_position_collateral(&mut position, &deposit_reserve, 100).unwrap();
+        deposit_position_collateral(&mut position, &deposit_reserve, 50).unwrap();
+        assert_eq!(position.deposits[0].deposited_amount, 150);
+
+        assert_eq!(
+            withdraw_position_collateral(&mut position, &deposit_reserve, 151),
+            Err(SolarisAutoError::PositionNotEntitled.into()),
+        );
+        assert_eq!(
+            withdraw_position_collateral(&mut position, &Pubkey::new_unique(), 1),
+            Err(SolarisAutoError::PositionNotEntitled.into()),
+        );
+
+        withdraw_position_collateral(&mut position, &deposit_reserve, 150).unwrap();
+        assert!(position.deposits.is_empty());
+    }
+
+    #[test]
+    fn position_liquidity() {
+        let borrow_reserve = Pubkey::new_unique();
+        let wad = WAD as u128;
+        let mut position = SolendPosition::new(Pubkey::new_unique(), Pubkey::new_unique());
+
+        borrow_position_liquidity(&mut position, &borrow_reserve, U256::from(100 * wad), U256::from(wad)).unwrap();
+
+        // Borrow rate grew by 10% since the borrow
+        let obligation = Obligation {
+            lending_market: Pubkey::new_unique(),
+            owner: Pubkey::new_unique(),
+            deposits: vec![],
+            borrows: vec![ObligationLiquidity {
+                borrow_reserve,
+                cumulative_borrow_rate_wads: Decimal::from_scaled_val(wad * 11 / 10),
+                borrowed_amount_wads: Decimal::from(1_000),
+                market_value: Decimal::from(1_000),
+            }],
+            deposited_value: Decimal::default(),
+            borrowed_value: Decimal::default(),
+            allowed_borrow_value: Decimal::default(),
+            unhealthy_borrow_value: Decimal::default(),
+        };
+
+        accrue_position_interest(&mut position, &obligation).unwrap();
+        assert_eq!(get_position_borrowed_amount(&position, &borrow_reserve), Ok(110));
+
+        repay_position_liquidity(&mut position, &borrow_reserve, 60).unwrap();
+        assert_eq!(get_position_borrowed_amount(&position, &borrow_reserve), Ok(50));
+
+        repay_position_liquidity(&mut position, &borrow_reserve, 60).unwrap();
+        assert!(position.borrows.is_empty());
+        assert_eq!(
+            get_position_borrowed_amount(&position, &borrow_reserve),
+            Err(SolarisAutoError::PositionNotEntitled.into()),
+        );
+    }
+
+    #[test]
+    fn position_health() {
+        let deposit_reserve = Pubkey::new_unique();
+        let borrow_reserve = Pubkey::new_unique();
+        let wad = WAD as u128;
+
+        // Obligation of two makers: 1_000 collateral worth 2_000, 1_000 borrowed worth 1_000
+        let obligation = Obligation {
+            lending_market: Pubkey::new_unique(),
+            owner: Pubkey::new_unique(),
+            deposits: vec![ObligationCollateral {
+                deposit_reserve,
+                deposited_amount: 1_000,
+                market_value: Decimal::from(2_000),
+            }],
+            borrows: vec![ObligationLiquidity {
+                borrow_reserve,
+                cumulative_borrow_rate_wads: Decimal::from(1),
+                borrowed_amount_wads: Decimal::from(1_000),
+                market_value: Decimal::from(1_000),
+            }],
+            deposited_value: Decimal::default(),
+            borrowed_value: Decimal::default(),
+            allowed_borrow_value: Decimal::default(),
+            unhealthy_borrow_value: Decimal::default(),
+        };
+        let reserves = [(deposit_reserve, reserve(75))];
+
+        // 400 collateral allows to borrow 400 * 2 * 0.75 = 600
+        let mut position = SolendPosition::new(Pubkey::new_unique(), Pubkey::new_unique());
+        deposit_position_collateral(&mut position, &deposit_reserve, 400).unwrap();
+        borrow_position_liquidity(&mut position, &borrow_reserve, U256::from(600 * wad), U256::from(wad)).unwrap();
+        assert_eq!(check_position_health(&position, &obligation, &reserves), Ok(()));
+
+        withdraw_position_collateral(&mut position, &deposit_reserve, 1).unwrap();
+        assert_eq!(
+            check_position_health(&position, &obligation, &reserves),
+            Err(SolarisAutoError::PositionUnhealthy.into()),
+        );
+
+        // Reserves of the maker deposits are required
+        assert_eq!(
+            check_position_health(&position, &obligation, &[]),
+            Err(ProgramError::NotEnoughAccountKeys),
+        );
+    }
+
+    #[test]
+    fn position_obligation() {
+        let deposit_reserve = Pubkey::new_unique();
+        let borrow_reserve = Pubkey::new_unique();
+        let wad = WAD as u128;
+
+        let obligation = Obligation {
+            lending_market: Pubkey::new_unique(),
+            owner: Pubkey::new_unique(),
+            deposits: vec![ObligationCollateral {
+                deposit_reserve,
+                deposited_amount: 1_000,
+                market_value: Decimal::from(2_000),
+            }],
+            borrows: vec![ObligationLiquidity {
+                borrow_reserve,
+                cumulative_borrow_rate_wads: Decimal::from(1),
+                borrowed_amount_wads: Decimal::from(1_000),
+                market_value: Decimal::from(1_000),
+            }],
+            deposited_value: Decimal::from(2_000),
+            borrowed_value: Decimal::from(1_000),
+            allowed_borrow_value: Decimal::from(1_500),
+            unhealthy_borrow_value: Decimal::from(1_600),
+        };
+        let reserves = [(deposit_reserve, reserve(75))];
+
+        let mut position = SolendPosition::new(Pubkey::new_unique(), Pubkey::new_unique());
+        deposit_position_collateral(&mut position, &deposit_reserve, 400).unwrap();
+        borrow_position_liquidity(&mut position, &borrow_reserve, U256::from(300 * wad), U256::from(wad)).unwrap();
+
+        let position_obligation = get_position_obligation(&position, &obligation, &reserves).unwrap();
+
+        assert_eq!(position_obligation.deposits[0].deposited_amount, 400);
+        assert_eq!(position_obligation.borrows[0].borrowed_amount_wads, Decimal::from(300));
+        assert_eq!(position_obligation.deposited_value, Decimal::from(800));
+        assert_eq!(position_obligation.borrowed_value, Decimal::from(300));
+        assert_eq!(position_obligation.allowed_borrow_value, Decimal::from(600));
+        assert_eq!(position_obligation.unhealthy_borrow_value, Decimal::from(640));
+    }
+}
@@ -0,0 +1,347 @@
+use solana_program::{
+    account_info::{AccountInfo, next_account_info},
+    pubkey::Pubkey,
+    msg,
+    entrypoint::ProgramResult,
+    instruction::Instruction,
+    program_error::ProgramError,
+};
+use arrayref::{array_refs, array_ref};
+use byteorder::ByteOrder;
+
+use crate::error::SolarisAutoError;
+
+use super::lending_healthfactor::{Decimal, U192, WAD, unpack_decimal};
+
+// Pubkey is "6Y48i4cXucvgQuFCwedfoH9F9KXy7VmcWykhwResyJC5"
+pub const PREDICATE_RESERVE_RATE_ID: &[u8] = &[82, 62, 251, 106, 62, 255, 151, 128, 76, 203, 18, 129, 224, 124, 115, 168, 8, 119, 69, 69, 87, 230, 22, 84, 9, 4, 160, 146, 31, 1, 114, 230];
+
+const RESERVE_LEN: usize = 619; // 1 + 8 + 1 + 32 + 32 + 1 + 32 + 32 + 32 + 8 + 16 + 16 + 16 + 32 + 8 + 32 + 1 + 1 + 1 + 1 + 1 + 1 + 1 + 8 + 8 + 1 + 8 + 8 + 32 + 248
+
+/// 100% in basis points
+pub const BPS: u64 = 10_000;
+
+/// Solend reserve. Only fields that are used by predicates and callbacks are unpacked
+#[derive(Clone, Debug, PartialEq)]
+pub struct Reserve {
+    /// Lending market address
+    pub lending_market: Pubkey,
+    /// Reserve liquidity mint address
+    pub liquidity_mint: Pubkey,
+    /// Reserve liquidity mint decimals
+    pub liquidity_mint_decimals: u8,
+    /// Reserve liquidity supply address
+    pub liquidity_supply: Pubkey,
+    /// Reserve liquidity pyth oracle account
+    pub liquidity_pyth_oracle: Pubkey,
+    /// Reserve liquidity switchboard oracle account
+    pub liquidity_switchboard_oracle: Pubkey,
+    /// Reserve liquidity available
+    pub liquidity_available_amount: u64,
+    /// Reserve liquidity borrowed
+    pub liquidity_borrowed_amount_wads: Decimal,
+    /// Reserve liquidity market price in quote currency
+    pub liquidity_market_price: Decimal,
+    /// Reserve collateral mint address
+    pub collateral_mint: Pubkey,
+    /// Reserve collateral mint supply, used for exchange rate
+    pub collateral_mint_total_supply: u64,
+    /// Reserve collateral supply address
+    pub collateral_supply: Pubkey,
+    /// Optimal utilization rate, as a percentage
+    pub optimal_utilization_rate: u8,
+    /// Target ratio of the value of borrows to deposits, as a percentage
+    pub loan_to_value_ratio: u8,
+    /// Bonus a liquidator gets when repaying part of an unhealthy obligation, as a percentage
+    pub liquidation_bonus: u8,
+    /// Loan to value ratio at which an obligation can be liquidated, as a percentage
+    pub liquidation_threshold: u8,
+    /// Min borrow APY
+    pub min_borrow_rate: u8,
+    /// Optimal (utilization) borrow APY
+    pub optimal_borrow_rate: u8,
+    /// Max borrow APY
+    pub max_borrow_rate: u8,
+}
+
+impl Reserve {
+    pub fn unpack(src: &[u8]) -> Result<Reserve, ProgramError> {
+        if src.len() < RESERVE_LEN {
+            return Err(SolarisAutoError::InvalidReserveData.into())
+        }
+
+        let input = array_ref![src, 0, RESERVE_LEN];
+
+        let (
+            _version,
+            _last_update_slot,
+            _last_update_stale,
+            lending_market,
+            liquidity_mint,
+            liquidity_mint_decimals,
+            liquidity_supply,
+            liquidity_pyth_oracle,
+            liquidity_switchboard_oracle,
+            liquidity_available_amount,
+            liquidity_borrowed_amount_wads,
+            _liquidity_cumulative_borrow_rate_wads,
+            liquidity_market_price,
+            collateral_mint,
+            collateral_mint_total_supply,
+            collateral_supply,
+            optimal_utilization_rate,
+            loan_to_value_ratio,
+            liquidation_bonus,
+            liquidation_threshold,
+            min_borrow_rate,
+            optimal_borrow_rate,
+            max_borrow_rate,
+            _borrow_fee_wad,
+            _flash_loan_fee_wad,
+            _host_fee_percentage,
+            _deposit_limit,
+            _borrow_limit,
+            _fee_receiver,
+            _padding,
+        ) = array_refs![
+            input,
+            1,
+            8,
+            1,
+            32,
+            32,
+            1,
+            32,
+            32,
+            32,
+            8,
+            16,
+            16,
+            16,
+            32,
+            8,
+            32,
+            1,
+            1,
+            1,
+            1,
+            1,
+            1,
+            1,
+            8,
+            8,
+            1,
+            8,
+            8,
+            32,
+            248
+        ];
+
+        Ok(Reserve {
+            lending_market: Pubkey::new_from_array(*lending_market),
+            liquidity_mint: Pubkey::new_from_array(*liquidity_mint),
+            liquidity_mint_decimals: liquidity_mint_decimals[0],
+            liquidity_supply: Pubkey::new_from_array(*liquidity_supply),
+            liquidity_pyth_oracle: Pubkey::new_from_array(*liquidity_pyth_oracle),
+            liquidity_switchboard_oracle: Pubkey::new_from_array(*liquidity_switchboard_oracle),
+            liquidity_available_amount: u64::from_le_bytes(*liquidity_available_amount),
+            liquidity_borrowed_amount_wads: unpack_decimal(liquidity_borrowed_amount_wads),
+            liquidity_market_price: unpack_decimal(liquidity_market_price),
+            collateral_mint: Pubkey::new_from_array(*collateral_mint),
+            collateral_mint_total_supply: u64::from_le_bytes(*collateral_mint_total_supply),
+            collateral_supply: Pubkey::new_from_array(*collateral_supply),
+            optimal_utilization_rate: optimal_utilization_rate[0],
+            loan_to_value_ratio: loan_to_value_ratio[0],
+            liquidation_bonus: liquidation_bonus[0],
+            liquidation_threshold: liquidation_threshold[0],
+            min_borrow_rate: min_borrow_rate[0],
+            optimal_borrow_rate: optimal_borrow_rate[0],
+            max_borrow_rate: max_borrow_rate[0],
+        })
+    }
+
+    /// Total liquidity of the reserve scaled by WAD: available + borrowed
+    pub fn total_liquidity_wads(&self) -> Decimal {
+        Decimal(Decimal::from(self.liquidity_available_amount).0 + self.liquidity_borrowed_amount_wads.0)
+    }
+
+    /// Collateral amount that is redeemed for at least `liquidity_amount`.
+    /// Redeem rounds liquidity down, so collateral is rounded up.
+    /// Empty reserve has 1:1 exchange rate
+    pub fn collateral_to_redeem(&self, liquidity_amount: u64) -> Result<u64, ProgramError> {
+        let total_liquidity = self.total_liquidity_wads();
+
+        if self.collateral_mint_total_supply == 0 || total_liquidity.0.is_zero() {
+            return Ok(liquidity_amount)
+        }
+
+        let (collateral_amount, remainder) = (
+            U192::from(liquidity_amount)
+                * U192::from(self.collateral_mint_total_supply)
+                * U192::from(WAD)
+        ).div_mod(total_liquidity.0);
+
+        let collateral_amount = match remainder.is_zero() {
+            true => collateral_amount,
+            false => collateral_amount + 1,
+        };
+
+        if collateral_amount > U192::from(u64::MAX) {
+            return Err(SolarisAutoError::MathOverflow.into())
+        }
+
+        Ok(collateral_amount.as_u64())
+    }
+
+    /// Utilization rate in basis points: borrowed / (available + borrowed)
+    pub fn utilization_rate_bps(&self) -> u64 {
+        let total_liquidity = self.total_liquidity_wads();
+
+        if total_liquidity.0.is_zero() {
+            return 0
+        }
+
+        (self.liquidity_borrowed_amount_wads.0 * U192::from(BPS) / total_liquidity.0).as_u64()
+    }
+
+    /// Current borrow APR in basis points. Follows the Solend kinked rate model
+    pub fn current_borrow_rate_bps(&self) -> u64 {
+        let utilization_rate = self.utilization_rate_bps();
+        let optimal_utilization_rate = self.optimal_utilization_rate as u64 * 100;
+        let min_borrow_rate = self.min_borrow_rate as u64 * 100;
+        let optimal_borrow_rate = self.optimal_borrow_rate as u64 * 100;
+        let max_borrow_rate = self.max_borrow_rate as u64 * 100;
+
+        if utilization_rate < optimal_utilization_rate || optimal_utilization_rate == BPS {
+            let rate_range = optimal_borrow_rate.saturating_sub(min_borrow_rate);
+
+            min_borrow_rate + utilization_rate * rate_range / optimal_utilization_rate
+        } else {
+            let rate_range = max_borrow_rate.saturating_sub(optimal_borrow_rate);
+
+            optimal_borrow_rate
+                + (utilization_rate - optimal_utilization_rate) * rate_range
+                    / (BPS - optimal_utilization_rate)
+        }
+    }
+}
+
+/// Predicate that return Ok(()) if utilization rate or borrow APR
+/// of the reserve is [more/less] than threshold. Values are taken
+/// from the last reserve refresh.
+///
+/// Accounts required:
+///
+/// 0. `[]` Predicate reserve rate program id: 6Y48i4cXucvgQuFCwedfoH9F9KXy7VmcWykhwResyJC5
+/// 1. `[]` Reserve account
+///
+/// Instruction data format is
+/// ```
+/// pub struct LendingReserveRate {
+///     is_borrow_rate: bool,   // _false_ - utilization rate,
+///                             // _true_ - borrow APR
+///     threshold_bps: u64,
+///     less_than_threshold: bool,
+/// }
+/// ```
+pub fn process_reserve_rate(
+    instr: &Instruction,
+    accounts: &[AccountInfo],
+) -> ProgramResult {
+    let account_info_iter = &mut accounts.iter();
+
+    let _program_info = next_account_info(account_info_iter)?;
+    let reserve_info = next_account_info(account_info_iter)?;
+
+    let reserve = Reserve::unpack(&reserve_info.data.borrow())?;
+
+    if instr.data.len() < 10 {
+        return Err(SolarisAutoError::InvalidPredicateInst.into())
+    }
+
+    let is_borrow_rate = instr.data[0] != 0;
+    let threshold_bps = byteorder::LE::read_u64(&instr.data[1..9]);
+    let less_than_threshold = instr.data[9] != 0;
+
+    let actual_bps = match is_borrow_rate {
+        true => reserve.current_borrow_rate_bps(),
+        false => reserve.utilization_rate_bps(),
+    };
+
+    msg!("actual rate is {} bps", actual_bps);
+    msg!("threshold is {} bps", threshold_bps);
+
+    let is_passed = match less_than_threshold {
+        true => actual_bps < threshold_bps,
+        false => actual_bps >= threshold_bps,
+    };
+
+    if is_passed {
+        Ok(())
+    } else {
+        Err(SolarisAutoError::LendingReserveRateFailed.into())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn reserve(available: u64, borrowed: u64) -> Reserve {
+        Reserve {
+            lending_market: Pubkey::new_unique(),
+            liquidity_mint: Pubkey::new_unique(),
+            liquidity_mint_decimals: 6,
+            liquidity_supply: Pubkey::new_unique(),
+            liquidity_pyth_oracle: Pubkey::new_unique(),
+            liquidity_switchboard_oracle: Pubkey::new_unique(),
+            liquidity_available_amount: available,
+            liquidity_borrowed_amount_wads: Decimal::from(borrowed),
+            liquidity_market_price: Decimal::from(1),
+            collateral_mint: Pubkey::new_unique(),
+            collateral_mint_total_supply: available + borrowed,
+            collateral_supply: Pubkey::new_unique(),
+            optimal_utilization_rate: 80,
+            loan_to_value_ratio: 75,
+            liquidation_bonus: 5,
+            liquidation_threshold: 80,
+            min_borrow_rate: 0,
+            optimal_borrow_rate: 8,
+            max_borrow_rate: 50,
+        }
+    }
+
+    #[test]
+    fn utilization_rate() {
+        assert_eq!(reserve(0, 0).utilization_rate_bps(), 0);
+        assert_eq!(reserve(100, 0).utilization_rate_bps(), 0);
+        assert_eq!(reserve(50, 50).utilization_rate_bps(), 5_000);
+        assert_eq!(reserve(5, 95).utilization_rate_bps(), 9_500);
+        assert_eq!(reserve(0, 100).utilization_rate_bps(), BPS);
+    }
+
+    #[test]
+    fn borrow_rate() {
+        // Below optimal utilization: 0% + 40 / 80 * (8% - 0%)
+        assert_eq!(reserve(60, 40).current_borrow_rate_bps(), 400);
+        // At optimal utilization
+        assert_eq!(reserve(20, 80).current_borrow_rate_bps(), 800);
+        // Above optimal utilization: 8% + (90 - 80) / (100 - 80) * (50% - 8%)
+        assert_eq!(reserve(10, 90).current_borrow_rate_bps(), 2_900);
+        assert_eq!(reserve(0, 100).current_borrow_rate_bps(), 5_000);
+    }
+
+    #[test]
+    fn collateral_to_redeem() {
+        // Exchange rate 1:1
+        assert_eq!(reserve(60, 40).collateral_to_redeem(30), Ok(30));
+        assert_eq!(reserve(0, 0).collateral_to_redeem(30), Ok(30));
+
+        // 1 collateral is redeemed for 1.5 liquidity
+        let mut reserve = reserve(90, 60);
+        reserve.collateral_mint_total_supply = 100;
+        assert_eq!(reserve.collateral_to_redeem(150), Ok(100));
+        assert_eq!(reserve.collateral_to_redeem(151), Ok(101));
+        assert_eq!(reserve.collateral_to_redeem(1), Ok(1));
+    }
+}
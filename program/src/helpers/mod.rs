@@ -1,4 +1,14 @@
 pub mod predicate_helpers;
 pub mod oracle_price;
 pub mod get_amounts;
-pub mod lending_healthfactor;
\ No newline at end of file
+pub mod lending_healthfactor;
+pub mod lending_reserve;
+pub mod lending_adapter;
+pub mod call_and_compare;
+pub mod dutch_auction;
+pub mod range_order;
+pub mod oracle_pegged;
+pub mod lending_position;
+
+#[cfg(test)]
+mod test_utils;
@@ -0,0 +1,186 @@
+use solana_program::{
+    account_info::{AccountInfo, next_account_info},
+    instruction::Instruction,
+    program_error::ProgramError,
+    msg,
+};
+use byteorder::ByteOrder;
+
+use crate::error::SolarisAutoError;
+
+use super::oracle_price::{load_pyth_price, get_amount_price_wad};
+use super::lending_healthfactor::{U192, WAD};
+use super::lending_reserve::BPS;
+
+// Pubkey is "AuqeMe1uZRKYbTLEuHuzLSHCiD5CG1KKnDRbaLzkj8HJ"
+pub const HELPER_ORACLE_PEGGED_ID: &[u8] = &[147, 66, 98, 237, 18, 10, 111, 140, 81, 120, 227, 223, 143, 54, 49, 50, 178, 162, 122, 195, 102, 13, 23, 206, 94, 223, 168, 168, 18, 71, 167, 205];
+
+/// Built-in get [maker/taker] amount pegged to the oracle price.
+/// Pyth price of the maker asset quoted in the taker asset is converted
+/// to amounts with mint decimals and moved by `spread_bps`
+/// (e.g. -100 means oracle price minus 1%). The result is clamped
+/// by `min_price` and `max_price`, so the maker is protected from oracle failure.
+/// Prices are taker amount per one maker amount scaled by WAD, 0 means no limit.
+///
+/// Accounts required:
+///
+/// 0. `[]` Helper oracle pegged program id: AuqeMe1uZRKYbTLEuHuzLSHCiD5CG1KKnDRbaLzkj8HJ
+/// 1. `[]` Pyth price account
+///
+/// Instruction data format is
+/// ```
+/// pub struct HelperOraclePegged {
+///     get_maker_amount: bool,
+///     spread_bps: i64,
+///     maker_decimals: u8,
+///     taker_decimals: u8,
+///     min_price: u128,
+///     max_price: u128,
+///     swap_amount: u64, // swap_taker_amount if `get_maker_amount` == _true_
+///                       // swap_maker_amount if `get_maker_amount` == _false_
+///                       // appended by `invoke_get_amount`
+/// }
+/// ```
+pub fn process_oracle_pegged(
+    instr: &Instruction,
+    accounts: &[AccountInfo],
+) -> Result<u64, ProgramError> {
+    let account_info_iter = &mut accounts.iter();
+
+    let _program_info = next_account_info(account_info_iter)?;
+    let pyth_price_info = next_account_info(account_info_iter)?;
+
+    if instr.data.len() < 51 {
+        return Err(SolarisAutoError::InvalidGetAmountInst.into())
+    }
+
+    let is_get_maker_amount = instr.data[0] != 0;
+    let spread_bps = byteorder::LE::read_i64(&instr.data[1..9]);
+    let maker_decimals = instr.data[9];
+    let taker_decimals = instr.data[10];
+    let min_price = byteorder::LE::read_u128(&instr.data[11..27]);
+    let max_price = byteorder::LE::read_u128(&instr.data[27..43]);
+    let swap_amount = byteorder::LE::read_u64(&instr.data[43..51]);
+
+    let pyth_price = load_pyth_price(pyth_price_info)?;
+    let price = get_amount_price_wad(&pyth_price, maker_decimals, taker_decimals)
+        .and_then(|price| apply_spread(price, spread_bps))
+        .map(|price| clamp_price(price, min_price, max_price))?;
+
+    msg!("pegged price is {}", price);
+
+    match is_get_maker_amount {
+        true => get_maker_amount(price, swap_amount),
+        false => get_taker_amount(price, swap_amount),
+    }
+}
+
+/// Moves price by signed spread in basis points. Rounded up in favour of the maker.
+pub fn apply_spread(price_wad: u128, spread_bps: i64) -> Result<u128, ProgramError> {
+    if spread_bps <= -(BPS as i64) {
+        return Err(SolarisAutoError::InvalidGetAmountInst.into())
+    }
+
+    let multiplier = (BPS as i64 + spread_bps) as u64;
+    let (price, remainder) = (U192::from(price_wad) * U192::from(multiplier))
+        .div_mod(U192::from(BPS));
+
+    let price = match remainder.is_zero() {
+        true => price,
+        false => price + 1,
+    };
+
+    if price > U192::from(u128::MAX) {
+        return Err(SolarisAutoError::MathOverflow.into())
+    }
+
+    Ok(price.as_u128())
+}
+
+pub fn clamp_price(price_wad: u128, min_price: u128, max_price: u128) -> u128 {
+    let price = match max_price != 0 && price_wad > max_price {
+        true => max_price,
+        false => price_wad,
+    };
+
+    match price < min_price {
+        true => min_price,
+        false => price,
+    }
+}
+
+/// Maker amount for taker amount by price. Rounded down in favour of the maker.
+pub fn get_maker_amount(price_wad: u128, swap_taker_amount: u64) -> Result<u64, ProgramError> {
+    if price_wad == 0 {
+        return Err(SolarisAutoError::MathOverflow.into())
+    }
+
+    let amount = U192::from(swap_taker_amount) * U192::from(WAD) / U192::from(price_wad);
+
+    if amount > U192::from(u64::MAX) {
+        return Err(SolarisAutoError::MathOverflow.into())
+    }
+
+    Ok(amount.as_u64())
+}
+
+/// Taker amount for maker amount by price. Rounded up in favour of the maker.
+pub fn get_taker_amount(price_wad: u128, swap_maker_amount: u64) -> Result<u64, ProgramError> {
+    let (amount, remainder) = (U192::from(swap_maker_amount) * U192::from(price_wad))
+        .div_mod(U192::from(WAD));
+
+    let amount = match remainder.is_zero() {
+        true => amount,
+        false => amount + 1,
+    };
+
+    if amount > U192::from(u64::MAX) {
+        return Err(SolarisAutoError::MathOverflow.into())
+    }
+
+    Ok(amount.as_u64())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn spread() {
+        let price = 150 * WAD as u128;
+
+        assert_eq!(apply_spread(price, 0), Ok(price));
+        assert_eq!(apply_spread(price, -100), Ok(148_500_000_000_000_000_000));
+        assert_eq!(apply_spread(price, 250), Ok(153_750_000_000_000_000_000));
+        assert_eq!(apply_spread(3, -1), Ok(3));
+        assert_eq!(
+            apply_spread(price, -10_000),
+            Err(ProgramError::from(SolarisAutoError::InvalidGetAmountInst)),
+        );
+    }
+
+    #[test]
+    fn clamp() {
+        assert_eq!(clamp_price(100, 0, 0), 100);
+        assert_eq!(clamp_price(100, 120, 0), 120);
+        assert_eq!(clamp_price(100, 0, 90), 90);
+        assert_eq!(clamp_price(100, 90, 110), 100);
+    }
+
+    #[test]
+    fn pegged_amounts() {
+        // 0.1485 USDC (6 decimals) per lamport
+        let price = 148_500_000_000_000_000;
+
+        assert_eq!(get_taker_amount(price, 1_000_000_000), Ok(148_500_000));
+        assert_eq!(get_maker_amount(price, 148_500_000), Ok(1_000_000_000));
+
+        // Rounding in favour of the maker
+        assert_eq!(get_taker_amount(price, 1), Ok(1));
+        assert_eq!(get_maker_amount(price, 1), Ok(6));
+        assert_eq!(
+            get_maker_amount(0, 1),
+            Err(ProgramError::from(SolarisAutoError::MathOverflow)),
+        );
+    }
+}
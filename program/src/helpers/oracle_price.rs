@@ -2,6 +2,7 @@ use solana_program::{
     account_info::{AccountInfo, next_account_info},
     instruction::Instruction,
     entrypoint::ProgramResult,
+    program_error::ProgramError,
     pubkey::Pubkey,
     msg,
 };
@@ -10,13 +11,17 @@ use std::convert::TryInto;
 use byteorder::ByteOrder;
 
 use chainlink_solana; 
-use pyth_client;
+use pyth_client::{self, PriceConf};
 
 use crate::{
     utils::assert_owned_by,
     error::SolarisAutoError,
+    state::PriceGuard,
 };
 
+use super::lending_healthfactor::{U192, WAD};
+use super::lending_reserve::BPS;
+
 //Pubkey is "5kwKgdtbBN4HtGHtTuhDr37vJWAxTfx8QkxFGWwFqeoq"
 pub const HELPER_PYTH_ID: &[u8] = &[70, 176, 37, 12, 106, 201, 74, 156, 64, 246, 254, 0, 195, 85, 90, 97, 88, 148, 195, 146, 24, 6, 246, 114, 86, 228, 185, 63, 193, 54, 105, 176];
 
@@ -43,22 +48,20 @@ pub fn process_pyth_price(
 
     let program_info = next_account_info(account_info_iter)?;
     let pyth_price_info = next_account_info(account_info_iter)?;
-    assert_owned_by(pyth_price_info, &Pubkey::from_str("gSbePebfvPy7tRqimPoVecS2UsBvYv46ynrzWocc92s").unwrap())?;
-    // assert_owned_by(pyth_price_info, pyth_client::ID)?;
-
-    let pyth_price = pyth_client::load_price(&pyth_price_info.data.borrow_mut())
-        .and_then(|price| {
-            let price_conf = price.get_current_price().unwrap();
 
-            Ok(price_conf)
-        })?;
-
-    msg!("price_conf is {:?}", pyth_price);
+    if instr.data.len() < 9 {
+        return Err(SolarisAutoError::InvalidPredicateInst.into())
+    }
 
     let amount = byteorder::LE::read_u64(&instr.data[0..8]);
     let price_less_than_amount = instr.data[8] != 0;
 
-    let actual_price: u64 = pyth_price.price.try_into().unwrap();
+    let pyth_price = load_pyth_price(pyth_price_info)?;
+
+    // Negative price isn't comparable with the amount
+    let actual_price: u64 = pyth_price.price
+        .try_into()
+        .or(Err(ProgramError::from(SolarisAutoError::OraclePriceUnavailable)))?;
 
     // TODO: comparison with +-conf
     match price_less_than_amount {
@@ -77,4 +80,184 @@ pub fn process_pyth_price(
     }
 
     return Err(SolarisAutoError::OraclePredicateFailed.into())
-}
\ No newline at end of file
+}
+
+/// Loads current price from Pyth price account. Fails if the price
+/// is not available, e.g. the product is not trading.
+pub fn load_pyth_price(
+    pyth_price_info: &AccountInfo,
+) -> Result<PriceConf, ProgramError> {
+    assert_owned_by(pyth_price_info, &Pubkey::from_str("gSbePebfvPy7tRqimPoVecS2UsBvYv46ynrzWocc92s").unwrap())?;
+    // assert_owned_by(pyth_price_info, pyth_client::ID)?;
+
+    let pyth_price = pyth_client::load_price(&pyth_price_info.data.borrow())?
+        .get_current_price()
+        .ok_or(ProgramError::from(SolarisAutoError::OraclePriceUnavailable))?;
+
+    msg!("price_conf is {:?}", pyth_price);
+
+    Ok(pyth_price)
+}
+
+/// Converts Pyth price of the maker asset quoted in the taker asset into
+/// taker amount per one maker amount scaled by WAD, taking into account
+/// decimals of both mints. Rounded up in favour of the maker.
+pub fn get_amount_price_wad(
+    price: &PriceConf,
+    maker_decimals: u8,
+    taker_decimals: u8,
+) -> Result<u128, ProgramError> {
+    if price.price <= 0 {
+        return Err(SolarisAutoError::OraclePriceUnavailable.into())
+    }
+
+    let exponent = price.expo as i64 + taker_decimals as i64 - maker_decimals as i64;
+    if exponent.abs() > 38 {
+        return Err(SolarisAutoError::MathOverflow.into())
+    }
+
+    let price_wad = U192::from(price.price as u64) * U192::from(WAD);
+
+    let price_wad = match exponent >= 0 {
+        true => U192::exp10(exponent as usize)
+            .checked_mul(price_wad)
+            .ok_or(ProgramError::from(SolarisAutoError::MathOverflow))?,
+        false => {
+            let divisor = U192::exp10((-exponent) as usize);
+            let (quotient, remainder) = price_wad.div_mod(divisor);
+
+            match remainder.is_zero() {
+                true => quotient,
+                false => quotient + 1,
+            }
+        }
+    };
+
+    if price_wad > U192::from(u128::MAX) {
+        return Err(SolarisAutoError::MathOverflow.into())
+    }
+
+    Ok(price_wad.as_u128())
+}
+
+/// Rejects the fill if its price deviates from the oracle price
+/// by less than the tolerance of the price guard
+pub fn check_price_guard(
+    price_guard: &PriceGuard,
+    pyth_price_info: &AccountInfo,
+    making_amount: u64,
+    taking_amount: u64,
+) -> ProgramResult {
+    if *pyth_price_info.key != price_guard.pyth_price {
+        return Err(SolarisAutoError::InvalidPriceGuardOracle.into())
+    }
+
+    let pyth_price = load_pyth_price(pyth_price_info)?;
+    let oracle_price = get_amount_price_wad(
+        &pyth_price,
+        price_guard.maker_decimals,
+        price_guard.taker_decimals,
+    )?;
+
+    msg!("oracle price is {}", oracle_price);
+
+    match is_within_price_band(making_amount, taking_amount, oracle_price, price_guard.tolerance_bps) {
+        true => Ok(()),
+        false => Err(SolarisAutoError::PriceGuardFailed.into()),
+    }
+}
+
+/// taking_amount / making_amount >= oracle_price * (1 + tolerance_bps / BPS)
+pub fn is_within_price_band(
+    making_amount: u64,
+    taking_amount: u64,
+    oracle_price_wad: u128,
+    tolerance_bps: i64,
+) -> bool {
+    let multiplier = BPS as i64 + tolerance_bps;
+    if multiplier <= 0 {
+        return true
+    }
+
+    let fill_value = U192::from(taking_amount) * U192::from(WAD) * U192::from(BPS);
+    let oracle_value = U192::from(oracle_price_wad)
+        * U192::from(multiplier as u64)
+        * U192::from(making_amount);
+
+    fill_value >= oracle_value
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn price(price: i64, expo: i32) -> PriceConf {
+        PriceConf {
+            price,
+            conf: 0,
+            expo,
+        }
+    }
+
+    #[test]
+    fn amount_price() {
+        // SOL/USD is 150.5, SOL has 9 decimals, USDC has 6
+        assert_eq!(
+            get_amount_price_wad(&price(15_050_000_000, -8), 9, 6),
+            Ok(150_500_000_000_000_000),
+        );
+        // USDC per USDC
+        assert_eq!(
+            get_amount_price_wad(&price(100_000_000, -8), 6, 6),
+            Ok(WAD as u128),
+        );
+        // Rounded up
+        assert_eq!(get_amount_price_wad(&price(1, -8), 18, 0), Ok(1));
+        assert_eq!(
+            get_amount_price_wad(&price(-1, -8), 9, 6),
+            Err(ProgramError::from(SolarisAutoError::OraclePriceUnavailable)),
+        );
+    }
+
+    #[test]
+    fn pyth_price_short_data() {
+        let program_id = Pubkey::new(HELPER_PYTH_ID);
+        let pyth_price_id = Pubkey::new_unique();
+        let owner = Pubkey::new_unique();
+        let mut lamports = [0u64; 2];
+        let mut data = [[0u8; 0]; 2];
+        let [program_lamports, pyth_price_lamports] = &mut lamports;
+        let [program_data, pyth_price_data] = &mut data;
+
+        let infos = [
+            AccountInfo::new(&program_id, false, false, program_lamports, program_data, &owner, false, 0),
+            AccountInfo::new(&pyth_price_id, false, false, pyth_price_lamports, pyth_price_data, &owner, false, 0),
+        ];
+
+        let instruction = Instruction {
+            program_id,
+            accounts: vec![],
+            data: 100u64.to_le_bytes().to_vec(),
+        };
+
+        assert_eq!(
+            process_pyth_price(&instruction, &infos),
+            Err(ProgramError::from(SolarisAutoError::InvalidPredicateInst)),
+        );
+    }
+
+    #[test]
+    fn price_band() {
+        let oracle_price = 2 * WAD as u128;
+
+        assert!(is_within_price_band(100, 200, oracle_price, 0));
+        assert!(!is_within_price_band(100, 199, oracle_price, 0));
+        // 2% below the oracle is allowed
+        assert!(is_within_price_band(100, 196, oracle_price, -200));
+        assert!(!is_within_price_band(100, 195, oracle_price, -200));
+        // At least 1% above the oracle is required
+        assert!(is_within_price_band(100, 202, oracle_price, 100));
+        assert!(!is_within_price_band(100, 201, oracle_price, 100));
+        assert!(is_within_price_band(100, 0, oracle_price, -10_000));
+    }
+}
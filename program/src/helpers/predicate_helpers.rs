@@ -8,11 +8,14 @@ use solana_program::{
 
 use crate::{
     error::SolarisAutoError,
+    utils::assert_instruction_accounts,
 };
 
 use super::oracle_price;
 use super::get_amounts;
 use super::lending_healthfactor;
+use super::lending_reserve;
+use super::call_and_compare;
 
 //Pubkey is "3Lf5PRfK3nibfrChcx2Hrh7g2WSgu3QBxLXSFY5WqMCA"
 pub const HELPER_AND_ID: &[u8] = &[34, 192, 118, 35, 128, 32, 126, 54, 71, 146, 146, 47, 241, 227, 117, 146, 224, 12, 197, 13, 212, 150, 35, 113, 137, 30, 41, 185, 2, 214, 159, 231];
@@ -29,26 +32,75 @@ fn _check_predicate<F>(
     accounts: &[AccountInfo],
     invoke_predicate: F,
 ) -> ProgramResult 
+    where F: Fn(&Instruction, &[AccountInfo]) -> ProgramResult {
+    _run_predicate(instr, accounts, invoke_predicate)
+        .map_err(|(index, error)| match index {
+            Some(_) => {
+                error.print::<SolarisAutoError>();
+                ProgramError::from(SolarisAutoError::PredicateAndFail)
+            },
+            None => error,
+        })
+}
+
+/// Evaluates predicate like `check_predicate`, but on failure returns index
+/// of the failed sub-predicate of helper AND (0 for a single predicate)
+/// together with its error instead of the aggregated one.
+pub fn evaluate_predicate(
+    inst: &[u8],
+    accounts: &[AccountInfo],
+) -> Result<(), (u8, ProgramError)> {
+    _evaluate_predicate(inst, accounts, invoke_predicate)
+}
+
+fn _evaluate_predicate<F>(
+    instr: &[u8],
+    accounts: &[AccountInfo],
+    invoke_predicate: F,
+) -> Result<(), (u8, ProgramError)>
+    where F: Fn(&Instruction, &[AccountInfo]) -> ProgramResult {
+    _run_predicate(instr, accounts, invoke_predicate)
+        .map_err(|(index, error)| (index.unwrap_or(0), error))
+}
+
+/// Runs the predicate, error has the index of the failed sub-predicate
+/// if the predicate is helper AND
+fn _run_predicate<F>(
+    instr: &[u8],
+    accounts: &[AccountInfo],
+    invoke_predicate: F,
+) -> Result<(), (Option<u8>, ProgramError)>
     where F: Fn(&Instruction, &[AccountInfo]) -> ProgramResult {
     // TODO: Find a way to avoid `bincode::deserialize` because
     //       it takes lots of CU.
-    bincode::deserialize::<Instruction>(instr)
-        .or(Err(ProgramError::from(SolarisAutoError::InvalidPredicateInst)))
-        .and_then(|predicate| {
-            match predicate.program_id.as_ref() {
-                HELPER_AND_ID => {
-                    process_and(&predicate.data, accounts, invoke_predicate)
-                },
-                // TODO: Find a way to create helper OR
-                /*
-                HELPER_OR_ID => {
-                    // process_or(&predicate.data, accounts)
-                }*/
-                _ => {
-                    invoke_predicate(&predicate, accounts)
-                }
+    let predicate = bincode::deserialize::<Instruction>(instr)
+        .or(Err((None, ProgramError::from(SolarisAutoError::InvalidPredicateInst))))?;
+
+    assert_instruction_accounts(&predicate, accounts)
+        .map_err(|error| (None, error))?;
+
+    match predicate.program_id.as_ref() {
+        HELPER_AND_ID => {
+            let instructions: Vec<Instruction> = bincode::deserialize(&predicate.data)
+                .or(Err((None, ProgramError::from(SolarisAutoError::InvalidInstrAnd))))?;
+
+            for (i, instruction) in instructions.iter().enumerate() {
+                invoke_predicate(instruction, accounts)
+                    .map_err(|error| (Some(i as u8), error))?;
             }
-        })
+
+            Ok(())
+        },
+        // TODO: Find a way to create helper OR
+        /*
+        HELPER_OR_ID => {
+            // process_or(&predicate.data, accounts)
+        }*/
+        _ => {
+            invoke_predicate(&predicate, accounts)
+                .map_err(|error| (None, error))
+        }
+    }
 }
 
 fn invoke_predicate(
@@ -62,30 +114,19 @@ fn invoke_predicate(
         lending_healthfactor::PREDICATE_HEALTHFACTOR_ID => {
             lending_healthfactor::process_healthfactor(instr, accounts)
         },
+        lending_healthfactor::PREDICATE_RESERVE_POSITION_ID => {
+            lending_healthfactor::process_reserve_position(instr, accounts)
+        },
+        lending_reserve::PREDICATE_RESERVE_RATE_ID => {
+            lending_reserve::process_reserve_rate(instr, accounts)
+        },
+        call_and_compare::HELPER_CALL_AND_COMPARE_ID => {
+            call_and_compare::process_call_and_compare(instr, accounts)
+        },
         _ => invoke(instr, accounts)
     }
 }
 
-fn process_and<F>(
-    encoded_instr: &[u8],
-    accounts: &[AccountInfo],
-    invoke_predicate: F,
-) -> ProgramResult 
-    where F: Fn(&Instruction, &[AccountInfo]) -> ProgramResult {
-    let instructions: Vec<Instruction> = bincode::deserialize(encoded_instr)
-        .or(Err(ProgramError::from(SolarisAutoError::InvalidInstrAnd)))?;
-    
-    let instr_len = instructions.len();
-    for i in 0..instr_len {
-        if let Err(error) = invoke_predicate(&instructions[i], accounts) {
-            error.print::<SolarisAutoError>();
-            return Err(ProgramError::from(SolarisAutoError::PredicateAndFail))
-        }
-    }
-
-    Ok(())
-}
-
 fn process_or(
     encoded_instr: &[u8],
     accounts: &[AccountInfo],
@@ -116,10 +157,22 @@ mod tests {
 
     #[test]
     fn check_predicate() {
+        let program_id = Pubkey::new_unique();
         let some_pubkey = Pubkey::new_unique();
+        let other_pubkey = Pubkey::new_unique();
+        let owner = Pubkey::new_unique();
+        let mut lamports = [0u64; 3];
+        let mut data = [[0u8; 0]; 3];
+        let [program_lamports, some_lamports, other_lamports] = &mut lamports;
+        let [program_data, some_data, other_data] = &mut data;
+
+        let infos = [
+            AccountInfo::new(&program_id, false, false, program_lamports, program_data, &owner, false, 0),
+            AccountInfo::new(&some_pubkey, false, true, some_lamports, some_data, &owner, false, 0),
+        ];
 
         let instruction = bincode::serialize(&Instruction{
-            program_id: Pubkey::new_unique(),
+            program_id,
             accounts: vec![AccountMeta::new(some_pubkey, false)],
             data: vec![],
         }).unwrap();
@@ -128,19 +181,45 @@ mod tests {
         let invoke_plug_err = |_instr: &Instruction, _infos: &[AccountInfo]| Err(ProgramError::Custom(0));
 
         assert_eq!(
-            _check_predicate(&instruction, &[], invoke_plug_ok),
+            _check_predicate(&instruction, &infos, invoke_plug_ok),
             Ok(())
         );
 
         assert_eq!(
-            _check_predicate(&instruction, &[], invoke_plug_err),
+            _check_predicate(&instruction, &infos, invoke_plug_err),
             Err(ProgramError::Custom(0)),
         );
+
+        // Accounts that don't match signed instruction
+        let substituted_infos = [
+            infos[0].clone(),
+            AccountInfo::new(&other_pubkey, false, true, other_lamports, other_data, &owner, false, 0),
+        ];
+        let mut readonly_infos = infos.clone();
+        readonly_infos[1].is_writable = false;
+
+        for accounts in [&infos[..1], &substituted_infos[..], &readonly_infos[..]].iter() {
+            assert_eq!(
+                _check_predicate(&instruction, accounts, invoke_plug_ok),
+                Err(ProgramError::from(SolarisAutoError::InstructionAccountsMismatch)),
+            );
+        }
     }
 
     #[test]
     fn check_predicate_and() {
         let some_pubkey = Pubkey::new_unique();
+        let helper_and_id = Pubkey::new(HELPER_AND_ID);
+        let owner = Pubkey::new_unique();
+        let mut lamports = [0u64; 2];
+        let mut data = [[0u8; 0]; 2];
+        let [program_lamports, some_lamports] = &mut lamports;
+        let [program_data, some_data] = &mut data;
+
+        let infos = [
+            AccountInfo::new(&helper_and_id, false, false, program_lamports, program_data, &owner, false, 0),
+            AccountInfo::new(&some_pubkey, false, true, some_lamports, some_data, &owner, false, 0),
+        ];
 
         let instructions_and = bincode::serialize(&vec![
             Instruction{
@@ -165,13 +244,62 @@ mod tests {
         let invoke_plug_err = |_instr: &Instruction, _infos: &[AccountInfo]| Err(ProgramError::Custom(0));
 
         assert_eq!(
-            _check_predicate(&instruction, &[], invoke_plug_ok),
+            _check_predicate(&instruction, &infos, invoke_plug_ok),
             Ok(())
         );
 
         assert_eq!(
-            _check_predicate(&instruction, &[], invoke_plug_err),
+            _check_predicate(&instruction, &infos, invoke_plug_err),
             Err(ProgramError::from(SolarisAutoError::PredicateAndFail)),
         );
     }
+
+    #[test]
+    fn evaluate_predicate_and() {
+        let helper_and_id = Pubkey::new(HELPER_AND_ID);
+        let owner = Pubkey::new_unique();
+        let mut lamports = 0;
+        let mut data = [0u8; 0];
+
+        let infos = [
+            AccountInfo::new(&helper_and_id, false, false, &mut lamports, &mut data, &owner, false, 0),
+        ];
+
+        let instructions_and = bincode::serialize(&vec![
+            Instruction{
+                program_id: Pubkey::new_unique(),
+                accounts: vec![],
+                data: vec![1],
+            },
+            Instruction{
+                program_id: Pubkey::new_unique(),
+                accounts: vec![],
+                data: vec![0],
+            }
+        ]).unwrap();
+
+        let instruction = bincode::serialize(&Instruction{
+            program_id: Pubkey::new(HELPER_AND_ID),
+            accounts: vec![],
+            data: instructions_and,
+        }).unwrap();
+
+        // Predicate returns Ok(()) only if the first byte of data is 1
+        let invoke_plug = |instr: &Instruction, _infos: &[AccountInfo]| {
+            match instr.data[0] {
+                1 => Ok(()),
+                _ => Err(ProgramError::Custom(0)),
+            }
+        };
+
+        assert_eq!(
+            _evaluate_predicate(&instruction, &infos, invoke_plug),
+            Err((1, ProgramError::Custom(0))),
+        );
+
+        assert_eq!(
+            _evaluate_predicate(&[0, 1], &infos, invoke_plug),
+            Err((0, ProgramError::from(SolarisAutoError::InvalidPredicateInst))),
+        );
+    }
 }
\ No newline at end of file
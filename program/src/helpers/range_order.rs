@@ -0,0 +1,318 @@
+use solana_program::{
+    account_info::AccountInfo,
+    instruction::Instruction,
+    program_error::ProgramError,
+    msg,
+};
+use byteorder::ByteOrder;
+use uint::construct_uint;
+
+use crate::error::SolarisAutoError;
+
+use super::lending_healthfactor::WAD;
+
+// Pubkey is "Cb5HkFFbWMSpVGHfB9uLZRZ89FeysjfH5rb62g3wmvMV"
+pub const HELPER_RANGE_ORDER_ID: &[u8] = &[172, 43, 39, 40, 212, 184, 2, 134, 253, 235, 172, 199, 48, 0, 246, 179, 88, 90, 0, 220, 253, 110, 233, 191, 117, 54, 198, 245, 39, 53, 195, 216];
+
+// U256 with 256 bits consisting of 4x64-bit words
+construct_uint! {
+    pub struct U256(4);
+}
+
+/// Built-in get [maker/taker] amount for range orders. Price (taker amount
+/// per one maker amount, scaled by WAD) grows linearly from `price_start`
+/// when nothing is filled to `price_end` when the whole `making_amount` is filled.
+/// Every fill pays the average price over its interval, so the result
+/// doesn't depend on how the order is split into fills.
+///
+/// Accounts required:
+///
+/// 0. `[]` Helper range order program id: Cb5HkFFbWMSpVGHfB9uLZRZ89FeysjfH5rb62g3wmvMV
+///
+/// Instruction data format is
+/// ```
+/// pub struct HelperRangeOrder {
+///     get_maker_amount: bool,
+///     making_amount: u64,
+///     price_start: u128,
+///     price_end: u128,
+///     swap_amount: u64, // swap_taker_amount if `get_maker_amount` == _true_
+///                       // swap_maker_amount if `get_maker_amount` == _false_
+///                       // appended by `invoke_get_amount`
+/// }
+/// ```
+pub fn process_range_order(
+    instr: &Instruction,
+    _accounts: &[AccountInfo],
+    remaining_maker_amount: u64,
+) -> Result<u64, ProgramError> {
+    if instr.data.len() < 49 {
+        return Err(SolarisAutoError::InvalidGetAmountInst.into())
+    }
+
+    let is_get_maker_amount = instr.data[0] != 0;
+    let making_amount = byteorder::LE::read_u64(&instr.data[1..9]);
+    let price_start = byteorder::LE::read_u128(&instr.data[9..25]);
+    let price_end = byteorder::LE::read_u128(&instr.data[25..41]);
+    let swap_amount = byteorder::LE::read_u64(&instr.data[41..49]);
+
+    let range = RangeOrder {
+        making_amount,
+        price_start,
+        price_end,
+    };
+
+    msg!("remaining_maker_amount is {}", remaining_maker_amount);
+
+    match is_get_maker_amount {
+        true => range.get_maker_amount(remaining_maker_amount, swap_amount),
+        false => range.get_taker_amount(remaining_maker_amount, swap_amount),
+    }
+}
+
+pub struct RangeOrder {
+    pub making_amount: u64,
+    pub price_start: u128,
+    pub price_end: u128,
+}
+
+impl RangeOrder {
+    /// Taker amount for `swap_maker_amount` filled after the current position:
+    /// integral of the price over [filled, filled + swap_maker_amount].
+    /// Rounded up in favour of the maker.
+    pub fn get_taker_amount(
+        &self,
+        remaining_maker_amount: u64,
+        swap_maker_amount: u64,
+    ) -> Result<u64, ProgramError> {
+        let (total, filled, price_delta) = self.unpack(remaining_maker_amount)?;
+        let swap_maker_amount = U256::from(swap_maker_amount);
+        let price_start = U256::from(self.price_start);
+
+        // swap * (2 * price_start * total + price_delta * (2 * filled + swap)) / (2 * total * WAD)
+        let numerator = filled
+            .checked_mul(U256::from(2))
+            .and_then(|value| value.checked_add(swap_maker_amount))
+            .and_then(|value| value.checked_mul(price_delta))
+            .and_then(|value| {
+                price_start
+                    .checked_mul(total)?
+                    .checked_mul(U256::from(2))?
+                    .checked_add(value)
+            })
+            .and_then(|value| value.checked_mul(swap_maker_amount))
+            .ok_or(ProgramError::from(SolarisAutoError::MathOverflow))?;
+        let denominator = total * U256::from(2) * U256::from(WAD);
+
+        to_u64(ceil_div(numerator, denominator)?)
+    }
+
+    /// Maker amount for `swap_taker_amount` paid at the current position.
+    /// Solves `get_taker_amount(f) == swap_taker_amount` for f:
+    /// f = 2 * swap * total * WAD / (p0 + sqrt(p0^2 + 2 * price_delta * total * WAD * swap)),
+    /// where p0 = price_start * total + price_delta * filled.
+    /// Square root is rounded up, so the result is rounded down in favour of the maker.
+    pub fn get_maker_amount(
+        &self,
+        remaining_maker_amount: u64,
+        swap_taker_amount: u64,
+    ) -> Result<u64, ProgramError> {
+        let (total, filled, price_delta) = self.unpack(remaining_maker_amount)?;
+        let swap_taker_amount = U256::from(swap_taker_amount);
+        let total_wad = total * U256::from(WAD);
+
+        let price_current = U256::from(self.price_start)
+            .checked_mul(total)
+            .and_then(|value| value.checked_add(price_delta.checked_mul(filled)?))
+            .ok_or(ProgramError::from(SolarisAutoError::MathOverflow))?;
+
+        let discriminant = price_current
+            .checked_mul(price_current)
+            .and_then(|value| {
+                price_delta
+                    .checked_mul(total_wad)?
+                    .checked_mul(swap_taker_amount)?
+                    .checked_mul(U256::from(2))?
+                    .checked_add(value)
+            })
+            .ok_or(ProgramError::from(SolarisAutoError::MathOverflow))?;
+
+        let numerator = swap_taker_amount
+            .checked_mul(total_wad)
+            .and_then(|value| value.checked_mul(U256::from(2)))
+            .ok_or(ProgramError::from(SolarisAutoError::MathOverflow))?;
+        let denominator = price_current
+            .checked_add(ceil_sqrt(discriminant))
+            .ok_or(ProgramError::from(SolarisAutoError::MathOverflow))?;
+
+        to_u64(
+            numerator
+                .checked_div(denominator)
+                .ok_or(ProgramError::from(SolarisAutoError::MathOverflow))?
+        )
+    }
+
+    fn unpack(
+        &self,
+        remaining_maker_amount: u64,
+    ) -> Result<(U256, U256, U256), ProgramError> {
+        if self.making_amount == 0 || self.price_end < self.price_start {
+            return Err(SolarisAutoError::InvalidGetAmountInst.into())
+        }
+
+        let filled = self.making_amount
+            .checked_sub(remaining_maker_amount)
+            .ok_or(ProgramError::from(SolarisAutoError::MathOverflow))?;
+
+        Ok((
+            U256::from(self.making_amount),
+            U256::from(filled),
+            U256::from(self.price_end - self.price_start),
+        ))
+    }
+}
+
+/// Integer square root rounded down, Newton's method
+pub fn integer_sqrt(value: U256) -> U256 {
+    if value < U256::from(4) {
+        return match value.is_zero() {
+            true => U256::zero(),
+            false => U256::one(),
+        }
+    }
+
+    let mut x = value;
+    let mut y = (value >> 1) + 1;
+    while y < x {
+        x = y;
+        y = (x + value / x) >> 1;
+    }
+
+    x
+}
+
+fn ceil_sqrt(value: U256) -> U256 {
+    let root = integer_sqrt(value);
+
+    match root * root == value {
+        true => root,
+        false => root + 1,
+    }
+}
+
+fn ceil_div(numerator: U256, denominator: U256) -> Result<U256, ProgramError> {
+    if denominator.is_zero() {
+        return Err(SolarisAutoError::MathOverflow.into())
+    }
+
+    let (quotient, remainder) = numerator.div_mod(denominator);
+
+    match remainder.is_zero() {
+        true => Ok(quotient),
+        false => Ok(quotient + 1),
+    }
+}
+
+fn to_u64(value: U256) -> Result<u64, ProgramError> {
+    if value > U256::from(u64::MAX) {
+        return Err(SolarisAutoError::MathOverflow.into())
+    }
+
+    Ok(value.as_u64())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::helpers::test_utils::Lcg;
+
+    fn range(making_amount: u64, price_start: u64, price_end: u64) -> RangeOrder {
+        RangeOrder {
+            making_amount,
+            price_start: price_start as u128 * WAD as u128,
+            price_end: price_end as u128 * WAD as u128,
+        }
+    }
+
+    #[test]
+    fn sqrt() {
+        for value in 0..10_000u64 {
+            let root = integer_sqrt(U256::from(value)).as_u64();
+
+            assert!(root * root <= value);
+            assert!((root + 1) * (root + 1) > value);
+        }
+
+        assert_eq!(integer_sqrt(U256::MAX), U256::from(u128::MAX));
+    }
+
+    #[test]
+    fn range_amounts() {
+        let order = range(100, 1, 3);
+
+        // Price 1 -> 2 on the first half and 2 -> 3 on the second
+        assert_eq!(order.get_taker_amount(100, 50), Ok(75));
+        assert_eq!(order.get_taker_amount(50, 50), Ok(125));
+        assert_eq!(order.get_taker_amount(100, 100), Ok(200));
+
+        assert_eq!(order.get_maker_amount(100, 75), Ok(50));
+        assert_eq!(order.get_maker_amount(50, 125), Ok(50));
+        assert_eq!(order.get_maker_amount(100, 200), Ok(100));
+
+        // Constant price
+        let order = range(100, 2, 2);
+        assert_eq!(order.get_taker_amount(70, 15), Ok(30));
+        assert_eq!(order.get_maker_amount(70, 31), Ok(15));
+
+        assert_eq!(
+            range(100, 3, 1).get_taker_amount(100, 50),
+            Err(ProgramError::from(SolarisAutoError::InvalidGetAmountInst)),
+        );
+        assert_eq!(
+            order.get_taker_amount(101, 1),
+            Err(ProgramError::from(SolarisAutoError::MathOverflow)),
+        );
+    }
+
+    #[test]
+    fn partial_fills_pay_average_price() {
+        let mut rng = Lcg(42);
+
+        for _ in 0..200 {
+            let making_amount = rng.next(1_000_000);
+            let price_start = rng.next(1_000);
+            let order = range(making_amount, price_start, price_start + rng.next(1_000));
+
+            let mut remaining = making_amount;
+            let mut total_taking = 0u128;
+            while remaining > 0 {
+                let making = match rng.next(2) {
+                    1 => rng.next(remaining),
+                    _ => {
+                        let taking = rng.next(1_000_000);
+                        let making = order.get_maker_amount(remaining, taking).unwrap();
+
+                        if making == 0 || making > remaining {
+                            continue
+                        }
+
+                        // Taker doesn't pay more than it asked for the filled amount
+                        assert!(order.get_taker_amount(remaining, making).unwrap() <= taking);
+
+                        making
+                    },
+                };
+
+                total_taking += order.get_taker_amount(remaining, making).unwrap() as u128;
+                remaining -= making;
+            }
+
+            // Never less than the integral of the price over the whole order
+            let exact = making_amount as u128
+                * (order.price_start + order.price_end)
+                / 2
+                / WAD as u128;
+            assert!(total_taking >= exact);
+        }
+    }
+}
@@ -0,0 +1,14 @@
+/// Deterministic pseudo random generator for tests, good enough to walk
+/// through lots of order ratios and fill sizes
+pub struct Lcg(pub u64);
+
+impl Lcg {
+    /// Next number in `1..=max`
+    pub fn next(&mut self, max: u64) -> u64 {
+        self.0 = self.0
+            .wrapping_mul(6364136223846793005)
+            .wrapping_add(1442695040888963407);
+
+        (self.0 >> 11) % max + 1
+    }
+}
@@ -11,7 +11,7 @@ use borsh::{
     BorshSchema,
 };
 
-use crate::state::OrderStage;
+use crate::state::{OrderStage, PriceGuard, SpendLimit};
 
 #[derive(BorshSchema, BorshSerialize, BorshDeserialize)]
 #[repr(C)]
@@ -22,8 +22,13 @@ pub struct Order {
     pub maker: Pubkey,
     pub making_amount: u64,
     pub taking_amount: u64,
+    pub get_maker_amount: Vec<u8>,
+    pub get_taker_amount: Vec<u8>,
     pub predicate: Vec<u8>,
+    pub post_condition: Vec<u8>,
     pub callback: Vec<u8>,
+    pub price_guard: Option<PriceGuard>,
+    pub spend_limits: Vec<SpendLimit>,
 }
 
 #[derive(BorshSchema, BorshSerialize, BorshDeserialize)]
@@ -32,8 +37,26 @@ pub struct FillOrderArgs {
     pub order: Option<Order>,
     pub making_amount: u64,
     pub taking_amount: u64,
+    pub get_maker_amount_infos_num: u8,
+    pub get_taker_amount_infos_num: u8,
     pub predicate_infos_num: u8,
+    pub post_condition_infos_num: u8,
     pub callback_infos_num: u8,
+    /// Only upload the order onchain without filling it.
+    /// Used for orders that don't fit into one transaction
+    pub upload_only: bool,
+    /// Taker interaction. Bincode serialized `Instruction`, empty if none
+    pub interaction: Vec<u8>,
+    pub interaction_infos_num: u8,
+}
+
+#[derive(BorshSchema, BorshSerialize, BorshDeserialize)]
+#[repr(C)]
+pub struct CheckPredicateArgs {
+    /// Predicate is taken from the onchain order if hash is provided
+    pub order_hash: Option<[u8; 32]>,
+    /// Raw predicate. Used if `order_hash` is None
+    pub predicate: Vec<u8>,
 }
 
 #[derive(BorshSchema, BorshSerialize, BorshDeserialize)]
@@ -48,15 +71,34 @@ pub enum SolarisAutoInstruction {
     /// 3. `[]` Sysvar instructions
     /// 4. `[writable]` Onchain order. Seeds: [prefix, onchain_order, order_hash]
     /// 5. `[]` system-program
+    /// 
+    /// If `upload_only` is set the order is only uploaded onchain
+    /// and no more accounts are expected. Otherwise the order is filled
+    /// in the same transaction. Callback and transfers
+    /// 
     /// 6.. Accounts that required by get_maker_amount instruction  
+    ///  .. Accounts that required by get_taker_amount instruction  
     ///  .. Accounts that required by predicate instruction
+    ///  .. Accounts that required by post condition instruction. Post condition
+    ///     is evaluated like predicate after callback and both transfers
+    ///  .. Accounts that required by callback instructions: callback program
+    ///     and its accounts for every callback in order. Net outflow from maker
+    ///     token accounts among them is limited by order spend limits, other
+    ///     token accounts the delegate can move can't lose tokens.
+    ///     Callback in the target health mode quotes the taking amount,
+    ///     nonzero taking amount of the taker is the maximum then
+    ///  .. Taker interaction program and its accounts, if `interaction` is set.
+    ///     Interaction runs after maker asset is sent to the taker and before
+    ///     taker asset is sent to the maker. Can't be used with callbacks
     /// 
-    /// OrderStage == Filled. Callback and transfers
+    /// Accounts of get amount, predicate and callback instructions must match
+    /// the signed account metas: pubkey, signer and writable flags
     /// 
     /// .. `[writable]` Taker token-account
     /// .. `[writable]` Maker token-account
     /// .. `[]` delegate
     /// .. `[]` spl-token
+    /// .. `[]` Pyth price account of the price guard, if order has it
     FillOrder(FillOrderArgs),
     ///
     /// 1
@@ -80,8 +122,10 @@ pub enum SolarisAutoInstruction {
     /// 13 `[]` Clock sysvar.
     /// 14 `[]` Token program id.
     /// 15 `[]` Source user liquidity token account
-    /// 16 `[]` Maker. Owner of the account 15
-    /// 15 `[]` Solend program
+    /// 16 `[signer, writable]` Maker. Owner of the account 15
+    /// 17 `[]` Solend program
+    /// 18 `[writable]` Maker position. Seeds: [prefix, solend_position, maker, obligation]
+    /// 19 `[]` system-program
     SolendProxyDepositReserveLiquidityAndObligationCollateral {
         liquidity_amount: u64,
     },
@@ -110,8 +154,12 @@ pub enum SolarisAutoInstruction {
     /// 13 `[signer]` Obligation owner.
     /// 14 `[]` Clock sysvar.
     /// 15 `[]` Token program id.
-    /// 16 `[optional, writable]` Host fee receiver account.
-    /// 17 `[]` Solend program
+    /// 16 `[]` Solend program
+    /// 17 `[signer, writable]` Maker. Borrow is added to the maker position,
+    ///     fails if the position becomes unhealthy. The obligation may hold
+    ///     only the collateral and the liquidity reserves above
+    /// 18 `[writable]` Maker position. Seeds: [prefix, solend_position, maker, obligation]
+    /// 19 `[]` system-program
     SolendProxyBorrowObligationLiquidity {
         liquidity_amount: u64,
     },
@@ -143,6 +191,108 @@ pub enum SolarisAutoInstruction {
     /// 1. `[writable]` PDA delegate. Seeds: ["solaris-automations", "delegate", bump]
     /// 2. `[]` system-program
     InitDelegate,
+    ///
+    /// 5
+    /// Read-only evaluation of predicate for keepers. Never fails on
+    /// failed predicate, result is set as return data in format
+    /// of `state::CheckPredicateResult`. Note that failed CPI into
+    /// external predicate program still aborts the transaction.
+    ///
+    /// Accounts expected:
+    ///
+    /// 0. `[]` Onchain order. Seeds: [prefix, onchain_order, order_hash]. Only if `order_hash` is provided
+    /// .. Accounts that required by predicate instruction
+    CheckPredicate(CheckPredicateArgs),
+    ///
+    /// 6
+    /// Create order buffer for orders that don't fit into one transaction
+    /// with the Ed25519 instruction. Buffer size is the header and `order_len` bytes.
+    ///
+    /// Accounts expected:
+    ///
+    /// 0. `[signer, writable]` Authority. Maker or taker
+    /// 1. `[writable]` Order buffer. Seeds: [prefix, order_buffer, authority]
+    /// 2. `[]` system-program
+    InitOrderBuffer {
+        order_len: u32,
+    },
+    ///
+    /// 7
+    /// Write part of the borsh serialized order into the order buffer at `offset`
+    ///
+    /// Accounts expected:
+    ///
+    /// 0. `[signer]` Authority
+    /// 1. `[writable]` Order buffer. Seeds: [prefix, order_buffer, authority]
+    WriteOrderBuffer {
+        offset: u32,
+        bytes: Vec<u8>,
+    },
+    ///
+    /// 8
+    /// Verify maker signature over the hash of the buffered order, create
+    /// onchain order ready to be filled and close the order buffer.
+    /// Ed25519 instruction must go right before this one.
+    ///
+    /// Accounts expected:
+    ///
+    /// 0. `[signer, writable]` Authority. Receives lamports of the closed buffer
+    /// 1. `[]` Sysvar instructions
+    /// 2. `[writable]` Order buffer. Seeds: [prefix, order_buffer, authority]
+    /// 3. `[writable]` Onchain order. Seeds: [prefix, onchain_order, order_hash]
+    /// 4. `[]` system-program
+    FinalizeOrder,
+    ///
+    /// 9
+    /// Repay the maker borrow in the delegate obligation, at most the borrow
+    /// of the maker position with interest. `u64::MAX` repays all of it
+    ///
+    /// Accounts expected:
+    ///
+    /// 0. `[signer]` Maker. Transfer authority of the source liquidity
+    /// 1. `[writable]` Maker position. Seeds: [prefix, solend_position, maker, obligation]
+    /// 2. `[]` Solend program
+    /// 3. `[writable]` Source liquidity token account of the maker.
+    /// 4. `[writable]` Repay reserve liquidity supply SPL Token account.
+    /// 5. `[writable]` Repay reserve account.
+    /// 6. `[writable]` Obligation account of the delegate.
+    /// 7. `[]` Lending market account.
+    /// 8. `[]` Clock sysvar.
+    /// 9. `[]` Token program id.
+    /// 10.. For every reserve of the obligation, deposits and then borrows:
+    ///     `[writable]` Reserve account, `[]` Pyth price, `[]` Switchboard price
+    SolendProxyRepayObligationLiquidity {
+        liquidity_amount: u64,
+    },
+    ///
+    /// 10
+    /// Withdraw the maker collateral from the delegate obligation and redeem it
+    /// into the maker token account. Collateral can't exceed the maker position
+    /// and the rest of the position must cover its borrows. `u64::MAX` withdraws
+    /// all collateral of the maker in the reserve
+    ///
+    /// Accounts expected:
+    ///
+    /// 0. `[signer]` Maker
+    /// 1. `[writable]` Maker position. Seeds: [prefix, solend_position, maker, obligation]
+    /// 2. `[]` Solend program
+    /// 3. `[writable]` Withdraw reserve collateral supply SPL Token account.
+    /// 4. `[writable]` Collateral token account of the delegate. Seeds: [prefix, collateral_ta, bump]
+    /// 5. `[writable]` Withdraw reserve account.
+    /// 6. `[writable]` Obligation account of the delegate.
+    /// 7. `[]` Lending market account.
+    /// 8. `[]` Derived lending market authority.
+    /// 9. `[writable]` Destination liquidity token account of the maker.
+    /// 10 `[writable]` Withdraw reserve collateral SPL Token mint.
+    /// 11 `[writable]` Withdraw reserve liquidity supply SPL Token account.
+    /// 12 `[]` PDA delegate. Seeds: ["solaris-automations", "delegate", bump]
+    /// 13 `[]` Clock sysvar.
+    /// 14 `[]` Token program id.
+    /// 15.. For every reserve of the obligation, deposits and then borrows:
+    ///     `[writable]` Reserve account, `[]` Pyth price, `[]` Switchboard price
+    SolendProxyWithdrawObligationCollateral {
+        collateral_amount: u64,
+    },
 }
 
 pub fn fill_order(
@@ -151,17 +301,23 @@ pub fn fill_order(
     taker: &Pubkey,
     onchain_order: &Pubkey, 
     delegate: &Pubkey,
-    predicate_accounts: &[Pubkey],
-    callback_accounts: &[Pubkey],
+    mut get_maker_amount_accounts: Vec<AccountMeta>,
+    mut get_taker_amount_accounts: Vec<AccountMeta>,
+    mut predicate_accounts: Vec<AccountMeta>,
+    mut post_condition_accounts: Vec<AccountMeta>,
+    mut callback_accounts: Vec<AccountMeta>,
+    mut interaction_accounts: Vec<AccountMeta>,
     taker_ta_taker_asset_account: &Pubkey,
     maker_ta_taker_asset_account: &Pubkey,
     taker_ta_maker_asset_account: &Pubkey,
     maker_ta_maker_asset_account: &Pubkey,
+    price_guard_oracle: Option<&Pubkey>,
 
     order: Option<Order>,
     making_amount: u64,
     taking_amount: u64,
     threshold_amount: u64,
+    interaction: Vec<u8>,
 
     order_stage: OrderStage,
 ) -> Instruction {
@@ -169,8 +325,14 @@ pub fn fill_order(
         order,
         making_amount,
         taking_amount,
+        get_maker_amount_infos_num: get_maker_amount_accounts.len() as u8,
+        get_taker_amount_infos_num: get_taker_amount_accounts.len() as u8,
         predicate_infos_num: predicate_accounts.len() as u8,
+        post_condition_infos_num: post_condition_accounts.len() as u8,
         callback_infos_num: callback_accounts.len() as u8,   
+        upload_only: matches!(order_stage, OrderStage::Create),
+        interaction,
+        interaction_infos_num: interaction_accounts.len() as u8,
     };
 
     let data = SolarisAutoInstruction::FillOrder(fill_order_args)
@@ -184,10 +346,12 @@ pub fn fill_order(
         AccountMeta::new_readonly(system_program::ID, false),
     ];
     
-    predicate_accounts.iter()
-        .for_each(|id| accounts.push(AccountMeta::new(*id, false)));
-    callback_accounts.iter()
-        .for_each(|id| accounts.push(AccountMeta::new(*id, false)));
+    accounts.append(&mut get_maker_amount_accounts);
+    accounts.append(&mut get_taker_amount_accounts);
+    accounts.append(&mut predicate_accounts);
+    accounts.append(&mut post_condition_accounts);
+    accounts.append(&mut callback_accounts);
+    accounts.append(&mut interaction_accounts);
 
     match order_stage {
         OrderStage::Create => {
@@ -199,6 +363,11 @@ pub fn fill_order(
             accounts.push(AccountMeta::new(*maker_ta_maker_asset_account, false));
             accounts.push(AccountMeta::new(*taker_ta_maker_asset_account, false));
             accounts.push(AccountMeta::new(*delegate, false));
+            accounts.push(AccountMeta::new_readonly(spl_token::id(), false));
+
+            if let Some(price_guard_oracle) = price_guard_oracle {
+                accounts.push(AccountMeta::new_readonly(*price_guard_oracle, false));
+            }
         }, 
         OrderStage::Closed => {
 
@@ -212,6 +381,36 @@ pub fn fill_order(
     }
 }
 
+pub fn check_predicate(
+    program_id: &Pubkey,
+    onchain_order: Option<&Pubkey>,
+    order_hash: Option<[u8; 32]>,
+    predicate: Vec<u8>,
+    predicate_accounts: Vec<AccountMeta>,
+) -> Instruction {
+    let check_predicate_args = CheckPredicateArgs {
+        order_hash,
+        predicate,
+    };
+
+    let data = SolarisAutoInstruction::CheckPredicate(check_predicate_args)
+        .try_to_vec().unwrap();
+
+    let mut accounts = vec![];
+
+    if let Some(onchain_order) = onchain_order {
+        accounts.push(AccountMeta::new_readonly(*onchain_order, false));
+    }
+
+    accounts.extend(predicate_accounts);
+
+    Instruction{
+        program_id: *program_id,
+        accounts,
+        data,
+    }
+}
+
 pub fn init_delegate(
     program_id: &Pubkey,
     payer: &Pubkey,
@@ -231,4 +430,169 @@ pub fn init_delegate(
         accounts,
         data,
     }
+}
+
+pub fn init_order_buffer(
+    program_id: &Pubkey,
+    authority: &Pubkey,
+    order_buffer: &Pubkey,
+    order_len: u32,
+) -> Instruction {
+    let data = SolarisAutoInstruction::InitOrderBuffer { order_len }
+        .try_to_vec().unwrap();
+
+    let accounts = vec![
+        AccountMeta::new(*authority, true),
+        AccountMeta::new(*order_buffer, false),
+        AccountMeta::new_readonly(system_program::id(), false),
+    ];
+
+    Instruction{
+        program_id: *program_id,
+        accounts,
+        data,
+    }
+}
+
+pub fn write_order_buffer(
+    program_id: &Pubkey,
+    authority: &Pubkey,
+    order_buffer: &Pubkey,
+    offset: u32,
+    bytes: Vec<u8>,
+) -> Instruction {
+    let data = SolarisAutoInstruction::WriteOrderBuffer { offset, bytes }
+        .try_to_vec().unwrap();
+
+    let accounts = vec![
+        AccountMeta::new_readonly(*authority, true),
+        AccountMeta::new(*order_buffer, false),
+    ];
+
+    Instruction{
+        program_id: *program_id,
+        accounts,
+        data,
+    }
+}
+
+pub fn finalize_order(
+    program_id: &Pubkey,
+    authority: &Pubkey,
+    order_buffer: &Pubkey,
+    onchain_order: &Pubkey,
+) -> Instruction {
+    let data = SolarisAutoInstruction::FinalizeOrder
+        .try_to_vec().unwrap();
+
+    let accounts = vec![
+        AccountMeta::new(*authority, true),
+        AccountMeta::new_readonly(sysvar::instructions::id(), false),
+        AccountMeta::new(*order_buffer, false),
+        AccountMeta::new(*onchain_order, false),
+        AccountMeta::new_readonly(system_program::id(), false),
+    ];
+
+    Instruction{
+        program_id: *program_id,
+        accounts,
+        data,
+    }
+}
+
+/// Refresh reserves are (reserve, pyth price, switchboard price) of every
+/// reserve of the obligation, deposits and then borrows
+pub fn solend_proxy_repay_obligation_liquidity(
+    program_id: &Pubkey,
+    maker: &Pubkey,
+    solend_position: &Pubkey,
+    solend_program: &Pubkey,
+    source_liquidity: &Pubkey,
+    repay_reserve_liquidity_supply: &Pubkey,
+    repay_reserve: &Pubkey,
+    obligation: &Pubkey,
+    lending_market: &Pubkey,
+    refresh_reserves: &[(Pubkey, Pubkey, Pubkey)],
+    liquidity_amount: u64,
+) -> Instruction {
+    let data = SolarisAutoInstruction::SolendProxyRepayObligationLiquidity { liquidity_amount }
+        .try_to_vec().unwrap();
+
+    let mut accounts = vec![
+        AccountMeta::new_readonly(*maker, true),
+        AccountMeta::new(*solend_position, false),
+        AccountMeta::new_readonly(*solend_program, false),
+        AccountMeta::new(*source_liquidity, false),
+        AccountMeta::new(*repay_reserve_liquidity_supply, false),
+        AccountMeta::new(*repay_reserve, false),
+        AccountMeta::new(*obligation, false),
+        AccountMeta::new_readonly(*lending_market, false),
+        AccountMeta::new_readonly(sysvar::clock::id(), false),
+        AccountMeta::new_readonly(spl_token::id(), false),
+    ];
+    accounts.extend(refresh_reserve_metas(refresh_reserves));
+
+    Instruction{
+        program_id: *program_id,
+        accounts,
+        data,
+    }
+}
+
+pub fn solend_proxy_withdraw_obligation_collateral(
+    program_id: &Pubkey,
+    maker: &Pubkey,
+    solend_position: &Pubkey,
+    solend_program: &Pubkey,
+    reserve_collateral_supply: &Pubkey,
+    collateral_ta: &Pubkey,
+    withdraw_reserve: &Pubkey,
+    obligation: &Pubkey,
+    lending_market: &Pubkey,
+    lending_market_authority: &Pubkey,
+    destination_liquidity: &Pubkey,
+    reserve_collateral_mint: &Pubkey,
+    reserve_liquidity_supply: &Pubkey,
+    delegate: &Pubkey,
+    refresh_reserves: &[(Pubkey, Pubkey, Pubkey)],
+    collateral_amount: u64,
+) -> Instruction {
+    let data = SolarisAutoInstruction::SolendProxyWithdrawObligationCollateral { collateral_amount }
+        .try_to_vec().unwrap();
+
+    let mut accounts = vec![
+        AccountMeta::new_readonly(*maker, true),
+        AccountMeta::new(*solend_position, false),
+        AccountMeta::new_readonly(*solend_program, false),
+        AccountMeta::new(*reserve_collateral_supply, false),
+        AccountMeta::new(*collateral_ta, false),
+        AccountMeta::new(*withdraw_reserve, false),
+        AccountMeta::new(*obligation, false),
+        AccountMeta::new_readonly(*lending_market, false),
+        AccountMeta::new_readonly(*lending_market_authority, false),
+        AccountMeta::new(*destination_liquidity, false),
+        AccountMeta::new(*reserve_collateral_mint, false),
+        AccountMeta::new(*reserve_liquidity_supply, false),
+        AccountMeta::new_readonly(*delegate, false),
+        AccountMeta::new_readonly(sysvar::clock::id(), false),
+        AccountMeta::new_readonly(spl_token::id(), false),
+    ];
+    accounts.extend(refresh_reserve_metas(refresh_reserves));
+
+    Instruction{
+        program_id: *program_id,
+        accounts,
+        data,
+    }
+}
+
+fn refresh_reserve_metas(refresh_reserves: &[(Pubkey, Pubkey, Pubkey)]) -> Vec<AccountMeta> {
+    refresh_reserves
+        .iter()
+        .flat_map(|(reserve, pyth_price, switchboard_price)| vec![
+            AccountMeta::new(*reserve, false),
+            AccountMeta::new_readonly(*pyth_price, false),
+            AccountMeta::new_readonly(*switchboard_price, false),
+        ])
+        .collect()
 }
\ No newline at end of file
@@ -1,49 +1,88 @@
+use std::cell::Cell;
 use solana_program::{
     account_info::{next_account_info, AccountInfo},
     entrypoint::ProgramResult,
-    program_error::ProgramError, 
-    program::{invoke, invoke_signed}, 
+    program_error::{ProgramError, PrintProgramError}, 
+    program::{invoke, invoke_signed, set_return_data}, 
     pubkey::Pubkey,
     system_instruction,
     msg,
     keccak,
     sysvar::Sysvar,
     rent::Rent,
-    instruction::{Instruction, AccountMeta},
 };
 use borsh::{
     BorshDeserialize,
     BorshSerialize,
 };
-use byteorder::{ByteOrder, LittleEndian};
 
 use crate::{
     helpers::{
-        predicate_helpers::check_predicate,
-        //get_amounts::process_get_amounts,
-        get_amounts::{get_maker_amount, get_taker_amount}, 
+        predicate_helpers::{check_predicate, evaluate_predicate},
+        get_amounts::{get_maker_amount, get_taker_amount, invoke_get_amount}, 
+        oracle_price::check_price_guard,
+        lending_healthfactor::Obligation,
+        lending_position::{
+            assert_solend_program,
+            assert_delegate_obligation,
+            load_solend_position,
+            get_refresh_reserves,
+            deposit_position_collateral,
+            withdraw_position_collateral,
+            accrue_position_interest,
+            borrow_position_liquidity,
+            get_position_borrowed_amount,
+            repay_position_liquidity,
+            check_position_health,
+        },
     },
     callbacks::{
-        process_callback::process_callback,
+        process_callback::{process_callback, quote_callback, CallbackContext},
+        interaction::process_interaction,
+        spend_limits::{get_callback_balances, check_spend_limits},
+        liquidation_protection::{refresh_obligation, to_u256},
     },
     instruction::{
         SolarisAutoInstruction,
+        Order,
         FillOrderArgs,
+        CheckPredicateArgs,
     },
     state::{
         Key,
         OnchainOrder,
+        OrderBuffer,
         OrderStage,
+        CheckPredicateResult,
+        SolendPosition,
         PREFIX,
         ONCHAIN_ORDER,
+        ORDER_BUFFER,
+        SOLEND_POSITION,
+        ORDER_BUFFER_HEADER_SIZE,
     },
     utils::{
         get_seeds_delegate,
+        get_delegate_id,
         get_seeds_collateral_ta,
         get_bump_onchain_order,
+        get_bump_order_buffer,
+        get_bump_solend_position,
         create_onchain_order,
+        create_order_buffer,
+        create_solend_position,
         create_collateral_token_account,
         solend_init_obligation,
+        solend_refresh_reserve,
+        solend_refresh_obligation,
+        solend_deposit_reserve_liquidity_and_obligation_collateral,
+        solend_borrow_obligation_liquidity,
+        solend_repay_obligation_liquidity,
+        solend_withdraw_obligation_collateral_and_redeem_reserve_collateral,
+        assert_owned_by,
+        assert_onchain_order,
+        assert_maker_token_account,
+        get_token_account_amount,
     },
     verify_sign::is_valid_signature,
     error::SolarisAutoError,
@@ -86,11 +125,46 @@ impl Processor {
                 msg!("Instruction: InitDelegate");
                 Self::process_init_delegate(program_id, accounts)
             }
+            SolarisAutoInstruction::CheckPredicate(args)
+            => {
+                msg!("Instruction: CheckPredicate");
+                Self::process_check_predicate(program_id, accounts, args)
+            }
+            SolarisAutoInstruction::InitOrderBuffer {
+                order_len,
+            } => {
+                msg!("Instruction: InitOrderBuffer");
+                Self::process_init_order_buffer(program_id, accounts, order_len)
+            }
+            SolarisAutoInstruction::WriteOrderBuffer {
+                offset,
+                bytes,
+            } => {
+                msg!("Instruction: WriteOrderBuffer");
+                Self::process_write_order_buffer(program_id, accounts, offset, bytes)
+            }
+            SolarisAutoInstruction::FinalizeOrder
+            => {
+                msg!("Instruction: FinalizeOrder");
+                Self::process_finalize_order(program_id, accounts)
+            }
+            SolarisAutoInstruction::SolendProxyRepayObligationLiquidity {
+                liquidity_amount,
+            } => {
+                msg!("Instruction: SolendProxyRepayObligationLiquidity");
+                Self::process_solend_proxy_repay_obligation_liquidity(program_id, accounts, liquidity_amount)
+            }
+            SolarisAutoInstruction::SolendProxyWithdrawObligationCollateral {
+                collateral_amount,
+            } => {
+                msg!("Instruction: SolendProxyWithdrawObligationCollateral");
+                Self::process_solend_proxy_withdraw_obligation_collateral(program_id, accounts, collateral_amount)
+            }
         }
     }
 
     pub fn process_fill_order(
-        _program_id: &Pubkey,
+        program_id: &Pubkey,
         accounts: &[AccountInfo],
         args: FillOrderArgs,
     ) -> ProgramResult {
@@ -108,58 +182,37 @@ impl Processor {
                     .ok_or(ProgramError::from(SolarisAutoError::OrderIsNone))?;
                 let order_hash = keccak::hash(&order.try_to_vec().unwrap());
 
-                is_valid_signature(maker_info.key, order_hash.as_ref(), sysvar_instr)?;
+                is_valid_signature(&order.maker, order_hash.as_ref(), sysvar_instr)?;
 
-                let sign_seeds_onchain_order = 
-                    [
-                        PREFIX.as_bytes(),
-                        ONCHAIN_ORDER.as_bytes(),
-                        order_hash.as_ref(),
-                        &[get_bump_onchain_order(order_hash.as_ref())]
-                    ];
-                
-                invoke_signed(
-                    &create_onchain_order(
-                        taker_info.key,
-                        onchain_order_info.key,
-                        &order,
-                    )?,
-                    &[
-                        taker_info.clone(),
-                        onchain_order_info.clone(),
-                        system_program_info.clone(),
-                    ],
-                    &[&sign_seeds_onchain_order],
-                );
+                Self::create_onchain_order_account(
+                    taker_info,
+                    onchain_order_info,
+                    system_program_info,
+                    &order,
+                    order_hash.as_ref(),
+                )?;
 
-                let temp_stage = match order.callback.is_empty() {
-                    true => {
-                        OrderStage::Filled
-                    }, 
-                    false => {
-                        OrderStage::Create
-                    }
+                let temp_stage = match args.upload_only {
+                    true => OrderStage::Create,
+                    false => OrderStage::Filled,
                 };
                 
-                OnchainOrder {
-                    key: Key::OnchainOrder,
-                    order_hash: order_hash.to_bytes(),
-                    making_amount: order.making_amount,
-                    taking_amount: order.taking_amount,
-                    remaining_maker_amount: order.making_amount,
-                    predicate: order.predicate,
-                    callback: order.callback,
-                    stage: temp_stage,
-                }
-                
+                OnchainOrder::new(order_hash.to_bytes(), order, temp_stage)
             },
             false => {
-                // TODO: Validate onchain_order as PDA with seeds [prefix, order, order_hash]
-                // TODO: assert_is_owner(program_id, onchain_order_info)?;
+                assert_owned_by(onchain_order_info, program_id)?;
 
-                OnchainOrder::from_account_info(onchain_order_info)?
+                let onchain_order = OnchainOrder::from_account_info(onchain_order_info)?;
+                assert_onchain_order(onchain_order_info, &onchain_order.order_hash)?;
+
+                onchain_order
             }
         };
+
+        // Maker accounts of callbacks and spend limits are decided by the signed maker
+        if *maker_info.key != onchain_order.maker {
+            return Err(SolarisAutoError::InvalidMaker.into())
+        }
     
         match onchain_order.stage {
             OrderStage::Create => {
@@ -168,18 +221,42 @@ impl Processor {
                 onchain_order.serialize(&mut *onchain_order_info.data.borrow_mut())?;
             },
             OrderStage::Filled => {
+                let get_maker_amount_infos: Vec<AccountInfo> = 
+                    account_info_iter
+                        .take(args.get_maker_amount_infos_num as usize)
+                        .cloned()
+                        .collect();
+
+                let get_taker_amount_infos: Vec<AccountInfo> = 
+                    account_info_iter
+                        .take(args.get_taker_amount_infos_num as usize)
+                        .cloned()
+                        .collect();
+
                 let predicate_infos: Vec<AccountInfo> = 
                     account_info_iter
                         .take(args.predicate_infos_num as usize)
                         .cloned()
                         .collect();
 
+                let post_condition_infos: Vec<AccountInfo> = 
+                    account_info_iter
+                        .take(args.post_condition_infos_num as usize)
+                        .cloned()
+                        .collect();
+
                 let callback_infos: Vec<AccountInfo> =
                     account_info_iter
                         .take(args.callback_infos_num as usize)
                         .cloned()
                         .collect();
 
+                let interaction_infos: Vec<AccountInfo> =
+                    account_info_iter
+                        .take(args.interaction_infos_num as usize)
+                        .cloned()
+                        .collect();
+
                 let taker_ta_taker_asset_info = next_account_info(account_info_iter)?;
                 let maker_ta_taker_asset_info = next_account_info(account_info_iter)?;
 
@@ -194,43 +271,90 @@ impl Processor {
                     check_predicate(&onchain_order.predicate, &predicate_infos[..])?;
                 }   
 
+                // Callback in the target health mode quotes the taking amount instead of the taker
+                let quoted_taking_amount = match onchain_order.callback.is_empty() {
+                    true => None,
+                    false => quote_callback(
+                        &onchain_order.callback,
+                        &callback_infos,
+                        &onchain_order.maker,
+                        onchain_order.making_amount,
+                        onchain_order.taking_amount,
+                    )?,
+                };
+
+                let (args_making_amount, args_taking_amount) = match quoted_taking_amount {
+                    // Nonzero taking amount of the taker is the maximum it pays
+                    Some(taking_amount) if args.taking_amount != 0 && taking_amount > args.taking_amount => {
+                        return Err(SolarisAutoError::QuotedTakingAmountTooHigh.into())
+                    },
+                    Some(taking_amount) => (0, taking_amount),
+                    None => (args.making_amount, args.taking_amount),
+                };
+
                 // TODO: check that args.making_amount != args.taking_amount != 0
-                let (taking_amount, making_amount) = match args.making_amount { 
+                let (taking_amount, making_amount) = match args_making_amount { 
                     0 => {
                         // I'm a taker
-                        let making_amount = get_maker_amount(
-                            onchain_order.making_amount,
-                            onchain_order.taking_amount,
-                            args.taking_amount,
-                        );
+                        let making_amount = match onchain_order.get_maker_amount.is_empty() {
+                            true => get_maker_amount(
+                                onchain_order.making_amount,
+                                onchain_order.taking_amount,
+                                args_taking_amount,
+                            )?,
+                            false => invoke_get_amount(
+                                &onchain_order.get_maker_amount,
+                                args_taking_amount,
+                                onchain_order.remaining_maker_amount,
+                                &get_maker_amount_infos,
+                            )?,
+                        };
 
                         msg!("making_amount is {}", making_amount);
 
-                        // TODO: if making_amount > onchain_order.remaining_maker_amount
+                        if making_amount > onchain_order.remaining_maker_amount {
+                            return Err(SolarisAutoError::NotEnoughRemainingMakerAmount.into())
+                        }
 
-                        (args.taking_amount, making_amount)
+                        (args_taking_amount, making_amount)
                     },
                     _ => {
                         
                         // I'm a maker
                         let making_amount = 
-                            match args.making_amount > onchain_order.remaining_maker_amount {
+                            match args_making_amount > onchain_order.remaining_maker_amount {
                                 true => onchain_order.remaining_maker_amount,
-                                false => args.making_amount
+                                false => args_making_amount
+                        };
+
+                        let taking_amount = match onchain_order.get_taker_amount.is_empty() {
+                            true => get_taker_amount(
+                                onchain_order.making_amount,
+                                onchain_order.taking_amount,
+                                making_amount,
+                            )?,
+                            false => invoke_get_amount(
+                                &onchain_order.get_taker_amount,
+                                making_amount,
+                                onchain_order.remaining_maker_amount,
+                                &get_taker_amount_infos,
+                            )?,
                         };
 
-                        let taking_amount = get_taker_amount(
-                            onchain_order.making_amount,
-                            onchain_order.taking_amount,
-                            making_amount,
-                        );
+                        msg!("taking_amount is {}", taking_amount);
 
                         (taking_amount, making_amount)
                     }
                 };
+
+                if let Some(price_guard) = &onchain_order.price_guard {
+                    let pyth_price_info = next_account_info(account_info_iter)?;
+
+                    check_price_guard(price_guard, pyth_price_info, making_amount, taking_amount)?;
+                }
                 
                 // Taker => Maker
-                invoke(
+                let transfer_taking_amount = || invoke(
                     &spl_token::instruction::transfer(
                         &spl_token::id(),
                         taker_ta_taker_asset_info.key,
@@ -245,15 +369,10 @@ impl Processor {
                         taker_info.clone(),
                         token_program.clone(),
                     ],
-                )?;
-
-                // Assert accounts match with onchain_order.accounts
-                if !onchain_order.callback.is_empty() {
-                    process_callback(&onchain_order.callback, &callback_infos)?;
-                }
+                );
 
                 // Maker => Taker
-                invoke_signed(
+                let transfer_making_amount = || invoke_signed(
                     &spl_token::instruction::transfer(
                         &spl_token::id(),   
                         maker_ta_maker_asset_info.key,
@@ -269,11 +388,81 @@ impl Processor {
                         token_program.clone(),
                     ],
                     &[&get_seeds_delegate()],
-                )?;
+                );
 
-                onchain_order.stage = OrderStage::Closed;
-                
-                //onchain_order.serialize(&mut *onchain_order_info.data.borrow_mut())?;
+                match args.interaction.is_empty() {
+                    true => {
+                        transfer_taking_amount()?;
+
+                        if !onchain_order.callback.is_empty() {
+                            let callback_balances = get_callback_balances(
+                                &onchain_order.maker,
+                                &get_delegate_id()?,
+                                &callback_infos,
+                            );
+
+                            let callback_context = CallbackContext {
+                                maker: onchain_order.maker,
+                                making_amount,
+                                taking_amount,
+                                topped_up_amount: Cell::new(onchain_order.topped_up_amount),
+                            };
+
+                            process_callback(&onchain_order.callback, &callback_infos, &callback_context)?;
+
+                            onchain_order.topped_up_amount = callback_context.topped_up_amount.get();
+
+                            check_spend_limits(&onchain_order.spend_limits, &callback_balances, &callback_infos)?;
+                        }
+
+                        transfer_making_amount()?;
+                    },
+                    false => {
+                        if !onchain_order.callback.is_empty() {
+                            return Err(SolarisAutoError::InteractionWithCallback.into())
+                        }
+
+                        // Received amount is checked on the maker's own account
+                        assert_maker_token_account(maker_ta_taker_asset_info, &onchain_order.maker)?;
+
+                        let maker_taker_asset_before = get_token_account_amount(maker_ta_taker_asset_info)?;
+
+                        // Taker gets maker asset first and sources taker asset in the interaction
+                        transfer_making_amount()?;
+                        process_interaction(&args.interaction, &interaction_infos)?;
+                        transfer_taking_amount()?;
+
+                        let maker_taker_asset_after = get_token_account_amount(maker_ta_taker_asset_info)?;
+
+                        let is_received = maker_taker_asset_after
+                            .checked_sub(maker_taker_asset_before)
+                            .map_or(false, |received| received >= taking_amount);
+
+                        if !is_received {
+                            return Err(SolarisAutoError::NotEnoughTakerAmountReceived.into())
+                        }
+                    },
+                }
+
+                if !onchain_order.post_condition.is_empty() {
+                    check_predicate(&onchain_order.post_condition, &post_condition_infos[..])
+                        .map_err(|error| {
+                            msg!("Post condition failed");
+                            error
+                        })?;
+                }
+
+                onchain_order.remaining_maker_amount = onchain_order.remaining_maker_amount
+                    .checked_sub(making_amount)
+                    .ok_or(ProgramError::from(SolarisAutoError::MathOverflow))?;
+
+                msg!("remaining_maker_amount is {}", onchain_order.remaining_maker_amount);
+
+                if onchain_order.remaining_maker_amount == 0 {
+                    onchain_order.stage = OrderStage::Closed;
+                }
+
+                onchain_order.serialize(&mut *onchain_order_info.data.borrow_mut())?;
             },
             OrderStage::Closed => {
                 return Err(SolarisAutoError::OrderClosed.into())
@@ -283,9 +472,223 @@ impl Processor {
         Ok(())
     }
 
-    pub fn process_solend_proxy_deposit_reserve_liquidity_and_obligation_collateral(
+    fn create_onchain_order_account<'a>(
+        payer_info: &AccountInfo<'a>,
+        onchain_order_info: &AccountInfo<'a>,
+        system_program_info: &AccountInfo<'a>,
+        order: &Order,
+        order_hash: &[u8],
+    ) -> ProgramResult {
+        let sign_seeds_onchain_order = 
+            [
+                PREFIX.as_bytes(),
+                ONCHAIN_ORDER.as_bytes(),
+                order_hash,
+                &[get_bump_onchain_order(order_hash)]
+            ];
+        
+        invoke_signed(
+            &create_onchain_order(
+                payer_info.key,
+                onchain_order_info.key,
+                order,
+            )?,
+            &[
+                payer_info.clone(),
+                onchain_order_info.clone(),
+                system_program_info.clone(),
+            ],
+            &[&sign_seeds_onchain_order],
+        )
+    }
+
+    pub fn process_init_order_buffer(
         _program_id: &Pubkey,
         accounts: &[AccountInfo],
+        order_len: u32,
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+
+        let authority_info = next_account_info(account_info_iter)?;
+        let order_buffer_info = next_account_info(account_info_iter)?;
+        let system_program_info = next_account_info(account_info_iter)?;
+
+        let sign_seeds_order_buffer = 
+            [
+                PREFIX.as_bytes(),
+                ORDER_BUFFER.as_bytes(),
+                authority_info.key.as_ref(),
+                &[get_bump_order_buffer(authority_info.key)]
+            ];
+
+        invoke_signed(
+            &create_order_buffer(
+                authority_info.key,
+                order_buffer_info.key,
+                order_len,
+            )?,
+            &[
+                authority_info.clone(),
+                order_buffer_info.clone(),
+                system_program_info.clone(),
+            ],
+            &[&sign_seeds_order_buffer],
+        )?;
+
+        OrderBuffer {
+            key: Key::OrderBuffer,
+            authority: *authority_info.key,
+            order_len,
+        }.serialize(&mut *order_buffer_info.data.borrow_mut())?;
+
+        Ok(())
+    }
+
+    pub fn process_write_order_buffer(
+        program_id: &Pubkey,
+        accounts: &[AccountInfo],
+        offset: u32,
+        bytes: Vec<u8>,
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+
+        let authority_info = next_account_info(account_info_iter)?;
+        let order_buffer_info = next_account_info(account_info_iter)?;
+
+        let order_buffer = Self::get_order_buffer(program_id, authority_info, order_buffer_info)?;
+
+        let end = (offset as usize)
+            .checked_add(bytes.len())
+            .filter(|end| *end <= order_buffer.order_len as usize)
+            .ok_or(ProgramError::from(SolarisAutoError::OrderBufferOverflow))?;
+
+        order_buffer_info.data.borrow_mut()
+            [ORDER_BUFFER_HEADER_SIZE + offset as usize..ORDER_BUFFER_HEADER_SIZE + end]
+            .copy_from_slice(&bytes);
+
+        Ok(())
+    }
+
+    pub fn process_finalize_order(
+        program_id: &Pubkey,
+        accounts: &[AccountInfo],
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+
+        let authority_info = next_account_info(account_info_iter)?;
+        let sysvar_instr = next_account_info(account_info_iter)?;
+        let order_buffer_info = next_account_info(account_info_iter)?;
+        let onchain_order_info = next_account_info(account_info_iter)?;
+        let system_program_info = next_account_info(account_info_iter)?;
+
+        let order_buffer = Self::get_order_buffer(program_id, authority_info, order_buffer_info)?;
+
+        let order = Order::try_from_slice(
+            &order_buffer_info.data.borrow()
+                [ORDER_BUFFER_HEADER_SIZE..ORDER_BUFFER_HEADER_SIZE + order_buffer.order_len as usize],
+        ).or(Err(ProgramError::from(SolarisAutoError::InvalidOrderBuffer)))?;
+        let order_hash = keccak::hash(&order.try_to_vec().unwrap());
+
+        is_valid_signature(&order.maker, order_hash.as_ref(), sysvar_instr)?;
+
+        Self::create_onchain_order_account(
+            authority_info,
+            onchain_order_info,
+            system_program_info,
+            &order,
+            order_hash.as_ref(),
+        )?;
+
+        OnchainOrder::new(order_hash.to_bytes(), order, OrderStage::Filled)
+            .serialize(&mut *onchain_order_info.data.borrow_mut())?;
+
+        // Close order buffer
+        let authority_lamports = authority_info.lamports()
+            .checked_add(order_buffer_info.lamports())
+            .ok_or(ProgramError::from(SolarisAutoError::MathOverflow))?;
+
+        **authority_info.lamports.borrow_mut() = authority_lamports;
+        **order_buffer_info.lamports.borrow_mut() = 0;
+        order_buffer_info.data.borrow_mut().fill(0);
+
+        Ok(())
+    }
+
+    fn get_order_buffer(
+        program_id: &Pubkey,
+        authority_info: &AccountInfo,
+        order_buffer_info: &AccountInfo,
+    ) -> Result<OrderBuffer, ProgramError> {
+        if !authority_info.is_signer {
+            return Err(ProgramError::MissingRequiredSignature)
+        }
+
+        assert_owned_by(order_buffer_info, program_id)?;
+
+        let order_buffer = OrderBuffer::from_account_info(order_buffer_info)?;
+
+        if order_buffer.authority != *authority_info.key {
+            return Err(SolarisAutoError::InvalidOrderBuffer.into())
+        }
+
+        Ok(order_buffer)
+    }
+
+    pub fn process_check_predicate(
+        program_id: &Pubkey,
+        accounts: &[AccountInfo],
+        args: CheckPredicateArgs,
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+
+        let predicate = match args.order_hash {
+            Some(order_hash) => {
+                let onchain_order_info = next_account_info(account_info_iter)?;
+
+                assert_owned_by(onchain_order_info, program_id)?;
+                assert_onchain_order(onchain_order_info, &order_hash)?;
+
+                OnchainOrder::from_account_info(onchain_order_info)?.predicate
+            },
+            None => args.predicate,
+        };
+
+        let predicate_infos: Vec<AccountInfo> = 
+            account_info_iter
+                .cloned()
+                .collect();
+
+        let result = match predicate.is_empty() {
+            true => Ok(()),
+            false => evaluate_predicate(&predicate, &predicate_infos[..]),
+        };
+
+        let check_predicate_result = match result {
+            Ok(()) => CheckPredicateResult {
+                is_passed: true,
+                failed_predicate_index: None,
+                error: None,
+            },
+            Err((index, error)) => {
+                msg!("Predicate {} failed", index);
+                error.print::<SolarisAutoError>();
+
+                CheckPredicateResult {
+                    is_passed: false,
+                    failed_predicate_index: Some(index),
+                    error: Some(u64::from(error)),
+                }
+            }
+        };
+
+        set_return_data(&check_predicate_result.try_to_vec()?);
+
+        Ok(())
+    }
+
+    pub fn process_solend_proxy_deposit_reserve_liquidity_and_obligation_collateral(
+        program_id: &Pubkey,
+        accounts: &[AccountInfo],
         liquidity_amount: u64,
     ) -> ProgramResult {
         let account_info_iter = &mut accounts.iter();
@@ -300,9 +703,26 @@ impl Processor {
         let source_user_liquidity_ta = next_account_info(account_info_iter)?;
         let maker = next_account_info(account_info_iter)?;
         let solend_program = next_account_info(account_info_iter)?;
+        let solend_position_info = next_account_info(account_info_iter)?;
+        let system_program_info = next_account_info(account_info_iter)?;
         
         solend_deposit_infos.push(token_program.clone()); // 14
 
+        let reserve_id = *solend_deposit_infos[2].key;
+        let obligation_info = solend_deposit_infos[8].clone();
+
+        assert_delegate_obligation(solend_program, &obligation_info)?;
+
+        let mut solend_position = Self::get_or_create_solend_position(
+            program_id,
+            maker,
+            solend_position_info,
+            system_program_info,
+            obligation_info.key,
+        )?;
+
+        let deposited_amount_before = Self::get_obligation_deposited_amount(&obligation_info, &reserve_id)?;
+
         invoke(
             &spl_token::instruction::transfer(
                 token_program.key,
@@ -320,31 +740,23 @@ impl Processor {
             ],
         )?;
 
-        let mut data = [0; 9];
-        LittleEndian::write_u64(&mut data[1..9], liquidity_amount);
-        data[0] = 14;
- 
-        let solend_deposit = Instruction {
-            program_id: *solend_program.key,
-            accounts: vec![
-                AccountMeta::new(*solend_deposit_infos[0].key, false),
-                AccountMeta::new(*solend_deposit_infos[1].key, false),
-                AccountMeta::new(*solend_deposit_infos[2].key, false),
-                AccountMeta::new(*solend_deposit_infos[3].key, false),
-                AccountMeta::new(*solend_deposit_infos[4].key, false),
-                AccountMeta::new(*solend_deposit_infos[5].key, false),
-                AccountMeta::new_readonly(*solend_deposit_infos[6].key, false),
-                AccountMeta::new(*solend_deposit_infos[7].key, false),
-                AccountMeta::new(*solend_deposit_infos[8].key, false),
-                AccountMeta::new(*solend_deposit_infos[9].key, true),
-                AccountMeta::new_readonly(*solend_deposit_infos[10].key, false),
-                AccountMeta::new_readonly(*solend_deposit_infos[11].key, false),
-                AccountMeta::new(*solend_deposit_infos[12].key, true),
-                AccountMeta::new_readonly(*solend_deposit_infos[13].key, false),
-                AccountMeta::new_readonly(*solend_deposit_infos[14].key, false),
-            ],
-            data: data.to_vec(),
-        };        
+        let solend_deposit = solend_deposit_reserve_liquidity_and_obligation_collateral(
+            solend_program.key,
+            solend_deposit_infos[0].key,
+            solend_deposit_infos[1].key,
+            solend_deposit_infos[2].key,
+            solend_deposit_infos[3].key,
+            solend_deposit_infos[4].key,
+            solend_deposit_infos[5].key,
+            solend_deposit_infos[6].key,
+            solend_deposit_infos[7].key,
+            solend_deposit_infos[8].key,
+            solend_deposit_infos[9].key,
+            solend_deposit_infos[10].key,
+            solend_deposit_infos[11].key,
+            solend_deposit_infos[12].key,
+            liquidity_amount,
+        );
 
         invoke_signed(
             &solend_deposit,
@@ -352,11 +764,19 @@ impl Processor {
             &[&get_seeds_delegate()],
         )?;
 
+        let collateral_amount = Self::get_obligation_deposited_amount(&obligation_info, &reserve_id)?
+            .checked_sub(deposited_amount_before)
+            .ok_or(ProgramError::from(SolarisAutoError::MathOverflow))?;
+
+        deposit_position_collateral(&mut solend_position, &reserve_id, collateral_amount)?;
+
+        solend_position.serialize(&mut *solend_position_info.data.borrow_mut())?;
+
         Ok(())
     }
 
     pub fn process_solend_proxy_borrow_obligation_liquidity(
-        _program_id: &Pubkey,
+        program_id: &Pubkey,
         accounts: &[AccountInfo],
         liquidity_amount: u64,
     ) -> ProgramResult {
@@ -384,6 +804,22 @@ impl Processor {
         let clock = next_account_info(account_info_iter)?;
         let token_program = next_account_info(account_info_iter)?;
         let solend_program = next_account_info(account_info_iter)?;
+        let maker = next_account_info(account_info_iter)?;
+        let solend_position_info = next_account_info(account_info_iter)?;
+        let system_program_info = next_account_info(account_info_iter)?;
+
+        let borrow_reserve_id = *solend_borrow_infos[2].key;
+        let obligation_info = solend_borrow_infos[4].clone();
+
+        assert_delegate_obligation(solend_program, &obligation_info)?;
+
+        let mut solend_position = Self::get_or_create_solend_position(
+            program_id,
+            maker,
+            solend_position_info,
+            system_program_info,
+            obligation_info.key,
+        )?;
 
         solend_refresh_reserve_collateral_infos.push(clock.clone()); // 3
         solend_refresh_reserve_liquidity_infos.push(clock.clone()); // 3
@@ -392,67 +828,49 @@ impl Processor {
         solend_borrow_infos.push(clock.clone()); // 8
         solend_borrow_infos.push(token_program.clone()); // 9
 
-        let refresh_reserve_collateral = Instruction {
-            program_id: *solend_program.key,
-            accounts: vec![
-                AccountMeta::new(*solend_refresh_reserve_collateral_infos[0].key, false),
-                AccountMeta::new_readonly(*solend_refresh_reserve_collateral_infos[1].key, false),
-                AccountMeta::new_readonly(*solend_refresh_reserve_collateral_infos[2].key, false),
-                AccountMeta::new_readonly(*clock.key, false),
-            ],
-            data: vec![3],  
-        };
+        let refresh_reserve_collateral = solend_refresh_reserve(
+            solend_program.key,
+            solend_refresh_reserve_collateral_infos[0].key,
+            solend_refresh_reserve_collateral_infos[1].key,
+            solend_refresh_reserve_collateral_infos[2].key,
+        );
 
-        let refresh_reserve_liquidity = Instruction {
-            program_id: *solend_program.key,
-            accounts: vec![
-                AccountMeta::new(*solend_refresh_reserve_liquidity_infos[0].key, false),
-                AccountMeta::new_readonly(*solend_refresh_reserve_liquidity_infos[1].key, false),
-                AccountMeta::new_readonly(*solend_refresh_reserve_liquidity_infos[2].key, false),
-                AccountMeta::new_readonly(*clock.key, false),
-            ],
-            data: vec![3],  
-        };
+        let refresh_reserve_liquidity = solend_refresh_reserve(
+            solend_program.key,
+            solend_refresh_reserve_liquidity_infos[0].key,
+            solend_refresh_reserve_liquidity_infos[1].key,
+            solend_refresh_reserve_liquidity_infos[2].key,
+        );
 
         // TODO: dont' know. It works. All questions for the token-lending program
-        let mut solend_refresh_obligation_infos = vec![
+        let solend_refresh_obligation_infos = vec![
             solend_borrow_infos[4].clone(),
             clock.clone(),
             solend_refresh_reserve_liquidity_infos[0].clone(),
             solend_refresh_reserve_liquidity_infos[0].clone(),
         ];
 
-        let refresh_obligation = Instruction {
-            program_id: *solend_program.key,
-            accounts: vec![
-                AccountMeta::new(*solend_refresh_obligation_infos[0].key, false),
-                AccountMeta::new_readonly(*solend_refresh_obligation_infos[1].key, false),
-                AccountMeta::new_readonly(*solend_refresh_obligation_infos[2].key, false),
-                AccountMeta::new_readonly(*solend_refresh_obligation_infos[3].key, false),
+        let refresh_obligation = solend_refresh_obligation(
+            solend_program.key,
+            solend_refresh_obligation_infos[0].key,
+            &[
+                *solend_refresh_obligation_infos[2].key,
+                *solend_refresh_obligation_infos[3].key,
             ],
-            data: vec![7],
-        };
+        );
 
-        let mut data = [0; 9];
-        LittleEndian::write_u64(&mut data[1..9], liquidity_amount);
-        data[0] = 10;
-
-        let solend_borrow = Instruction {
-            program_id: *solend_program.key,
-            accounts: vec![
-                AccountMeta::new(*solend_borrow_infos[0].key, false),
-                AccountMeta::new(*solend_borrow_infos[1].key, false),
-                AccountMeta::new(*solend_borrow_infos[2].key, false),
-                AccountMeta::new(*solend_borrow_infos[3].key, false),
-                AccountMeta::new(*solend_borrow_infos[4].key, false),
-                AccountMeta::new_readonly(*solend_borrow_infos[5].key, false),
-                AccountMeta::new_readonly(*solend_borrow_infos[6].key, false),
-                AccountMeta::new(*solend_borrow_infos[7].key, true),
-                AccountMeta::new_readonly(*solend_borrow_infos[8].key, false),
-                AccountMeta::new_readonly(*solend_borrow_infos[9].key, false),
-            ],
-            data: data.to_vec(),
-        };
+        let solend_borrow = solend_borrow_obligation_liquidity(
+            solend_program.key,
+            solend_borrow_infos[0].key,
+            solend_borrow_infos[1].key,
+            solend_borrow_infos[2].key,
+            solend_borrow_infos[3].key,
+            solend_borrow_infos[4].key,
+            solend_borrow_infos[5].key,
+            solend_borrow_infos[6].key,
+            solend_borrow_infos[7].key,
+            liquidity_amount,
+        );
 
         invoke(
             &refresh_reserve_collateral,
@@ -469,15 +887,283 @@ impl Processor {
             &solend_refresh_obligation_infos,
         )?;
 
+        let obligation = Obligation::unpack(&obligation_info.data.borrow())?;
+        accrue_position_interest(&mut solend_position, &obligation)?;
+
+        let borrowed_amount_wads_before = obligation
+            .find_liquidity(&borrow_reserve_id)
+            .map(|liquidity| to_u256(&liquidity.borrowed_amount_wads))
+            .unwrap_or_default();
+
         invoke_signed(
             &solend_borrow,
             &solend_borrow_infos,
             &[&get_seeds_delegate()],
         )?;
 
+        // Borrowed amount includes the borrow fee
+        let obligation = Obligation::unpack(&obligation_info.data.borrow())?;
+        let liquidity = obligation
+            .find_liquidity(&borrow_reserve_id)
+            .ok_or(ProgramError::from(SolarisAutoError::InvalidObligationData))?;
+
+        let borrowed_amount_wads = to_u256(&liquidity.borrowed_amount_wads)
+            .checked_sub(borrowed_amount_wads_before)
+            .ok_or(ProgramError::from(SolarisAutoError::MathOverflow))?;
+
+        borrow_position_liquidity(
+            &mut solend_position,
+            &borrow_reserve_id,
+            borrowed_amount_wads,
+            to_u256(&liquidity.cumulative_borrow_rate_wads),
+        )?;
+
+        // Borrow marks the obligation stale, market values of the new borrow
+        // come from the refresh. Obligation refresh takes deposits and then borrows
+        let reserve_infos = [
+            &solend_refresh_reserve_collateral_infos[..3],
+            &solend_refresh_reserve_liquidity_infos[..3],
+        ];
+
+        let mut refresh_reserve_infos: Vec<&AccountInfo> = vec![];
+
+        for reserve_id in obligation.deposits
+            .iter()
+            .map(|collateral| &collateral.deposit_reserve)
+            .chain(obligation.borrows.iter().map(|liquidity| &liquidity.borrow_reserve)) {
+            let infos = reserve_infos
+                .iter()
+                .find(|infos| infos[0].key == reserve_id)
+                .ok_or(ProgramError::NotEnoughAccountKeys)?;
+
+            refresh_reserve_infos.extend(infos.iter());
+        }
+
+        refresh_obligation(solend_program, &obligation_info, &refresh_reserve_infos, accounts)?;
+
+        let obligation = Obligation::unpack(&obligation_info.data.borrow())?;
+        check_position_health(&solend_position, &obligation, &get_refresh_reserves(&refresh_reserve_infos)?)?;
+
+        solend_position.serialize(&mut *solend_position_info.data.borrow_mut())?;
+
+        Ok(())
+    }
+
+    pub fn process_solend_proxy_repay_obligation_liquidity(
+        program_id: &Pubkey,
+        accounts: &[AccountInfo],
+        liquidity_amount: u64,
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+
+        let maker_info = next_account_info(account_info_iter)?;
+        let solend_position_info = next_account_info(account_info_iter)?;
+        let solend_program_info = next_account_info(account_info_iter)?;
+        let source_liquidity_info = next_account_info(account_info_iter)?;
+        let repay_reserve_liquidity_supply_info = next_account_info(account_info_iter)?;
+        let repay_reserve_info = next_account_info(account_info_iter)?;
+        let obligation_info = next_account_info(account_info_iter)?;
+        let lending_market_info = next_account_info(account_info_iter)?;
+        let _clock_info = next_account_info(account_info_iter)?;
+        let _token_program_info = next_account_info(account_info_iter)?;
+
+        let refresh_reserve_infos: Vec<&AccountInfo> = account_info_iter.collect();
+
+        if refresh_reserve_infos.is_empty() || refresh_reserve_infos.len() % 3 != 0 {
+            return Err(ProgramError::NotEnoughAccountKeys)
+        }
+
+        let mut solend_position = Self::get_solend_position(
+            program_id,
+            maker_info,
+            solend_position_info,
+            obligation_info.key,
+        )?;
+
+        assert_delegate_obligation(solend_program_info, obligation_info)?;
+
+        refresh_obligation(solend_program_info, obligation_info, &refresh_reserve_infos, accounts)?;
+
+        let obligation = Obligation::unpack(&obligation_info.data.borrow())?;
+        accrue_position_interest(&mut solend_position, &obligation)?;
+
+        let repay_amount = liquidity_amount.min(
+            get_position_borrowed_amount(&solend_position, repay_reserve_info.key)?
+        );
+
+        msg!("repay_amount is {}", repay_amount);
+
+        let source_liquidity_before = get_token_account_amount(source_liquidity_info)?;
+
+        // Maker is the transfer authority, so the delegate doesn't sign
+        invoke(
+            &solend_repay_obligation_liquidity(
+                solend_program_info.key,
+                source_liquidity_info.key,
+                repay_reserve_liquidity_supply_info.key,
+                repay_reserve_info.key,
+                obligation_info.key,
+                lending_market_info.key,
+                maker_info.key,
+                repay_amount,
+            ),
+            accounts,
+        )?;
+
+        // Lending program settles at most the obligation borrow
+        let repaid_amount = source_liquidity_before
+            .checked_sub(get_token_account_amount(source_liquidity_info)?)
+            .ok_or(ProgramError::from(SolarisAutoError::MathOverflow))?;
+
+        repay_position_liquidity(&mut solend_position, repay_reserve_info.key, repaid_amount)?;
+
+        solend_position.serialize(&mut *solend_position_info.data.borrow_mut())?;
+
+        Ok(())
+    }
+
+    pub fn process_solend_proxy_withdraw_obligation_collateral(
+        program_id: &Pubkey,
+        accounts: &[AccountInfo],
+        collateral_amount: u64,
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+
+        let maker_info = next_account_info(account_info_iter)?;
+        let solend_position_info = next_account_info(account_info_iter)?;
+        let solend_program_info = next_account_info(account_info_iter)?;
+        let reserve_collateral_info = next_account_info(account_info_iter)?;
+        let collateral_ta_info = next_account_info(account_info_iter)?;
+        let withdraw_reserve_info = next_account_info(account_info_iter)?;
+        let obligation_info = next_account_info(account_info_iter)?;
+        let lending_market_info = next_account_info(account_info_iter)?;
+        let lending_market_authority_info = next_account_info(account_info_iter)?;
+        let destination_liquidity_info = next_account_info(account_info_iter)?;
+        let reserve_collateral_mint_info = next_account_info(account_info_iter)?;
+        let reserve_liquidity_supply_info = next_account_info(account_info_iter)?;
+        let delegate_info = next_account_info(account_info_iter)?;
+        let _clock_info = next_account_info(account_info_iter)?;
+        let _token_program_info = next_account_info(account_info_iter)?;
+
+        let refresh_reserve_infos: Vec<&AccountInfo> = account_info_iter.collect();
+
+        if refresh_reserve_infos.is_empty() || refresh_reserve_infos.len() % 3 != 0 {
+            return Err(ProgramError::NotEnoughAccountKeys)
+        }
+
+        let mut solend_position = Self::get_solend_position(
+            program_id,
+            maker_info,
+            solend_position_info,
+            obligation_info.key,
+        )?;
+
+        assert_delegate_obligation(solend_program_info, obligation_info)?;
+
+        refresh_obligation(solend_program_info, obligation_info, &refresh_reserve_infos, accounts)?;
+
+        let obligation = Obligation::unpack(&obligation_info.data.borrow())?;
+        accrue_position_interest(&mut solend_position, &obligation)?;
+
+        let collateral_amount = match collateral_amount {
+            u64::MAX => solend_position.deposits
+                .iter()
+                .find(|collateral| collateral.deposit_reserve == *withdraw_reserve_info.key)
+                .map(|collateral| collateral.deposited_amount)
+                .ok_or(ProgramError::from(SolarisAutoError::PositionNotEntitled))?,
+            _ => collateral_amount,
+        };
+
+        msg!("collateral_amount is {}", collateral_amount);
+
+        withdraw_position_collateral(&mut solend_position, withdraw_reserve_info.key, collateral_amount)?;
+
+        check_position_health(&solend_position, &obligation, &get_refresh_reserves(&refresh_reserve_infos)?)?;
+
+        invoke_signed(
+            &solend_withdraw_obligation_collateral_and_redeem_reserve_collateral(
+                solend_program_info.key,
+                reserve_collateral_info.key,
+                collateral_ta_info.key,
+                withdraw_reserve_info.key,
+                obligation_info.key,
+                lending_market_info.key,
+                lending_market_authority_info.key,
+                destination_liquidity_info.key,
+                reserve_collateral_mint_info.key,
+                reserve_liquidity_supply_info.key,
+                delegate_info.key,
+                delegate_info.key,
+                collateral_amount,
+            ),
+            accounts,
+            &[&get_seeds_delegate()],
+        )?;
+
+        solend_position.serialize(&mut *solend_position_info.data.borrow_mut())?;
+
         Ok(())
     }
 
+    fn get_obligation_deposited_amount(
+        obligation_info: &AccountInfo,
+        reserve_id: &Pubkey,
+    ) -> Result<u64, ProgramError> {
+        let obligation = Obligation::unpack(&obligation_info.data.borrow())?;
+
+        Ok(obligation.find_collateral(reserve_id).map_or(0, |collateral| collateral.deposited_amount))
+    }
+
+    fn get_solend_position(
+        program_id: &Pubkey,
+        maker_info: &AccountInfo,
+        solend_position_info: &AccountInfo,
+        obligation_id: &Pubkey,
+    ) -> Result<SolendPosition, ProgramError> {
+        if !maker_info.is_signer {
+            return Err(ProgramError::MissingRequiredSignature)
+        }
+
+        load_solend_position(program_id, solend_position_info, maker_info.key, obligation_id)
+    }
+
+    /// Maker pays for the position account at the first deposit or borrow
+    fn get_or_create_solend_position<'a>(
+        program_id: &Pubkey,
+        maker_info: &AccountInfo<'a>,
+        solend_position_info: &AccountInfo<'a>,
+        system_program_info: &AccountInfo<'a>,
+        obligation_id: &Pubkey,
+    ) -> Result<SolendPosition, ProgramError> {
+        if !solend_position_info.data_is_empty() {
+            return Self::get_solend_position(program_id, maker_info, solend_position_info, obligation_id)
+        }
+
+        let sign_seeds_solend_position =
+            [
+                PREFIX.as_bytes(),
+                SOLEND_POSITION.as_bytes(),
+                maker_info.key.as_ref(),
+                obligation_id.as_ref(),
+                &[get_bump_solend_position(maker_info.key, obligation_id)]
+            ];
+
+        invoke_signed(
+            &create_solend_position(
+                maker_info.key,
+                solend_position_info.key,
+            )?,
+            &[
+                maker_info.clone(),
+                solend_position_info.clone(),
+                system_program_info.clone(),
+            ],
+            &[&sign_seeds_solend_position],
+        )?;
+
+        Ok(SolendPosition::new(*maker_info.key, *obligation_id))
+    }
+
     pub fn process_solend_init_accounts_for_delegate(
         _program_id: &Pubkey,
         accounts: &[AccountInfo],
@@ -496,6 +1182,8 @@ impl Processor {
         let clock_info = next_account_info(account_info_iter)?;
         let rent_info = next_account_info(account_info_iter)?;
 
+        assert_solend_program(solend_program_info)?;
+
         let rent = Rent::get()?;
         let size: u64 = 1300;
 
@@ -6,21 +6,36 @@ use borsh::{
 use solana_program::{
     program_error::ProgramError,
     account_info::AccountInfo,
+    pubkey::Pubkey,
+};
+
+use crate::{
+    error::SolarisAutoError,
+    instruction::Order,
 };
 
 pub const PREFIX: &str = "solaris-automations";
 pub const ONCHAIN_ORDER: &str = "onchain_order";
 pub const DELEGATE: &str = "delegate";
 pub const COLLATERAL_TA: &str = "collateral_ta_v2";
+pub const ORDER_BUFFER: &str = "order_buffer";
+pub const SOLEND_POSITION: &str = "solend_position";
 
 pub const BUMP_DELEGATE: u8 = 255;
 pub const BUMP_COLLATERAL_TA: u8 = 254;
 
-pub const ONCHAIN_ORDER_STATE_SIZE: usize = 58; 
+pub const ONCHAIN_ORDER_STATE_SIZE: usize = 98; 
+pub const PRICE_GUARD_SIZE: usize = 42;
+pub const SPEND_LIMIT_SIZE: usize = 40;
+pub const ORDER_BUFFER_HEADER_SIZE: usize = 37;
+pub const MAX_POSITION_RESERVES: usize = 10;
+pub const SOLEND_POSITION_SIZE: usize = 713; // 1 + 32 + 32 + 4 + 4 + (64 * 10)
 
 #[derive(BorshSchema, BorshDeserialize, BorshSerialize)]
 pub enum Key {
     OnchainOrder,
+    OrderBuffer,
+    SolendPosition,
 }
 
 #[derive(BorshSchema, BorshDeserialize, BorshSerialize)]
@@ -34,15 +49,110 @@ pub enum OrderStage {
 pub struct OnchainOrder {
     pub key: Key,
     pub order_hash: [u8; 32],
+    pub maker: Pubkey,
     pub making_amount: u64,
     pub taking_amount: u64,
     pub remaining_maker_amount: u64,
+    /// Liquidity deposited by the top-up callback over all fills
+    pub topped_up_amount: u64,
+    pub get_maker_amount: Vec<u8>,
+    pub get_taker_amount: Vec<u8>,
     pub predicate: Vec<u8>,
+    pub post_condition: Vec<u8>,
     pub callback: Vec<u8>,
+    pub price_guard: Option<PriceGuard>,
+    pub spend_limits: Vec<SpendLimit>,
     pub stage: OrderStage,
 }
 
+/// Header of the order buffer. Serialized order of `order_len` bytes
+/// is written right after the header with `WriteOrderBuffer`
+#[derive(BorshSchema, BorshDeserialize, BorshSerialize)]
+pub struct OrderBuffer {
+    pub key: Key,
+    pub authority: Pubkey,
+    pub order_len: u32,
+}
+
+/// One-sided oracle price guard of the order. Fill is rejected if the fill price
+/// (taking amount per making amount) is below `oracle * (1 + tolerance_bps / 10000)`,
+/// e.g. -200 allows fills at most 2% below the oracle. Fills above the oracle
+/// are never rejected. `tolerance_bps <= -10000` disables the guard.
+#[derive(BorshSchema, BorshDeserialize, BorshSerialize, Clone, Debug, PartialEq)]
+pub struct PriceGuard {
+    /// Pyth price account of the maker asset quoted in the taker asset
+    pub pyth_price: Pubkey,
+    pub maker_decimals: u8,
+    pub taker_decimals: u8,
+    pub tolerance_bps: i64,
+}
+
+/// Maximum net outflow of the mint from maker token accounts during callbacks.
+/// Mints without limit can't be spent by callbacks at all
+#[derive(BorshSchema, BorshDeserialize, BorshSerialize, Clone, Debug, PartialEq)]
+pub struct SpendLimit {
+    pub mint: Pubkey,
+    pub max_outflow: u64,
+}
+
+/// Share of the maker in the obligation of the delegate. Updated by the Solend
+/// proxy instructions, repay and withdraw proxies are limited by it
+#[derive(BorshSchema, BorshDeserialize, BorshSerialize, Clone, Debug, PartialEq)]
+pub struct SolendPosition {
+    pub key: Key,
+    pub maker: Pubkey,
+    pub obligation: Pubkey,
+    pub deposits: Vec<PositionCollateral>,
+    pub borrows: Vec<PositionLiquidity>,
+}
+
+#[derive(BorshSchema, BorshDeserialize, BorshSerialize, Clone, Debug, PartialEq)]
+pub struct PositionCollateral {
+    pub deposit_reserve: Pubkey,
+    /// Amount of collateral deposited by the maker
+    pub deposited_amount: u64,
+}
+
+#[derive(BorshSchema, BorshDeserialize, BorshSerialize, Clone, Debug, PartialEq)]
+pub struct PositionLiquidity {
+    pub borrow_reserve: Pubkey,
+    /// Liquidity borrowed by the maker plus interest, scaled by WAD
+    pub borrowed_amount_wads: u128,
+    /// Cumulative borrow rate of the reserve at the last update, scaled by WAD
+    pub cumulative_borrow_rate_wads: u128,
+}
+
+/// Result of `CheckPredicate` instruction. It's set as return data
+#[derive(BorshSchema, BorshDeserialize, BorshSerialize, Debug, PartialEq)]
+pub struct CheckPredicateResult {
+    pub is_passed: bool,
+    /// Index of the failed sub-predicate of helper AND, 0 for a single predicate
+    pub failed_predicate_index: Option<u8>,
+    /// `ProgramError` of the failed predicate converted to u64
+    pub error: Option<u64>,
+}
+
 impl OnchainOrder {
+    pub fn new(order_hash: [u8; 32], order: Order, stage: OrderStage) -> OnchainOrder {
+        OnchainOrder {
+            key: Key::OnchainOrder,
+            order_hash,
+            maker: order.maker,
+            making_amount: order.making_amount,
+            taking_amount: order.taking_amount,
+            remaining_maker_amount: order.making_amount,
+            topped_up_amount: 0,
+            get_maker_amount: order.get_maker_amount,
+            get_taker_amount: order.get_taker_amount,
+            predicate: order.predicate,
+            post_condition: order.post_condition,
+            callback: order.callback,
+            price_guard: order.price_guard,
+            spend_limits: order.spend_limits,
+            stage,
+        }
+    }
+
     pub fn from_account_info(a: &AccountInfo) -> Result<OnchainOrder, ProgramError> {
         let onchain_order = OnchainOrder::try_from_slice(
             &a.data.borrow_mut(),
@@ -50,4 +160,44 @@ impl OnchainOrder {
 
         Ok(onchain_order)
     }
+}
+
+impl OrderBuffer {
+    pub fn from_account_info(a: &AccountInfo) -> Result<OrderBuffer, ProgramError> {
+        if a.data_len() < ORDER_BUFFER_HEADER_SIZE {
+            return Err(SolarisAutoError::InvalidOrderBuffer.into())
+        }
+
+        let order_buffer = OrderBuffer::try_from_slice(
+            &a.data.borrow()[..ORDER_BUFFER_HEADER_SIZE],
+        )?;
+
+        match order_buffer.key {
+            Key::OrderBuffer => Ok(order_buffer),
+            _ => Err(SolarisAutoError::InvalidOrderBuffer.into()),
+        }
+    }
+}
+
+impl SolendPosition {
+    pub fn new(maker: Pubkey, obligation: Pubkey) -> SolendPosition {
+        SolendPosition {
+            key: Key::SolendPosition,
+            maker,
+            obligation,
+            deposits: vec![],
+            borrows: vec![],
+        }
+    }
+
+    /// Account is padded up to `SOLEND_POSITION_SIZE`
+    pub fn from_account_info(a: &AccountInfo) -> Result<SolendPosition, ProgramError> {
+        let solend_position = SolendPosition::deserialize(&mut &a.data.borrow()[..])
+            .or(Err(ProgramError::from(SolarisAutoError::InvalidSolendPosition)))?;
+
+        match solend_position.key {
+            Key::SolendPosition => Ok(solend_position),
+            _ => Err(SolarisAutoError::InvalidSolendPosition.into()),
+        }
+    }
 }
\ No newline at end of file
@@ -18,9 +18,11 @@ use crate::{
     error::SolarisAutoError,
     state::{
         Key,
-        PREFIX, ONCHAIN_ORDER, DELEGATE, 
+        PREFIX, ONCHAIN_ORDER, DELEGATE, ORDER_BUFFER, SOLEND_POSITION,
         BUMP_DELEGATE, 
-        ONCHAIN_ORDER_STATE_SIZE, COLLATERAL_TA, BUMP_COLLATERAL_TA
+        ONCHAIN_ORDER_STATE_SIZE, COLLATERAL_TA, BUMP_COLLATERAL_TA,
+        PRICE_GUARD_SIZE, SPEND_LIMIT_SIZE, ORDER_BUFFER_HEADER_SIZE,
+        SOLEND_POSITION_SIZE,
     },
 };
 
@@ -28,6 +30,10 @@ pub fn get_seeds_delegate() -> [&'static [u8]; 3] {
     [PREFIX.as_bytes(), DELEGATE.as_bytes(), &[BUMP_DELEGATE]]
 }
 
+pub fn get_delegate_id() -> Result<Pubkey, ProgramError> {
+    Ok(Pubkey::create_program_address(&get_seeds_delegate(), &program_id())?)
+}
+
 pub fn get_seeds_collateral_ta() -> [&'static [u8]; 3] {
     [PREFIX.as_bytes(), COLLATERAL_TA.as_bytes(), &[BUMP_COLLATERAL_TA]]
 }
@@ -41,6 +47,45 @@ pub fn get_bump_onchain_order(order_hash: &[u8]) -> u8 {
     bump
 } 
 
+pub fn get_bump_order_buffer(authority: &Pubkey) -> u8 {
+    let (_, bump) = Pubkey::find_program_address(
+        &[PREFIX.as_bytes(), ORDER_BUFFER.as_bytes(), authority.as_ref()],
+        &program_id(),
+    );
+
+    bump
+}
+
+pub fn get_bump_solend_position(maker: &Pubkey, obligation: &Pubkey) -> u8 {
+    let (_, bump) = Pubkey::find_program_address(
+        &[PREFIX.as_bytes(), SOLEND_POSITION.as_bytes(), maker.as_ref(), obligation.as_ref()],
+        &program_id(),
+    );
+
+    bump
+}
+
+pub fn assert_onchain_order(
+    info: &AccountInfo,
+    order_hash: &[u8],
+) -> ProgramResult {
+    let onchain_order_id = Pubkey::create_program_address(
+        &[
+            PREFIX.as_bytes(),
+            ONCHAIN_ORDER.as_bytes(),
+            order_hash,
+            &[get_bump_onchain_order(order_hash)],
+        ],
+        &program_id(),
+    )?;
+
+    if *info.key != onchain_order_id {
+        Err(SolarisAutoError::InvalidOnchainOrder.into())
+    } else {
+        Ok(())
+    }
+}
+
 pub fn assert_owned_by(
     info: &AccountInfo,
     owner_id: &Pubkey
@@ -52,6 +97,47 @@ pub fn assert_owned_by(
     }
 }
 
+/// Checks that supplied accounts are exactly the program and the accounts
+/// of the signed instruction. Pubkeys must match, signer and writable flags
+/// of the signed instruction must be granted to the supplied accounts
+pub fn assert_instruction_accounts(
+    instr: &Instruction,
+    infos: &[AccountInfo],
+) -> ProgramResult {
+    if infos.len() != 1 + instr.accounts.len() || *infos[0].key != instr.program_id {
+        return Err(SolarisAutoError::InstructionAccountsMismatch.into())
+    }
+
+    let is_matched = instr.accounts
+        .iter()
+        .zip(infos[1..].iter())
+        .all(|(meta, info)| {
+            meta.pubkey == *info.key
+                && (!meta.is_signer || info.is_signer)
+                && (!meta.is_writable || info.is_writable)
+        });
+
+    match is_matched {
+        true => Ok(()),
+        false => Err(SolarisAutoError::InstructionAccountsMismatch.into()),
+    }
+}
+
+pub fn get_token_account_amount(info: &AccountInfo) -> Result<u64, ProgramError> {
+    Ok(TokenAccount::unpack(&info.data.borrow())?.amount)
+}
+
+/// Checks that `info` is an SPL token account of `maker`
+pub fn assert_maker_token_account(info: &AccountInfo, maker: &Pubkey) -> ProgramResult {
+    assert_owned_by(info, &spl_token::id())?;
+
+    if TokenAccount::unpack(&info.data.borrow())?.owner != *maker {
+        return Err(SolarisAutoError::InvalidMakerTokenAccount.into())
+    }
+
+    Ok(())
+}
+
 pub fn create_onchain_order(
     from_id: &Pubkey,
     onchain_order_id: &Pubkey,
@@ -59,8 +145,13 @@ pub fn create_onchain_order(
 ) -> Result<Instruction, ProgramError> {
     let rent = Rent::get()?;
     let size = ONCHAIN_ORDER_STATE_SIZE + 
+        4 + order.get_maker_amount.len() +
+        4 + order.get_taker_amount.len() +
         4 + order.predicate.len() +
-        4 + order.callback.len();
+        4 + order.post_condition.len() +
+        4 + order.callback.len() +
+        1 + order.price_guard.as_ref().map_or(0, |_| PRICE_GUARD_SIZE) +
+        4 + order.spend_limits.len() * SPEND_LIMIT_SIZE;
 
     let min_rent_exempt = rent.minimum_balance(size);
 
@@ -73,6 +164,41 @@ pub fn create_onchain_order(
     ))
 }
 
+pub fn create_order_buffer(
+    from_id: &Pubkey,
+    order_buffer_id: &Pubkey,
+    order_len: u32,
+) -> Result<Instruction, ProgramError> {
+    let rent = Rent::get()?;
+    let size = ORDER_BUFFER_HEADER_SIZE + order_len as usize;
+
+    let min_rent_exempt = rent.minimum_balance(size);
+
+    Ok(system_instruction::create_account(
+        from_id,
+        order_buffer_id,
+        min_rent_exempt,
+        size as u64,
+        &program_id(),
+    ))
+}
+
+pub fn create_solend_position(
+    from_id: &Pubkey,
+    solend_position_id: &Pubkey,
+) -> Result<Instruction, ProgramError> {
+    let rent = Rent::get()?;
+    let min_rent_exempt = rent.minimum_balance(SOLEND_POSITION_SIZE);
+
+    Ok(system_instruction::create_account(
+        from_id,
+        solend_position_id,
+        min_rent_exempt,
+        SOLEND_POSITION_SIZE as u64,
+        &program_id(),
+    ))
+}
+
 pub fn create_collateral_token_account(
     from_id: &Pubkey,
     collateral_token_account_id: &Pubkey,
@@ -109,4 +235,222 @@ pub fn solend_init_obligation(
         ],
         data: vec![6],
     }
+}
+
+pub fn solend_refresh_reserve(
+    solend_program_id: &Pubkey,
+    reserve_id: &Pubkey,
+    pyth_price_id: &Pubkey,
+    switchboard_price_id: &Pubkey,
+) -> Instruction {
+    Instruction{
+        program_id: *solend_program_id,
+        accounts: vec![
+            AccountMeta::new(*reserve_id, false),
+            AccountMeta::new_readonly(*pyth_price_id, false),
+            AccountMeta::new_readonly(*switchboard_price_id, false),
+            AccountMeta::new_readonly(Clock::id(), false),
+        ],
+        data: vec![3],
+    }
+}
+
+/// Reserves are deposit reserves and then borrow reserves in order of the obligation
+pub fn solend_refresh_obligation(
+    solend_program_id: &Pubkey,
+    obligation_account_id: &Pubkey,
+    reserve_ids: &[Pubkey],
+) -> Instruction {
+    let mut accounts = vec![
+        AccountMeta::new(*obligation_account_id, false),
+        AccountMeta::new_readonly(Clock::id(), false),
+    ];
+    accounts.extend(reserve_ids.iter().map(|id| AccountMeta::new_readonly(*id, false)));
+
+    Instruction{
+        program_id: *solend_program_id,
+        accounts,
+        data: vec![7],
+    }
+}
+
+pub fn solend_deposit_reserve_liquidity_and_obligation_collateral(
+    solend_program_id: &Pubkey,
+    source_liquidity_id: &Pubkey,
+    destination_collateral_id: &Pubkey,
+    reserve_id: &Pubkey,
+    reserve_liquidity_supply_id: &Pubkey,
+    reserve_collateral_mint_id: &Pubkey,
+    lending_market_id: &Pubkey,
+    lending_market_authority_id: &Pubkey,
+    destination_deposit_collateral_id: &Pubkey,
+    obligation_account_id: &Pubkey,
+    obligation_owner_id: &Pubkey,
+    reserve_liquidity_pyth_oracle_id: &Pubkey,
+    reserve_liquidity_switchboard_oracle_id: &Pubkey,
+    transfer_authority_id: &Pubkey,
+    liquidity_amount: u64,
+) -> Instruction {
+    let mut data = vec![14];
+    data.extend_from_slice(&liquidity_amount.to_le_bytes());
+
+    Instruction{
+        program_id: *solend_program_id,
+        accounts: vec![
+            AccountMeta::new(*source_liquidity_id, false),
+            AccountMeta::new(*destination_collateral_id, false),
+            AccountMeta::new(*reserve_id, false),
+            AccountMeta::new(*reserve_liquidity_supply_id, false),
+            AccountMeta::new(*reserve_collateral_mint_id, false),
+            AccountMeta::new_readonly(*lending_market_id, false),
+            AccountMeta::new_readonly(*lending_market_authority_id, false),
+            AccountMeta::new(*destination_deposit_collateral_id, false),
+            AccountMeta::new(*obligation_account_id, false),
+            AccountMeta::new_readonly(*obligation_owner_id, true),
+            AccountMeta::new_readonly(*reserve_liquidity_pyth_oracle_id, false),
+            AccountMeta::new_readonly(*reserve_liquidity_switchboard_oracle_id, false),
+            AccountMeta::new_readonly(*transfer_authority_id, true),
+            AccountMeta::new_readonly(Clock::id(), false),
+            AccountMeta::new_readonly(spl_token::ID, false),
+        ],
+        data,
+    }
+}
+
+pub fn solend_borrow_obligation_liquidity(
+    solend_program_id: &Pubkey,
+    source_liquidity_id: &Pubkey,
+    destination_liquidity_id: &Pubkey,
+    borrow_reserve_id: &Pubkey,
+    borrow_reserve_liquidity_fee_receiver_id: &Pubkey,
+    obligation_account_id: &Pubkey,
+    lending_market_id: &Pubkey,
+    lending_market_authority_id: &Pubkey,
+    obligation_owner_id: &Pubkey,
+    liquidity_amount: u64,
+) -> Instruction {
+    let mut data = vec![10];
+    data.extend_from_slice(&liquidity_amount.to_le_bytes());
+
+    Instruction{
+        program_id: *solend_program_id,
+        accounts: vec![
+            AccountMeta::new(*source_liquidity_id, false),
+            AccountMeta::new(*destination_liquidity_id, false),
+            AccountMeta::new(*borrow_reserve_id, false),
+            AccountMeta::new(*borrow_reserve_liquidity_fee_receiver_id, false),
+            AccountMeta::new(*obligation_account_id, false),
+            AccountMeta::new_readonly(*lending_market_id, false),
+            AccountMeta::new_readonly(*lending_market_authority_id, false),
+            AccountMeta::new_readonly(*obligation_owner_id, true),
+            AccountMeta::new_readonly(Clock::id(), false),
+            AccountMeta::new_readonly(spl_token::ID, false),
+        ],
+        data,
+    }
+}
+
+pub fn solend_repay_obligation_liquidity(
+    solend_program_id: &Pubkey,
+    source_liquidity_id: &Pubkey,
+    destination_liquidity_id: &Pubkey,
+    repay_reserve_id: &Pubkey,
+    obligation_account_id: &Pubkey,
+    lending_market_id: &Pubkey,
+    transfer_authority_id: &Pubkey,
+    liquidity_amount: u64,
+) -> Instruction {
+    let mut data = vec![11];
+    data.extend_from_slice(&liquidity_amount.to_le_bytes());
+
+    Instruction{
+        program_id: *solend_program_id,
+        accounts: vec![
+            AccountMeta::new(*source_liquidity_id, false),
+            AccountMeta::new(*destination_liquidity_id, false),
+            AccountMeta::new(*repay_reserve_id, false),
+            AccountMeta::new(*obligation_account_id, false),
+            AccountMeta::new_readonly(*lending_market_id, false),
+            AccountMeta::new_readonly(*transfer_authority_id, true),
+            AccountMeta::new_readonly(Clock::id(), false),
+            AccountMeta::new_readonly(spl_token::ID, false),
+        ],
+        data,
+    }
+}
+
+pub fn solend_withdraw_obligation_collateral_and_redeem_reserve_collateral(
+    solend_program_id: &Pubkey,
+    source_collateral_id: &Pubkey,
+    destination_collateral_id: &Pubkey,
+    withdraw_reserve_id: &Pubkey,
+    obligation_account_id: &Pubkey,
+    lending_market_id: &Pubkey,
+    lending_market_authority_id: &Pubkey,
+    destination_liquidity_id: &Pubkey,
+    reserve_collateral_mint_id: &Pubkey,
+    reserve_liquidity_supply_id: &Pubkey,
+    obligation_owner_id: &Pubkey,
+    transfer_authority_id: &Pubkey,
+    collateral_amount: u64,
+) -> Instruction {
+    let mut data = vec![15];
+    data.extend_from_slice(&collateral_amount.to_le_bytes());
+
+    Instruction{
+        program_id: *solend_program_id,
+        accounts: vec![
+            AccountMeta::new(*source_collateral_id, false),
+            AccountMeta::new(*destination_collateral_id, false),
+            AccountMeta::new(*withdraw_reserve_id, false),
+            AccountMeta::new(*obligation_account_id, false),
+            AccountMeta::new_readonly(*lending_market_id, false),
+            AccountMeta::new_readonly(*lending_market_authority_id, false),
+            AccountMeta::new(*destination_liquidity_id, false),
+            AccountMeta::new(*reserve_collateral_mint_id, false),
+            AccountMeta::new(*reserve_liquidity_supply_id, false),
+            AccountMeta::new_readonly(*obligation_owner_id, true),
+            AccountMeta::new_readonly(*transfer_authority_id, true),
+            AccountMeta::new_readonly(Clock::id(), false),
+            AccountMeta::new_readonly(spl_token::ID, false),
+        ],
+        data,
+    }
+}
+
+/// SPL token-swap `Swap` instruction, also served by `swap_example`
+pub fn token_swap(
+    swap_program_id: &Pubkey,
+    swap_id: &Pubkey,
+    swap_authority_id: &Pubkey,
+    transfer_authority_id: &Pubkey,
+    source_id: &Pubkey,
+    swap_source_id: &Pubkey,
+    swap_destination_id: &Pubkey,
+    destination_id: &Pubkey,
+    pool_mint_id: &Pubkey,
+    pool_fee_id: &Pubkey,
+    amount_in: u64,
+    minimum_amount_out: u64,
+) -> Instruction {
+    let mut data = vec![1];
+    data.extend_from_slice(&amount_in.to_le_bytes());
+    data.extend_from_slice(&minimum_amount_out.to_le_bytes());
+
+    Instruction{
+        program_id: *swap_program_id,
+        accounts: vec![
+            AccountMeta::new_readonly(*swap_id, false),
+            AccountMeta::new_readonly(*swap_authority_id, false),
+            AccountMeta::new_readonly(*transfer_authority_id, true),
+            AccountMeta::new(*source_id, false),
+            AccountMeta::new(*swap_source_id, false),
+            AccountMeta::new(*swap_destination_id, false),
+            AccountMeta::new(*destination_id, false),
+            AccountMeta::new(*pool_mint_id, false),
+            AccountMeta::new(*pool_fee_id, false),
+            AccountMeta::new_readonly(spl_token::ID, false),
+        ],
+        data,
+    }
 }
\ No newline at end of file
@@ -0,0 +1,542 @@
+#![allow(dead_code)]
+use borsh::{BorshDeserialize, BorshSerialize};
+use solana_program_test::*;
+use solana_program::{
+    program_option::COption,
+    program_pack::Pack,
+};
+use solana_sdk::{
+    account::Account,
+    clock::Clock,
+    sysvar::SysvarId,
+    ed25519_instruction,
+    instruction::{AccountMeta, Instruction},
+    keccak,
+    pubkey::Pubkey,
+    signature::{Keypair, Signer},
+    system_program,
+    transaction::{Transaction, TransactionError},
+};
+use spl_token::state::{
+    Account as TokenAccount,
+    AccountState,
+    Mint,
+};
+use lending_example::state::{
+    get_lending_market_authority,
+    LastUpdate,
+    Obligation,
+    ObligationCollateral,
+    ObligationLiquidity,
+    Reserve,
+    WAD,
+};
+use solaris_automations::{
+    id,
+    processor::Processor,
+    instruction::{fill_order, Order},
+    state::{
+        OrderStage,
+        PositionCollateral,
+        PositionLiquidity,
+        SolendPosition,
+        ONCHAIN_ORDER,
+        PREFIX,
+        SOLEND_POSITION,
+        SOLEND_POSITION_SIZE,
+    },
+    utils::get_delegate_id,
+};
+
+pub fn program_test() -> ProgramTest {
+    ProgramTest::new(
+        "solaris_automations",
+        id(),
+        processor!(Processor::process),
+    )
+}
+
+/// Deploys the example lending program at one of the supported Solend ids
+pub fn add_lending_program(program_test: &mut ProgramTest, program_id: &[u8]) -> Pubkey {
+    let program_id = Pubkey::new(program_id);
+
+    program_test.add_program(
+        "lending_example",
+        program_id,
+        processor!(lending_example::processor::Processor::process),
+    );
+
+    program_id
+}
+
+pub fn add_mint(program_test: &mut ProgramTest, mint_authority: COption<Pubkey>, supply: u64) -> Pubkey {
+    let mint = Pubkey::new_unique();
+
+    program_test.add_packable_account(
+        mint,
+        u32::MAX as u64,
+        &Mint {
+            mint_authority,
+            supply,
+            decimals: 6,
+            is_initialized: true,
+            freeze_authority: COption::None,
+        },
+        &spl_token::id(),
+    );
+
+    mint
+}
+
+pub fn add_token_account(
+    program_test: &mut ProgramTest,
+    mint: &Pubkey,
+    owner: &Pubkey,
+    amount: u64,
+) -> Pubkey {
+    let token_account = Pubkey::new_unique();
+
+    program_test.add_packable_account(
+        token_account,
+        u32::MAX as u64,
+        &TokenAccount {
+            mint: *mint,
+            owner: *owner,
+            amount,
+            state: AccountState::Initialized,
+            ..TokenAccount::default()
+        },
+        &spl_token::id(),
+    );
+
+    token_account
+}
+
+/// Maker token account approved to the delegate, as the maker does it
+/// before signing orders
+pub fn add_maker_token_account(
+    program_test: &mut ProgramTest,
+    mint: &Pubkey,
+    maker: &Pubkey,
+    amount: u64,
+) -> Pubkey {
+    let token_account = Pubkey::new_unique();
+
+    program_test.add_packable_account(
+        token_account,
+        u32::MAX as u64,
+        &TokenAccount {
+            mint: *mint,
+            owner: *maker,
+            amount,
+            delegate: COption::Some(get_delegate_id().unwrap()),
+            state: AccountState::Initialized,
+            delegated_amount: u64::MAX,
+            ..TokenAccount::default()
+        },
+        &spl_token::id(),
+    );
+
+    token_account
+}
+
+pub struct TestMarket {
+    pub program_id: Pubkey,
+    pub lending_market: Pubkey,
+    pub authority: Pubkey,
+}
+
+impl TestMarket {
+    pub fn new(program_id: Pubkey) -> TestMarket {
+        let lending_market = Pubkey::new_unique();
+        let (authority, _) = get_lending_market_authority(&program_id, &lending_market);
+
+        TestMarket {
+            program_id,
+            lending_market,
+            authority,
+        }
+    }
+}
+
+pub struct TestReserve {
+    pub id: Pubkey,
+    pub liquidity_mint: Pubkey,
+    pub liquidity_supply: Pubkey,
+    pub collateral_mint: Pubkey,
+    pub collateral_supply: Pubkey,
+    pub pyth_price: Pubkey,
+    pub switchboard_price: Pubkey,
+    pub fee_receiver: Pubkey,
+}
+
+impl TestReserve {
+    /// [reserve, pyth price, switchboard price] refresh triple
+    pub fn refresh_accounts(&self) -> Vec<AccountMeta> {
+        vec![
+            AccountMeta::new(self.id, false),
+            AccountMeta::new_readonly(self.pyth_price, false),
+            AccountMeta::new_readonly(self.switchboard_price, false),
+        ]
+    }
+}
+
+/// Stale reserve with 6 decimals, 75% loan to value and 80% liquidation threshold.
+/// All collateral is held by obligations and exchanges 1:1 for liquidity
+pub fn add_reserve(
+    program_test: &mut ProgramTest,
+    market: &TestMarket,
+    liquidity_mint: &Pubkey,
+    price: u64,
+    available_amount: u64,
+    borrowed_amount: u64,
+) -> TestReserve {
+    let collateral_amount = available_amount + borrowed_amount;
+
+    let liquidity_supply = add_token_account(program_test, liquidity_mint, &market.authority, available_amount);
+    let collateral_mint = add_mint(program_test, COption::Some(market.authority), collateral_amount);
+    let collateral_supply = add_token_account(program_test, &collateral_mint, &market.authority, collateral_amount);
+
+    let pyth_price = Pubkey::new_unique();
+
+    program_test.add_account(
+        pyth_price,
+        Account {
+            lamports: u32::MAX as u64,
+            data: price.to_le_bytes().to_vec(),
+            owner: system_program::id(),
+            executable: false,
+            rent_epoch: 0,
+        },
+    );
+
+    let test_reserve = TestReserve {
+        id: Pubkey::new_unique(),
+        liquidity_mint: *liquidity_mint,
+        liquidity_supply,
+        collateral_mint,
+        collateral_supply,
+        pyth_price,
+        switchboard_price: Pubkey::new_unique(),
+        fee_receiver: Pubkey::new_unique(),
+    };
+
+    program_test.add_packable_account(
+        test_reserve.id,
+        u32::MAX as u64,
+        &Reserve {
+            version: 1,
+            last_update: LastUpdate { slot: 0, stale: true },
+            lending_market: market.lending_market,
+            liquidity_mint: *liquidity_mint,
+            liquidity_mint_decimals: 6,
+            liquidity_supply,
+            liquidity_pyth_oracle: pyth_price,
+            liquidity_switchboard_oracle: test_reserve.switchboard_price,
+            liquidity_available_amount: available_amount,
+            liquidity_borrowed_amount_wads: borrowed_amount as u128 * WAD,
+            liquidity_cumulative_borrow_rate_wads: WAD,
+            liquidity_market_price: price as u128 * WAD,
+            collateral_mint,
+            collateral_mint_total_supply: collateral_amount,
+            collateral_supply,
+            loan_to_value_ratio: 75,
+            liquidation_threshold: 80,
+            fee_receiver: test_reserve.fee_receiver,
+        },
+        &market.program_id,
+    );
+
+    test_reserve
+}
+
+/// Stale obligation of `owner` with collateral and borrowed liquidity amounts
+pub fn add_obligation(
+    program_test: &mut ProgramTest,
+    market: &TestMarket,
+    owner: &Pubkey,
+    deposits: &[(&TestReserve, u64)],
+    borrows: &[(&TestReserve, u64)],
+) -> Pubkey {
+    let obligation = Pubkey::new_unique();
+
+    program_test.add_packable_account(
+        obligation,
+        u32::MAX as u64,
+        &Obligation {
+            version: 1,
+            last_update: LastUpdate { slot: 0, stale: true },
+            lending_market: market.lending_market,
+            owner: *owner,
+            deposits: deposits
+                .iter()
+                .map(|(reserve, amount)| ObligationCollateral {
+                    deposit_reserve: reserve.id,
+                    deposited_amount: *amount,
+                    market_value: 0,
+                })
+                .collect(),
+            borrows: borrows
+                .iter()
+                .map(|(reserve, amount)| ObligationLiquidity {
+                    borrow_reserve: reserve.id,
+                    cumulative_borrow_rate_wads: WAD,
+                    borrowed_amount_wads: *amount as u128 * WAD,
+                    market_value: 0,
+                })
+                .collect(),
+            ..Obligation::default()
+        },
+        &market.program_id,
+    );
+
+    obligation
+}
+
+/// Position of `maker` in the delegate obligation, at its program address
+pub fn add_solend_position(
+    program_test: &mut ProgramTest,
+    maker: &Pubkey,
+    obligation: &Pubkey,
+    deposits: &[(&TestReserve, u64)],
+    borrows: &[(&TestReserve, u64)],
+) -> Pubkey {
+    let (solend_position, _) = Pubkey::find_program_address(
+        &[PREFIX.as_bytes(), SOLEND_POSITION.as_bytes(), maker.as_ref(), obligation.as_ref()],
+        &id(),
+    );
+
+    let mut data = SolendPosition {
+        deposits: deposits
+            .iter()
+            .map(|(reserve, amount)| PositionCollateral {
+                deposit_reserve: reserve.id,
+                deposited_amount: *amount,
+            })
+            .collect(),
+        borrows: borrows
+            .iter()
+            .map(|(reserve, amount)| PositionLiquidity {
+                borrow_reserve: reserve.id,
+                borrowed_amount_wads: *amount as u128 * WAD,
+                cumulative_borrow_rate_wads: WAD,
+            })
+            .collect(),
+        ..SolendPosition::new(*maker, *obligation)
+    }.try_to_vec().unwrap();
+    data.resize(SOLEND_POSITION_SIZE, 0);
+
+    program_test.add_account(
+        solend_position,
+        Account {
+            lamports: u32::MAX as u64,
+            data,
+            owner: id(),
+            executable: false,
+            rent_epoch: 0,
+        },
+    );
+
+    solend_position
+}
+
+/// Maker and taker of 1:1 orders. Every fill is 1 taker asset for 1 maker
+/// asset, so callbacks run without the taker liquidity
+pub struct TestOrder {
+    pub maker: Keypair,
+    pub taker: Keypair,
+    pub maker_asset: Pubkey,
+    pub taker_asset: Pubkey,
+    pub maker_ta_maker_asset: Pubkey,
+    pub maker_ta_taker_asset: Pubkey,
+    pub taker_ta_maker_asset: Pubkey,
+    pub taker_ta_taker_asset: Pubkey,
+}
+
+pub fn add_test_order(program_test: &mut ProgramTest) -> TestOrder {
+    let maker = Keypair::new();
+    let taker = Keypair::new();
+
+    program_test.add_account(
+        taker.pubkey(),
+        Account::new(u32::MAX as u64, 0, &system_program::id()),
+    );
+
+    let maker_asset = add_mint(program_test, COption::None, u32::MAX as u64);
+    let taker_asset = add_mint(program_test, COption::None, u32::MAX as u64);
+
+    TestOrder {
+        maker_ta_maker_asset: add_maker_token_account(program_test, &maker_asset, &maker.pubkey(), 10),
+        maker_ta_taker_asset: add_token_account(program_test, &taker_asset, &maker.pubkey(), 0),
+        taker_ta_maker_asset: add_token_account(program_test, &maker_asset, &taker.pubkey(), 0),
+        taker_ta_taker_asset: add_token_account(program_test, &taker_asset, &taker.pubkey(), 10),
+        maker,
+        taker,
+        maker_asset,
+        taker_asset,
+    }
+}
+
+/// Signs the order with `callback` and fills it in one transaction.
+/// Errors of the fill instruction are reported at index 1
+pub async fn fill_order_with_callback(
+    context: &mut ProgramTestContext,
+    test_order: &TestOrder,
+    salt: u64,
+    callback: Instruction,
+) -> Result<(), TransactionError> {
+    fill_signed_order_with_callback(context, test_order, &test_order.maker, salt, callback).await
+}
+
+/// Same as `fill_order_with_callback`, but the order is signed by `signer`
+pub async fn fill_signed_order_with_callback(
+    context: &mut ProgramTestContext,
+    test_order: &TestOrder,
+    signer: &Keypair,
+    salt: u64,
+    callback: Instruction,
+) -> Result<(), TransactionError> {
+    let mut callback_accounts = vec![AccountMeta::new_readonly(callback.program_id, false)];
+    callback_accounts.extend(callback.accounts.iter().cloned());
+
+    fill_test_order(
+        context,
+        test_order,
+        signer,
+        salt,
+        bincode::serialize(&vec![callback]).unwrap(),
+        callback_accounts,
+        &test_order.maker_ta_taker_asset,
+        vec![],
+        vec![],
+    ).await
+}
+
+/// Signs the order without a callback and fills it with the taker `interaction`.
+/// Taker asset is received by `maker_ta_taker_asset`
+pub async fn fill_order_with_interaction(
+    context: &mut ProgramTestContext,
+    test_order: &TestOrder,
+    maker_ta_taker_asset: &Pubkey,
+    salt: u64,
+    interaction: Instruction,
+) -> Result<(), TransactionError> {
+    let mut interaction_accounts = vec![AccountMeta::new_readonly(interaction.program_id, false)];
+    interaction_accounts.extend(interaction.accounts.iter().cloned());
+
+    fill_test_order(
+        context,
+        test_order,
+        &test_order.maker,
+        salt,
+        vec![],
+        vec![],
+        maker_ta_taker_asset,
+        bincode::serialize(&interaction).unwrap(),
+        interaction_accounts,
+    ).await
+}
+
+async fn fill_test_order(
+    context: &mut ProgramTestContext,
+    test_order: &TestOrder,
+    signer: &Keypair,
+    salt: u64,
+    callback: Vec<u8>,
+    callback_accounts: Vec<AccountMeta>,
+    maker_ta_taker_asset: &Pubkey,
+    interaction: Vec<u8>,
+    interaction_accounts: Vec<AccountMeta>,
+) -> Result<(), TransactionError> {
+    let order = Order {
+        salt,
+        maker_asset: test_order.maker_asset,
+        taker_asset: test_order.taker_asset,
+        maker: test_order.maker.pubkey(),
+        making_amount: 1,
+        taking_amount: 1,
+        get_maker_amount: vec![],
+        get_taker_amount: vec![],
+        predicate: vec![],
+        post_condition: vec![],
+        callback,
+        price_guard: None,
+        spend_limits: vec![],
+    };
+
+    let order_hash = keccak::hash(&order.try_to_vec().unwrap());
+    let (onchain_order, _) = Pubkey::find_program_address(
+        &[PREFIX.as_bytes(), ONCHAIN_ORDER.as_bytes(), order_hash.as_ref()],
+        &id(),
+    );
+
+    let signer_dalek = ed25519_dalek::Keypair::from_bytes(&signer.to_bytes()).unwrap();
+
+    let mut tx = Transaction::new_with_payer(
+        &[
+            ed25519_instruction::new_ed25519_instruction(&signer_dalek, order_hash.as_ref()),
+            fill_order(
+                &id(),
+                &test_order.maker.pubkey(),
+                &test_order.taker.pubkey(),
+                &onchain_order,
+                &get_delegate_id().unwrap(),
+                vec![],
+                vec![],
+                vec![],
+                vec![],
+                callback_accounts,
+                interaction_accounts,
+                &test_order.taker_ta_taker_asset,
+                maker_ta_taker_asset,
+                &test_order.taker_ta_maker_asset,
+                &test_order.maker_ta_maker_asset,
+                None,
+                Some(order),
+                0,
+                1,
+                0,
+                interaction,
+                OrderStage::Filled,
+            ),
+        ],
+        Some(&context.payer.pubkey()),
+    );
+    tx.sign(&[&context.payer, &test_order.taker], context.last_blockhash);
+
+    context.banks_client
+        .process_transaction(tx)
+        .await
+        .map_err(|error| error.unwrap())
+}
+
+pub async fn get_token_amount(banks_client: &mut BanksClient, token_account: &Pubkey) -> u64 {
+    let account = banks_client.get_account(*token_account).await.unwrap().unwrap();
+
+    TokenAccount::unpack(&account.data).unwrap().amount
+}
+
+pub async fn get_obligation(banks_client: &mut BanksClient, obligation: &Pubkey) -> Obligation {
+    let account = banks_client.get_account(*obligation).await.unwrap().unwrap();
+
+    Obligation::unpack(&account.data).unwrap()
+}
+
+pub async fn get_solend_position(banks_client: &mut BanksClient, solend_position: &Pubkey) -> SolendPosition {
+    let account = banks_client.get_account(*solend_position).await.unwrap().unwrap();
+
+    SolendPosition::deserialize(&mut &account.data[..]).unwrap()
+}
+
+/// Deposited collateral and borrowed liquidity amounts of the obligation,
+/// market values change with every refresh
+pub fn get_obligation_amounts(obligation: &Obligation) -> (Vec<(Pubkey, u64)>, Vec<(Pubkey, u128)>) {
+    (
+        obligation.deposits.iter().map(|collateral| (collateral.deposit_reserve, collateral.deposited_amount)).collect(),
+        obligation.borrows.iter().map(|liquidity| (liquidity.borrow_reserve, liquidity.borrowed_amount_wads)).collect(),
+    )
+}
+
+pub fn clock_account() -> AccountMeta {
+    AccountMeta::new_readonly(Clock::id(), false)
+}
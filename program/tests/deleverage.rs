@@ -0,0 +1,207 @@
+#![cfg(feature = "test-bpf")]
+mod common;
+
+use solana_program_test::*;
+use solana_program::{
+    program_option::COption,
+    program_pack::Pack,
+};
+use solana_sdk::{
+    account::Account,
+    instruction::{AccountMeta, Instruction, InstructionError},
+    pubkey::Pubkey,
+    signature::Signer,
+    transaction::{Transaction, TransactionError},
+};
+use spl_token::state::Mint;
+use lending_example::state::WAD;
+use swap_example::{
+    instruction::initialize,
+    state::SWAP_STATE_SIZE,
+};
+use solaris_automations::{
+    error::SolarisAutoError,
+    callbacks::deleverage::CALLBACK_SOLEND_DELEVERAGE,
+    helpers::lending_adapter::SOLEND_ID,
+    state::{PositionCollateral, PositionLiquidity},
+    utils::get_delegate_id,
+};
+
+use common::*;
+
+#[tokio::test]
+async fn test_deleverage() {
+    let mut program_test = program_test();
+    let delegate = get_delegate_id().unwrap();
+
+    let swap_program_id = Pubkey::new_unique();
+    program_test.add_program(
+        "swap_example",
+        swap_program_id,
+        processor!(swap_example::processor::Processor::process),
+    );
+
+    let market = TestMarket::new(add_lending_program(&mut program_test, SOLEND_ID));
+    let collateral_mint = add_mint(&mut program_test, COption::None, u32::MAX as u64);
+    let debt_mint = add_mint(&mut program_test, COption::None, u32::MAX as u64);
+
+    // 1_000 collateral at 20 in the obligation, 600 of them are the maker's
+    let collateral_reserve = add_reserve(&mut program_test, &market, &collateral_mint, 20, 1_000_000_000, 0);
+    let debt_reserve = add_reserve(&mut program_test, &market, &debt_mint, 1, 10_000_000_000, 5_000_000_000);
+
+    let obligation = add_obligation(
+        &mut program_test,
+        &market,
+        &delegate,
+        &[(&collateral_reserve, 1_000_000_000)],
+        &[(&debt_reserve, 5_000_000_000)],
+    );
+
+    let test_order = add_test_order(&mut program_test);
+    let maker = test_order.maker.pubkey();
+    let solend_position = add_solend_position(
+        &mut program_test,
+        &maker,
+        &obligation,
+        &[(&collateral_reserve, 600_000_000)],
+        &[(&debt_reserve, 3_000_000_000)],
+    );
+
+    // Another maker can't use the position
+    let other_order = add_test_order(&mut program_test);
+
+    let destination_collateral = add_token_account(&mut program_test, &collateral_reserve.collateral_mint, &delegate, 0);
+    let maker_collateral_liquidity = add_maker_token_account(&mut program_test, &collateral_mint, &maker, 0);
+    let maker_debt_liquidity = add_maker_token_account(&mut program_test, &debt_mint, &maker, 0);
+
+    // Pool prices the collateral at 20
+    let swap = Pubkey::new_unique();
+    let (swap_authority, _) = Pubkey::find_program_address(&[swap.as_ref()], &swap_program_id);
+
+    program_test.add_account(
+        swap,
+        Account {
+            lamports: u32::MAX as u64,
+            data: vec![0; SWAP_STATE_SIZE],
+            owner: swap_program_id,
+            executable: false,
+            rent_epoch: 0,
+        },
+    );
+
+    let pool_mint = add_mint(&mut program_test, COption::None, 0);
+    let swap_collateral = add_token_account(&mut program_test, &collateral_mint, &swap_authority, 1_000_000_000);
+    let swap_debt = add_token_account(&mut program_test, &debt_mint, &swap_authority, 20_000_000_000);
+    let pool_fee = add_token_account(&mut program_test, &pool_mint, &swap_authority, 0);
+
+    let mut context = program_test.start_with_context().await;
+
+    let mut tx = Transaction::new_with_payer(
+        &[initialize(&swap_program_id, &swap, &swap_authority, &swap_collateral, &swap_debt, &pool_mint, &pool_fee, 0, 1)],
+        Some(&context.payer.pubkey()),
+    );
+    tx.sign(&[&context.payer], context.last_blockhash);
+    context.banks_client.process_transaction(tx).await.unwrap();
+
+    let deleverage_with = |lending_program_id: Pubkey, liquidity_amount: u64| {
+        let mut accounts = vec![
+            AccountMeta::new_readonly(lending_program_id, false),
+            AccountMeta::new(collateral_reserve.collateral_supply, false),
+            AccountMeta::new(destination_collateral, false),
+            AccountMeta::new(collateral_reserve.id, false),
+            AccountMeta::new(obligation, false),
+            AccountMeta::new_readonly(market.lending_market, false),
+            AccountMeta::new_readonly(market.authority, false),
+            AccountMeta::new(maker_collateral_liquidity, false),
+            AccountMeta::new(collateral_reserve.collateral_mint, false),
+            AccountMeta::new(collateral_reserve.liquidity_supply, false),
+            AccountMeta::new_readonly(swap_program_id, false),
+            AccountMeta::new_readonly(swap, false),
+            AccountMeta::new_readonly(swap_authority, false),
+            AccountMeta::new(swap_collateral, false),
+            AccountMeta::new(swap_debt, false),
+            AccountMeta::new(pool_mint, false),
+            AccountMeta::new(pool_fee, false),
+            AccountMeta::new(maker_debt_liquidity, false),
+            AccountMeta::new(debt_reserve.liquidity_supply, false),
+            AccountMeta::new(debt_reserve.id, false),
+            AccountMeta::new_readonly(delegate, false),
+            clock_account(),
+            AccountMeta::new_readonly(spl_token::id(), false),
+            AccountMeta::new(solend_position, false),
+        ];
+        accounts.extend(collateral_reserve.refresh_accounts());
+        accounts.extend(debt_reserve.refresh_accounts());
+
+        Instruction {
+            program_id: Pubkey::new(CALLBACK_SOLEND_DELEVERAGE),
+            accounts,
+            data: [liquidity_amount.to_le_bytes(), 1u64.to_le_bytes()].concat(),
+        }
+    };
+    let deleverage = |liquidity_amount: u64| deleverage_with(market.program_id, liquidity_amount);
+
+    fill_order_with_callback(&mut context, &test_order, 0, deleverage(100_000_000)).await.unwrap();
+
+    // 20_000 * 100 / (1_000 + 100) is swapped and repaid
+    let repaid_amount: u64 = 1_818_181_818;
+
+    assert_eq!(get_token_amount(&mut context.banks_client, &maker_collateral_liquidity).await, 0);
+    assert_eq!(get_token_amount(&mut context.banks_client, &maker_debt_liquidity).await, 0);
+    assert_eq!(get_token_amount(&mut context.banks_client, &destination_collateral).await, 0);
+    assert_eq!(get_token_amount(&mut context.banks_client, &swap_collateral).await, 1_100_000_000);
+    assert_eq!(get_token_amount(&mut context.banks_client, &swap_debt).await, 20_000_000_000 - repaid_amount);
+
+    // Withdrawn collateral is burned
+    let account = context.banks_client.get_account(collateral_reserve.collateral_mint).await.unwrap().unwrap();
+    assert_eq!(Mint::unpack(&account.data).unwrap().supply, 900_000_000);
+
+    let obligation_state = get_obligation(&mut context.banks_client, &obligation).await;
+    assert_eq!(
+        get_obligation_amounts(&obligation_state),
+        (
+            vec![(collateral_reserve.id, 900_000_000)],
+            vec![(debt_reserve.id, (5_000_000_000 - repaid_amount) as u128 * WAD)],
+        ),
+    );
+
+    let position = get_solend_position(&mut context.banks_client, &solend_position).await;
+    assert_eq!(
+        position.deposits,
+        vec![PositionCollateral {
+            deposit_reserve: collateral_reserve.id,
+            deposited_amount: 500_000_000,
+        }],
+    );
+    assert_eq!(
+        position.borrows,
+        vec![PositionLiquidity {
+            borrow_reserve: debt_reserve.id,
+            borrowed_amount_wads: (3_000_000_000 - repaid_amount) as u128 * WAD,
+            cumulative_borrow_rate_wads: WAD,
+        }],
+    );
+
+    // Obligation has 900 collateral, but only 500 are the maker's
+    assert_eq!(
+        fill_order_with_callback(&mut context, &test_order, 1, deleverage(600_000_000)).await.unwrap_err(),
+        TransactionError::InstructionError(1, InstructionError::Custom(SolarisAutoError::PositionNotEntitled as u32)),
+    );
+
+    assert_eq!(
+        fill_order_with_callback(&mut context, &other_order, 0, deleverage(100_000_000)).await.unwrap_err(),
+        TransactionError::InstructionError(1, InstructionError::Custom(SolarisAutoError::InvalidSolendPosition as u32)),
+    );
+
+    // The delegate only signs for known lending programs
+    assert_eq!(
+        fill_order_with_callback(&mut context, &test_order, 3, deleverage_with(swap_program_id, 100_000_000)).await.unwrap_err(),
+        TransactionError::InstructionError(1, InstructionError::Custom(SolarisAutoError::UnsupportedLendingProgram as u32)),
+    );
+
+    // Nor sign an order in the name of the maker
+    assert_eq!(
+        fill_signed_order_with_callback(&mut context, &test_order, &other_order.maker, 2, deleverage(100_000_000)).await.unwrap_err(),
+        TransactionError::InstructionError(1, InstructionError::Custom(SolarisAutoError::InvalidSigner as u32)),
+    );
+}
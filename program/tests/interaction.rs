@@ -0,0 +1,54 @@
+#![cfg(feature = "test-bpf")]
+mod common;
+
+use solana_sdk::{
+    instruction::InstructionError,
+    pubkey::Pubkey,
+    signature::Signer,
+    transaction::TransactionError,
+};
+use solaris_automations::error::SolarisAutoError;
+
+use common::*;
+
+#[tokio::test]
+async fn test_fill_order_with_interaction() {
+    let mut program_test = program_test();
+    let test_order = add_test_order(&mut program_test);
+
+    let mut context = program_test.start_with_context().await;
+
+    // Taker sources the taker asset in the interaction, here from its own account
+    let interaction = |maker_ta_taker_asset: &Pubkey| spl_token::instruction::transfer(
+        &spl_token::id(),
+        &test_order.taker_ta_taker_asset,
+        maker_ta_taker_asset,
+        &test_order.taker.pubkey(),
+        &[],
+        1,
+    ).unwrap();
+
+    fill_order_with_interaction(
+        &mut context,
+        &test_order,
+        &test_order.maker_ta_taker_asset,
+        0,
+        interaction(&test_order.maker_ta_taker_asset),
+    ).await.unwrap();
+
+    assert_eq!(get_token_amount(&mut context.banks_client, &test_order.maker_ta_taker_asset).await, 2);
+    assert_eq!(get_token_amount(&mut context.banks_client, &test_order.taker_ta_taker_asset).await, 8);
+    assert_eq!(get_token_amount(&mut context.banks_client, &test_order.taker_ta_maker_asset).await, 1);
+
+    // Taker asset must be received by the maker, not by an account of the taker
+    assert_eq!(
+        fill_order_with_interaction(
+            &mut context,
+            &test_order,
+            &test_order.taker_ta_taker_asset,
+            1,
+            interaction(&test_order.taker_ta_taker_asset),
+        ).await.unwrap_err(),
+        TransactionError::InstructionError(1, InstructionError::Custom(SolarisAutoError::InvalidMakerTokenAccount as u32)),
+    );
+}
@@ -0,0 +1,187 @@
+#![cfg(feature = "test-bpf")]
+mod common;
+
+use solana_program::program_option::COption;
+use solana_sdk::{
+    instruction::{AccountMeta, Instruction, InstructionError},
+    pubkey::Pubkey,
+    signature::Signer,
+    transaction::TransactionError,
+};
+use lending_example::state::WAD;
+use solaris_automations::{
+    error::SolarisAutoError,
+    callbacks::refinance::CALLBACK_SOLEND_REFINANCE,
+    helpers::lending_adapter::{SOLEND_ID, SOLEND_DEVNET_ID},
+    state::{PositionCollateral, PositionLiquidity},
+    utils::get_delegate_id,
+};
+
+use common::*;
+
+#[tokio::test]
+async fn test_refinance() {
+    let mut program_test = program_test();
+    let delegate = get_delegate_id().unwrap();
+
+    // Markets A and B are different deployments of the lending program
+    let market_a = TestMarket::new(add_lending_program(&mut program_test, SOLEND_ID));
+    let market_b = TestMarket::new(add_lending_program(&mut program_test, SOLEND_DEVNET_ID));
+
+    let collateral_mint = add_mint(&mut program_test, COption::None, u32::MAX as u64);
+    let debt_mint = add_mint(&mut program_test, COption::None, u32::MAX as u64);
+
+    let collateral_reserve_a = add_reserve(&mut program_test, &market_a, &collateral_mint, 20, 1_000_000_000, 0);
+    let debt_reserve_a = add_reserve(&mut program_test, &market_a, &debt_mint, 1, 10_000_000_000, 5_000_000_000);
+    let collateral_reserve_b = add_reserve(&mut program_test, &market_b, &collateral_mint, 20, 300_000_000, 0);
+    let debt_reserve_b = add_reserve(&mut program_test, &market_b, &debt_mint, 1, 10_000_000_000, 0);
+
+    // Obligation A is shared with other makers, obligation B already has collateral for the borrow
+    let obligation_a = add_obligation(
+        &mut program_test,
+        &market_a,
+        &delegate,
+        &[(&collateral_reserve_a, 1_000_000_000)],
+        &[(&debt_reserve_a, 5_000_000_000)],
+    );
+    let obligation_b = add_obligation(
+        &mut program_test,
+        &market_b,
+        &delegate,
+        &[(&collateral_reserve_b, 300_000_000)],
+        &[],
+    );
+
+    let test_order = add_test_order(&mut program_test);
+    let maker = test_order.maker.pubkey();
+
+    let solend_position_a = add_solend_position(
+        &mut program_test,
+        &maker,
+        &obligation_a,
+        &[(&collateral_reserve_a, 600_000_000)],
+        &[(&debt_reserve_a, 3_000_000_000)],
+    );
+    let solend_position_b = add_solend_position(
+        &mut program_test,
+        &maker,
+        &obligation_b,
+        &[(&collateral_reserve_b, 300_000_000)],
+        &[],
+    );
+
+    let destination_collateral_a = add_token_account(&mut program_test, &collateral_reserve_a.collateral_mint, &delegate, 0);
+    let destination_collateral_b = add_token_account(&mut program_test, &collateral_reserve_b.collateral_mint, &delegate, 0);
+    let maker_debt_liquidity = add_maker_token_account(&mut program_test, &debt_mint, &maker, 0);
+    let maker_collateral_liquidity = add_maker_token_account(&mut program_test, &collateral_mint, &maker, 0);
+
+    let mut context = program_test.start_with_context().await;
+
+    let refinance = |borrow_amount: u64, withdraw_amount: u64| {
+        let mut accounts = vec![
+            AccountMeta::new_readonly(market_a.program_id, false),
+            AccountMeta::new(obligation_a, false),
+            AccountMeta::new_readonly(market_a.lending_market, false),
+            AccountMeta::new_readonly(market_a.authority, false),
+            AccountMeta::new(debt_reserve_a.id, false),
+            AccountMeta::new(debt_reserve_a.liquidity_supply, false),
+            AccountMeta::new(collateral_reserve_a.id, false),
+            AccountMeta::new(collateral_reserve_a.collateral_supply, false),
+            AccountMeta::new(collateral_reserve_a.collateral_mint, false),
+            AccountMeta::new(collateral_reserve_a.liquidity_supply, false),
+            AccountMeta::new(destination_collateral_a, false),
+            AccountMeta::new_readonly(market_b.program_id, false),
+            AccountMeta::new(obligation_b, false),
+            AccountMeta::new_readonly(market_b.lending_market, false),
+            AccountMeta::new_readonly(market_b.authority, false),
+            AccountMeta::new(debt_reserve_b.id, false),
+            AccountMeta::new(debt_reserve_b.liquidity_supply, false),
+            AccountMeta::new(debt_reserve_b.fee_receiver, false),
+            AccountMeta::new(collateral_reserve_b.id, false),
+            AccountMeta::new(collateral_reserve_b.liquidity_supply, false),
+            AccountMeta::new(collateral_reserve_b.collateral_mint, false),
+            AccountMeta::new(collateral_reserve_b.collateral_supply, false),
+            AccountMeta::new_readonly(collateral_reserve_b.pyth_price, false),
+            AccountMeta::new_readonly(collateral_reserve_b.switchboard_price, false),
+            AccountMeta::new(destination_collateral_b, false),
+            AccountMeta::new(maker_debt_liquidity, false),
+            AccountMeta::new(maker_collateral_liquidity, false),
+            AccountMeta::new_readonly(delegate, false),
+            clock_account(),
+            AccountMeta::new_readonly(spl_token::id(), false),
+            AccountMeta::new(solend_position_a, false),
+            AccountMeta::new(solend_position_b, false),
+        ];
+        accounts.extend(collateral_reserve_a.refresh_accounts());
+        accounts.extend(debt_reserve_a.refresh_accounts());
+        accounts.extend(collateral_reserve_b.refresh_accounts());
+        accounts.extend(debt_reserve_b.refresh_accounts());
+
+        let mut data = [borrow_amount.to_le_bytes(), withdraw_amount.to_le_bytes()].concat();
+        data.push(2);
+
+        Instruction {
+            program_id: Pubkey::new(CALLBACK_SOLEND_REFINANCE),
+            accounts,
+            data,
+        }
+    };
+
+    // Borrow the maker debt on B, repay it on A and move all maker collateral
+    fill_order_with_callback(&mut context, &test_order, 0, refinance(3_000_000_000, u64::MAX)).await.unwrap();
+
+    assert_eq!(get_token_amount(&mut context.banks_client, &maker_debt_liquidity).await, 0);
+    assert_eq!(get_token_amount(&mut context.banks_client, &maker_collateral_liquidity).await, 0);
+    assert_eq!(get_token_amount(&mut context.banks_client, &destination_collateral_a).await, 0);
+    assert_eq!(get_token_amount(&mut context.banks_client, &destination_collateral_b).await, 0);
+    assert_eq!(get_token_amount(&mut context.banks_client, &debt_reserve_a.liquidity_supply).await, 13_000_000_000);
+    assert_eq!(get_token_amount(&mut context.banks_client, &debt_reserve_b.liquidity_supply).await, 7_000_000_000);
+    assert_eq!(get_token_amount(&mut context.banks_client, &collateral_reserve_a.liquidity_supply).await, 400_000_000);
+    assert_eq!(get_token_amount(&mut context.banks_client, &collateral_reserve_b.liquidity_supply).await, 900_000_000);
+
+    // Other makers' share stays on A
+    let obligation_a_state = get_obligation(&mut context.banks_client, &obligation_a).await;
+    assert_eq!(
+        get_obligation_amounts(&obligation_a_state),
+        (
+            vec![(collateral_reserve_a.id, 400_000_000)],
+            vec![(debt_reserve_a.id, 2_000_000_000 * WAD)],
+        ),
+    );
+
+    let obligation_b_state = get_obligation(&mut context.banks_client, &obligation_b).await;
+    assert_eq!(
+        get_obligation_amounts(&obligation_b_state),
+        (
+            vec![(collateral_reserve_b.id, 900_000_000)],
+            vec![(debt_reserve_b.id, 3_000_000_000 * WAD)],
+        ),
+    );
+
+    let position_a = get_solend_position(&mut context.banks_client, &solend_position_a).await;
+    assert!(position_a.deposits.is_empty());
+    assert!(position_a.borrows.is_empty());
+
+    let position_b = get_solend_position(&mut context.banks_client, &solend_position_b).await;
+    assert_eq!(
+        position_b.deposits,
+        vec![PositionCollateral {
+            deposit_reserve: collateral_reserve_b.id,
+            deposited_amount: 900_000_000,
+        }],
+    );
+    assert_eq!(
+        position_b.borrows,
+        vec![PositionLiquidity {
+            borrow_reserve: debt_reserve_b.id,
+            borrowed_amount_wads: 3_000_000_000 * WAD,
+            cumulative_borrow_rate_wads: WAD,
+        }],
+    );
+
+    // Debt left on A belongs to other makers
+    assert_eq!(
+        fill_order_with_callback(&mut context, &test_order, 1, refinance(1_000_000_000, u64::MAX)).await.unwrap_err(),
+        TransactionError::InstructionError(1, InstructionError::Custom(SolarisAutoError::PositionNotEntitled as u32)),
+    );
+}
@@ -0,0 +1,202 @@
+#![cfg(feature = "test-bpf")]
+mod common;
+
+use solana_program_test::*;
+use solana_program::program_option::COption;
+use solana_sdk::{
+    instruction::{Instruction, InstructionError},
+    pubkey::Pubkey,
+    signature::{Keypair, Signer},
+    transaction::{Transaction, TransactionError},
+};
+use lending_example::state::WAD;
+use solaris_automations::{
+    id,
+    error::SolarisAutoError,
+    helpers::lending_adapter::SOLEND_ID,
+    instruction::{
+        solend_proxy_repay_obligation_liquidity,
+        solend_proxy_withdraw_obligation_collateral,
+    },
+    state::{PositionCollateral, PositionLiquidity},
+    utils::get_delegate_id,
+};
+
+use common::*;
+
+async fn process_maker_instruction(
+    context: &mut ProgramTestContext,
+    maker: &Keypair,
+    instruction: Instruction,
+) -> Result<(), TransactionError> {
+    let mut tx = Transaction::new_with_payer(&[instruction], Some(&context.payer.pubkey()));
+    tx.sign(&[&context.payer, maker], context.last_blockhash);
+
+    context.banks_client
+        .process_transaction(tx)
+        .await
+        .map_err(|error| error.unwrap())
+}
+
+#[tokio::test]
+async fn test_solend_proxy_repay_and_withdraw() {
+    let mut program_test = program_test();
+    let delegate = get_delegate_id().unwrap();
+
+    let market = TestMarket::new(add_lending_program(&mut program_test, SOLEND_ID));
+    let collateral_mint = add_mint(&mut program_test, COption::None, u32::MAX as u64);
+    let debt_mint = add_mint(&mut program_test, COption::None, u32::MAX as u64);
+
+    let collateral_reserve = add_reserve(&mut program_test, &market, &collateral_mint, 20, 1_000_000_000, 0);
+    let debt_reserve = add_reserve(&mut program_test, &market, &debt_mint, 1, 10_000_000_000, 5_000_000_000);
+
+    // Obligation is shared with other makers, 600 collateral and 3_000 debt are the maker's
+    let obligation = add_obligation(
+        &mut program_test,
+        &market,
+        &delegate,
+        &[(&collateral_reserve, 1_000_000_000)],
+        &[(&debt_reserve, 5_000_000_000)],
+    );
+
+    let maker = Keypair::new();
+    let solend_position = add_solend_position(
+        &mut program_test,
+        &maker.pubkey(),
+        &obligation,
+        &[(&collateral_reserve, 600_000_000)],
+        &[(&debt_reserve, 3_000_000_000)],
+    );
+
+    // Same position in an obligation of someone else
+    let foreign_obligation = add_obligation(
+        &mut program_test,
+        &market,
+        &Pubkey::new_unique(),
+        &[(&collateral_reserve, 1_000_000_000)],
+        &[(&debt_reserve, 5_000_000_000)],
+    );
+    let foreign_solend_position = add_solend_position(
+        &mut program_test,
+        &maker.pubkey(),
+        &foreign_obligation,
+        &[(&collateral_reserve, 600_000_000)],
+        &[(&debt_reserve, 3_000_000_000)],
+    );
+
+    let collateral_ta = add_token_account(&mut program_test, &collateral_reserve.collateral_mint, &delegate, 0);
+    let maker_debt_liquidity = add_token_account(&mut program_test, &debt_mint, &maker.pubkey(), 4_000_000_000);
+    let maker_collateral_liquidity = add_token_account(&mut program_test, &collateral_mint, &maker.pubkey(), 0);
+
+    let mut context = program_test.start_with_context().await;
+
+    let refresh_reserves = [
+        (collateral_reserve.id, collateral_reserve.pyth_price, collateral_reserve.switchboard_price),
+        (debt_reserve.id, debt_reserve.pyth_price, debt_reserve.switchboard_price),
+    ];
+
+    let repay = |solend_position: &Pubkey, obligation: &Pubkey, liquidity_amount: u64| {
+        solend_proxy_repay_obligation_liquidity(
+            &id(),
+            &maker.pubkey(),
+            solend_position,
+            &market.program_id,
+            &maker_debt_liquidity,
+            &debt_reserve.liquidity_supply,
+            &debt_reserve.id,
+            obligation,
+            &market.lending_market,
+            &refresh_reserves,
+            liquidity_amount,
+        )
+    };
+
+    let withdraw = |solend_position: &Pubkey, obligation: &Pubkey, collateral_amount: u64| {
+        solend_proxy_withdraw_obligation_collateral(
+            &id(),
+            &maker.pubkey(),
+            solend_position,
+            &market.program_id,
+            &collateral_reserve.collateral_supply,
+            &collateral_ta,
+            &collateral_reserve.id,
+            obligation,
+            &market.lending_market,
+            &market.authority,
+            &maker_collateral_liquidity,
+            &collateral_reserve.collateral_mint,
+            &collateral_reserve.liquidity_supply,
+            &delegate,
+            &refresh_reserves,
+            collateral_amount,
+        )
+    };
+
+    // 100 collateral at 20 and 75% loan to value don't cover 3_000 debt
+    assert_eq!(
+        process_maker_instruction(&mut context, &maker, withdraw(&solend_position, &obligation, 500_000_000)).await.unwrap_err(),
+        TransactionError::InstructionError(0, InstructionError::Custom(SolarisAutoError::PositionUnhealthy as u32)),
+    );
+
+    // Obligation has 1_000 collateral, but only 600 are the maker's
+    assert_eq!(
+        process_maker_instruction(&mut context, &maker, withdraw(&solend_position, &obligation, 700_000_000)).await.unwrap_err(),
+        TransactionError::InstructionError(0, InstructionError::Custom(SolarisAutoError::PositionNotEntitled as u32)),
+    );
+
+    process_maker_instruction(&mut context, &maker, repay(&solend_position, &obligation, 1_000_000_000)).await.unwrap();
+
+    let position = get_solend_position(&mut context.banks_client, &solend_position).await;
+    assert_eq!(
+        position.borrows,
+        vec![PositionLiquidity {
+            borrow_reserve: debt_reserve.id,
+            borrowed_amount_wads: 2_000_000_000 * WAD,
+            cumulative_borrow_rate_wads: WAD,
+        }],
+    );
+
+    // Repay of all is capped by the maker borrow, not the obligation borrow
+    process_maker_instruction(&mut context, &maker, repay(&solend_position, &obligation, u64::MAX)).await.unwrap();
+
+    assert_eq!(get_token_amount(&mut context.banks_client, &maker_debt_liquidity).await, 1_000_000_000);
+    assert_eq!(get_token_amount(&mut context.banks_client, &debt_reserve.liquidity_supply).await, 13_000_000_000);
+
+    let position = get_solend_position(&mut context.banks_client, &solend_position).await;
+    assert!(position.borrows.is_empty());
+
+    // Withdrawal of all is the maker collateral
+    process_maker_instruction(&mut context, &maker, withdraw(&solend_position, &obligation, u64::MAX)).await.unwrap();
+
+    assert_eq!(get_token_amount(&mut context.banks_client, &maker_collateral_liquidity).await, 600_000_000);
+    assert_eq!(get_token_amount(&mut context.banks_client, &collateral_ta).await, 0);
+    assert_eq!(get_token_amount(&mut context.banks_client, &collateral_reserve.liquidity_supply).await, 400_000_000);
+
+    let obligation_state = get_obligation(&mut context.banks_client, &obligation).await;
+    assert_eq!(
+        get_obligation_amounts(&obligation_state),
+        (
+            vec![(collateral_reserve.id, 400_000_000)],
+            vec![(debt_reserve.id, 2_000_000_000 * WAD)],
+        ),
+    );
+
+    let position = get_solend_position(&mut context.banks_client, &solend_position).await;
+    assert!(position.deposits.is_empty());
+    assert!(position.borrows.is_empty());
+
+    // The delegate doesn't act on obligations of others
+    assert_eq!(
+        process_maker_instruction(&mut context, &maker, repay(&foreign_solend_position, &foreign_obligation, 1_000_000_000)).await.unwrap_err(),
+        TransactionError::InstructionError(0, InstructionError::Custom(SolarisAutoError::InvalidObligationOwner as u32)),
+    );
+
+    let position = get_solend_position(&mut context.banks_client, &foreign_solend_position).await;
+    assert_eq!(
+        position.deposits,
+        vec![PositionCollateral {
+            deposit_reserve: collateral_reserve.id,
+            deposited_amount: 600_000_000,
+        }],
+    );
+}
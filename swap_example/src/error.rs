@@ -0,0 +1,52 @@
+use thiserror::Error;
+use num_derive::FromPrimitive;
+use solana_program::{
+    program_error::{
+        PrintProgramError, 
+        ProgramError
+    },
+    decode_error::DecodeError,
+    msg,
+};
+
+#[derive(Error, Debug, Copy, Clone, FromPrimitive)]
+pub enum SwapError {
+    #[error("Swap account already in use")]
+    AlreadyInUse,
+
+    #[error("Invalid program address generated from bump seed and key")]
+    InvalidProgramAddress,
+
+    #[error("Address of the provided swap token account is incorrect")]
+    IncorrectSwapAccount,
+
+    #[error("Token account is not owned by the swap authority")]
+    InvalidOwner,
+
+    #[error("Fee is invalid")]
+    InvalidFee,
+
+    #[error("Swap instruction exceeds desired slippage limit")]
+    ExceededSlippage,
+
+    #[error("General calculation failure due to overflow or underflow")]
+    CalculationFailure,
+}
+
+impl PrintProgramError for SwapError {
+    fn print<E>(&self) {
+        msg!(&self.to_string());
+    }
+}
+
+impl<T> DecodeError<T> for SwapError {
+    fn type_of() -> &'static str {
+        "Swap Error"
+    }
+}
+
+impl From<SwapError> for ProgramError {
+    fn from(e: SwapError) -> Self {
+        ProgramError::Custom(e as u32)
+    }
+}
@@ -0,0 +1,111 @@
+use solana_program::{
+    instruction::{AccountMeta, Instruction},
+    pubkey::Pubkey,
+};
+use borsh::{
+    BorshSerialize,     
+    BorshDeserialize,
+    BorshSchema,
+};
+
+#[derive(BorshSchema, BorshSerialize, BorshDeserialize)]
+pub enum SwapInstruction {
+    /// Initializes a new constant product pool of tokens A and B.
+    ///
+    /// Accounts expected:
+    ///
+    /// 0. `[writable]` Swap account, allocated with `SWAP_STATE_SIZE` and owned by the program
+    /// 1. `[]` Swap authority derived from `[swap account]`
+    /// 2. `[]` Token A account owned by swap authority
+    /// 3. `[]` Token B account owned by swap authority
+    /// 4. `[]` Pool token mint
+    /// 5. `[]` Pool fee account
+    /// 6. `[]` Token program id
+    Initialize {
+        fee_numerator: u64,
+        fee_denominator: u64,
+    },
+    /// Swaps tokens of the pool. Accounts and data are the same
+    /// as in the SPL token-swap `Swap` instruction.
+    ///
+    /// Accounts expected:
+    ///
+    /// 0. `[]` Swap account
+    /// 1. `[]` Swap authority
+    /// 2. `[signer]` User transfer authority
+    /// 3. `[writable]` User source token account, `amount_in` is transferable by user transfer authority
+    /// 4. `[writable]` Swap token account to swap INTO, same mint as the user source
+    /// 5. `[writable]` Swap token account to swap FROM, same mint as the user destination
+    /// 6. `[writable]` User destination token account
+    /// 7. `[writable]` Pool token mint
+    /// 8. `[writable]` Pool fee account
+    /// 9. `[]` Token program id
+    Swap {
+        amount_in: u64,
+        minimum_amount_out: u64,
+    },
+}
+
+pub fn initialize(
+    program_id: &Pubkey,
+    swap_id: &Pubkey,
+    swap_authority_id: &Pubkey,
+    token_a_id: &Pubkey,
+    token_b_id: &Pubkey,
+    pool_mint_id: &Pubkey,
+    pool_fee_id: &Pubkey,
+    fee_numerator: u64,
+    fee_denominator: u64,
+) -> Instruction {
+    Instruction::new_with_borsh(
+        *program_id,
+        &SwapInstruction::Initialize {
+            fee_numerator,
+            fee_denominator,
+        },
+        vec![
+            AccountMeta::new(*swap_id, false),
+            AccountMeta::new_readonly(*swap_authority_id, false),
+            AccountMeta::new_readonly(*token_a_id, false),
+            AccountMeta::new_readonly(*token_b_id, false),
+            AccountMeta::new_readonly(*pool_mint_id, false),
+            AccountMeta::new_readonly(*pool_fee_id, false),
+            AccountMeta::new_readonly(spl_token::id(), false),
+        ],
+    )
+}
+
+pub fn swap(
+    program_id: &Pubkey,
+    swap_id: &Pubkey,
+    swap_authority_id: &Pubkey,
+    user_transfer_authority_id: &Pubkey,
+    source_id: &Pubkey,
+    swap_source_id: &Pubkey,
+    swap_destination_id: &Pubkey,
+    destination_id: &Pubkey,
+    pool_mint_id: &Pubkey,
+    pool_fee_id: &Pubkey,
+    amount_in: u64,
+    minimum_amount_out: u64,
+) -> Instruction {
+    Instruction::new_with_borsh(
+        *program_id,
+        &SwapInstruction::Swap {
+            amount_in,
+            minimum_amount_out,
+        },
+        vec![
+            AccountMeta::new_readonly(*swap_id, false),
+            AccountMeta::new_readonly(*swap_authority_id, false),
+            AccountMeta::new_readonly(*user_transfer_authority_id, true),
+            AccountMeta::new(*source_id, false),
+            AccountMeta::new(*swap_source_id, false),
+            AccountMeta::new(*swap_destination_id, false),
+            AccountMeta::new(*destination_id, false),
+            AccountMeta::new(*pool_mint_id, false),
+            AccountMeta::new(*pool_fee_id, false),
+            AccountMeta::new_readonly(spl_token::id(), false),
+        ],
+    )
+}
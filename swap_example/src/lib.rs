@@ -0,0 +1,7 @@
+pub mod error;
+pub mod instruction;
+pub mod processor;
+pub mod state;
+
+#[cfg(not(feature = "no-entrypoint"))]
+pub mod entrypoint;
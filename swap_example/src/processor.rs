@@ -0,0 +1,224 @@
+use solana_program::{
+    account_info::{next_account_info, AccountInfo},
+    entrypoint::ProgramResult,
+    msg,
+    program::{invoke, invoke_signed},
+    program_error::ProgramError,
+    program_pack::Pack,
+    pubkey::Pubkey,
+};
+use borsh::{
+    BorshDeserialize,
+    BorshSerialize,
+};
+
+use spl_token::state::Account as TokenAccount;
+
+use crate::{
+    error::SwapError,
+    instruction::SwapInstruction,
+    state::{SwapState, SWAP_STATE_SIZE},
+};
+
+pub struct Processor;
+impl Processor {
+    pub fn process(
+        program_id: &Pubkey,
+        accounts: &[AccountInfo],
+        instruction_data: &[u8],
+    ) -> ProgramResult {
+        let instruction = SwapInstruction::try_from_slice(instruction_data)?;
+
+        match instruction {
+            SwapInstruction::Initialize {
+                fee_numerator,
+                fee_denominator,
+            } => {
+                msg!("Instruction: Initialize");
+                Self::process_initialize(
+                    program_id,
+                    accounts,
+                    fee_numerator,
+                    fee_denominator,
+                )
+            },
+            SwapInstruction::Swap {
+                amount_in,
+                minimum_amount_out,
+            } => {
+                msg!("Instruction: Swap");
+                Self::process_swap(
+                    program_id,
+                    accounts,
+                    amount_in,
+                    minimum_amount_out,
+                )
+            },
+        }
+    }
+
+    pub fn process_initialize(
+        program_id: &Pubkey,
+        accounts: &[AccountInfo],
+        fee_numerator: u64,
+        fee_denominator: u64,
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+
+        let swap_info = next_account_info(account_info_iter)?;
+        let authority_info = next_account_info(account_info_iter)?;
+        let token_a_info = next_account_info(account_info_iter)?;
+        let token_b_info = next_account_info(account_info_iter)?;
+        let pool_mint_info = next_account_info(account_info_iter)?;
+        let pool_fee_info = next_account_info(account_info_iter)?;
+        let token_program_info = next_account_info(account_info_iter)?;
+
+        if swap_info.owner != program_id || swap_info.data_len() != SWAP_STATE_SIZE {
+            return Err(ProgramError::InvalidAccountData)
+        }
+
+        if swap_info.data.borrow()[0] != 0 {
+            return Err(SwapError::AlreadyInUse.into())
+        }
+
+        let (authority, bump_seed) = Pubkey::find_program_address(
+            &[swap_info.key.as_ref()],
+            program_id,
+        );
+
+        if authority != *authority_info.key {
+            return Err(SwapError::InvalidProgramAddress.into())
+        }
+
+        if token_a_info.key == token_b_info.key {
+            return Err(SwapError::IncorrectSwapAccount.into())
+        }
+
+        for token_info in [token_a_info, token_b_info].iter() {
+            let token_account = TokenAccount::unpack(&token_info.data.borrow())?;
+
+            if token_account.owner != authority {
+                return Err(SwapError::InvalidOwner.into())
+            }
+        }
+
+        if fee_denominator == 0 || fee_numerator >= fee_denominator {
+            return Err(SwapError::InvalidFee.into())
+        }
+
+        let state = SwapState {
+            is_initialized: true,
+            bump_seed,
+            token_program_id: *token_program_info.key,
+            token_a: *token_a_info.key,
+            token_b: *token_b_info.key,
+            pool_mint: *pool_mint_info.key,
+            pool_fee_account: *pool_fee_info.key,
+            fee_numerator,
+            fee_denominator,
+        };
+
+        state.serialize(&mut *swap_info.data.borrow_mut())?;
+
+        Ok(())
+    }
+
+    pub fn process_swap(
+        program_id: &Pubkey,
+        accounts: &[AccountInfo],
+        amount_in: u64,
+        minimum_amount_out: u64,
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+
+        let swap_info = next_account_info(account_info_iter)?;
+        let authority_info = next_account_info(account_info_iter)?;
+        let user_transfer_authority_info = next_account_info(account_info_iter)?;
+        let source_info = next_account_info(account_info_iter)?;
+        let swap_source_info = next_account_info(account_info_iter)?;
+        let swap_destination_info = next_account_info(account_info_iter)?;
+        let destination_info = next_account_info(account_info_iter)?;
+        let pool_mint_info = next_account_info(account_info_iter)?;
+        let pool_fee_info = next_account_info(account_info_iter)?;
+        let token_program_info = next_account_info(account_info_iter)?;
+
+        if swap_info.owner != program_id {
+            return Err(ProgramError::IncorrectProgramId)
+        }
+
+        let state = SwapState::try_from_slice(&swap_info.data.borrow())?;
+
+        if !state.is_initialized {
+            return Err(ProgramError::UninitializedAccount)
+        }
+
+        let authority = Pubkey::create_program_address(
+            &[swap_info.key.as_ref(), &[state.bump_seed]],
+            program_id,
+        )?;
+
+        if authority != *authority_info.key {
+            return Err(SwapError::InvalidProgramAddress.into())
+        }
+
+        let is_pool_pair = (*swap_source_info.key == state.token_a && *swap_destination_info.key == state.token_b)
+            || (*swap_source_info.key == state.token_b && *swap_destination_info.key == state.token_a);
+
+        if !is_pool_pair
+            || *pool_mint_info.key != state.pool_mint
+            || *pool_fee_info.key != state.pool_fee_account
+            || *token_program_info.key != state.token_program_id {
+            return Err(SwapError::IncorrectSwapAccount.into())
+        }
+
+        let swap_source = TokenAccount::unpack(&swap_source_info.data.borrow())?;
+        let swap_destination = TokenAccount::unpack(&swap_destination_info.data.borrow())?;
+
+        let amount_out = state
+            .swap_amount_out(swap_source.amount, swap_destination.amount, amount_in)
+            .ok_or(SwapError::CalculationFailure)?;
+
+        msg!("amount_out is {}", amount_out);
+
+        if amount_out == 0 || amount_out < minimum_amount_out {
+            return Err(SwapError::ExceededSlippage.into())
+        }
+
+        invoke(
+            &spl_token::instruction::transfer(
+                token_program_info.key,
+                source_info.key,
+                swap_source_info.key,
+                user_transfer_authority_info.key,
+                &[],
+                amount_in,
+            )?,
+            &[
+                source_info.clone(),
+                swap_source_info.clone(),
+                user_transfer_authority_info.clone(),
+                token_program_info.clone(),
+            ],
+        )?;
+
+        invoke_signed(
+            &spl_token::instruction::transfer(
+                token_program_info.key,
+                swap_destination_info.key,
+                destination_info.key,
+                authority_info.key,
+                &[],
+                amount_out,
+            )?,
+            &[
+                swap_destination_info.clone(),
+                destination_info.clone(),
+                authority_info.clone(),
+                token_program_info.clone(),
+            ],
+            &[&[swap_info.key.as_ref(), &[state.bump_seed]]],
+        )?;
+
+        Ok(())
+    }
+}
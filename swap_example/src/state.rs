@@ -0,0 +1,78 @@
+use solana_program::pubkey::Pubkey;
+use borsh::{
+    BorshSerialize,     
+    BorshDeserialize,
+};
+
+pub const SWAP_STATE_SIZE: usize = 178; // 1 + 1 + 32 * 5 + 8 + 8
+
+#[derive(BorshSerialize, BorshDeserialize, Debug, PartialEq)]
+pub struct SwapState {
+    pub is_initialized: bool,
+    pub bump_seed: u8,
+    pub token_program_id: Pubkey,
+    pub token_a: Pubkey,
+    pub token_b: Pubkey,
+    pub pool_mint: Pubkey,
+    pub pool_fee_account: Pubkey,
+    pub fee_numerator: u64,
+    pub fee_denominator: u64,
+}
+
+impl SwapState {
+    /// Constant product swap: amount out for `amount_in` after the fee.
+    /// Fee is rounded up and amount out is rounded down in favour of the pool
+    pub fn swap_amount_out(
+        &self,
+        source_amount: u64,
+        destination_amount: u64,
+        amount_in: u64,
+    ) -> Option<u64> {
+        let amount_in = amount_in as u128;
+        let fee = amount_in
+            .checked_mul(self.fee_numerator as u128)?
+            .checked_add((self.fee_denominator as u128).checked_sub(1)?)?
+            .checked_div(self.fee_denominator as u128)?;
+        let amount_in_after_fee = amount_in.checked_sub(fee)?;
+
+        let amount_out = (destination_amount as u128)
+            .checked_mul(amount_in_after_fee)?
+            .checked_div((source_amount as u128).checked_add(amount_in_after_fee)?)?;
+
+        Some(amount_out as u64)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn constant_product() {
+        let mut state = SwapState {
+            is_initialized: true,
+            bump_seed: 255,
+            token_program_id: spl_token::id(),
+            token_a: Pubkey::new_unique(),
+            token_b: Pubkey::new_unique(),
+            pool_mint: Pubkey::new_unique(),
+            pool_fee_account: Pubkey::new_unique(),
+            fee_numerator: 0,
+            fee_denominator: 1,
+        };
+
+        // 1_000 * 2_000 = (1_000 + 1_000) * (2_000 - 1_000)
+        assert_eq!(state.swap_amount_out(1_000, 2_000, 1_000), Some(1_000));
+        assert_eq!(state.swap_amount_out(1_000, 2_000, 1), Some(1));
+        assert_eq!(state.swap_amount_out(0, 0, 1_000), Some(0));
+
+        // 0.3% fee of 1_000 is 3
+        state.fee_numerator = 3;
+        state.fee_denominator = 1_000;
+        assert_eq!(state.swap_amount_out(1_000, 2_000, 1_000), Some(998));
+        assert_eq!(state.swap_amount_out(1_000, 2_000, 1), Some(0));
+
+        state.fee_denominator = 0;
+        assert_eq!(state.swap_amount_out(1_000, 2_000, 1_000), None);
+    }
+}
@@ -0,0 +1,150 @@
+#![cfg(feature = "test-bpf")]
+use solana_program_test::*;
+use solana_program::{
+    program_option::COption,
+    program_pack::Pack,
+};
+use solana_sdk::{
+    account::Account,
+    instruction::InstructionError,
+    pubkey::Pubkey,
+    signature::{Keypair, Signer},
+    transaction::{Transaction, TransactionError},
+};
+use spl_token::state::{
+    Account as TokenAccount,
+    AccountState,
+    Mint,
+};
+use swap_example::{
+    error::SwapError,
+    instruction::{initialize, swap},
+    processor::Processor,
+    state::SWAP_STATE_SIZE,
+};
+
+fn add_mint(program_test: &mut ProgramTest) -> Pubkey {
+    let mint = Pubkey::new_unique();
+
+    program_test.add_packable_account(
+        mint,
+        u32::MAX as u64,
+        &Mint {
+            mint_authority: COption::None,
+            supply: u32::MAX as u64,
+            decimals: 6,
+            is_initialized: true,
+            freeze_authority: COption::None,
+        },
+        &spl_token::id(),
+    );
+
+    mint
+}
+
+fn add_token_account(
+    program_test: &mut ProgramTest,
+    mint: &Pubkey,
+    owner: &Pubkey,
+    amount: u64,
+) -> Pubkey {
+    let token_account = Pubkey::new_unique();
+
+    program_test.add_packable_account(
+        token_account,
+        u32::MAX as u64,
+        &TokenAccount {
+            mint: *mint,
+            owner: *owner,
+            amount,
+            state: AccountState::Initialized,
+            ..TokenAccount::default()
+        },
+        &spl_token::id(),
+    );
+
+    token_account
+}
+
+async fn get_token_amount(banks_client: &mut BanksClient, token_account: &Pubkey) -> u64 {
+    let account = banks_client.get_account(*token_account).await.unwrap().unwrap();
+
+    TokenAccount::unpack(&account.data).unwrap().amount
+}
+
+#[tokio::test]
+async fn test_swap() {
+    let program_id = Pubkey::new_unique();
+    let mut program_test = ProgramTest::new(
+        "swap_example",
+        program_id,
+        processor!(Processor::process),
+    );
+
+    let swap = Pubkey::new_unique();
+    let (swap_authority, _) = Pubkey::find_program_address(&[swap.as_ref()], &program_id);
+    let user = Keypair::new();
+
+    program_test.add_account(
+        swap,
+        Account {
+            lamports: u32::MAX as u64,
+            data: vec![0; SWAP_STATE_SIZE],
+            owner: program_id,
+            executable: false,
+            rent_epoch: 0,
+        },
+    );
+
+    let mint_a = add_mint(&mut program_test);
+    let mint_b = add_mint(&mut program_test);
+    let pool_mint = add_mint(&mut program_test);
+    let token_a = add_token_account(&mut program_test, &mint_a, &swap_authority, 1_000);
+    let token_b = add_token_account(&mut program_test, &mint_b, &swap_authority, 2_000);
+    let pool_fee = add_token_account(&mut program_test, &pool_mint, &swap_authority, 0);
+    let source = add_token_account(&mut program_test, &mint_a, &user.pubkey(), 2_000);
+    let destination = add_token_account(&mut program_test, &mint_b, &user.pubkey(), 0);
+
+    let mut context = program_test.start_with_context().await;
+    let payer = context.payer.pubkey();
+
+    let swap_ix = |amount_in: u64, minimum_amount_out: u64| swap(
+        &program_id,
+        &swap,
+        &swap_authority,
+        &user.pubkey(),
+        &source,
+        &token_a,
+        &token_b,
+        &destination,
+        &pool_mint,
+        &pool_fee,
+        amount_in,
+        minimum_amount_out,
+    );
+
+    let mut tx = Transaction::new_with_payer(
+        &[
+            initialize(&program_id, &swap, &swap_authority, &token_a, &token_b, &pool_mint, &pool_fee, 3, 1_000),
+            swap_ix(1_000, 998),
+        ],
+        Some(&payer),
+    );
+    tx.sign(&[&context.payer, &user], context.last_blockhash);
+    context.banks_client.process_transaction(tx).await.unwrap();
+
+    // 0.3% fee: 2_000 * 997 / (1_000 + 997)
+    assert_eq!(get_token_amount(&mut context.banks_client, &destination).await, 998);
+    assert_eq!(get_token_amount(&mut context.banks_client, &source).await, 1_000);
+    assert_eq!(get_token_amount(&mut context.banks_client, &token_a).await, 2_000);
+    assert_eq!(get_token_amount(&mut context.banks_client, &token_b).await, 1_002);
+
+    // Price moved: 1_002 * 997 / (2_000 + 997) = 333
+    let mut tx = Transaction::new_with_payer(&[swap_ix(1_000, 334)], Some(&payer));
+    tx.sign(&[&context.payer, &user], context.last_blockhash);
+
+    assert_eq!(
+        context.banks_client.process_transaction(tx).await.unwrap_err().unwrap(),
+        TransactionError::InstructionError(0, InstructionError::Custom(SwapError::ExceededSlippage as u32)),
+    );
+}